@@ -0,0 +1,221 @@
+//! Measures the overhead of glue's dynamic dispatch against a handcoded,
+//! match-based contract doing the same work, for payloads of increasing
+//! size. With `GLUE_BENCH_ALLOCATIONS` set, the number of heap allocations
+//! per call is reported on stderr as well, tracking the cost of envelope
+//! parsing and response conversion.
+//!
+//! Run with `cargo bench`, or `GLUE_BENCH_ALLOCATIONS=1 cargo bench`.
+
+use burnt_glue::cosmwasm_std::testing::{mock_dependencies, mock_env};
+use burnt_glue::cosmwasm_std::{
+    self, from_json, to_json_binary, Addr, Binary, Deps, DepsMut, Env, MessageInfo, StdError,
+    StdResult,
+};
+use burnt_glue::manager::Manager;
+use burnt_glue::module::Module;
+use burnt_glue::response::Response;
+use burnt_glue::shared;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use serde::{Deserialize, Serialize};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Counts heap allocations made through the global allocator.
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ExecuteMsg {
+    Store { items: Vec<String> },
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum QueryMsg {
+    Count { items: Vec<String> },
+}
+
+/// The handcoded contract's execute message, addressing the same module.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum HandcodedExecuteMsg {
+    Store(ExecuteMsg),
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum HandcodedQueryMsg {
+    Store(QueryMsg),
+}
+
+fn store(deps: &mut DepsMut, items: Vec<String>) -> usize {
+    deps.storage.set(b"items", &(items.len() as u64).to_be_bytes());
+    items.len()
+}
+
+struct Store;
+
+impl Module for Store {
+    type InstantiateMsg = ();
+    type ExecuteMsg = ExecuteMsg;
+    type QueryMsg = QueryMsg;
+    type QueryResp = usize;
+    type Error = StdError;
+
+    fn instantiate(
+        &mut self,
+        _deps: &mut DepsMut,
+        _env: &Env,
+        _info: &MessageInfo,
+        _msg: (),
+    ) -> Result<Response, StdError> {
+        Ok(Response::new())
+    }
+
+    fn execute(
+        &mut self,
+        deps: &mut DepsMut,
+        _env: Env,
+        _info: MessageInfo,
+        msg: ExecuteMsg,
+    ) -> Result<Response, StdError> {
+        let ExecuteMsg::Store { items } = msg;
+        let count = store(deps, items);
+        Ok(Response::new().add_attribute("count", count.to_string()))
+    }
+
+    fn query(&self, _deps: &Deps, _env: Env, msg: QueryMsg) -> Result<usize, StdError> {
+        let QueryMsg::Count { items } = msg;
+        Ok(items.len())
+    }
+}
+
+fn handcoded_execute(
+    mut deps: DepsMut,
+    _env: Env,
+    _info: MessageInfo,
+    msg: &[u8],
+) -> StdResult<cosmwasm_std::Response> {
+    match from_json(msg)? {
+        HandcodedExecuteMsg::Store(ExecuteMsg::Store { items }) => {
+            let count = store(&mut deps, items);
+            Ok(cosmwasm_std::Response::new().add_attribute("count", count.to_string()))
+        }
+    }
+}
+
+fn handcoded_query(_deps: Deps, _env: Env, msg: &[u8]) -> StdResult<Binary> {
+    match from_json(msg)? {
+        HandcodedQueryMsg::Store(QueryMsg::Count { items }) => to_json_binary(&items.len()),
+    }
+}
+
+fn manager() -> Manager {
+    Manager::builder()
+        .with_module("store", shared::shared(Store))
+        .unwrap()
+        .build()
+}
+
+fn items(n: usize) -> Vec<String> {
+    (0..n).map(|i| format!("item-{}", i)).collect()
+}
+
+/// Report the allocations made by a single call of `f`, if requested with
+/// `GLUE_BENCH_ALLOCATIONS`.
+fn report_allocations(name: &str, size: usize, f: impl FnOnce()) {
+    if std::env::var_os("GLUE_BENCH_ALLOCATIONS").is_none() {
+        return;
+    }
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    f();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+    eprintln!("{}/{}: {} allocations per call", name, size, allocations);
+}
+
+fn execute(c: &mut Criterion) {
+    let mut group = c.benchmark_group("execute");
+    for size in [1, 16, 256] {
+        let msg = serde_json::to_vec(&HandcodedExecuteMsg::Store(ExecuteMsg::Store {
+            items: items(size),
+        }))
+        .unwrap();
+        let info = MessageInfo {
+            sender: Addr::unchecked("sender"),
+            funds: vec![],
+        };
+        let mut deps = mock_dependencies();
+        let mut glue = manager();
+
+        report_allocations("execute/glue", size, || {
+            glue.execute_raw(&mut deps.as_mut(), mock_env(), info.clone(), &msg)
+                .unwrap();
+        });
+        report_allocations("execute/handcoded", size, || {
+            handcoded_execute(deps.as_mut(), mock_env(), info.clone(), &msg).unwrap();
+        });
+
+        group.bench_with_input(BenchmarkId::new("glue", size), &msg, |b, msg| {
+            b.iter(|| {
+                glue.execute_raw(&mut deps.as_mut(), mock_env(), info.clone(), black_box(msg))
+                    .unwrap()
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("handcoded", size), &msg, |b, msg| {
+            b.iter(|| {
+                handcoded_execute(deps.as_mut(), mock_env(), info.clone(), black_box(msg))
+                    .unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+fn query(c: &mut Criterion) {
+    let mut group = c.benchmark_group("query");
+    for size in [1, 16, 256] {
+        let msg = serde_json::to_vec(&HandcodedQueryMsg::Store(QueryMsg::Count {
+            items: items(size),
+        }))
+        .unwrap();
+        let deps = mock_dependencies();
+        let mut glue = manager();
+
+        report_allocations("query/glue", size, || {
+            glue.query_raw(&deps.as_ref(), mock_env(), &msg).unwrap();
+        });
+        report_allocations("query/handcoded", size, || {
+            handcoded_query(deps.as_ref(), mock_env(), &msg).unwrap();
+        });
+
+        group.bench_with_input(BenchmarkId::new("glue", size), &msg, |b, msg| {
+            b.iter(|| {
+                glue.query_raw(&deps.as_ref(), mock_env(), black_box(msg))
+                    .unwrap()
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("handcoded", size), &msg, |b, msg| {
+            b.iter(|| handcoded_query(deps.as_ref(), mock_env(), black_box(msg)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, execute, query);
+criterion_main!(benches);
@@ -0,0 +1,79 @@
+//! Throws arbitrary bytes at every Manager entrypoint. Any panic, including
+//! a stack overflow, is a bug: malformed input must only ever produce an
+//! error.
+//!
+//! Run with `cargo +nightly fuzz run dispatch` from the repository root.
+
+#![no_main]
+
+use burnt_glue::manager::Manager;
+use burnt_glue::module::Module;
+use burnt_glue::response::Response;
+use burnt_glue::shared::shared;
+use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+use cosmwasm_std::{Deps, DepsMut, Env, MessageInfo, StdError};
+use libfuzzer_sys::fuzz_target;
+use serde_json::Value;
+
+/// A module accepting any payload, echoing it back as data.
+struct Echo;
+
+impl Module for Echo {
+    type InstantiateMsg = Value;
+    type ExecuteMsg = Value;
+    type QueryMsg = Value;
+    type QueryResp = Value;
+    type Error = StdError;
+
+    fn instantiate(
+        &mut self,
+        _deps: &mut DepsMut,
+        _env: &Env,
+        _info: &MessageInfo,
+        msg: Value,
+    ) -> Result<Response, StdError> {
+        Ok(Response::new().set_data(msg))
+    }
+
+    fn execute(
+        &mut self,
+        _deps: &mut DepsMut,
+        _env: Env,
+        _info: MessageInfo,
+        msg: Value,
+    ) -> Result<Response, StdError> {
+        Ok(Response::new().set_data(msg))
+    }
+
+    fn query(&self, _deps: &Deps, _env: Env, msg: Value) -> Result<Value, StdError> {
+        Ok(msg)
+    }
+}
+
+fn manager(limited: bool) -> Manager {
+    let mut manager = Manager::builder()
+        .with_module("echo", shared(Echo))
+        .unwrap()
+        .with_module("other", shared(Echo))
+        .unwrap()
+        .build();
+    if limited {
+        manager.set_max_payload_bytes(Some(4096));
+        manager.set_max_depth(Some(16));
+    }
+    manager
+}
+
+fuzz_target!(|data: &[u8]| {
+    let (limited, msg) = match data.split_first() {
+        Some((flag, msg)) => (flag & 1 == 1, msg),
+        None => return,
+    };
+    let mut deps = mock_dependencies();
+    let info = mock_info("sender", &[]);
+
+    let _ = manager(limited).instantiate_raw(deps.as_mut(), mock_env(), info.clone(), msg);
+    let _ = manager(limited).execute_raw(&mut deps.as_mut(), mock_env(), info, msg);
+    let _ = manager(limited).query_raw(&deps.as_ref(), mock_env(), msg);
+    let _ = manager(limited).migrate_raw(deps.as_mut(), mock_env(), msg);
+});
@@ -0,0 +1,302 @@
+//! Running a glue contract as an Abstract app.
+//!
+//! Abstract accounts address their apps with messages wrapped in a `base`
+//! key, for messages handled by the Abstract SDK, or a `module` key, for
+//! messages handled by the app itself. The [execute], [query], and
+//! [instantiate] entrypoints translate these into the glue convention: the
+//! `module` payload is a regular glue envelope addressing one of the
+//! Manager's modules, and the `base` payload is dispatched to the
+//! [AbstractBase] module, which must be registered as [BASE_MODULE]:
+//!
+//! ```javascript
+//! { "module": { "token": { "transfer": { "recipient": "...", "amount": "1" } } } }
+//! { "base": { "base_config": {} } }
+//! ```
+//!
+//! The instantiate message carries the base payload next to the instantiate
+//! payloads of the modules:
+//!
+//! ```javascript
+//! {
+//!   "base": { "ans_host_address": "...", "version_control_address": "...",
+//!             "account_base": { "manager": "...", "proxy": "..." } },
+//!   "module": { "token": { /* ... */ } }
+//! }
+//! ```
+//!
+//! Tokens sent with a `receive` hook are routed as described in
+//! [receive][crate::receive].
+
+use crate::admin;
+use crate::error::Error;
+use crate::manager::Manager;
+use crate::module::Module;
+use crate::receive::CW20_RECEIVE_KEY;
+use crate::response::Response;
+use crate::storage;
+use cosmwasm_std::{
+    Addr, Binary, CustomQuery, Deps, DepsMut, Env, MessageInfo, StdError, StdResult, Storage,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+const NAMESPACE: &[u8] = b"abstract_base";
+
+/// The name the [AbstractBase] module must be registered under.
+pub const BASE_MODULE: &str = "abstract_base";
+
+/// Dispatch an Abstract execute message to the Manager.
+pub fn execute<C: Clone, Q: CustomQuery>(
+    manager: &mut Manager<C, Q>,
+    deps: &mut DepsMut<Q>,
+    env: Env,
+    info: MessageInfo,
+    msg: &[u8],
+) -> Result<cosmwasm_std::Response<C>, Error> {
+    let msg = match single_key(msg)? {
+        (key, payload) if key == "module" => payload,
+        (key, payload) if key == "base" => envelope(BASE_MODULE, payload),
+        (key, payload) if key == CW20_RECEIVE_KEY => envelope(CW20_RECEIVE_KEY, payload),
+        (key, _) => return Err(unsupported(&key).into()),
+    };
+    manager.execute_raw(deps, env, info, &serde_json::to_vec(&msg).map_err(parse_err)?)
+}
+
+/// Dispatch an Abstract query message to the Manager.
+pub fn query<C: Clone, Q: CustomQuery>(
+    manager: &mut Manager<C, Q>,
+    deps: &Deps<Q>,
+    env: Env,
+    msg: &[u8],
+) -> StdResult<Binary> {
+    let msg = match single_key(msg)? {
+        (key, payload) if key == "module" => payload,
+        (key, payload) if key == "base" => envelope(BASE_MODULE, payload),
+        (key, _) => return Err(unsupported(&key)),
+    };
+    manager.query_raw(deps, env, &serde_json::to_vec(&msg).map_err(parse_err)?)
+}
+
+/// Dispatch an Abstract instantiate message to the Manager.
+pub fn instantiate<C: Clone, Q: CustomQuery>(
+    manager: &mut Manager<C, Q>,
+    deps: DepsMut<Q>,
+    env: Env,
+    info: MessageInfo,
+    msg: &[u8],
+) -> Result<cosmwasm_std::Response<C>, Error> {
+    let mut msg: Map<String, Value> = serde_json::from_slice(msg).map_err(parse_err)?;
+    let base = msg
+        .remove("base")
+        .ok_or_else(|| StdError::generic_err("missing base instantiate message"))?;
+    let mut msgs = match msg.remove("module") {
+        Some(Value::Object(msgs)) => msgs,
+        Some(_) => return Err(StdError::generic_err("module payloads must be an object").into()),
+        None => Map::new(),
+    };
+    if let Some(key) = msg.keys().next() {
+        return Err(unsupported(key).into());
+    }
+    msgs.insert(BASE_MODULE.to_string(), base);
+    let msg = serde_json::to_vec(&msgs).map_err(parse_err)?;
+    manager.instantiate_raw(deps, env, info, &msg)
+}
+
+fn single_key(msg: &[u8]) -> StdResult<(String, Value)> {
+    let msg: Map<String, Value> = serde_json::from_slice(msg).map_err(parse_err)?;
+    let mut entries = msg.into_iter();
+    match (entries.next(), entries.next()) {
+        (Some(entry), None) => Ok(entry),
+        _ => Err(StdError::generic_err("expected a single key")),
+    }
+}
+
+fn envelope(key: &str, payload: Value) -> Value {
+    Value::Object(Map::from_iter([(key.to_string(), payload)]))
+}
+
+fn unsupported(key: &str) -> StdError {
+    StdError::generic_err(format!("unsupported Abstract message {:?}", key))
+}
+
+fn parse_err(err: serde_json::Error) -> StdError {
+    StdError::parse_err("abstract message", err)
+}
+
+/// The account an app is installed on.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct AccountBase {
+    pub manager: Addr,
+    pub proxy: Addr,
+}
+
+/// The `base` instantiate message of an Abstract app.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct InstantiateMsg {
+    pub ans_host_address: String,
+    pub version_control_address: String,
+    pub account_base: AccountBase,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecuteMsg {
+    UpdateConfig {
+        #[serde(default)]
+        ans_host_address: Option<String>,
+        #[serde(default)]
+        version_control_address: Option<String>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMsg {
+    BaseConfig {},
+    BaseAdmin {},
+    ModuleData {},
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Config {
+    pub ans_host_address: Addr,
+    pub version_control_address: Addr,
+    pub account_base: AccountBase,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct AppConfigResponse {
+    pub proxy_address: Addr,
+    pub ans_host_address: Addr,
+    pub manager_address: Addr,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct AdminResponse {
+    pub admin: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ModuleDataResponse {
+    pub module_id: String,
+    pub version: String,
+    pub dependencies: Vec<Value>,
+    pub metadata: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum QueryResp {
+    BaseConfig(AppConfigResponse),
+    BaseAdmin(AdminResponse),
+    ModuleData(ModuleDataResponse),
+}
+
+/// A module handling the `base` messages of an Abstract app.
+///
+/// The manager of the account the app is installed on becomes the
+/// [admin][crate::admin] of the contract, unless one is already set.
+#[derive(Clone, Debug)]
+pub struct AbstractBase {
+    module_id: String,
+    version: String,
+}
+
+impl AbstractBase {
+    /// The base of the app published as `module_id` at `version`, e.g.
+    /// `"glue:shop"` and `"0.1.0"`.
+    pub fn new(module_id: impl Into<String>, version: impl Into<String>) -> Self {
+        AbstractBase {
+            module_id: module_id.into(),
+            version: version.into(),
+        }
+    }
+}
+
+fn config_key() -> Vec<u8> {
+    storage::key(&[NAMESPACE], b"config")
+}
+
+/// Load the configuration of the app.
+pub fn config(storage: &dyn Storage) -> StdResult<Config> {
+    storage::load(storage, &config_key())?.ok_or_else(|| StdError::not_found("abstract config"))
+}
+
+impl<C: Clone, Q: CustomQuery> Module<C, Q> for AbstractBase {
+    type InstantiateMsg = InstantiateMsg;
+    type ExecuteMsg = ExecuteMsg;
+    type QueryMsg = QueryMsg;
+    type QueryResp = QueryResp;
+    type Error = Error;
+
+    fn instantiate(
+        &mut self,
+        deps: &mut DepsMut<Q>,
+        _env: &Env,
+        _info: &MessageInfo,
+        msg: InstantiateMsg,
+    ) -> Result<Response<C>, Error> {
+        let config = Config {
+            ans_host_address: deps.api.addr_validate(&msg.ans_host_address)?,
+            version_control_address: deps.api.addr_validate(&msg.version_control_address)?,
+            account_base: msg.account_base,
+        };
+        if admin::get_admin(deps.storage)?.is_none() {
+            admin::set_admin(deps.storage, &config.account_base.manager)?;
+        }
+        storage::save(deps.storage, &config_key(), &config)?;
+        Ok(Response::new())
+    }
+
+    fn execute(
+        &mut self,
+        deps: &mut DepsMut<Q>,
+        _env: Env,
+        info: MessageInfo,
+        msg: ExecuteMsg,
+    ) -> Result<Response<C>, Error> {
+        admin::assert_admin(deps.storage, &info.sender)?;
+        match msg {
+            ExecuteMsg::UpdateConfig {
+                ans_host_address,
+                version_control_address,
+            } => {
+                let mut config = config(deps.storage)?;
+                if let Some(address) = ans_host_address {
+                    config.ans_host_address = deps.api.addr_validate(&address)?;
+                }
+                if let Some(address) = version_control_address {
+                    config.version_control_address = deps.api.addr_validate(&address)?;
+                }
+                storage::save(deps.storage, &config_key(), &config)?;
+                Ok(Response::new().add_attribute("action", "update_config"))
+            }
+        }
+    }
+
+    fn query(&self, deps: &Deps<Q>, _env: Env, msg: QueryMsg) -> Result<QueryResp, Error> {
+        match msg {
+            QueryMsg::BaseConfig {} => {
+                let config = config(deps.storage)?;
+                Ok(QueryResp::BaseConfig(AppConfigResponse {
+                    proxy_address: config.account_base.proxy,
+                    ans_host_address: config.ans_host_address,
+                    manager_address: config.account_base.manager,
+                }))
+            }
+            QueryMsg::BaseAdmin {} => Ok(QueryResp::BaseAdmin(AdminResponse {
+                admin: admin::get_admin(deps.storage)?.map(String::from),
+            })),
+            QueryMsg::ModuleData {} => Ok(QueryResp::ModuleData(ModuleDataResponse {
+                module_id: self.module_id.clone(),
+                version: self.version.clone(),
+                dependencies: vec![],
+                metadata: None,
+            })),
+        }
+    }
+
+    fn version(&self) -> Option<String> {
+        Some(self.version.clone())
+    }
+}
@@ -0,0 +1,45 @@
+//! The admin of a glue contract.
+//!
+//! The admin's address is stored under a glue-reserved key and authorizes the
+//! messages the Manager handles itself under the `_manager` key, such as
+//! pausing modules. Contracts typically set it at instantiation:
+//!
+//! ```ignore
+//! burnt_glue::admin::set_admin(deps.storage, &info.sender)?;
+//! ```
+//!
+//! Alternatively, register the [Admin][crate::modules::admin::Admin] module,
+//! which manages the admin as the contract's owner.
+
+use crate::error::Error;
+use crate::storage;
+use cosmwasm_std::{Addr, StdResult, Storage};
+
+fn key() -> Vec<u8> {
+    storage::key(&[b"admin"], b"")
+}
+
+/// Load the admin, if one is set.
+pub fn get_admin(storage: &dyn Storage) -> StdResult<Option<Addr>> {
+    storage::load(storage, &key())
+}
+
+/// Set the admin to `admin`.
+pub fn set_admin(storage: &mut dyn Storage, admin: &Addr) -> StdResult<()> {
+    storage::save(storage, &key(), admin)
+}
+
+/// Remove the admin, leaving admin-only actions unavailable.
+pub fn clear_admin(storage: &mut dyn Storage) {
+    storage::remove(storage, &key())
+}
+
+/// Fail with [Unauthorized][Error::Unauthorized] unless `sender` is the admin.
+pub fn assert_admin(storage: &dyn Storage, sender: &Addr) -> Result<(), Error> {
+    match get_admin(storage)? {
+        Some(admin) if admin == *sender => Ok(()),
+        _ => Err(Error::Unauthorized {
+            sender: sender.to_string(),
+        }),
+    }
+}
@@ -0,0 +1,906 @@
+//! Normalization of the case of field and variant names.
+//!
+//! Frontends written in JavaScript tend to send camelCase keys, while the
+//! message types of modules expect snake_case. With
+//! [Manager::set_key_case][crate::manager::Manager::set_key_case], the names
+//! of the struct fields and enum variants of payloads are matched in
+//! snake_case, so both are accepted:
+//!
+//! ```javascript
+//! { "sale": { "buyNow": { "tokenId": "1" } } }
+//! ```
+//!
+//! is handed to the `sale` module as `{ "buy_now": { "token_id": "1" } }`.
+//! Only names are converted: the keys of maps, such as those of a `Value`
+//! field holding token metadata, are kept as sent, and a payload naming the
+//! same field twice, e.g. as `tokenId` and `token_id`, is rejected. Fields of
+//! flattened structs and of internally tagged or untagged enums are matched
+//! as sent. Module names are left untouched.
+//!
+//! With [KeyCase::CamelCaseOut], the names of the fields and variants of
+//! query responses are converted back to camelCase. The data of execute and
+//! instantiate responses is set as JSON by modules and is kept as it is, as
+//! are the responses of the Manager's own queries.
+
+use serde::de::{self, DeserializeSeed, Deserializer, EnumAccess, MapAccess, SeqAccess};
+use serde::de::{VariantAccess, Visitor};
+use serde::ser::{self, Serialize, Serializer};
+use serde_json::{Map, Value};
+use std::fmt;
+
+/// How the Manager converts the names of fields and variants.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum KeyCase {
+    /// Leave names untouched.
+    #[default]
+    Preserve,
+    /// Match the names of payloads in snake_case.
+    SnakeCaseIn,
+    /// Match the names of payloads in snake_case, and convert those of query
+    /// responses to camelCase.
+    CamelCaseOut,
+}
+
+impl KeyCase {
+    /// Whether the names of payloads are matched in snake_case.
+    pub fn converts_payloads(&self) -> bool {
+        *self != KeyCase::Preserve
+    }
+
+    /// Whether the names of query responses are converted to camelCase.
+    pub fn converts_responses(&self) -> bool {
+        *self == KeyCase::CamelCaseOut
+    }
+}
+
+/// `key` in snake_case, e.g. `token_id` for `tokenId` or `tokenID`.
+/// Acronyms are kept together, so `HTTPServer` becomes `http_server`.
+pub fn to_snake_case(key: &str) -> String {
+    let chars: Vec<char> = key.chars().collect();
+    let mut out = String::with_capacity(key.len() + 4);
+    for (i, &c) in chars.iter().enumerate() {
+        if !c.is_ascii_uppercase() {
+            out.push(c);
+            continue;
+        }
+        let starts_word = match i.checked_sub(1).map(|prev| chars[prev]) {
+            Some(prev) if prev.is_ascii_lowercase() || prev.is_ascii_digit() => true,
+            Some(prev) if prev.is_ascii_uppercase() => {
+                chars.get(i + 1).is_some_and(char::is_ascii_lowercase)
+            }
+            _ => false,
+        };
+        if starts_word {
+            out.push('_');
+        }
+        out.push(c.to_ascii_lowercase());
+    }
+    out
+}
+
+/// `key` in camelCase, e.g. `tokenId` for `token_id`. Only underscores
+/// between a word and a lowercase letter are dropped, so leading and
+/// trailing ones, as in `type_`, are kept.
+pub fn to_camel_case(key: &str) -> String {
+    let mut out = String::with_capacity(key.len());
+    let mut chars = key.chars().peekable();
+    while let Some(c) = chars.next() {
+        let joins = c == '_'
+            && out.chars().last().is_some_and(|prev| prev != '_')
+            && chars.peek().is_some_and(char::is_ascii_lowercase);
+        match chars.next_if(|_| joins) {
+            Some(next) => out.push(next.to_ascii_uppercase()),
+            None => out.push(c),
+        }
+    }
+    out
+}
+
+/// A deserializer matching the names of struct fields and enum variants in
+/// snake_case. Serde asks for these names as identifiers, which the keys of
+/// maps are not, so those are deserialized as sent.
+pub(crate) struct SnakeCase<D>(pub D);
+
+/// Forward the `deserialize_*` methods of a deserializer wrapper, handing
+/// `$de`, the wrapper, to `$target` along with `$wrap`, the visitor.
+macro_rules! forward_deserialize {
+    ($de:ident => $target:expr, $visitor:ident => $wrap:expr;
+     $($method:ident($($arg:ident: $ty:ty),*)),* $(,)?) => {
+        $(
+            fn $method<V>(self, $($arg: $ty,)* $visitor: V) -> Result<V::Value, Self::Error>
+            where
+                V: Visitor<'de>,
+            {
+                let $de = self;
+                $target.$method($($arg,)* $wrap)
+            }
+        )*
+    };
+}
+
+/// The `deserialize_*` methods of [SnakeCase] and [Key], other than
+/// `deserialize_identifier`.
+macro_rules! deserialize_values {
+    ($de:ident => $target:expr, $visitor:ident => $wrap:expr) => {
+        forward_deserialize! {
+            $de => $target, $visitor => $wrap;
+            deserialize_any(), deserialize_bool(), deserialize_i8(), deserialize_i16(),
+            deserialize_i32(), deserialize_i64(), deserialize_i128(), deserialize_u8(),
+            deserialize_u16(), deserialize_u32(), deserialize_u64(), deserialize_u128(),
+            deserialize_f32(), deserialize_f64(), deserialize_char(), deserialize_str(),
+            deserialize_string(), deserialize_bytes(), deserialize_byte_buf(),
+            deserialize_option(), deserialize_unit(),
+            deserialize_unit_struct(name: &'static str),
+            deserialize_newtype_struct(name: &'static str),
+            deserialize_seq(), deserialize_tuple(len: usize),
+            deserialize_tuple_struct(name: &'static str, len: usize),
+            deserialize_map(),
+            deserialize_struct(name: &'static str, fields: &'static [&'static str]),
+            deserialize_enum(name: &'static str, variants: &'static [&'static str]),
+            deserialize_ignored_any(),
+        }
+    };
+}
+
+impl<'de, D: Deserializer<'de>> Deserializer<'de> for SnakeCase<D> {
+    type Error = D::Error;
+
+    deserialize_values!(de => de.0, visitor => Wrap(visitor));
+
+    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        Key::new(self.0, None).deserialize_identifier(visitor)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.0.is_human_readable()
+    }
+}
+
+/// A visitor handing the values nested in what it visits to [SnakeCase].
+struct Wrap<V>(V);
+
+/// Forward the `visit_*` methods of primitives to the wrapped visitor.
+macro_rules! forward_visit {
+    ($($method:ident($ty:ty)),* $(,)?) => {
+        $(
+            fn $method<E: de::Error>(self, v: $ty) -> Result<Self::Value, E> {
+                self.0.$method(v)
+            }
+        )*
+    };
+}
+
+impl<'de, V: Visitor<'de>> Visitor<'de> for Wrap<V> {
+    type Value = V::Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.expecting(f)
+    }
+
+    forward_visit! {
+        visit_bool(bool), visit_i8(i8), visit_i16(i16), visit_i32(i32), visit_i64(i64),
+        visit_i128(i128), visit_u8(u8), visit_u16(u16), visit_u32(u32), visit_u64(u64),
+        visit_u128(u128), visit_f32(f32), visit_f64(f64), visit_char(char),
+        visit_str(&str), visit_borrowed_str(&'de str), visit_string(String),
+        visit_bytes(&[u8]), visit_borrowed_bytes(&'de [u8]), visit_byte_buf(Vec<u8>),
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+        self.0.visit_none()
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        self.0.visit_unit()
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        self.0.visit_some(SnakeCase(deserializer))
+    }
+
+    fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        self.0.visit_newtype_struct(SnakeCase(deserializer))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
+        self.0.visit_seq(Seq(seq))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+        self.0.visit_map(Entries {
+            map,
+            names: Vec::new(),
+        })
+    }
+
+    fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<Self::Value, A::Error> {
+        self.0.visit_enum(Variants(data))
+    }
+}
+
+/// A seed deserializing its value through [SnakeCase].
+struct Seed<S>(S);
+
+impl<'de, S: DeserializeSeed<'de>> DeserializeSeed<'de> for Seed<S> {
+    type Value = S::Value;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<S::Value, D::Error> {
+        self.0.deserialize(SnakeCase(deserializer))
+    }
+}
+
+struct Seq<A>(A);
+
+impl<'de, A: SeqAccess<'de>> SeqAccess<'de> for Seq<A> {
+    type Error = A::Error;
+
+    fn next_element_seed<S>(&mut self, seed: S) -> Result<Option<S::Value>, A::Error>
+    where
+        S: DeserializeSeed<'de>,
+    {
+        self.0.next_element_seed(Seed(seed))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.0.size_hint()
+    }
+}
+
+/// The entries of a map or struct, along with the keys it was sent with and
+/// the names they were matched as, to reject a name sent twice.
+struct Entries<A> {
+    map: A,
+    names: Vec<(String, String)>,
+}
+
+impl<'de, A: MapAccess<'de>> MapAccess<'de> for Entries<A> {
+    type Error = A::Error;
+
+    fn next_key_seed<S>(&mut self, seed: S) -> Result<Option<S::Value>, A::Error>
+    where
+        S: DeserializeSeed<'de>,
+    {
+        self.map.next_key_seed(KeySeed {
+            seed,
+            names: Some(&mut self.names),
+        })
+    }
+
+    fn next_value_seed<S>(&mut self, seed: S) -> Result<S::Value, A::Error>
+    where
+        S: DeserializeSeed<'de>,
+    {
+        self.map.next_value_seed(Seed(seed))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.map.size_hint()
+    }
+}
+
+struct Variants<A>(A);
+
+impl<'de, A: EnumAccess<'de>> EnumAccess<'de> for Variants<A> {
+    type Error = A::Error;
+    type Variant = Variant<A::Variant>;
+
+    fn variant_seed<S>(self, seed: S) -> Result<(S::Value, Self::Variant), A::Error>
+    where
+        S: DeserializeSeed<'de>,
+    {
+        let seed = KeySeed { seed, names: None };
+        let (value, variant) = self.0.variant_seed(seed)?;
+        Ok((value, Variant(variant)))
+    }
+}
+
+struct Variant<A>(A);
+
+impl<'de, A: VariantAccess<'de>> VariantAccess<'de> for Variant<A> {
+    type Error = A::Error;
+
+    fn unit_variant(self) -> Result<(), A::Error> {
+        self.0.unit_variant()
+    }
+
+    fn newtype_variant_seed<S>(self, seed: S) -> Result<S::Value, A::Error>
+    where
+        S: DeserializeSeed<'de>,
+    {
+        self.0.newtype_variant_seed(Seed(seed))
+    }
+
+    fn tuple_variant<V>(self, len: usize, visitor: V) -> Result<V::Value, A::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.0.tuple_variant(len, Wrap(visitor))
+    }
+
+    fn struct_variant<V>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, A::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.0.struct_variant(fields, Wrap(visitor))
+    }
+}
+
+/// A seed deserializing a map key or an enum variant through [Key].
+struct KeySeed<'n, S> {
+    seed: S,
+    names: Option<&'n mut Vec<(String, String)>>,
+}
+
+impl<'de, S: DeserializeSeed<'de>> DeserializeSeed<'de> for KeySeed<'_, S> {
+    type Value = S::Value;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<S::Value, D::Error> {
+        self.seed.deserialize(Key::new(deserializer, self.names))
+    }
+}
+
+/// The deserializer of a map key or an enum variant, converting it to
+/// snake_case when it is deserialized as an identifier. With `names`, the
+/// names already matched in the map, a name matched twice is an error.
+struct Key<'n, D> {
+    inner: D,
+    names: Option<&'n mut Vec<(String, String)>>,
+}
+
+impl<'n, D> Key<'n, D> {
+    fn new(inner: D, names: Option<&'n mut Vec<(String, String)>>) -> Self {
+        Key { inner, names }
+    }
+}
+
+impl<'de, D: Deserializer<'de>> Deserializer<'de> for Key<'_, D> {
+    type Error = D::Error;
+
+    deserialize_values!(key => SnakeCase(key.inner), visitor => visitor);
+
+    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.inner.deserialize_identifier(Identifier {
+            visitor,
+            names: self.names,
+        })
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.inner.is_human_readable()
+    }
+}
+
+/// A visitor handing identifiers to the wrapped one in snake_case.
+struct Identifier<'n, V> {
+    visitor: V,
+    names: Option<&'n mut Vec<(String, String)>>,
+}
+
+impl<V> Identifier<'_, V> {
+    /// The name `key` is matched as, failing if another key of the same map
+    /// was matched as it too.
+    fn name<E: de::Error>(&mut self, key: &str) -> Result<String, E> {
+        let name = to_snake_case(key);
+        if let Some(names) = self.names.as_mut() {
+            if let Some((other, _)) = names.iter().find(|(_, seen)| *seen == name) {
+                let msg = format!("keys {:?} and {:?} both name {:?}", other, key, name);
+                return Err(E::custom(msg));
+            }
+            names.push((key.to_string(), name.clone()));
+        }
+        Ok(name)
+    }
+}
+
+impl<'de, V: Visitor<'de>> Visitor<'de> for Identifier<'_, V> {
+    type Value = V::Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.visitor.expecting(f)
+    }
+
+    fn visit_str<E: de::Error>(mut self, v: &str) -> Result<Self::Value, E> {
+        let name = self.name(v)?;
+        self.visitor.visit_string(name)
+    }
+
+    fn visit_borrowed_str<E: de::Error>(self, v: &'de str) -> Result<Self::Value, E> {
+        self.visit_str(v)
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Self::Value, E> {
+        self.visit_str(&v)
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+        self.visitor.visit_u64(v)
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+        match std::str::from_utf8(v) {
+            Ok(v) => self.visit_str(v),
+            Err(_) => self.visitor.visit_bytes(v),
+        }
+    }
+}
+
+/// `value` as JSON, with the names of its struct fields and enum variants in
+/// camelCase. The keys of maps are kept as they are.
+pub(crate) fn to_camel_case_value<T>(value: &T) -> Result<Value, serde_json::Error>
+where
+    T: Serialize + ?Sized,
+{
+    value.serialize(CamelCase)
+}
+
+/// A serializer building the JSON of a value with the names of its fields
+/// and variants in camelCase.
+struct CamelCase;
+
+/// Forward the `serialize_*` methods of primitives to the serializer of
+/// `serde_json`.
+macro_rules! forward_serialize {
+    ($($method:ident($ty:ty)),* $(,)?) => {
+        $(
+            fn $method(self, v: $ty) -> Result<Value, serde_json::Error> {
+                serde_json::value::Serializer.$method(v)
+            }
+        )*
+    };
+}
+
+impl Serializer for CamelCase {
+    type Ok = Value;
+    type Error = serde_json::Error;
+    type SerializeSeq = Items;
+    type SerializeTuple = Items;
+    type SerializeTupleStruct = Items;
+    type SerializeTupleVariant = Items;
+    type SerializeMap = Fields;
+    type SerializeStruct = Fields;
+    type SerializeStructVariant = Fields;
+
+    forward_serialize! {
+        serialize_bool(bool), serialize_i8(i8), serialize_i16(i16), serialize_i32(i32),
+        serialize_i64(i64), serialize_i128(i128), serialize_u8(u8), serialize_u16(u16),
+        serialize_u32(u32), serialize_u64(u64), serialize_u128(u128), serialize_f32(f32),
+        serialize_f64(f64), serialize_char(char), serialize_str(&str), serialize_bytes(&[u8]),
+    }
+
+    fn serialize_none(self) -> Result<Value, serde_json::Error> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Value, serde_json::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Value, serde_json::Error> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Value, serde_json::Error> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<Value, serde_json::Error> {
+        Ok(Value::String(to_camel_case(variant)))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Value, serde_json::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Value, serde_json::Error> {
+        Ok(tagged(Some(variant), value.serialize(self)?))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Items, serde_json::Error> {
+        Ok(Items::new(None, len.unwrap_or_default()))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Items, serde_json::Error> {
+        Ok(Items::new(None, len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Items, serde_json::Error> {
+        Ok(Items::new(None, len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Items, serde_json::Error> {
+        Ok(Items::new(Some(variant), len))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Fields, serde_json::Error> {
+        Ok(Fields::new(None))
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Fields, serde_json::Error> {
+        Ok(Fields::new(None))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Fields, serde_json::Error> {
+        Ok(Fields::new(Some(variant)))
+    }
+}
+
+/// `value`, under the name of `variant` in camelCase if it is one.
+fn tagged(variant: Option<&str>, value: Value) -> Value {
+    match variant {
+        Some(variant) => Value::Object(Map::from_iter([(to_camel_case(variant), value)])),
+        None => value,
+    }
+}
+
+/// The items of a sequence or tuple, or of a tuple variant.
+struct Items {
+    variant: Option<&'static str>,
+    items: Vec<Value>,
+}
+
+impl Items {
+    fn new(variant: Option<&'static str>, len: usize) -> Self {
+        Items {
+            variant,
+            items: Vec::with_capacity(len),
+        }
+    }
+
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), serde_json::Error> {
+        self.items.push(value.serialize(CamelCase)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Value, serde_json::Error> {
+        Ok(tagged(self.variant, Value::Array(self.items)))
+    }
+}
+
+/// Implement the serializer traits of sequences for [Items].
+macro_rules! serialize_items {
+    ($($trait:ident::$method:ident),* $(,)?) => {
+        $(
+            impl ser::$trait for Items {
+                type Ok = Value;
+                type Error = serde_json::Error;
+
+                fn $method<T>(&mut self, value: &T) -> Result<(), serde_json::Error>
+                where
+                    T: Serialize + ?Sized,
+                {
+                    self.push(value)
+                }
+
+                fn end(self) -> Result<Value, serde_json::Error> {
+                    self.finish()
+                }
+            }
+        )*
+    };
+}
+
+serialize_items! {
+    SerializeSeq::serialize_element,
+    SerializeTuple::serialize_element,
+    SerializeTupleStruct::serialize_field,
+    SerializeTupleVariant::serialize_field,
+}
+
+/// The entries of a map, or the fields of a struct or struct variant.
+struct Fields {
+    variant: Option<&'static str>,
+    fields: Map<String, Value>,
+    key: Option<String>,
+}
+
+impl Fields {
+    fn new(variant: Option<&'static str>) -> Self {
+        Fields {
+            variant,
+            fields: Map::new(),
+            key: None,
+        }
+    }
+
+    /// Add the field named `name` in camelCase, failing if another field of
+    /// the struct is named the same in camelCase.
+    fn field<T>(&mut self, name: &'static str, value: &T) -> Result<(), serde_json::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let key = to_camel_case(name);
+        if self.fields.contains_key(&key) {
+            return Err(ser::Error::custom(format!(
+                "two fields are named {:?}",
+                key
+            )));
+        }
+        self.fields.insert(key, value.serialize(CamelCase)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Value, serde_json::Error> {
+        Ok(tagged(self.variant, Value::Object(self.fields)))
+    }
+}
+
+impl ser::SerializeMap for Fields {
+    type Ok = Value;
+    type Error = serde_json::Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), serde_json::Error> {
+        let key = match key.serialize(serde_json::value::Serializer)? {
+            Value::String(key) => key,
+            key @ (Value::Number(_) | Value::Bool(_)) => key.to_string(),
+            _ => return Err(ser::Error::custom("key must be a string")),
+        };
+        self.key = Some(key);
+        Ok(())
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), serde_json::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| ser::Error::custom("serialize_value called before serialize_key"))?;
+        self.fields.insert(key, value.serialize(CamelCase)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, serde_json::Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for Fields {
+    type Ok = Value;
+    type Error = serde_json::Error;
+
+    fn serialize_field<T>(&mut self, name: &'static str, value: &T) -> Result<(), serde_json::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.field(name, value)
+    }
+
+    fn end(self) -> Result<Value, serde_json::Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for Fields {
+    type Ok = Value;
+    type Error = serde_json::Error;
+
+    fn serialize_field<T>(&mut self, name: &'static str, value: &T) -> Result<(), serde_json::Error>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.field(name, value)
+    }
+
+    fn end(self) -> Result<Value, serde_json::Error> {
+        self.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+    use crate::manager::Manager;
+    use crate::module::Module;
+    use crate::response::Response;
+    use crate::test_utils::info;
+    use cosmwasm_std::testing::{mock_dependencies, mock_env};
+    use cosmwasm_std::{from_json, Deps, DepsMut, Env, MessageInfo};
+    use serde::{Deserialize, Serialize};
+    use serde_json::json;
+
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+    #[serde(rename_all = "snake_case")]
+    enum SaleMsg {
+        BuyNow { token_id: String, extension: Value },
+        CancelAll,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+    #[serde(rename_all = "snake_case")]
+    enum SaleQuery {
+        LastSale {},
+    }
+
+    /// A module answering queries with the last message it executed.
+    #[derive(Default)]
+    struct Sale {
+        last: Option<SaleMsg>,
+    }
+
+    impl Module for Sale {
+        type InstantiateMsg = Value;
+        type ExecuteMsg = SaleMsg;
+        type QueryMsg = SaleQuery;
+        type QueryResp = Option<SaleMsg>;
+        type Error = Error;
+
+        fn instantiate(
+            &mut self,
+            _deps: &mut DepsMut,
+            _env: &Env,
+            _info: &MessageInfo,
+            _msg: Value,
+        ) -> Result<Response, Error> {
+            Ok(Response::new())
+        }
+
+        fn execute(
+            &mut self,
+            _deps: &mut DepsMut,
+            _env: Env,
+            _info: MessageInfo,
+            msg: SaleMsg,
+        ) -> Result<Response, Error> {
+            self.last = Some(msg);
+            Ok(Response::new())
+        }
+
+        fn query(
+            &self,
+            _deps: &Deps,
+            _env: Env,
+            _msg: SaleQuery,
+        ) -> Result<Self::QueryResp, Error> {
+            Ok(self.last.clone())
+        }
+    }
+
+    fn parse(msg: Value) -> Result<SaleMsg, serde_json::Error> {
+        SaleMsg::deserialize(SnakeCase(&msg))
+    }
+
+    #[test]
+    fn snake_case_splits_words_and_keeps_acronyms() {
+        let cases = [
+            ("tokenId", "token_id"),
+            ("tokenID", "token_id"),
+            ("ID", "id"),
+            ("HTTPServer", "http_server"),
+            ("owner2Id", "owner2_id"),
+            ("token_id", "token_id"),
+            ("tokenId_", "token_id_"),
+            ("type_", "type_"),
+            ("_private", "_private"),
+        ];
+        for (key, expected) in cases {
+            assert_eq!(to_snake_case(key), expected, "{}", key);
+        }
+    }
+
+    #[test]
+    fn camel_case_keeps_leading_and_trailing_underscores() {
+        let cases = [
+            ("token_id", "tokenId"),
+            ("http_server", "httpServer"),
+            ("token_id_", "tokenId_"),
+            ("type_", "type_"),
+            ("_private", "_private"),
+            ("a__b", "a__b"),
+            ("v_2", "v_2"),
+        ];
+        for (key, expected) in cases {
+            assert_eq!(to_camel_case(key), expected, "{}", key);
+        }
+    }
+
+    #[test]
+    fn only_field_and_variant_names_are_converted() {
+        let msg = json!({"buyNow": {"tokenId": "1", "extension": {"imageUrl": "ipfs://"}}});
+        let expected = SaleMsg::BuyNow {
+            token_id: "1".to_string(),
+            extension: json!({"imageUrl": "ipfs://"}),
+        };
+        assert_eq!(parse(msg).unwrap(), expected);
+        assert_eq!(parse(json!("cancelAll")).unwrap(), SaleMsg::CancelAll);
+    }
+
+    #[test]
+    fn names_sent_twice_are_rejected() {
+        let msg = json!({"buyNow": {"tokenId": "1", "token_id": "2", "extension": null}});
+        let err = parse(msg).unwrap_err();
+        assert!(
+            err.to_string().contains("both name \"token_id\""),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn responses_are_camel_cased_but_map_keys_are_kept() {
+        let msg = SaleMsg::BuyNow {
+            token_id: "1".to_string(),
+            extension: json!({"image_url": "ipfs://"}),
+        };
+        let expected = json!({"buyNow": {"tokenId": "1", "extension": {"image_url": "ipfs://"}}});
+        assert_eq!(to_camel_case_value(&msg).unwrap(), expected);
+        assert_eq!(
+            to_camel_case_value(&SaleMsg::CancelAll).unwrap(),
+            json!("cancelAll")
+        );
+    }
+
+    #[test]
+    fn manager_converts_payloads_and_query_responses() {
+        let mut manager = Manager::builder()
+            .with_boxed_module("sale", Box::new(Sale::default()))
+            .unwrap()
+            .key_case(KeyCase::CamelCaseOut)
+            .build();
+        let mut deps = mock_dependencies();
+        let buy =
+            r#"{"sale": {"buyNow": {"tokenId": "1", "extension": {"image_url": "ipfs://"}}}}"#;
+        manager
+            .execute(&mut deps.as_mut(), mock_env(), info("buyer", &[]), buy)
+            .unwrap();
+
+        let resp = manager
+            .query(&deps.as_ref(), mock_env(), r#"{"sale": {"lastSale": {}}}"#)
+            .unwrap();
+        let expected = json!({"buyNow": {"tokenId": "1", "extension": {"image_url": "ipfs://"}}});
+        assert_eq!(from_json::<Value>(&resp).unwrap(), expected);
+
+        let twice = r#"{"sale": {"buyNow": {"tokenId": "1", "token_id": "2", "extension": {}}}}"#;
+        manager
+            .execute(&mut deps.as_mut(), mock_env(), info("buyer", &[]), twice)
+            .unwrap_err();
+    }
+}
@@ -0,0 +1,143 @@
+//! Calling glue contracts from other contracts.
+//!
+//! A [ModuleClient] addresses one module of a glue contract, and builds the
+//! messages and queries for it with the payload wrapped in the module's key,
+//! so contract-to-contract calls need no hand-rolled JSON:
+//!
+//! ```ignore
+//! let token = GlueClient::new(token_contract).module("token");
+//! let balance: BalanceResponse =
+//!     token.query(&deps.querier, &cw20::QueryMsg::Balance { address })?;
+//! let transfer = token.execute(&cw20::ExecuteMsg::Transfer { recipient, amount }, vec![])?;
+//! Ok(Response::new().add_message(transfer))
+//! ```
+//!
+//! One-off queries can go through the [GlueQuerier] extension of
+//! `QuerierWrapper` instead:
+//!
+//! ```ignore
+//! let balance: BalanceResponse = deps.querier.query_glue_module(
+//!     token_contract,
+//!     "token",
+//!     &cw20::QueryMsg::Balance { address },
+//! )?;
+//! ```
+
+use cosmwasm_std::{
+    to_json_binary, Addr, Binary, Coin, CosmosMsg, CustomQuery, QuerierWrapper, QueryRequest,
+    StdResult, WasmMsg, WasmQuery,
+};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// A glue contract.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GlueClient {
+    contract: String,
+}
+
+impl GlueClient {
+    pub fn new(contract: impl Into<String>) -> Self {
+        GlueClient {
+            contract: contract.into(),
+        }
+    }
+
+    /// The address of the contract.
+    pub fn contract(&self) -> &str {
+        &self.contract
+    }
+
+    /// The module of the contract registered as `module`.
+    pub fn module(&self, module: impl Into<String>) -> ModuleClient {
+        ModuleClient {
+            contract: self.contract.clone(),
+            module: module.into(),
+        }
+    }
+}
+
+impl From<Addr> for GlueClient {
+    fn from(contract: Addr) -> Self {
+        GlueClient::new(contract)
+    }
+}
+
+/// A module of a glue contract.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ModuleClient {
+    contract: String,
+    module: String,
+}
+
+impl ModuleClient {
+    /// The address of the contract.
+    pub fn contract(&self) -> &str {
+        &self.contract
+    }
+
+    /// The name of the module.
+    pub fn module(&self) -> &str {
+        &self.module
+    }
+
+    /// The message `msg` as addressed to the module.
+    pub fn payload(&self, msg: &impl Serialize) -> StdResult<Binary> {
+        to_json_binary(&BTreeMap::from([(&self.module, msg)]))
+    }
+
+    /// The message executing `msg` on the module, sending `funds` along.
+    pub fn execute<C>(&self, msg: &impl Serialize, funds: Vec<Coin>) -> StdResult<CosmosMsg<C>> {
+        Ok(WasmMsg::Execute {
+            contract_addr: self.contract.clone(),
+            msg: self.payload(msg)?,
+            funds,
+        }
+        .into())
+    }
+
+    /// The request querying the module with `msg`.
+    pub fn query_request<Q: CustomQuery>(
+        &self,
+        msg: &impl Serialize,
+    ) -> StdResult<QueryRequest<Q>> {
+        Ok(WasmQuery::Smart {
+            contract_addr: self.contract.clone(),
+            msg: self.payload(msg)?,
+        }
+        .into())
+    }
+
+    /// Query the module with `msg`, decoding the response as `T`.
+    pub fn query<T: DeserializeOwned, Q: CustomQuery>(
+        &self,
+        querier: &QuerierWrapper<Q>,
+        msg: &impl Serialize,
+    ) -> StdResult<T> {
+        querier.query(&self.query_request(msg)?)
+    }
+}
+
+/// Queries to the modules of glue contracts through a `QuerierWrapper`.
+pub trait GlueQuerier {
+    /// Query the module registered as `module_name` of the glue contract at
+    /// `contract_addr` with `msg`, decoding the response as `R`.
+    fn query_glue_module<R: DeserializeOwned>(
+        &self,
+        contract_addr: impl Into<String>,
+        module_name: impl Into<String>,
+        msg: &impl Serialize,
+    ) -> StdResult<R>;
+}
+
+impl<Q: CustomQuery> GlueQuerier for QuerierWrapper<'_, Q> {
+    fn query_glue_module<R: DeserializeOwned>(
+        &self,
+        contract_addr: impl Into<String>,
+        module_name: impl Into<String>,
+        msg: &impl Serialize,
+    ) -> StdResult<R> {
+        GlueClient::new(contract_addr).module(module_name).query(self, msg)
+    }
+}
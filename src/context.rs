@@ -0,0 +1,494 @@
+//! The context in which the Manager dispatches a message to a module.
+//!
+//! A [Context] is handed to the `*_with_context` handlers of a
+//! [Module][crate::module::Module]. It gives the module access to the
+//! [Manager][crate::manager::Manager] it is registered with for the duration
+//! of the call, which lets modules read from their sibling modules without
+//! holding references to them:
+//!
+//! ```ignore
+//! fn execute_with_context(
+//!     &mut self,
+//!     ctx: &Context,
+//!     deps: &mut DepsMut,
+//!     env: Env,
+//!     info: MessageInfo,
+//!     msg: ExecuteMsg,
+//! ) -> Result<Response, Error> {
+//!     let owner: OwnerOfResponse = ctx.query(
+//!         &deps.as_ref(),
+//!         &env,
+//!         "nft",
+//!         &NftQueryMsg::OwnerOf { token_id: msg.token_id.clone() },
+//!     )?;
+//!     // ...
+//! }
+//! ```
+
+use crate::case::KeyCase;
+use crate::error::Error;
+use crate::factory::{self, PendingChild};
+use crate::manager::Manager;
+use crate::receive::Received;
+use crate::reply;
+use crate::response::Response;
+use crate::shared::Shared;
+use cosmwasm_std::{
+    from_json, Addr, Binary, CosmosMsg, CustomQuery, Deps, DepsMut, Empty, Env, MessageInfo,
+    StdError, Storage, SubMsg, WasmMsg,
+};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::cell::RefCell;
+
+/// The context of a single dispatch to a module.
+pub struct Context<'a, C = Binary, Q: CustomQuery = Empty> {
+    manager: &'a Manager<C, Q>,
+    module: &'a str,
+    published: RefCell<Vec<(String, Value)>>,
+    received: Option<Received>,
+    signer: Option<Addr>,
+    converts_response: bool,
+}
+
+impl<'a, C: Clone, Q: CustomQuery> Context<'a, C, Q> {
+    pub(crate) fn new(manager: &'a Manager<C, Q>, module: &'a str) -> Self {
+        Context {
+            manager,
+            module,
+            published: RefCell::new(Vec::new()),
+            received: None,
+            signer: None,
+            converts_response: false,
+        }
+    }
+
+    pub(crate) fn with_received(mut self, received: Option<Received>) -> Self {
+        self.received = received;
+        self
+    }
+
+    pub(crate) fn with_signer(mut self, signer: Option<Addr>) -> Self {
+        self.signer = signer;
+        self
+    }
+
+    pub(crate) fn with_converted_response(mut self, converts: bool) -> Self {
+        self.converts_response = converts;
+        self
+    }
+
+    /// The tokens received along with the message being handled, if it was
+    /// routed to the module from a token receive hook; see
+    /// [receive][crate::receive].
+    pub fn received(&self) -> Option<&Received> {
+        self.received.as_ref()
+    }
+
+    /// The signer of the permit the query being handled was authenticated
+    /// with, if any; see [permit][crate::permit].
+    pub fn signer(&self) -> Option<&Addr> {
+        self.signer.as_ref()
+    }
+
+    /// The reply id to send a submessage with for the reply to reach the
+    /// module's [reply][crate::module::Module::reply] handler with `id`.
+    ///
+    /// The top bit of `id` is reserved for the ids of
+    /// [callbacks][Self::send_with_callback], so ids from `2^31` on fail with
+    /// [ReservedReplyId][Error::ReservedReplyId].
+    pub fn reply_id(&self, id: u32) -> Result<u64, Error> {
+        if id & reply::CALLBACK_FLAG != 0 {
+            return Err(Error::ReservedReplyId {
+                module: self.module.to_string(),
+                id,
+            });
+        }
+        Ok(reply::reply_id(self.module, id))
+    }
+
+    /// The submessage sending `msg` with `callback` attached. Once `msg`
+    /// completes, successfully or not, `callback` is handed to the module's
+    /// [on_callback][crate::module::Module::on_callback] handler; see
+    /// [reply][crate::reply].
+    pub fn send_with_callback(
+        &self,
+        storage: &mut dyn Storage,
+        env: &Env,
+        msg: impl Into<CosmosMsg<C>>,
+        callback: &impl Serialize,
+    ) -> Result<SubMsg<C>, Error> {
+        let callback = serde_json::to_value(callback)?;
+        let id = reply::save_callback(storage, env, self.module, callback, None)?;
+        Ok(SubMsg::reply_always(msg, id))
+    }
+
+    /// The submessage sending the `WasmMsg::Instantiate` `msg`. Once the
+    /// child contract is instantiated, glue records its address under the
+    /// module and the label of `msg`, to be looked up with [factory::child];
+    /// see [factory][crate::factory]. Labels are unique per module.
+    pub fn instantiate_child(
+        &self,
+        storage: &mut dyn Storage,
+        env: &Env,
+        msg: WasmMsg,
+    ) -> Result<SubMsg<C>, Error> {
+        let (code_id, label) = match &msg {
+            WasmMsg::Instantiate { code_id, label, .. } => (*code_id, label.clone()),
+            _ => return Err(StdError::generic_err("expected a WasmMsg::Instantiate").into()),
+        };
+        if factory::child(storage, self.module, &label)?.is_some() {
+            return Err(Error::ChildAlreadyExists {
+                module: self.module.to_string(),
+                label,
+            });
+        }
+        let child = PendingChild { label, code_id };
+        let id = reply::save_callback(storage, env, self.module, Value::Null, Some(child))?;
+        Ok(SubMsg::reply_on_success(msg, id))
+    }
+
+    /// The Manager the module is registered with.
+    pub fn manager(&self) -> &Manager<C, Q> {
+        self.manager
+    }
+
+    /// Send the query `msg` to the sibling module registered under `module`
+    /// and decode its response.
+    ///
+    /// The query is dispatched in-process, without going through the chain's
+    /// querier. A module can't query itself or a module that is further up
+    /// the current call stack while that module is executing; doing so fails
+    /// with [ModuleBusy][Error::ModuleBusy].
+    pub fn query<T: DeserializeOwned>(
+        &self,
+        deps: &Deps<Q>,
+        env: &Env,
+        module: &str,
+        msg: &impl Serialize,
+    ) -> Result<T, Error> {
+        let payload = serde_json::to_value(msg)?;
+        let resp = self
+            .manager
+            .query_module(deps, env.clone(), module, &payload)?;
+        Ok(from_json(&resp)?)
+    }
+
+    /// Like [query][Self::query], but answers repeated queries from memory.
+    ///
+    /// Results are cached until the message the Manager is dispatching has
+    /// been handled, so they are shared by the entries of a batch, hook
+    /// subscribers, and nested [dispatches][Self::dispatch]. They are keyed
+    /// by the module, with aliases and ids resolved to its name, and the JSON
+    /// encoding of `msg`. Only use this for queries whose results can't
+    /// change during the dispatch, e.g. because no module writes to the state
+    /// they read.
+    pub fn query_cached<T: DeserializeOwned>(
+        &self,
+        deps: &Deps<Q>,
+        env: &Env,
+        module: &str,
+        msg: &impl Serialize,
+    ) -> Result<T, Error> {
+        let payload = serde_json::to_value(msg)?;
+        let key = payload.to_string();
+        if let Some(resp) = self.manager.cached_query(module, &key) {
+            return Ok(from_json(&resp)?);
+        }
+        let resp = self
+            .manager
+            .query_module(deps, env.clone(), module, &payload)?;
+        let result = from_json(&resp)?;
+        self.manager.cache_query(module, key, resp);
+        Ok(result)
+    }
+
+    /// Dispatch the execute message `msg`, a glue envelope such as
+    /// `{ "token": { ... } }`, through the Manager as if `info.sender` had
+    /// sent it, and return the response.
+    ///
+    /// The message runs through the same checks and middleware as a message
+    /// sent to the contract. Its response, including any messages, is not
+    /// added to the calling module's response; merge what you need into it.
+    /// Like [query][Self::query], a module can't dispatch to itself or to a
+    /// module further up the call stack.
+    pub fn dispatch(
+        &self,
+        deps: &mut DepsMut<Q>,
+        env: &Env,
+        info: &MessageInfo,
+        msg: &impl Serialize,
+    ) -> Result<Response<C>, Error> {
+        let msg = serde_json::to_value(msg)?;
+        self.manager.dispatch_value(deps, env, info, msg)
+    }
+
+    /// Resolve the implementation registered for the interface `name`; see
+    /// [Manager::register_interface].
+    ///
+    /// The implementation may be a module that is currently executing, e.g.
+    /// the calling module itself, so borrow it with
+    /// [try_borrow][crate::shared::try_borrow] or
+    /// [try_borrow_mut][crate::shared::try_borrow_mut].
+    pub fn interface<I: ?Sized + 'static>(&self, name: &str) -> Result<Shared<I>, Error> {
+        self.manager.interface(name)
+    }
+
+    /// The extension of type `T` registered with
+    /// [Manager::insert_extension], e.g. configuration shared by the modules
+    /// of the contract.
+    pub fn extension<T: 'static>(&self) -> Result<&'a T, Error> {
+        self.manager.extension().ok_or_else(|| Error::ExtensionNotFound {
+            extension: std::any::type_name::<T>().to_string(),
+        })
+    }
+
+    /// Publish `payload` on `topic`. Once the current handler returns, the
+    /// payload is delivered to every module subscribed to the topic with
+    /// [Manager::subscribe]. Hooks are only delivered during execute.
+    pub fn publish(&self, topic: impl Into<String>, payload: &impl Serialize) -> Result<(), Error> {
+        let payload = serde_json::to_value(payload)?;
+        self.published.borrow_mut().push((topic.into(), payload));
+        Ok(())
+    }
+
+    pub(crate) fn take_published(&self) -> Vec<(String, Value)> {
+        self.published.take()
+    }
+}
+
+impl<'a, C, Q: CustomQuery> Context<'a, C, Q> {
+    /// The name of the module being dispatched to.
+    pub fn module(&self) -> &str {
+        self.module
+    }
+
+    /// Whether the Manager parses payloads strictly; see
+    /// [Manager::set_strict].
+    pub fn is_strict(&self) -> bool {
+        self.manager.is_strict()
+    }
+
+    /// Whether the Manager validates payloads against module schemas; see
+    /// [Manager::set_validate_schemas].
+    pub fn validates_schemas(&self) -> bool {
+        self.manager.validates_schemas()
+    }
+
+    /// How the Manager converts the case of payload keys; see
+    /// [Manager::set_key_case].
+    pub fn key_case(&self) -> KeyCase {
+        self.manager.key_case()
+    }
+
+    /// Whether the response to the query being handled is encoded with the
+    /// names of its fields and variants in camelCase; see
+    /// [KeyCase::CamelCaseOut]. Only queries from outside the contract are.
+    pub(crate) fn converts_response(&self) -> bool {
+        self.converts_response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::module::Module;
+    use crate::test_utils::{info, MockDeps};
+    use cosmwasm_std::testing::{mock_dependencies, mock_env};
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+
+    /// A module counting the queries it answers.
+    struct Price {
+        queries: Arc<AtomicU32>,
+    }
+
+    impl Module for Price {
+        type InstantiateMsg = Value;
+        type ExecuteMsg = Value;
+        type QueryMsg = Value;
+        type QueryResp = u32;
+        type Error = Error;
+
+        fn instantiate(
+            &mut self,
+            _deps: &mut DepsMut,
+            _env: &Env,
+            _info: &MessageInfo,
+            _msg: Value,
+        ) -> Result<Response, Error> {
+            Ok(Response::new())
+        }
+
+        fn execute(
+            &mut self,
+            _deps: &mut DepsMut,
+            _env: Env,
+            _info: MessageInfo,
+            _msg: Value,
+        ) -> Result<Response, Error> {
+            Ok(Response::new())
+        }
+
+        fn query(&self, _deps: &Deps, _env: Env, _msg: Value) -> Result<u32, Error> {
+            Ok(self.queries.fetch_add(1, Ordering::SeqCst) + 1)
+        }
+    }
+
+    /// A module querying the price from the module its message names.
+    struct Buyer;
+
+    impl Module for Buyer {
+        type InstantiateMsg = Value;
+        type ExecuteMsg = String;
+        type QueryMsg = Value;
+        type QueryResp = Value;
+        type Error = Error;
+
+        fn instantiate(
+            &mut self,
+            _deps: &mut DepsMut,
+            _env: &Env,
+            _info: &MessageInfo,
+            _msg: Value,
+        ) -> Result<Response, Error> {
+            Ok(Response::new())
+        }
+
+        fn execute(
+            &mut self,
+            _deps: &mut DepsMut,
+            _env: Env,
+            _info: MessageInfo,
+            _msg: String,
+        ) -> Result<Response, Error> {
+            Err(StdError::generic_err("needs a context").into())
+        }
+
+        fn execute_with_context(
+            &mut self,
+            ctx: &Context,
+            deps: &mut DepsMut,
+            env: Env,
+            _info: MessageInfo,
+            module: String,
+        ) -> Result<Response, Error> {
+            let price: u32 = ctx.query_cached(&deps.as_ref(), &env, &module, &Value::Null)?;
+            Ok(Response::new().add_attribute("price", price.to_string()))
+        }
+
+        fn query(&self, _deps: &Deps, _env: Env, _msg: Value) -> Result<Value, Error> {
+            Ok(Value::Null)
+        }
+    }
+
+    /// A module quoting the price of the module its query names.
+    struct Quoter;
+
+    impl Module for Quoter {
+        type InstantiateMsg = Value;
+        type ExecuteMsg = Value;
+        type QueryMsg = String;
+        type QueryResp = u32;
+        type Error = Error;
+
+        fn instantiate(
+            &mut self,
+            _deps: &mut DepsMut,
+            _env: &Env,
+            _info: &MessageInfo,
+            _msg: Value,
+        ) -> Result<Response, Error> {
+            Ok(Response::new())
+        }
+
+        fn execute(
+            &mut self,
+            _deps: &mut DepsMut,
+            _env: Env,
+            _info: MessageInfo,
+            _msg: Value,
+        ) -> Result<Response, Error> {
+            Ok(Response::new())
+        }
+
+        fn query(&self, _deps: &Deps, _env: Env, _module: String) -> Result<u32, Error> {
+            Err(StdError::generic_err("needs a context").into())
+        }
+
+        fn query_with_context(
+            &self,
+            ctx: &Context,
+            deps: &Deps,
+            env: Env,
+            module: String,
+        ) -> Result<u32, Error> {
+            ctx.query(deps, &env, &module, &Value::Null)
+        }
+    }
+
+    fn execute(manager: &mut Manager, deps: &mut MockDeps, msg: &str) {
+        manager
+            .execute(&mut deps.as_mut(), mock_env(), info("sender", &[]), msg)
+            .unwrap();
+    }
+
+    #[test]
+    fn modules_query_their_siblings_in_process() {
+        let queries = Arc::new(AtomicU32::new(0));
+        let price = Price {
+            queries: queries.clone(),
+        };
+        let mut manager = Manager::builder()
+            .with_boxed_module("price", Box::new(price))
+            .unwrap()
+            .with_boxed_module("quoter", Box::new(Quoter))
+            .unwrap()
+            .with_boxed_module("buyer", Box::new(Buyer))
+            .unwrap()
+            .build();
+        let mut deps = mock_dependencies();
+        for expected in [1, 2] {
+            let msg = r#"{"quoter": "price"}"#;
+            let resp = manager.query(&deps.as_ref(), mock_env(), msg).unwrap();
+            assert_eq!(from_json::<u32>(resp).unwrap(), expected);
+        }
+
+        let msg = r#"{"quoter": "bank"}"#;
+        let err = manager.query(&deps.as_ref(), mock_env(), msg).unwrap_err();
+        assert!(err.to_string().contains("bank"), "{}", err);
+        let err = manager
+            .execute(
+                &mut deps.as_mut(),
+                mock_env(),
+                info("sender", &[]),
+                r#"{"buyer": "buyer"}"#,
+            )
+            .unwrap_err();
+        assert!(err.to_string().contains("is busy"), "{}", err);
+    }
+
+    #[test]
+    fn cached_queries_last_for_the_dispatch() {
+        let queries = Arc::new(AtomicU32::new(0));
+        let price = Price {
+            queries: queries.clone(),
+        };
+        let mut manager = Manager::builder()
+            .with_boxed_module("price", Box::new(price))
+            .unwrap()
+            .with_boxed_module("buyer", Box::new(Buyer))
+            .unwrap()
+            .build();
+        manager.register_alias("cost".to_string(), "price").unwrap();
+        let mut deps = mock_dependencies();
+
+        let batch = r#"{"_batch": [{"buyer": "price"}, {"buyer": "cost"}, {"buyer": "price"}]}"#;
+        execute(&mut manager, &mut deps, batch);
+        assert_eq!(queries.load(Ordering::SeqCst), 1);
+
+        execute(&mut manager, &mut deps, r#"{"buyer": "price"}"#);
+        assert_eq!(queries.load(Ordering::SeqCst), 2);
+    }
+}
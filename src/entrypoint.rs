@@ -0,0 +1,111 @@
+//! Building the Manager in contract entrypoints.
+//!
+//! Contracts build their [Manager] anew on every call. Rather than wiring
+//! the modules in each entrypoint, implement [ManagerFactory] once, sharing
+//! modules between their dependents as usual:
+//!
+//! ```ignore
+//! struct Shop;
+//!
+//! impl ManagerFactory for Shop {
+//!     fn build(&self) -> Result<Manager, Error> {
+//!         let token = shared(Token::new());
+//!         let sale = shared(Sale::new(token.clone()));
+//!         Ok(Manager::builder()
+//!             .with_module("token", token)?
+//!             .with_module("sale", sale)?
+//!             .build())
+//!     }
+//! }
+//! ```
+//!
+//! Each entrypoint then becomes a one-liner:
+//!
+//! ```ignore
+//! #[entry_point]
+//! pub fn execute(
+//!     deps: DepsMut,
+//!     env: Env,
+//!     info: MessageInfo,
+//!     msg: Binary,
+//! ) -> Result<Response, Error> {
+//!     Shop.execute(deps, env, info, &msg)
+//! }
+//!
+//! #[entry_point]
+//! pub fn query(deps: Deps, env: Env, msg: Binary) -> StdResult<Binary> {
+//!     Shop.query(deps, env, &msg)
+//! }
+//! ```
+//!
+//! Entrypoints that need the Manager for something else, e.g. to read a
+//! module's state, use [with_manager].
+
+use crate::error::Error;
+use crate::manager::Manager;
+use cosmwasm_std::{
+    Binary, CustomQuery, Deps, DepsMut, Empty, Env, MessageInfo, Reply, Response, StdResult,
+};
+
+/// Builds the [Manager] of a contract, with its modules registered.
+pub trait ManagerFactory<C: Clone = Binary, Q: CustomQuery = Empty> {
+    /// A new Manager with the contract's modules registered.
+    fn build(&self) -> Result<Manager<C, Q>, Error>;
+
+    /// Build the Manager and dispatch an instantiate message to it; see
+    /// [Manager::instantiate_raw].
+    fn instantiate(
+        &self,
+        deps: DepsMut<Q>,
+        env: Env,
+        info: MessageInfo,
+        msg: &[u8],
+    ) -> Result<Response<C>, Error> {
+        self.build()?.instantiate_raw(deps, env, info, msg)
+    }
+
+    /// Build the Manager and dispatch an execute message to it; see
+    /// [Manager::execute_raw].
+    fn execute(
+        &self,
+        mut deps: DepsMut<Q>,
+        env: Env,
+        info: MessageInfo,
+        msg: &[u8],
+    ) -> Result<Response<C>, Error> {
+        self.build()?.execute_raw(&mut deps, env, info, msg)
+    }
+
+    /// Build the Manager and dispatch a query message to it; see
+    /// [Manager::query_raw].
+    fn query(&self, deps: Deps<Q>, env: Env, msg: &[u8]) -> StdResult<Binary> {
+        self.build()?.query_raw(&deps, env, msg)
+    }
+
+    /// Build the Manager and dispatch a migrate message to it; see
+    /// [Manager::migrate_raw].
+    fn migrate(&self, deps: DepsMut<Q>, env: Env, msg: &[u8]) -> Result<Response<C>, Error> {
+        self.build()?.migrate_raw(deps, env, msg)
+    }
+
+    /// Build the Manager and hand it the reply to a submessage; see
+    /// [Manager::reply].
+    fn reply(&self, deps: DepsMut<Q>, env: Env, msg: Reply) -> Result<Response<C>, Error> {
+        self.build()?.reply(deps, env, msg)
+    }
+}
+
+/// Build the Manager with `factory` and call `f` with it.
+pub fn with_manager<F, C, Q, T, E>(
+    factory: &F,
+    f: impl FnOnce(&mut Manager<C, Q>) -> Result<T, E>,
+) -> Result<T, E>
+where
+    F: ManagerFactory<C, Q> + ?Sized,
+    C: Clone,
+    Q: CustomQuery,
+    E: From<Error>,
+{
+    let mut manager = factory.build()?;
+    f(&mut manager)
+}
@@ -1,5 +1,30 @@
 //! Errors generated by the module manager.
+//!
+//! Errors keep the errors they were caused by as their
+//! [sources][std::error::Error::source], down to e.g. the serde error a
+//! module failed to parse its payload with. [error_chain] renders an error
+//! with all of its sources, as does the conversion to [StdError].
+//!
+//! Besides its message, every [Error] has a stable, machine-readable
+//! [code][Error::code], so frontends can branch on failures without parsing
+//! messages. Modules opt into codes for their own errors with
+//! [error_code][crate::module::Module::error_code]. With
+//! [Manager::set_structured_errors][crate::manager::Manager::set_structured_errors]
+//! enabled, the Manager reports failures as an [ErrorEnvelope] serialized
+//! to JSON:
+//!
+//! ```javascript
+//! { "module": "sale", "code": "sold_out", "message": "error executing module \"sale\": ...",
+//!   "detail": { "remaining": "0" } }
+//! ```
 
+use crate::module::Module;
+use cosmwasm_std::{CustomQuery, StdError};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -7,15 +32,509 @@ pub enum Error {
     #[error("module {module:?} already registered")]
     ModuleAlreadyRegistered { module: String },
 
-    #[error("error executing module {module:?}: {err:?}")]
-    ExecutionError { module: String, err: String },
+    #[error("invalid module name {module:?}: {reason}")]
+    InvalidModuleName { module: String, reason: String },
 
-    #[error("error querying module {module:?}: {err:?}")]
-    QueryError { module: String, err: String },
+    #[error("error instantiating module {module:?}: {}", .err.chain())]
+    InstantiateError {
+        module: String,
+        #[source]
+        err: ModuleError,
+    },
+
+    #[error("error registering module {module:?}: {}", .err.chain())]
+    RegistrationError {
+        module: String,
+        #[source]
+        err: ModuleError,
+    },
+
+    #[error("invalid instantiate messages: {}", validation_report(.errors))]
+    InstantiateValidationError { errors: BTreeMap<String, String> },
+
+    #[error("error executing module {module:?}: {}", .err.chain())]
+    ExecutionError {
+        module: String,
+        #[source]
+        err: ModuleError,
+    },
+
+    #[error("error handling reply in module {module:?}: {}", .err.chain())]
+    ReplyError {
+        module: String,
+        #[source]
+        err: ModuleError,
+    },
+
+    #[error("error migrating module {module:?}: {}", .err.chain())]
+    MigrationError {
+        module: String,
+        #[source]
+        err: ModuleError,
+    },
+
+    #[error("cannot migrate module {module:?} from version {stored} to older version {version}")]
+    VersionDowngrade {
+        module: String,
+        stored: String,
+        version: String,
+    },
+
+    #[error("invalid version {version:?} for module {module:?}: {msg}")]
+    InvalidVersion {
+        module: String,
+        version: String,
+        msg: String,
+    },
+
+    #[error("error querying module {module:?}: {}", .err.chain())]
+    QueryError {
+        module: String,
+        #[source]
+        err: ModuleError,
+    },
+
+    #[error("error handling hook {topic:?} in module {module:?}: {}", .err.chain())]
+    HookError {
+        module: String,
+        topic: String,
+        #[source]
+        err: ModuleError,
+    },
+
+    #[error("exceeded the limit of {limit} hook deliveries in a single execute")]
+    HookLimitExceeded { limit: usize },
+
+    #[error("interface {interface:?} already registered")]
+    InterfaceAlreadyRegistered { interface: String },
+
+    #[error("interface {interface:?} not found")]
+    InterfaceNotFound { interface: String },
+
+    #[error("interface {interface:?} is registered with a different type")]
+    InterfaceTypeMismatch { interface: String },
+
+    #[error("no extension of type {extension} registered")]
+    ExtensionNotFound { extension: String },
+
+    #[error("module {module:?} is paused")]
+    ModulePaused { module: String },
+
+    #[error("contract is paused")]
+    ContractPaused {},
+
+    #[error("module {module:?} is disabled")]
+    ModuleDisabled { module: String },
+
+    #[error("module {module:?} is busy handling another message")]
+    ModuleBusy { module: String },
+
+    #[error("{sender} is not authorized to perform this action")]
+    Unauthorized { sender: String },
+
+    #[error("{sender} does not have the role {role:?}")]
+    MissingRole { sender: String, role: String },
+
+    #[error("invalid permit: {reason}")]
+    InvalidPermit { reason: String },
+
+    #[error("invalid nonce {nonce} for {address}, expected {expected}")]
+    InvalidNonce {
+        address: String,
+        expected: u64,
+        nonce: u64,
+    },
+
+    #[error("module {module:?} does not accept funds")]
+    NonPayable { module: String },
+
+    #[error("calling module {module:?} requires a fee of {fee}")]
+    InsufficientFee { module: String, fee: String },
+
+    #[error("allocated {allocated}{denom} across modules, but only {sent}{denom} was sent")]
+    FundsOverallocated {
+        denom: String,
+        allocated: String,
+        sent: String,
+    },
+
+    #[error("allocated {allocated}{denom} across modules, but {sent}{denom} was sent")]
+    FundsUnallocated {
+        denom: String,
+        allocated: String,
+        sent: String,
+    },
+
+    #[error("module dependencies form a cycle among {modules:?}")]
+    DependencyCycle { modules: Vec<String> },
+
+    #[error("module {module:?} is required but has no instantiate message")]
+    MissingInstantiateMsg { module: String },
+
+    #[error("rate limit for module {module:?} exceeded")]
+    RateLimited { module: String },
+
+    #[error("module {module:?} has not been instantiated")]
+    NotInstantiated { module: String },
+
+    #[error("module {module:?} already has a child contract labeled {label:?}")]
+    ChildAlreadyExists { module: String, label: String },
+
+    #[error("payload of {size} bytes exceeds the limit of {limit} bytes")]
+    PayloadTooLarge { size: usize, limit: usize },
+
+    #[error("payload exceeds the nesting depth limit of {limit}")]
+    PayloadTooDeep { limit: usize },
+
+    #[error("module {module:?} is addressed more than once in the message")]
+    DuplicateModuleKey { module: String },
+
+    #[error("reply id {id} of module {module:?} sets the bit reserved for callbacks")]
+    ReservedReplyId { module: String, id: u32 },
 
     #[error("error parsing request: {msg:?}")]
     ParseError { msg: Option<String> },
 
-    #[error("module {module:?} not found")]
-    NotFoundError { module: String },
+    #[error("error serializing response data: {msg:?}")]
+    SerializationError { msg: String },
+
+    #[error("module {module:?} not found{}", not_found_hint(.registered, .suggestion))]
+    NotFoundError {
+        module: String,
+        registered: Vec<String>,
+        suggestion: Option<String>,
+    },
+
+    #[error("{}", .0.to_json())]
+    Structured(ErrorEnvelope),
+
+    #[error(transparent)]
+    Std(#[from] StdError),
+
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+}
+
+impl From<Error> for StdError {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::Std(err) => err,
+            err => StdError::generic_err(error_chain(&err)),
+        }
+    }
+}
+
+impl Error {
+    /// The stable, machine-readable code of the error. Errors returned by a
+    /// module carry the code the module assigned them, if any.
+    pub fn code(&self) -> String {
+        let code = match self {
+            Error::ModuleAlreadyRegistered { .. } => "module_already_registered",
+            Error::InvalidModuleName { .. } => "invalid_module_name",
+            Error::InstantiateError { err, .. }
+            | Error::RegistrationError { err, .. }
+            | Error::ExecutionError { err, .. }
+            | Error::ReplyError { err, .. }
+            | Error::MigrationError { err, .. }
+            | Error::QueryError { err, .. }
+            | Error::HookError { err, .. }
+                if err.code.is_some() =>
+            {
+                return err.code.clone().unwrap_or_default();
+            }
+            Error::InstantiateError { .. } => "instantiate_error",
+            Error::RegistrationError { .. } => "registration_error",
+            Error::InstantiateValidationError { .. } => "instantiate_validation_error",
+            Error::ExecutionError { .. } => "execution_error",
+            Error::ReplyError { .. } => "reply_error",
+            Error::MigrationError { .. } => "migration_error",
+            Error::VersionDowngrade { .. } => "version_downgrade",
+            Error::InvalidVersion { .. } => "invalid_version",
+            Error::QueryError { .. } => "query_error",
+            Error::HookError { .. } => "hook_error",
+            Error::HookLimitExceeded { .. } => "hook_limit_exceeded",
+            Error::InterfaceAlreadyRegistered { .. } => "interface_already_registered",
+            Error::InterfaceNotFound { .. } => "interface_not_found",
+            Error::InterfaceTypeMismatch { .. } => "interface_type_mismatch",
+            Error::ExtensionNotFound { .. } => "extension_not_found",
+            Error::ModulePaused { .. } => "module_paused",
+            Error::ContractPaused {} => "contract_paused",
+            Error::ModuleDisabled { .. } => "module_disabled",
+            Error::ModuleBusy { .. } => "module_busy",
+            Error::Unauthorized { .. } => "unauthorized",
+            Error::MissingRole { .. } => "missing_role",
+            Error::InvalidPermit { .. } => "invalid_permit",
+            Error::InvalidNonce { .. } => "invalid_nonce",
+            Error::NonPayable { .. } => "non_payable",
+            Error::InsufficientFee { .. } => "insufficient_fee",
+            Error::FundsOverallocated { .. } => "funds_overallocated",
+            Error::FundsUnallocated { .. } => "funds_unallocated",
+            Error::DependencyCycle { .. } => "dependency_cycle",
+            Error::MissingInstantiateMsg { .. } => "missing_instantiate_msg",
+            Error::RateLimited { .. } => "rate_limited",
+            Error::NotInstantiated { .. } => "not_instantiated",
+            Error::ChildAlreadyExists { .. } => "child_already_exists",
+            Error::PayloadTooLarge { .. } => "payload_too_large",
+            Error::PayloadTooDeep { .. } => "payload_too_deep",
+            Error::DuplicateModuleKey { .. } => "duplicate_module_key",
+            Error::ReservedReplyId { .. } => "reserved_reply_id",
+            Error::ParseError { .. } => "parse_error",
+            Error::SerializationError { .. } => "serialization_error",
+            Error::NotFoundError { .. } => "module_not_found",
+            Error::Structured(envelope) => return envelope.code.clone(),
+            Error::Std(_) => "std_error",
+            Error::Json(_) => "json_error",
+        };
+        code.to_string()
+    }
+
+    /// The module the error concerns, if any.
+    pub fn module(&self) -> Option<&str> {
+        match self {
+            Error::ModuleAlreadyRegistered { module }
+            | Error::InvalidModuleName { module, .. }
+            | Error::InstantiateError { module, .. }
+            | Error::RegistrationError { module, .. }
+            | Error::ExecutionError { module, .. }
+            | Error::ReplyError { module, .. }
+            | Error::MigrationError { module, .. }
+            | Error::VersionDowngrade { module, .. }
+            | Error::InvalidVersion { module, .. }
+            | Error::QueryError { module, .. }
+            | Error::HookError { module, .. }
+            | Error::ModulePaused { module }
+            | Error::ModuleDisabled { module }
+            | Error::ModuleBusy { module }
+            | Error::NonPayable { module }
+            | Error::InsufficientFee { module, .. }
+            | Error::MissingInstantiateMsg { module }
+            | Error::RateLimited { module }
+            | Error::NotInstantiated { module }
+            | Error::ChildAlreadyExists { module, .. }
+            | Error::DuplicateModuleKey { module }
+            | Error::ReservedReplyId { module, .. }
+            | Error::NotFoundError { module, .. } => Some(module.as_str()),
+            Error::Structured(envelope) => envelope.module.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// The error as an [ErrorEnvelope].
+    pub fn envelope(&self) -> ErrorEnvelope {
+        if let Error::Structured(envelope) = self {
+            return envelope.clone();
+        }
+        let detail = match self {
+            Error::InstantiateError { err, .. }
+            | Error::RegistrationError { err, .. }
+            | Error::ExecutionError { err, .. }
+            | Error::ReplyError { err, .. }
+            | Error::MigrationError { err, .. }
+            | Error::QueryError { err, .. }
+            | Error::HookError { err, .. } => err.detail.as_deref().cloned(),
+            Error::InstantiateValidationError { errors } => serde_json::to_value(errors).ok(),
+            Error::NotFoundError { suggestion, .. } => suggestion
+                .as_ref()
+                .map(|suggestion| serde_json::json!({ "suggestion": suggestion })),
+            _ => None,
+        };
+        ErrorEnvelope {
+            module: self.module().map(String::from),
+            code: self.code(),
+            message: error_chain(self),
+            detail,
+        }
+    }
+
+    /// Build a [NotFoundError][Error::NotFoundError] for `module`, listing the
+    /// `registered` module names and suggesting the closest one, if any is
+    /// close enough to plausibly be a typo.
+    pub fn not_found(module: &str, registered: Vec<String>) -> Self {
+        let suggestion = closest_match(module, &registered);
+        Error::NotFoundError {
+            module: module.to_string(),
+            registered,
+            suggestion,
+        }
+    }
+}
+
+/// A failure reported by the Manager in a stable, machine-readable shape;
+/// see [Error::envelope].
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ErrorEnvelope {
+    /// The module the failure concerns, if any.
+    pub module: Option<String>,
+    /// The stable code of the failure, e.g. `"module_paused"`.
+    pub code: String,
+    /// A human readable description of the failure.
+    pub message: String,
+    /// Additional data describing the failure, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<Value>,
+}
+
+impl ErrorEnvelope {
+    /// The envelope serialized to JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_else(|_| self.message.clone())
+    }
+}
+
+/// The code a module assigns one of its errors with
+/// [error_code][crate::module::Module::error_code].
+#[derive(Clone, Debug, PartialEq)]
+pub struct ErrorCode {
+    pub code: String,
+    pub detail: Option<Value>,
+}
+
+impl ErrorCode {
+    pub fn new(code: impl Into<String>) -> Self {
+        ErrorCode {
+            code: code.into(),
+            detail: None,
+        }
+    }
+
+    /// Attach `detail` to the error, e.g. the values that made it fail.
+    pub fn with_detail(mut self, detail: &impl Serialize) -> Self {
+        self.detail = serde_json::to_value(detail).ok();
+        self
+    }
+}
+
+/// An error returned by a module through
+/// [GenericModule][crate::module::GenericModule], along with the code the
+/// module assigned it, if any. The module's error is kept, so its
+/// [sources][std::error::Error::source] are those of the module's error.
+#[derive(Clone, Debug)]
+pub struct ModuleError {
+    pub message: String,
+    pub code: Option<String>,
+    pub detail: Option<Box<Value>>,
+    error: Option<Arc<dyn std::error::Error + Send + Sync>>,
+}
+
+impl ModuleError {
+    /// The error `err` returned by `module`, with the code assigned by its
+    /// [error_code][crate::module::Module::error_code].
+    pub fn from_module<M, C, Q>(module: &M, err: M::Error) -> Self
+    where
+        M: Module<C, Q>,
+        Q: CustomQuery,
+    {
+        let code = module.error_code(&err);
+        ModuleError {
+            message: err.to_string(),
+            code: code.as_ref().map(|code| code.code.clone()),
+            detail: code.and_then(|code| code.detail).map(Box::new),
+            error: Some(Arc::new(err)),
+        }
+    }
+
+    /// The error `err`, raised while handling a message for a module, e.g.
+    /// while parsing its payload.
+    pub fn from_error(err: impl std::error::Error + Send + Sync + 'static) -> Self {
+        ModuleError {
+            message: err.to_string(),
+            code: None,
+            detail: None,
+            error: Some(Arc::new(err)),
+        }
+    }
+
+    /// The message of the error followed by those of its sources; see
+    /// [error_chain].
+    pub fn chain(&self) -> String {
+        error_chain(self)
+    }
+}
+
+impl From<String> for ModuleError {
+    fn from(message: String) -> Self {
+        ModuleError {
+            message,
+            code: None,
+            detail: None,
+            error: None,
+        }
+    }
+}
+
+impl fmt::Display for ModuleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for ModuleError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.error.as_ref().and_then(|err| err.source())
+    }
+}
+
+/// The message of `err` followed by those of its sources, separated by
+/// colons. Sources whose message is already part of the text, e.g. because
+/// the error embeds it in its own message, are skipped.
+pub fn error_chain(err: &(dyn std::error::Error + 'static)) -> String {
+    let mut chain = err.to_string();
+    let mut source = err.source();
+    while let Some(err) = source {
+        let message = err.to_string();
+        if !chain.contains(&message) {
+            chain.push_str(": ");
+            chain.push_str(&message);
+        }
+        source = err.source();
+    }
+    chain
+}
+
+fn not_found_hint(registered: &[String], suggestion: &Option<String>) -> String {
+    let mut hint = format!(" (registered modules: {:?})", registered);
+    if let Some(suggestion) = suggestion {
+        hint.push_str(&format!("; did you mean {:?}?", suggestion));
+    }
+    hint
+}
+
+fn validation_report(errors: &BTreeMap<String, String>) -> String {
+    errors
+        .iter()
+        .map(|(module, err)| format!("{:?}: {}", module, err))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Find the candidate with the smallest edit distance to `name`. Candidates
+/// further than a third of the name's length away are not considered matches.
+///
+/// `name` comes from the message, so candidates whose length alone puts them
+/// out of reach are skipped before computing the distance: the work is then
+/// bounded by the length of the registered names, however long `name` is.
+fn closest_match(name: &str, candidates: &[String]) -> Option<String> {
+    let len = name.chars().count();
+    let max_distance = std::cmp::max(1, len / 3);
+    candidates
+        .iter()
+        .filter(|candidate| candidate.chars().count().abs_diff(len) <= max_distance)
+        .map(|candidate| (levenshtein(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate.clone())
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut curr = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            curr[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(curr[j] + 1);
+        }
+        prev = curr;
+    }
+    prev[b.len()]
 }
@@ -0,0 +1,270 @@
+//! Child contracts instantiated by modules.
+//!
+//! Modules that instantiate contracts, e.g. a factory creating a collection
+//! per creator, send the `WasmMsg::Instantiate` through
+//! [Context::instantiate_child][crate::context::Context::instantiate_child].
+//! Glue captures the reply to the submessage and records the address of the
+//! new contract under the module and the label of the message, so the
+//! module needs no reply handler of its own:
+//!
+//! ```ignore
+//! let msg = WasmMsg::Instantiate {
+//!     admin: None,
+//!     code_id: self.collection_code_id,
+//!     msg: to_json_binary(&collection_msg)?,
+//!     funds: vec![],
+//!     label: format!("collection-{}", info.sender),
+//! };
+//! let msg = ctx.instantiate_child(deps.storage, &env, msg)?;
+//! Ok(Response::new().add_submessage(msg))
+//! ```
+//!
+//! Later calls read the address back with [child]. Clients list the children
+//! of a module with the `children` query under the reserved
+//! [MANAGER_NAMESPACE][crate::introspection::MANAGER_NAMESPACE]:
+//!
+//! ```javascript
+//! { "_manager": { "children": { "module": "collections", "limit": 10 } } }
+//! ```
+
+use crate::pagination;
+use crate::storage;
+use cosmwasm_std::{Addr, Event, StdError, StdResult, Storage, SubMsgResult};
+use serde::{Deserialize, Serialize};
+
+const NAMESPACE: &[u8] = b"factory";
+
+/// A contract instantiated by a module.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Child {
+    /// The name of the module that instantiated the contract.
+    pub module: String,
+    pub label: String,
+    pub address: Addr,
+    pub code_id: u64,
+}
+
+/// A child contract awaiting the reply to its instantiation.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct PendingChild {
+    pub label: String,
+    pub code_id: u64,
+}
+
+fn child_key(module: &str, label: &str) -> Vec<u8> {
+    storage::key(&[NAMESPACE, module.as_bytes()], label.as_bytes())
+}
+
+/// Load the child contract the module registered as `module` instantiated
+/// with `label`.
+pub fn child(storage: &dyn Storage, module: &str, label: &str) -> StdResult<Option<Child>> {
+    storage::load(storage, &child_key(module, label))
+}
+
+/// Load up to `limit` child contracts of the module registered as `module`,
+/// ordered by label, starting after the label `start_after` if given.
+pub fn children(
+    storage: &dyn Storage,
+    module: &str,
+    start_after: Option<&str>,
+    limit: Option<u32>,
+) -> StdResult<Vec<Child>> {
+    let limit = pagination::limit(limit);
+    let children = storage::range::<Child>(
+        storage,
+        &[NAMESPACE, module.as_bytes()],
+        start_after.map(str::as_bytes),
+        limit,
+    )?;
+    Ok(children.into_iter().map(|(_, child)| child).collect())
+}
+
+/// Record the child contract `pending` of the module registered as
+/// `module`, whose instantiation completed with `result`.
+pub(crate) fn save_child(
+    storage: &mut dyn Storage,
+    module: &str,
+    pending: PendingChild,
+    result: SubMsgResult,
+) -> StdResult<Child> {
+    let resp = result.into_result().map_err(StdError::generic_err)?;
+    let address = resp
+        .events
+        .iter()
+        .filter(|event| event.ty == "instantiate")
+        .flat_map(|event| &event.attributes)
+        .find(|attr| attr.key == "_contract_address")
+        .ok_or_else(|| StdError::generic_err("no contract address in instantiate reply"))?;
+    let child = Child {
+        module: module.to_string(),
+        label: pending.label,
+        address: Addr::unchecked(&address.value),
+        code_id: pending.code_id,
+    };
+    storage::save(storage, &child_key(module, &child.label), &child)?;
+    Ok(child)
+}
+
+/// The event recording the instantiation of `child`.
+pub(crate) fn child_event(child: &Child) -> Event {
+    Event::new("glue-child-instantiated")
+        .add_attribute("module", &child.module)
+        .add_attribute("label", &child.label)
+        .add_attribute("address", &child.address)
+        .add_attribute("code_id", child.code_id.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::Context;
+    use crate::error::Error;
+    use crate::manager::Manager;
+    use crate::module::Module;
+    use crate::response::Response;
+    use crate::test_utils::{addr, info, MockDeps};
+    use cosmwasm_std::testing::{mock_dependencies, mock_env};
+    use cosmwasm_std::{
+        from_json, to_json_binary, Deps, DepsMut, Env, MessageInfo, Reply, WasmMsg,
+    };
+    use serde_json::{json, Value};
+
+    /// A module instantiating a collection contract under the label it is
+    /// sent.
+    struct Collections;
+
+    impl Module for Collections {
+        type InstantiateMsg = Value;
+        type ExecuteMsg = String;
+        type QueryMsg = Value;
+        type QueryResp = Value;
+        type Error = Error;
+
+        fn instantiate(
+            &mut self,
+            _deps: &mut DepsMut,
+            _env: &Env,
+            _info: &MessageInfo,
+            _msg: Value,
+        ) -> Result<Response, Error> {
+            Ok(Response::new())
+        }
+
+        fn execute(
+            &mut self,
+            _deps: &mut DepsMut,
+            _env: Env,
+            _info: MessageInfo,
+            _msg: String,
+        ) -> Result<Response, Error> {
+            Err(StdError::generic_err("needs a context").into())
+        }
+
+        fn execute_with_context(
+            &mut self,
+            ctx: &Context,
+            deps: &mut DepsMut,
+            env: Env,
+            _info: MessageInfo,
+            label: String,
+        ) -> Result<Response, Error> {
+            let msg = WasmMsg::Instantiate {
+                admin: None,
+                code_id: 7,
+                msg: to_json_binary(&json!({}))?,
+                funds: vec![],
+                label,
+            };
+            let msg = ctx.instantiate_child(deps.storage, &env, msg)?;
+            Ok(Response::new().add_submessage(msg))
+        }
+
+        fn query(&self, _deps: &Deps, _env: Env, _msg: Value) -> Result<Value, Error> {
+            Ok(Value::Null)
+        }
+    }
+
+    struct Setup {
+        deps: MockDeps,
+        manager: Manager,
+    }
+
+    impl Setup {
+        fn new() -> Self {
+            let manager = Manager::builder()
+                .with_boxed_module("collections", Box::new(Collections))
+                .unwrap()
+                .build();
+            Setup {
+                deps: mock_dependencies(),
+                manager,
+            }
+        }
+
+        /// Instantiate the collection labelled `label`, replying that it
+        /// landed at `address`.
+        fn instantiate_child(&mut self, label: &str, address: &Addr) -> Result<(), String> {
+            let msg = json!({ "collections": label }).to_string();
+            let resp = self
+                .manager
+                .execute(
+                    &mut self.deps.as_mut(),
+                    mock_env(),
+                    info("alice", &[]),
+                    &msg,
+                )
+                .map_err(|e| e.to_string())?;
+            let reply: Reply = serde_json::from_value(json!({
+                "id": resp.messages[0].id,
+                "result": {"ok": {"events": [{
+                    "type": "instantiate",
+                    "attributes": [{"key": "_contract_address", "value": address}],
+                }], "data": null}},
+            }))
+            .unwrap();
+            let resp = self
+                .manager
+                .reply(self.deps.as_mut(), mock_env(), reply)
+                .map_err(|e| e.to_string())?;
+            assert_eq!(resp.events[0].ty, "glue-child-instantiated");
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn children_are_recorded_from_their_instantiate_reply() {
+        let mut setup = Setup::new();
+        setup.instantiate_child("punks", &addr("punks")).unwrap();
+        setup.instantiate_child("apes", &addr("apes")).unwrap();
+
+        let punks = Child {
+            module: "collections".to_string(),
+            label: "punks".to_string(),
+            address: addr("punks"),
+            code_id: 7,
+        };
+        assert_eq!(
+            child(&setup.deps.storage, "collections", "punks").unwrap(),
+            Some(punks.clone())
+        );
+
+        let msg =
+            json!({"_manager": {"children": {"module": "collections", "start_after": "apes"}}});
+        let resp = setup
+            .manager
+            .query(&setup.deps.as_ref(), mock_env(), &msg.to_string())
+            .unwrap();
+        let resp: Value = from_json(resp).unwrap();
+        assert_eq!(resp, json!({ "children": [punks] }));
+    }
+
+    #[test]
+    fn labels_are_unique_per_module() {
+        let mut setup = Setup::new();
+        setup.instantiate_child("punks", &addr("punks")).unwrap();
+        let err = setup
+            .instantiate_child("punks", &addr("other"))
+            .unwrap_err();
+        assert!(err.contains("already has a child contract"), "{}", err);
+    }
+}
@@ -0,0 +1,58 @@
+//! Dispatch from ICS-20 transfers with an ibc-hooks memo.
+//!
+//! Chains running the ibc-hooks middleware execute a contract when an
+//! ICS-20 transfer carries a `wasm` memo. With a glue envelope as the `msg`
+//! of the memo, the tokens arrive at the module it addresses:
+//!
+//! ```javascript
+//! { "wasm": { "contract": "<glue contract>", "msg": { "sale": { "buy": {} } } } }
+//! ```
+//!
+//! Contracts handed the memo itself, e.g. by a custom IBC middleware, can
+//! dispatch it with
+//! [Manager::execute_ibc_memo][crate::manager::Manager::execute_ibc_memo],
+//! which exposes the transferred tokens to the module as
+//! [Received::Ics20][crate::receive::Received::Ics20].
+//!
+//! The sender seen by the module is the intermediate account ibc-hooks
+//! derives from the channel and the original sender, not the original
+//! sender itself.
+
+use cosmwasm_std::{StdError, StdResult};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// The ibc-hooks memo of an ICS-20 transfer. Keys other than `wasm`, used
+/// by other middleware, are ignored.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Memo {
+    pub wasm: WasmHook,
+}
+
+/// The contract execution requested by a [Memo].
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct WasmHook {
+    pub contract: String,
+    pub msg: Value,
+}
+
+impl Memo {
+    /// The memo sending the transferred tokens to the module `module` of the
+    /// glue contract at `contract`, along with `msg`.
+    pub fn new(contract: impl Into<String>, module: &str, msg: &impl Serialize) -> StdResult<Self> {
+        let msg = serde_json::to_value(BTreeMap::from([(module, msg)]))
+            .map_err(|e| StdError::serialize_err("memo", e))?;
+        Ok(Memo {
+            wasm: WasmHook {
+                contract: contract.into(),
+                msg,
+            },
+        })
+    }
+
+    /// The memo as a string, to be set on an ICS-20 transfer.
+    pub fn to_memo(&self) -> StdResult<String> {
+        serde_json::to_string(self).map_err(|e| StdError::serialize_err("memo", e))
+    }
+}
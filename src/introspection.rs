@@ -0,0 +1,304 @@
+//! Messages handled by the Manager itself, under the reserved
+//! [MANAGER_NAMESPACE] key. Queries let explorers and frontends discover the
+//! surface of a glue contract at runtime:
+//!
+//! ```javascript
+//! { "_manager": { "list_modules": {} } }
+//! { "_manager": { "module_metadata": { "module": "token" } } }
+//! { "_manager": { "children": { "module": "collections" } } }
+//! ```
+//!
+//! Monitoring checks on a deployed contract with a single query, reporting
+//! the version and pause state of every module along with the status each
+//! reports through [on_health][crate::module::Module::on_health]:
+//!
+//! ```javascript
+//! { "_manager": { "health": {} } }
+//! ```
+//!
+//! Execute messages administer the contract and may only be sent by the
+//! [admin][crate::admin]:
+//!
+//! ```javascript
+//! { "_manager": { "pause_all": { "emergency_modules": ["admin"] } } }
+//! ```
+
+use crate::factory::Child;
+use crate::module::{Capabilities, Health, Metadata};
+use crate::pause::{Disabled, GlobalPause};
+use cosmwasm_std::Addr;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// The reserved key under which messages are handled by the Manager rather
+/// than a module. Module names beginning with an underscore are reserved.
+pub const MANAGER_NAMESPACE: &str = "_manager";
+
+/// Queries handled by the Manager under [MANAGER_NAMESPACE].
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ManagerQuery {
+    /// List the modules registered with the Manager.
+    ListModules {},
+    /// Describe the module registered under `module`.
+    ModuleMetadata { module: String },
+    /// The admin of the contract.
+    Admin {},
+    /// Whether the whole contract is paused.
+    GlobalPause {},
+    /// The contract messages addressed to unknown keys are forwarded to; see
+    /// [successor][crate::successor].
+    Successor {},
+    /// The child contract the module registered under `module` instantiated
+    /// with `label`; see [factory][crate::factory].
+    Child { module: String, label: String },
+    /// The child contracts of the module registered under `module`, ordered
+    /// by label.
+    Children {
+        module: String,
+        #[serde(default)]
+        start_after: Option<String>,
+        #[serde(default)]
+        limit: Option<u32>,
+    },
+    /// The status of the contract and of every module.
+    Health {},
+}
+
+/// Execute messages handled by the Manager under [MANAGER_NAMESPACE]. These
+/// may only be sent by the [admin][crate::admin].
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ManagerExecute {
+    /// Hand the admin role to `admin`.
+    UpdateAdmin { admin: String },
+    /// Forward messages addressed to unknown keys to the contract at
+    /// `successor`; see [successor][crate::successor].
+    SetSuccessor { successor: String },
+    /// Stop forwarding messages to the successor.
+    ClearSuccessor {},
+    /// Pause execute dispatch to a single module.
+    PauseModule { module: String },
+    /// Resume execute dispatch to a single module.
+    UnpauseModule { module: String },
+    /// Turn a single module off, keeping its queries live if `keep_queries`.
+    DisableModule {
+        module: String,
+        #[serde(default)]
+        keep_queries: bool,
+    },
+    /// Turn a disabled module back on.
+    EnableModule { module: String },
+    /// Pause execute dispatch to every module but `emergency_modules`.
+    PauseAll {
+        #[serde(default)]
+        emergency_modules: Vec<String>,
+    },
+    /// Lift a contract-wide pause.
+    UnpauseAll {},
+    /// Send `msg` to every module; see
+    /// [Manager::broadcast][crate::manager::Manager::broadcast].
+    Broadcast { msg: Value },
+}
+
+/// The response to [ManagerQuery::Admin].
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct AdminResponse {
+    pub admin: Option<Addr>,
+}
+
+/// The response to [ManagerQuery::GlobalPause].
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct GlobalPauseResponse {
+    pub paused: Option<GlobalPause>,
+}
+
+/// The response to [ManagerQuery::Successor].
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SuccessorResponse {
+    pub successor: Option<Addr>,
+}
+
+/// The response to [ManagerQuery::Child].
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ChildResponse {
+    pub child: Option<Child>,
+}
+
+/// The response to [ManagerQuery::Children].
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ChildrenResponse {
+    pub children: Vec<Child>,
+}
+
+/// The response to [ManagerQuery::ListModules].
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ListModulesResponse {
+    pub modules: Vec<ModuleInfo>,
+}
+
+/// The response to [ManagerQuery::ModuleMetadata].
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ModuleMetadataResponse {
+    pub name: String,
+    pub metadata: Metadata,
+}
+
+/// A description of a registered module.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ModuleInfo {
+    /// The name the module is registered under.
+    pub name: String,
+    /// Deprecated aliases routed to the module.
+    pub aliases: Vec<String>,
+    /// The compact identifier the module may also be addressed by; see
+    /// [Manager::register_id][crate::manager::Manager::register_id].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<u32>,
+    /// The module's version, if it reports one.
+    pub version: Option<String>,
+    /// The kinds of messages the module handles.
+    pub capabilities: Capabilities,
+    /// Whether execute dispatch to the module is paused.
+    pub paused: bool,
+    /// Whether the module is disabled, and if so whether its queries are
+    /// still answered.
+    #[serde(default)]
+    pub disabled: Option<Disabled>,
+}
+
+/// The response to [ManagerQuery::Health].
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct HealthResponse {
+    /// The contract-wide pause, if any.
+    pub global_pause: Option<GlobalPause>,
+    pub modules: Vec<ModuleHealth>,
+}
+
+/// The status of a registered module.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ModuleHealth {
+    /// The name the module is registered under.
+    pub name: String,
+    /// The module's version, if it reports one.
+    pub version: Option<String>,
+    /// Whether execute dispatch to the module is paused.
+    pub paused: bool,
+    /// Whether the module is disabled, and if so whether its queries are
+    /// still answered.
+    #[serde(default)]
+    pub disabled: Option<Disabled>,
+    /// The status the module reports, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<Health>,
+    /// Why the module's status could not be read, if it couldn't.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::admin;
+    use crate::manager::Manager;
+    use crate::test_utils::{addr, info, Deposits, MockDeps};
+    use cosmwasm_std::from_json;
+    use cosmwasm_std::testing::{mock_dependencies, mock_env};
+    use serde::de::DeserializeOwned;
+    use serde_json::json;
+
+    struct Setup {
+        deps: MockDeps,
+        manager: Manager,
+    }
+
+    impl Setup {
+        /// A contract with a `sale` module, also reachable as `shop` and by
+        /// id 1, and a `vault` module.
+        fn new() -> Self {
+            let mut manager = Manager::builder()
+                .with_boxed_module("sale", Box::new(Deposits))
+                .unwrap()
+                .with_boxed_module("vault", Box::new(Deposits))
+                .unwrap()
+                .build();
+            manager.register_alias("shop".to_string(), "sale").unwrap();
+            manager.register_id(1, "sale").unwrap();
+            let mut deps = mock_dependencies();
+            admin::set_admin(&mut deps.storage, &addr("admin")).unwrap();
+            Setup { deps, manager }
+        }
+
+        fn query<T: DeserializeOwned>(&mut self, msg: Value) -> Result<T, String> {
+            let msg = json!({ MANAGER_NAMESPACE: msg }).to_string();
+            let resp = self
+                .manager
+                .query(&self.deps.as_ref(), mock_env(), &msg)
+                .map_err(|e| e.to_string())?;
+            Ok(from_json(resp).unwrap())
+        }
+
+        fn execute(&mut self, sender: &str, msg: Value) -> Result<(), String> {
+            let msg = json!({ MANAGER_NAMESPACE: msg }).to_string();
+            self.manager
+                .execute(&mut self.deps.as_mut(), mock_env(), info(sender, &[]), &msg)
+                .map(|_| ())
+                .map_err(|e| e.to_string())
+        }
+    }
+
+    #[test]
+    fn list_modules_describes_every_module() {
+        let mut setup = Setup::new();
+        setup
+            .execute("admin", json!({"pause_module": {"module": "vault"}}))
+            .unwrap();
+
+        let resp: ListModulesResponse = setup.query(json!({"list_modules": {}})).unwrap();
+        let modules: Vec<_> = resp
+            .modules
+            .iter()
+            .map(|m| (m.name.as_str(), m.aliases.clone(), m.id, m.paused))
+            .collect();
+        assert_eq!(
+            modules,
+            [
+                ("sale", vec!["shop".to_string()], Some(1), false),
+                ("vault", vec![], None, true),
+            ]
+        );
+    }
+
+    #[test]
+    fn module_metadata_resolves_aliases() {
+        let mut setup = Setup::new();
+        let resp: ModuleMetadataResponse = setup
+            .query(json!({"module_metadata": {"module": "shop"}}))
+            .unwrap();
+        assert_eq!(resp.name, "sale");
+
+        let err = setup
+            .query::<ModuleMetadataResponse>(json!({"module_metadata": {"module": "bank"}}))
+            .unwrap_err();
+        assert!(err.contains("bank"), "{}", err);
+    }
+
+    #[test]
+    fn only_the_admin_administers_the_contract() {
+        let mut setup = Setup::new();
+        setup
+            .execute("alice", json!({"pause_all": {}}))
+            .unwrap_err();
+        let resp: GlobalPauseResponse = setup.query(json!({"global_pause": {}})).unwrap();
+        assert_eq!(resp.paused, None);
+
+        setup
+            .execute("admin", json!({"update_admin": {"admin": addr("alice")}}))
+            .unwrap();
+        let resp: AdminResponse = setup.query(json!({"admin": {}})).unwrap();
+        assert_eq!(resp.admin, Some(addr("alice")));
+        setup.execute("alice", json!({"pause_all": {}})).unwrap();
+        let resp: GlobalPauseResponse = setup.query(json!({"global_pause": {}})).unwrap();
+        assert!(resp.paused.is_some());
+    }
+}
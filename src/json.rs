@@ -0,0 +1,368 @@
+//! The JSON engine used internally to parse dispatched messages and to
+//! serialize aggregated response data.
+//!
+//! By default this is `serde_json`. With the `serde-json-wasm` feature
+//! enabled, `serde-json-wasm` is used instead, which rejects floating point
+//! numbers anywhere in a message, module payloads included, so that no
+//! module acts on a nondeterministic value. The feature is scoped to
+//! parsing: glue's API is built on [serde_json::Value], so `serde_json`
+//! remains a dependency and the feature does not make the contract smaller.
+//!
+//! With the `msgpack` feature enabled, messages may also be sent encoded with
+//! MessagePack; see [MSGPACK_KEY][crate::manager::MSGPACK_KEY].
+//!
+//! Both engines keep the last of the entries of an object sharing a key, so
+//! messages are parsed with [parse_message], which reports a key repeated
+//! in any of the objects of the envelope rather than dropping all but one of
+//! its entries: the root object, the entries of a batch, the queries of a
+//! multi query, and the allocation of funds. Module payloads are left to the
+//! modules, whose message types reject repeated fields themselves.
+
+use crate::error::Error;
+use crate::manager::{BATCH_KEY, FUNDS_KEY, MULTI_KEY, PARTIAL_BATCH_KEY};
+use serde::de::{DeserializeOwned, DeserializeSeed, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{Map, Value};
+use std::cell::RefCell;
+#[cfg(not(feature = "serde-json-wasm"))]
+use std::collections::BTreeMap;
+use std::fmt;
+#[cfg(not(feature = "serde-json-wasm"))]
+use std::marker::PhantomData;
+
+/// The root object of a message, with its entries in the order they appear.
+/// Unlike a map, entries sharing a key are all kept, so they can be reported.
+#[cfg(not(feature = "serde-json-wasm"))]
+pub(crate) struct Envelope<V>(Vec<(String, V)>);
+
+#[cfg(not(feature = "serde-json-wasm"))]
+impl<V> Envelope<V> {
+    /// The entries by key, failing with
+    /// [DuplicateModuleKey][Error::DuplicateModuleKey] if a key repeats.
+    pub(crate) fn into_map(self) -> Result<BTreeMap<String, V>, Error> {
+        let mut entries = BTreeMap::new();
+        for (key, value) in self.0 {
+            if entries.contains_key(&key) {
+                return Err(Error::DuplicateModuleKey { module: key });
+            }
+            entries.insert(key, value);
+        }
+        Ok(entries)
+    }
+}
+
+#[cfg(not(feature = "serde-json-wasm"))]
+impl<'de, V: Deserialize<'de>> Deserialize<'de> for Envelope<V> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct EnvelopeVisitor<V>(PhantomData<V>);
+
+        impl<'de, V: Deserialize<'de>> Visitor<'de> for EnvelopeVisitor<V> {
+            type Value = Envelope<V>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("an object of module payloads")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Envelope<V>, A::Error> {
+                let mut entries = Vec::with_capacity(map.size_hint().unwrap_or(0));
+                while let Some(entry) = map.next_entry()? {
+                    entries.push(entry);
+                }
+                Ok(Envelope(entries))
+            }
+        }
+
+        deserializer.deserialize_map(EnvelopeVisitor(PhantomData))
+    }
+}
+
+/// Where a value sits within a message, deciding how it is parsed.
+#[derive(Clone, Copy)]
+enum Shape {
+    /// An object keyed by module: the root object, an entry of a batch, a
+    /// multi query, or an allocation of funds. Repeated keys are reported,
+    /// and the values under reserved keys are parsed by their own shape.
+    Envelope,
+    /// The list of entries under a batch key.
+    Batch,
+    /// A module payload, parsed as is.
+    Payload,
+}
+
+impl Shape {
+    /// The shape of the value under `key` of an envelope.
+    fn of_key(key: &str) -> Shape {
+        match key {
+            BATCH_KEY | PARTIAL_BATCH_KEY => Shape::Batch,
+            MULTI_KEY | FUNDS_KEY => Shape::Envelope,
+            _ => Shape::Payload,
+        }
+    }
+}
+
+/// Parses a value of `shape`, recording the first key repeated within an
+/// envelope in `duplicate`. Parsing carries on past it, so the engine still
+/// checks the rest of the message.
+struct Seed<'a> {
+    shape: Shape,
+    duplicate: &'a RefCell<Option<String>>,
+}
+
+impl<'a> Seed<'a> {
+    fn with_shape(&self, shape: Shape) -> Seed<'a> {
+        Seed {
+            shape,
+            duplicate: self.duplicate,
+        }
+    }
+}
+
+impl<'de, 'a> DeserializeSeed<'de> for Seed<'a> {
+    type Value = Value;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        match self.shape {
+            Shape::Payload => Value::deserialize(deserializer),
+            Shape::Envelope | Shape::Batch => deserializer.deserialize_any(self),
+        }
+    }
+}
+
+impl<'de, 'a> Visitor<'de> for Seed<'a> {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a message")
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<Value, E> {
+        Ok(Value::from(v))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Value, E> {
+        Ok(Value::from(v))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Value, E> {
+        Ok(Value::from(v))
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<Value, E> {
+        Ok(Value::from(v))
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Value, E> {
+        Ok(Value::from(v))
+    }
+
+    fn visit_string<E>(self, v: String) -> Result<Value, E> {
+        Ok(Value::from(v))
+    }
+
+    fn visit_unit<E>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_none<E>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        self.deserialize(deserializer)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        let shape = match self.shape {
+            Shape::Batch => Shape::Envelope,
+            _ => Shape::Payload,
+        };
+        let mut items = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(item) = seq.next_element_seed(self.with_shape(shape))? {
+            items.push(item);
+        }
+        Ok(Value::Array(items))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+        let mut entries = Map::new();
+        while let Some(key) = map.next_key::<String>()? {
+            let shape = match self.shape {
+                Shape::Envelope => Shape::of_key(&key),
+                _ => Shape::Payload,
+            };
+            let value = map.next_value_seed(self.with_shape(shape))?;
+            if matches!(self.shape, Shape::Envelope) && entries.contains_key(&key) {
+                self.duplicate.borrow_mut().get_or_insert(key.clone());
+            }
+            entries.insert(key, value);
+        }
+        Ok(Value::Object(entries))
+    }
+}
+
+/// A message parsed by [parse_message], along with the first key repeated
+/// within its envelope, if any.
+struct Message {
+    value: Value,
+    duplicate: Option<String>,
+}
+
+impl Message {
+    fn into_value(self) -> Result<Value, Error> {
+        match self.duplicate {
+            Some(module) => Err(Error::DuplicateModuleKey { module }),
+            None => Ok(self.value),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Message {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let duplicate = RefCell::new(None);
+        let seed = Seed {
+            shape: Shape::Envelope,
+            duplicate: &duplicate,
+        };
+        let value = seed.deserialize(deserializer)?;
+        Ok(Message {
+            value,
+            duplicate: duplicate.into_inner(),
+        })
+    }
+}
+
+/// Parse a message like [from_slice], failing with
+/// [DuplicateModuleKey][Error::DuplicateModuleKey] if one of the objects of
+/// its envelope repeats a key.
+pub(crate) fn parse_message(bytes: &[u8]) -> Result<Value, Error> {
+    from_slice::<Message>(bytes)?.into_value()
+}
+
+/// Parse `payload`, found under `key` of the root object of a message, like
+/// [parse_message].
+#[cfg(not(feature = "serde-json-wasm"))]
+pub(crate) fn parse_entry(key: &str, payload: &str) -> Result<Value, Error> {
+    let duplicate = RefCell::new(None);
+    let seed = Seed {
+        shape: Shape::of_key(key),
+        duplicate: &duplicate,
+    };
+    let mut deserializer = serde_json::Deserializer::from_str(payload);
+    let value = seed.deserialize(&mut deserializer)?;
+    deserializer.end()?;
+    Message {
+        value,
+        duplicate: duplicate.into_inner(),
+    }
+    .into_value()
+}
+
+#[cfg(not(feature = "serde-json-wasm"))]
+pub(crate) fn from_slice<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Error> {
+    Ok(serde_json::from_slice(bytes)?)
+}
+
+#[cfg(feature = "serde-json-wasm")]
+pub(crate) fn from_slice<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Error> {
+    serde_json_wasm::from_slice(bytes).map_err(|e| Error::ParseError {
+        msg: Some(e.to_string()),
+    })
+}
+
+#[cfg(not(feature = "serde-json-wasm"))]
+pub(crate) fn to_vec<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, Error> {
+    serde_json::to_vec(value).map_err(|e| Error::SerializationError { msg: e.to_string() })
+}
+
+#[cfg(feature = "serde-json-wasm")]
+pub(crate) fn to_vec<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, Error> {
+    serde_json_wasm::to_vec(value).map_err(|e| Error::SerializationError { msg: e.to_string() })
+}
+
+#[cfg(feature = "msgpack")]
+pub(crate) fn from_msgpack<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Error> {
+    rmp_serde::from_slice(bytes).map_err(|e| Error::ParseError {
+        msg: Some(e.to_string()),
+    })
+}
+
+/// Parse a message encoded with MessagePack like [parse_message].
+#[cfg(feature = "msgpack")]
+pub(crate) fn parse_msgpack_message(bytes: &[u8]) -> Result<Value, Error> {
+    from_msgpack::<Message>(bytes)?.into_value()
+}
+
+#[cfg(feature = "msgpack")]
+pub(crate) fn to_msgpack<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, Error> {
+    rmp_serde::to_vec_named(value).map_err(|e| Error::SerializationError { msg: e.to_string() })
+}
+
+/// Fail if `value`, e.g. decoded from MessagePack, nests arrays and objects
+/// more than `limit` levels deep.
+#[cfg(feature = "msgpack")]
+pub(crate) fn check_value_depth(value: &Value, limit: usize) -> Result<(), Error> {
+    let mut stack = vec![(value, 0usize)];
+    while let Some((value, depth)) = stack.pop() {
+        let children: Box<dyn Iterator<Item = &Value>> = match value {
+            Value::Array(items) => Box::new(items.iter()),
+            Value::Object(obj) => Box::new(obj.values()),
+            _ => continue,
+        };
+        if depth + 1 > limit {
+            return Err(Error::PayloadTooDeep { limit });
+        }
+        stack.extend(children.map(|child| (child, depth + 1)));
+    }
+    Ok(())
+}
+
+/// Fail if `bytes` nests arrays and objects more than `limit` levels deep.
+///
+/// This scans the raw bytes without recursing, so hostile payloads are
+/// rejected before the parser recurses into them. Brackets inside strings
+/// are ignored; the payload is not otherwise validated.
+pub(crate) fn check_depth(bytes: &[u8], limit: usize) -> Result<(), Error> {
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    for &b in bytes {
+        if in_string {
+            match b {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match b {
+            b'"' => in_string = true,
+            b'{' | b'[' => {
+                depth += 1;
+                if depth > limit {
+                    return Err(Error::PayloadTooDeep { limit });
+                }
+            }
+            b'}' | b']' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(all(test, feature = "serde-json-wasm"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn floats_are_rejected() {
+        for msg in [
+            r#"{"token": {"amount": 1.5}}"#,
+            r#"{"_batch": [{"token": {"amount": 1e3}}]}"#,
+        ] {
+            let err = parse_message(msg.as_bytes()).unwrap_err();
+            assert!(matches!(err, Error::ParseError { .. }), "{:?}", err);
+        }
+        parse_message(br#"{"token": {"amount": 15}}"#).unwrap();
+    }
+}
@@ -10,15 +10,23 @@
 //!
 //! By convention, it's acceptable for modules to take references to other
 //! modules from their constructors. This allows modules to compose easily.
+//! Alternatively, modules may override the `*_with_context` handlers of the
+//! `Module` trait and reach their siblings through the dispatch
+//! [Context][crate::context::Context], e.g. to query them.
 //!
 //! ## Using Modules
 //! The [Manager][crate::manager::Manager] is a struct used to dynamically
 //! dispatch messages to their corresponding modules. Create a new Manager
 //! with [Manager::new][crate::manager::Manager::new] and then register
-//! modules for dynamic dispatch with [register][crate::manager::Manager::register].
+//! modules for dynamic dispatch with [register][crate::manager::Manager::register],
+//! or, for modules no other module holds a reference to,
+//! [register_boxed][crate::manager::Manager::register_boxed].
 //!
 //! When implementing the entrypoints for contracts built with glue, you can
 //! simply call the corresponding functions: `execute`, `query`, and `instantiate`.
+//! To build the Manager in one place for every entrypoint, implement
+//! [ManagerFactory][crate::entrypoint::ManagerFactory]; see
+//! [entrypoint][crate::entrypoint].
 //!
 //! Entities interacting with your contract may follow a simple convention for
 //! addressing messages to a specific module withing your contract. For
@@ -32,6 +40,11 @@
 //! **NOTE**: The root object must contain a single key. If you attempt to
 //! address more than one module in an `execute` call, it will fail.
 //!
+//! Keys beginning with an underscore are reserved for glue. In particular, the
+//! `_manager` key addresses messages handled by the `Manager` itself, such as
+//! introspection queries and admin actions like pausing the contract; see
+//! [introspection][crate::introspection].
+//!
 //! The `Manager` will automatically strip away the root object and forward the
 //! payload object to the relevant module. The response object returned by the
 //! module will be returned directly.
@@ -48,11 +61,74 @@
 //!   /* and so on */
 //! }
 //! ```
+//!
+//! ## CosmWasm Versions
+//! glue builds against cosmwasm-std 1.x by default, through the `cosmwasm-1`
+//! feature. Contracts on cosmwasm-std 2.x disable the default features and
+//! enable `cosmwasm-2` instead:
+//!
+//! ```toml
+//! burnt-glue = { version = "0.2", default-features = false, features = ["cosmwasm-2"] }
+//! ```
+//!
+//! Either way the selected version is re-exported as
+//! [cosmwasm_std][crate::cosmwasm_std], so the types in glue's API, e.g.
+//! [ContractResponse][crate::response::ContractResponse], are those of the
+//! contract. The `testing` and `cw-orch` features are only available with
+//! cosmwasm-std 1.x.
 
+#[cfg(all(feature = "cosmwasm-1", feature = "cosmwasm-2"))]
+compile_error!("the `cosmwasm-1` and `cosmwasm-2` features are mutually exclusive");
+
+#[cfg(not(any(feature = "cosmwasm-1", feature = "cosmwasm-2")))]
+compile_error!("one of the `cosmwasm-1` or `cosmwasm-2` features must be enabled");
+
+#[cfg(all(feature = "cosmwasm-2", any(feature = "testing", feature = "cw-orch")))]
+compile_error!("the `testing` and `cw-orch` features require cosmwasm-std 1.x");
+
+#[cfg(not(feature = "cosmwasm-2"))]
+pub extern crate cosmwasm_std;
+#[cfg(feature = "cosmwasm-2")]
+pub extern crate cosmwasm_std_v2 as cosmwasm_std;
+
+pub mod abstract_app;
+pub mod admin;
+pub mod case;
+pub mod client;
+pub mod context;
+pub mod entrypoint;
 pub mod error;
+pub mod factory;
+pub mod ibc_hooks;
+pub mod introspection;
+mod json;
 pub mod manager;
+pub mod middleware;
 pub mod module;
+pub mod modules;
+#[cfg(feature = "cw-orch")]
+pub mod orch;
+pub mod pagination;
+pub mod pause;
+pub mod permit;
+pub mod raw;
+pub mod receive;
+pub mod remote;
+pub mod reply;
 pub mod response;
+pub mod shared;
+pub mod static_manager;
+pub mod storage;
+pub mod successor;
+pub mod sylvia;
+#[cfg(test)]
+mod test_utils;
+#[cfg(feature = "testing")]
+pub mod testing;
+pub mod validation;
+pub mod version;
+#[cfg(feature = "schema")]
+pub mod schema;
 
 #[cfg(test)]
 mod tests {
@@ -1,148 +1,3210 @@
 //! A module manager that dynamically dispatches messages sent to a contract
 //! to modules registered to it.
 
-use crate::error::Error;
-use cosmwasm_std::{Binary, Deps, DepsMut, Env, MessageInfo, StdError, StdResult};
+use crate::admin;
+use crate::case::KeyCase;
+use crate::context::Context;
+use crate::error::{error_chain, Error};
+use crate::ibc_hooks::Memo;
+use crate::json;
+use cosmwasm_std::{
+    Addr, Binary, Coin, CustomQuery, Deps, DepsMut, Empty, Env, Event, MessageInfo, Reply, StdError,
+    StdResult, Storage, Uint128,
+};
+use serde_json::value::RawValue;
 use serde_json::Value;
 use serde_json::Value::Object;
-use std::cell::RefCell;
-use std::collections::HashMap;
-use std::ops::Deref;
-use std::rc::Rc;
+use std::any::TypeId;
+use std::collections::{BTreeMap, VecDeque};
+use std::ops::{Deref, DerefMut};
 
+use crate::factory;
+use crate::introspection::{
+    AdminResponse, ChildResponse, ChildrenResponse, GlobalPauseResponse, HealthResponse,
+    ListModulesResponse, ManagerExecute, ManagerQuery, ModuleHealth, ModuleInfo,
+    ModuleMetadataResponse, SuccessorResponse, MANAGER_NAMESPACE,
+};
+use crate::middleware::Middleware;
 use crate::module::GenericModule;
-use crate::response::Aggregator;
+use crate::pause::{self, Disabled, GlobalPause};
+use crate::permit::{self, PermitExecute, PermitQuery, PERMIT_KEY};
+use crate::receive::{
+    Cw20ReceiveMsg, Cw721ReceiveMsg, Received, CW20_RECEIVE_KEY, CW721_RECEIVE_KEY,
+};
+use crate::reply::{self, Callback, PendingCallback};
+use crate::response::{Aggregator, AttributeTagging, DataEnvelope, Response};
+use crate::shared::{self, AnyShared, MaybeSendSync, Ref, RefMut, Shared};
+use crate::successor;
+use crate::version;
+use serde::{Deserialize, Serialize};
+
+/// The maximum number of hook deliveries in a single execute, bounding chains
+/// of hooks that publish further hooks.
+const MAX_HOOK_DELIVERIES: usize = 64;
+
+/// The reserved key under which the attached funds are split across modules
+/// on instantiate, and across the entries of a batch; see
+/// [Manager::instantiate] and [Manager::execute].
+pub const FUNDS_KEY: &str = "_funds";
+
+/// The reserved execute key under which several module payloads are
+/// dispatched in order within one transaction; see [Manager::execute].
+pub const BATCH_KEY: &str = "_batch";
+
+/// The reserved execute key under which several module payloads are
+/// dispatched in order, skipping those that fail validation; see
+/// [Manager::execute].
+pub const PARTIAL_BATCH_KEY: &str = "_partial_batch";
+
+/// The reserved query key under which several modules are queried at once;
+/// see [Manager::query].
+pub const MULTI_KEY: &str = "_multi";
+
+/// The reserved key under which a message is sent encoded with MessagePack,
+/// as a base64 string:
+///
+/// ```javascript
+/// { "_msgpack": "gaV0b2tlboGkbWludIGmYW1vdW50pDEwMDA=" }
+/// ```
+///
+/// The message is decoded as if it had been sent as JSON, skipping the
+/// parsing of large payloads as JSON, and the data of the response, or the
+/// response to a query, is encoded with MessagePack in turn. Data that isn't
+/// JSON, such as that of [raw][crate::raw] modules, is returned as is.
+#[cfg(feature = "msgpack")]
+pub const MSGPACK_KEY: &str = "_msgpack";
+
+/// How a message sent to the Manager, and thus the data of its response, is
+/// encoded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Encoding {
+    Json,
+    #[cfg(feature = "msgpack")]
+    MessagePack,
+}
+
+/// An encoded message, parsed as far as dispatching it requires.
+enum Parsed<'m> {
+    /// A JSON message addressing a single registered module, whose payload
+    /// is left for the module to deserialize.
+    #[cfg_attr(feature = "serde-json-wasm", allow(dead_code))]
+    Single(String, &'m RawValue),
+    /// Any other message, parsed in full.
+    Value(Value, Encoding),
+}
 
 /// A struct that will dynamically dispatch messages to modules registered
 /// within it.
-#[derive(Default)]
-pub struct Manager {
-    modules: HashMap<String, Rc<RefCell<dyn GenericModule>>>,
+///
+/// Modules are kept ordered by name, so any iteration over them (and thus
+/// the order of aggregated messages, attributes, events, and data) is stable
+/// regardless of registration order or hashing.
+///
+/// The manager is generic over the chain's custom message type, `C`, which
+/// modules registered to it may emit through their responses, and the chain's
+/// custom query type, `Q`, which modules may use through `deps.querier`.
+pub struct Manager<C = Binary, Q: CustomQuery = Empty> {
+    modules: BTreeMap<String, ModuleSlot<C, Q>>,
+    options: BTreeMap<String, ModuleOptions>,
+    aliases: BTreeMap<String, String>,
+    ids: BTreeMap<u32, String>,
+    versions: BTreeMap<String, BTreeMap<u32, String>>,
+    interfaces: BTreeMap<String, Box<AnyShared>>,
+    extensions: BTreeMap<TypeId, Box<AnyShared>>,
+    subscriptions: BTreeMap<String, Vec<String>>,
+    middleware: Vec<Box<dyn Middleware<C, Q>>>,
+    fallback: Option<String>,
+    allow_overwrite: bool,
+    strict: bool,
+    lenient_instantiate: bool,
+    validate_schemas: bool,
+    structured_errors: bool,
+    diagnostics: bool,
+    max_payload_bytes: Option<usize>,
+    max_depth: Option<usize>,
+    attribute_tagging: AttributeTagging,
+    data_envelope: DataEnvelope,
+    wrap_execute_data: bool,
+    key_case: KeyCase,
+    /// The responses to [cached queries][Context::query_cached] between
+    /// modules, keyed by the queried module and the query, kept until the
+    /// current dispatch ends.
+    query_cache: Shared<BTreeMap<(String, String), Binary>>,
+}
+
+/// The payload handed to the [fallback][Manager::set_fallback] module for a
+/// message addressed to an unknown key. Fallback modules use this, or a type
+/// deserializing from the same shape, as their execute and query messages.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct FallbackMsg {
+    /// The key the message was addressed to.
+    pub key: String,
+    /// The payload under the key, as sent.
+    pub payload: Value,
+}
+
+/// A message addressed to a single module, built from a typed payload
+/// rather than JSON. Converts into the envelope accepted by
+/// [Manager::execute_value] and [Manager::query_value]:
+///
+/// ```ignore
+/// let msg = DispatchEnvelope::new("token", &TokenMsg::Mint { amount })?;
+/// manager.execute_value(&mut deps, env, info, msg.into())?;
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct DispatchEnvelope {
+    /// The name of the module the payload is addressed to.
+    pub module: String,
+    pub payload: Value,
+}
+
+impl DispatchEnvelope {
+    pub fn new(module: impl Into<String>, payload: &impl Serialize) -> Result<Self, Error> {
+        Ok(DispatchEnvelope {
+            module: module.into(),
+            payload: serde_json::to_value(payload)?,
+        })
+    }
+}
+
+impl From<DispatchEnvelope> for Value {
+    fn from(envelope: DispatchEnvelope) -> Self {
+        let mut obj = serde_json::Map::new();
+        obj.insert(envelope.module, envelope.payload);
+        Object(obj)
+    }
+}
+
+/// Options for a module, given when it is registered with
+/// [register_with_options][Manager::register_with_options].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ModuleOptions {
+    /// Whether instantiate fails with
+    /// [MissingInstantiateMsg][Error::MissingInstantiateMsg] if the module
+    /// has no payload and no default.
+    pub required: bool,
+    /// The payload the module is instantiated with when the instantiate
+    /// message has none for it.
+    pub default_instantiate: Option<Value>,
+    /// The modules this module must be instantiated, broadcast to, and
+    /// migrated after, e.g. because it reads their configuration.
+    pub depends_on: Vec<String>,
+}
+
+impl ModuleOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Require the module to be instantiated.
+    pub fn required(mut self) -> Self {
+        self.required = true;
+        self
+    }
+
+    /// Order the module after the module registered under `module`.
+    pub fn depends_on(mut self, module: impl Into<String>) -> Self {
+        self.depends_on.push(module.into());
+        self
+    }
+
+    /// Instantiate the module with `msg` when the instantiate message has no
+    /// payload for it.
+    pub fn default_instantiate(mut self, msg: &impl Serialize) -> Result<Self, Error> {
+        self.default_instantiate = Some(serde_json::to_value(msg)?);
+        Ok(self)
+    }
+}
+
+/// A module as registered with the manager, returned when it is
+/// [unregistered][Manager::unregister] or [replaced][Manager::replace].
+pub enum Registered<C, Q: CustomQuery> {
+    /// A module registered with [register][Manager::register], which other
+    /// modules may hold references to.
+    Shared(Shared<dyn GenericModule<C, Q>>),
+    /// A module registered with [register_boxed][Manager::register_boxed],
+    /// owned by the manager alone.
+    Owned(Box<dyn GenericModule<C, Q>>),
+}
+
+/// A registered module. Owned modules are still kept in a container, so
+/// that a module can be dispatched to while another one is executing, but
+/// the container is never handed out.
+enum ModuleSlot<C, Q: CustomQuery> {
+    Shared(Shared<dyn GenericModule<C, Q>>),
+    Owned(Shared<Box<dyn GenericModule<C, Q>>>),
+}
+
+impl<C, Q: CustomQuery> ModuleSlot<C, Q> {
+    /// Borrow the module registered as `name`, failing with
+    /// [ModuleBusy][Error::ModuleBusy] if it is executing.
+    fn borrow(&self, name: &str) -> Result<ModuleRef<'_, C, Q>, Error> {
+        let module = match self {
+            ModuleSlot::Shared(m) => shared::try_borrow(m).map(ModuleRef::Shared),
+            ModuleSlot::Owned(m) => shared::try_borrow(m).map(ModuleRef::Owned),
+        };
+        module.ok_or_else(|| Error::ModuleBusy {
+            module: name.to_string(),
+        })
+    }
+
+    /// Borrow the module registered as `name` exclusively, failing with
+    /// [ModuleBusy][Error::ModuleBusy] if it is already borrowed.
+    fn borrow_mut(&self, name: &str) -> Result<ModuleRefMut<'_, C, Q>, Error> {
+        let module = match self {
+            ModuleSlot::Shared(m) => shared::try_borrow_mut(m).map(ModuleRefMut::Shared),
+            ModuleSlot::Owned(m) => shared::try_borrow_mut(m).map(ModuleRefMut::Owned),
+        };
+        module.ok_or_else(|| Error::ModuleBusy {
+            module: name.to_string(),
+        })
+    }
+
+    fn into_registered(self) -> Registered<C, Q> {
+        match self {
+            ModuleSlot::Shared(m) => Registered::Shared(m),
+            ModuleSlot::Owned(m) => {
+                Registered::Owned(shared::into_inner(m).expect("owned modules are never shared"))
+            }
+        }
+    }
+}
+
+enum ModuleRef<'a, C, Q: CustomQuery> {
+    Shared(Ref<'a, dyn GenericModule<C, Q> + 'static>),
+    Owned(Ref<'a, Box<dyn GenericModule<C, Q>>>),
+}
+
+impl<'a, C, Q: CustomQuery> Deref for ModuleRef<'a, C, Q> {
+    type Target = dyn GenericModule<C, Q> + 'static;
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            ModuleRef::Shared(m) => &**m,
+            ModuleRef::Owned(m) => &***m,
+        }
+    }
+}
+
+enum ModuleRefMut<'a, C, Q: CustomQuery> {
+    Shared(RefMut<'a, dyn GenericModule<C, Q> + 'static>),
+    Owned(RefMut<'a, Box<dyn GenericModule<C, Q>>>),
+}
+
+impl<'a, C, Q: CustomQuery> Deref for ModuleRefMut<'a, C, Q> {
+    type Target = dyn GenericModule<C, Q> + 'static;
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            ModuleRefMut::Shared(m) => &**m,
+            ModuleRefMut::Owned(m) => &***m,
+        }
+    }
+}
+
+impl<'a, C, Q: CustomQuery> DerefMut for ModuleRefMut<'a, C, Q> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        match self {
+            ModuleRefMut::Shared(m) => &mut **m,
+            ModuleRefMut::Owned(m) => &mut ***m,
+        }
+    }
 }
 
-impl Manager {
+impl<C, Q: CustomQuery> Default for Manager<C, Q> {
+    fn default() -> Self {
+        Manager {
+            modules: BTreeMap::new(),
+            options: BTreeMap::new(),
+            aliases: BTreeMap::new(),
+            ids: BTreeMap::new(),
+            versions: BTreeMap::new(),
+            interfaces: BTreeMap::new(),
+            extensions: BTreeMap::new(),
+            subscriptions: BTreeMap::new(),
+            middleware: Vec::new(),
+            fallback: None,
+            allow_overwrite: false,
+            strict: false,
+            lenient_instantiate: false,
+            validate_schemas: false,
+            structured_errors: false,
+            diagnostics: false,
+            max_payload_bytes: None,
+            max_depth: None,
+            attribute_tagging: AttributeTagging::None,
+            data_envelope: DataEnvelope::JsonMap,
+            wrap_execute_data: false,
+            key_case: KeyCase::Preserve,
+            query_cache: shared::shared(BTreeMap::new()),
+        }
+    }
+}
+
+impl<C, Q: CustomQuery> Manager<C, Q> {
+    /// Whether payloads are parsed strictly; see
+    /// [set_strict][Self::set_strict].
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    /// Whether payloads are validated against module schemas; see
+    /// [set_validate_schemas][Self::set_validate_schemas].
+    pub fn validates_schemas(&self) -> bool {
+        self.validate_schemas
+    }
+
+    /// How the case of payload keys is converted; see
+    /// [set_key_case][Self::set_key_case].
+    pub fn key_case(&self) -> KeyCase {
+        self.key_case
+    }
+}
+
+impl<C: Clone, Q: CustomQuery> Manager<C, Q> {
     /// Create a new Manager with no modules registered to it.
     pub fn new() -> Self {
         Self::default()
     }
 
+    /// Create a [ManagerBuilder] for constructing a Manager fluently.
+    pub fn builder() -> ManagerBuilder<C, Q> {
+        ManagerBuilder::default()
+    }
+
     /// Register a module, `module`, to the manager under the name `name`.
     /// Entities interacting with the manager can address messages to this
     /// module by wrapping the payload in a root object with a key of `name`
     /// with the associated value, the payload.
+    ///
+    /// Names must be non-empty, must not have leading or trailing whitespace,
+    /// must not contain control characters, and must not begin with an
+    /// underscore, which is reserved for glue. Registering a name that is
+    /// already taken fails unless overwriting has been enabled with
+    /// [set_allow_overwrite][Self::set_allow_overwrite]. So is registering
+    /// a name sharing the hash of a registered one, as their
+    /// [reply ids][crate::reply::reply_id] could not be told apart.
+    ///
+    /// The module's [on_register][crate::module::Module::on_register] hook
+    /// runs before it is added, and its failure aborts the registration.
     pub fn register(
         &mut self,
         name: String,
-        module: Rc<RefCell<dyn GenericModule>>,
+        module: Shared<dyn GenericModule<C, Q>>,
+    ) -> Result<(), Error> {
+        self.register_slot(name, ModuleSlot::Shared(module))
+    }
+
+    /// Register `module` under `name` like [register][Self::register],
+    /// handing its ownership to the manager. Modules that no other module
+    /// holds a reference to need no shared container:
+    ///
+    /// ```ignore
+    /// manager.register_boxed("token".to_string(), Box::new(Cw20::new()))?;
+    /// ```
+    ///
+    /// Other modules reach an owned module through their [Context], by
+    /// querying or dispatching to it, and declare that they rely on it with
+    /// [ModuleOptions::depends_on] so they are instantiated and migrated
+    /// after it.
+    pub fn register_boxed(
+        &mut self,
+        name: String,
+        module: Box<dyn GenericModule<C, Q>>,
     ) -> Result<(), Error> {
-        match self.modules.insert(name.clone(), module) {
-            Some(_) => Err(Error::ModuleAlreadyRegistered { module: name }),
-            None => Ok(()),
+        self.register_slot(name, ModuleSlot::Owned(shared::shared(module)))
+    }
+
+    fn register_slot(&mut self, name: String, module: ModuleSlot<C, Q>) -> Result<(), Error> {
+        validate_module_name(&name)?;
+        if self.aliases.contains_key(&name)
+            || self.versions.contains_key(&name)
+            || self.is_id(&name)
+            || (!self.allow_overwrite && self.modules.contains_key(&name))
+        {
+            return Err(Error::ModuleAlreadyRegistered { module: name });
         }
+        let hash = reply::module_hash(&name);
+        if let Some(other) = self
+            .modules
+            .keys()
+            .find(|other| **other != name && reply::module_hash(other) == hash)
+        {
+            return Err(Error::InvalidModuleName {
+                reason: format!("its reply ids collide with those of module {:?}", other),
+                module: name,
+            });
+        }
+        self.check_registration(&name, &module)?;
+        self.modules.insert(name.clone(), module);
+        self.options.remove(&name);
+        Ok(())
     }
 
-    /// Dispatch a JSON-encoded execute message to the appropriate module
-    /// registered within the `Manager` instance.
-    pub fn execute(
+    /// Run the [on_register][crate::module::Module::on_register] hook of
+    /// `module`, about to be registered under `name`.
+    fn check_registration(&self, name: &str, module: &ModuleSlot<C, Q>) -> Result<(), Error> {
+        module
+            .borrow(name)?
+            .on_register_value(self, name)
+            .map_err(|err| Error::RegistrationError {
+                module: name.to_string(),
+                err,
+            })
+    }
+
+    /// Register `module` under `name` like [register][Self::register], with
+    /// `options` controlling how it is instantiated.
+    pub fn register_with_options(
         &mut self,
-        deps: &mut DepsMut,
-        env: Env,
-        info: MessageInfo,
-        msg: &str,
-    ) -> Result<cosmwasm_std::Response<Binary>, String> {
-        let val: Value = serde_json::from_str(msg).map_err(|e| e.to_string())?;
-        if let Object(obj) = val {
-            let vals: Vec<(String, Value)> = obj.into_iter().collect();
-            match &vals[..] {
-                [(module_name, payload)] => {
-                    if let Some(module) = self.modules.get(module_name) {
-                        module
-                            .deref()
-                            .borrow_mut()
-                            .execute_value(deps, env, info, payload)
-                            .map(|x| x.into())
-                    } else {
-                        let err = Error::NotFoundError {
-                            module: module_name.to_string(),
-                        };
-                        Err(format!("{:?}", err))
-                    }
+        name: String,
+        module: Shared<dyn GenericModule<C, Q>>,
+        options: ModuleOptions,
+    ) -> Result<(), Error> {
+        self.register(name.clone(), module)?;
+        self.options.insert(name, options);
+        Ok(())
+    }
+
+    /// Register the owned `module` under `name` like
+    /// [register_boxed][Self::register_boxed], with `options` controlling how
+    /// it is instantiated.
+    pub fn register_boxed_with_options(
+        &mut self,
+        name: String,
+        module: Box<dyn GenericModule<C, Q>>,
+        options: ModuleOptions,
+    ) -> Result<(), Error> {
+        self.register_boxed(name.clone(), module)?;
+        self.options.insert(name, options);
+        Ok(())
+    }
+
+    /// Register `alias` as a deprecated name for the module registered under
+    /// `target`. Messages addressed to the alias are routed to the target
+    /// module, and execute and instantiate responses gain a
+    /// `glue-deprecated-alias` event naming both so integrators know to
+    /// migrate.
+    pub fn register_alias(&mut self, alias: String, target: &str) -> Result<(), Error> {
+        validate_module_name(&alias)?;
+        if self.modules.contains_key(&alias)
+            || self.aliases.contains_key(&alias)
+            || self.versions.contains_key(&alias)
+            || self.is_id(&alias)
+        {
+            return Err(Error::ModuleAlreadyRegistered { module: alias });
+        }
+        if !self.modules.contains_key(target) {
+            return Err(self.not_found(target));
+        }
+        self.aliases.insert(alias, target.to_string());
+        Ok(())
+    }
+
+    /// Register `id` as a compact identifier of the module registered under
+    /// `target`. Gas-sensitive callers address the module by the decimal
+    /// form of the id instead of its name, e.g. `{ "1": { ... } }`. Ids are
+    /// listed by the `list_modules` query under [MANAGER_NAMESPACE].
+    ///
+    /// Fails if the id is taken, or if a module, alias, or versioned module
+    /// is named like it. Modules named like a registered id are rejected in
+    /// turn, so neither can shadow the other.
+    pub fn register_id(&mut self, id: u32, target: &str) -> Result<(), Error> {
+        let key = id.to_string();
+        if self.ids.contains_key(&id)
+            || self.modules.contains_key(&key)
+            || self.aliases.contains_key(&key)
+            || self.versions.contains_key(&key)
+        {
+            return Err(Error::ModuleAlreadyRegistered { module: key });
+        }
+        if !self.modules.contains_key(target) {
+            return Err(self.not_found(target));
+        }
+        self.ids.insert(id, target.to_string());
+        Ok(())
+    }
+
+    /// Whether `name` parses as a registered module [id][Self::register_id].
+    fn is_id(&self, name: &str) -> bool {
+        name.parse().is_ok_and(|id: u32| self.ids.contains_key(&id))
+    }
+
+    /// The compact identifier registered for the module `name` with
+    /// [register_id][Self::register_id], if any.
+    pub fn module_id(&self, name: &str) -> Option<u32> {
+        self.ids
+            .iter()
+            .find(|(_, target)| *target == name)
+            .map(|(id, _)| *id)
+    }
+
+    /// Register `module` as version `version` of the module `name`, under the
+    /// versioned name `"{name}@v{version}"`, e.g. `"sale@v2"`. Messages
+    /// addressed to `name` itself are routed to the latest version
+    /// registered, while integrators built against an older message format
+    /// keep addressing the version they know:
+    ///
+    /// ```ignore
+    /// manager.register_versioned("sale", 1, shared(SaleV1Compat::new()))?;
+    /// manager.register_versioned("sale", 2, shared(Sale::new()))?;
+    /// ```
+    ///
+    /// `name` must not contain `@` and must not be taken by a module or an
+    /// alias.
+    pub fn register_versioned(
+        &mut self,
+        name: &str,
+        version: u32,
+        module: Shared<dyn GenericModule<C, Q>>,
+    ) -> Result<(), Error> {
+        validate_module_name(name)?;
+        if name.contains('@') {
+            return Err(Error::InvalidModuleName {
+                module: name.to_string(),
+                reason: "versioned names must not contain '@'".to_string(),
+            });
+        }
+        if self.modules.contains_key(name) || self.aliases.contains_key(name) || self.is_id(name) {
+            return Err(Error::ModuleAlreadyRegistered {
+                module: name.to_string(),
+            });
+        }
+        let versioned = format!("{}@v{}", name, version);
+        self.register(versioned.clone(), module)?;
+        self.versions
+            .entry(name.to_string())
+            .or_default()
+            .insert(version, versioned);
+        Ok(())
+    }
+
+    /// Pause execute dispatch to the module registered under `module`, which
+    /// then fails with [ModulePaused][Error::ModulePaused]. Queries are still
+    /// dispatched. The flag is kept in storage until
+    /// [unpause_module][Self::unpause_module] is called.
+    pub fn pause_module(&self, storage: &mut dyn Storage, module: &str) -> Result<(), Error> {
+        let target = self.resolve(module);
+        if !self.modules.contains_key(target) {
+            return Err(self.not_found(module));
+        }
+        Ok(pause::pause(storage, target)?)
+    }
+
+    /// Resume execute dispatch to the module registered under `module`.
+    pub fn unpause_module(&self, storage: &mut dyn Storage, module: &str) -> Result<(), Error> {
+        let target = self.resolve(module);
+        if !self.modules.contains_key(target) {
+            return Err(self.not_found(module));
+        }
+        pause::unpause(storage, target);
+        Ok(())
+    }
+
+    /// Disable the module registered under `module`. Execute messages to it
+    /// then fail with [ModuleDisabled][Error::ModuleDisabled], and so do its
+    /// queries unless `keep_queries` is set. The flag is kept in storage
+    /// until [enable_module][Self::enable_module] is called.
+    pub fn disable_module(
+        &self,
+        storage: &mut dyn Storage,
+        module: &str,
+        keep_queries: bool,
+    ) -> Result<(), Error> {
+        let target = self.resolve(module);
+        if !self.modules.contains_key(target) {
+            return Err(self.not_found(module));
+        }
+        Ok(pause::disable(storage, target, &Disabled { keep_queries })?)
+    }
+
+    /// Enable the module registered under `module` again.
+    pub fn enable_module(&self, storage: &mut dyn Storage, module: &str) -> Result<(), Error> {
+        let target = self.resolve(module);
+        if !self.modules.contains_key(target) {
+            return Err(self.not_found(module));
+        }
+        pause::enable(storage, target);
+        Ok(())
+    }
+
+    /// Route execute messages and queries addressed to keys that match no
+    /// registered module or alias to the module registered under `module`,
+    /// instead of failing. The fallback module receives a [FallbackMsg]
+    /// holding the key and the payload, enabling proxies to other contracts
+    /// or the handling of legacy message shapes. Instantiate and migrate
+    /// messages are not routed to the fallback.
+    pub fn set_fallback(&mut self, module: &str) -> Result<(), Error> {
+        if !self.modules.contains_key(module) {
+            return Err(self.not_found(module));
+        }
+        self.fallback = Some(module.to_string());
+        Ok(())
+    }
+
+    /// Wrap a message addressed to the unknown key `name` for the fallback
+    /// module, returning the fallback's name and its payload. Fails with
+    /// [NotFoundError][Error::NotFoundError] if no fallback is set.
+    fn fallback(&self, name: &str, payload: &Value) -> Result<(&str, Value), Error> {
+        let fallback = self.fallback.as_deref().ok_or_else(|| self.not_found(name))?;
+        let msg = FallbackMsg {
+            key: name.to_string(),
+            payload: payload.clone(),
+        };
+        Ok((fallback, serde_json::to_value(msg)?))
+    }
+
+    /// The contract messages addressed to unknown keys are forwarded to,
+    /// unless a [fallback][Self::set_fallback] module handles them; see
+    /// [successor].
+    fn successor(&self, storage: &dyn Storage) -> StdResult<Option<Addr>> {
+        if self.fallback.is_some() {
+            return Ok(None);
+        }
+        successor::successor(storage)
+    }
+
+    /// Fail while the contract is paused, as execute messages are not
+    /// forwarded to the successor then.
+    fn check_forwardable(storage: &dyn Storage) -> Result<(), Error> {
+        if pause::global_pause(storage)?.is_some() {
+            return Err(Error::ContractPaused {});
+        }
+        Ok(())
+    }
+
+    /// Add `middleware` to run around module dispatch. Middleware run in the
+    /// order they were added before dispatch, and in reverse order after it.
+    pub fn add_middleware(&mut self, middleware: Box<dyn Middleware<C, Q>>) {
+        self.middleware.push(middleware);
+    }
+
+    /// Subscribe the module registered under `module` to hooks published on
+    /// `topic`. Whenever a module publishes on the topic during an execute,
+    /// with [Context::publish], the subscriber's
+    /// [on_hook][crate::module::Module::on_hook] handler is called and its
+    /// response is merged into the final response. Subscribers are called in
+    /// the order they subscribed.
+    pub fn subscribe(&mut self, topic: impl Into<String>, module: &str) -> Result<(), Error> {
+        if !self.modules.contains_key(module) {
+            return Err(self.not_found(module));
+        }
+        let subscribers = self.subscriptions.entry(topic.into()).or_default();
+        if !subscribers.iter().any(|s| s == module) {
+            subscribers.push(module.to_string());
+        }
+        Ok(())
+    }
+
+    /// Register `implementation` as the provider of the interface `name`, so
+    /// that modules can depend on the interface rather than a concrete module:
+    ///
+    /// ```ignore
+    /// let token = shared(TokenModule::new());
+    /// manager.register("token".to_string(), token.clone())?;
+    /// manager.register_interface::<dyn TokenInterface>("token", token)?;
+    /// ```
+    ///
+    /// Other modules can then resolve the dependency at dispatch time with
+    /// [Context::interface].
+    pub fn register_interface<I: ?Sized + MaybeSendSync + 'static>(
+        &mut self,
+        name: impl Into<String>,
+        implementation: Shared<I>,
+    ) -> Result<(), Error> {
+        let name = name.into();
+        if self.interfaces.contains_key(&name) {
+            return Err(Error::InterfaceAlreadyRegistered { interface: name });
+        }
+        self.interfaces.insert(name, Box::new(implementation));
+        Ok(())
+    }
+
+    /// Resolve the implementation registered for the interface `name`. Fails
+    /// if no implementation was registered, or if it was registered as a
+    /// different interface type than `I`.
+    pub fn interface<I: ?Sized + 'static>(&self, name: &str) -> Result<Shared<I>, Error> {
+        let implementation = self
+            .interfaces
+            .get(name)
+            .ok_or_else(|| Error::InterfaceNotFound {
+                interface: name.to_string(),
+            })?;
+        implementation
+            .downcast_ref::<Shared<I>>()
+            .cloned()
+            .ok_or_else(|| Error::InterfaceTypeMismatch {
+                interface: name.to_string(),
+            })
+    }
+
+    /// Make `value` available to the handlers of every module through
+    /// [Context::extension], replacing and returning the value of the same
+    /// type registered before, if any.
+    ///
+    /// Extensions hold configuration shared by several modules, such as the
+    /// contract owner or the staking denom, without threading it through
+    /// their constructors:
+    ///
+    /// ```ignore
+    /// manager.insert_extension(Config { denom: "uxion".to_string() });
+    ///
+    /// // In a handler:
+    /// let config: &Config = ctx.extension()?;
+    /// ```
+    pub fn insert_extension<T: MaybeSendSync + 'static>(&mut self, value: T) -> Option<T> {
+        let previous = self.extensions.insert(TypeId::of::<T>(), Box::new(value))?;
+        previous.downcast::<T>().ok().map(|previous| *previous)
+    }
+
+    /// The extension of type `T`, if one was registered with
+    /// [insert_extension][Self::insert_extension].
+    pub fn extension<T: 'static>(&self) -> Option<&T> {
+        self.extensions.get(&TypeId::of::<T>())?.downcast_ref()
+    }
+
+    /// Resolve `name`, which may be an alias, the unversioned name of a
+    /// [versioned][Self::register_versioned] module, or a module
+    /// [id][Self::register_id], to the name of a registered module.
+    pub(crate) fn resolve<'a>(&'a self, name: &'a str) -> &'a str {
+        if let Some(target) = self.aliases.get(name) {
+            return target;
+        }
+        if let Some(latest) = self.versions.get(name).and_then(|v| v.values().next_back()) {
+            return latest;
+        }
+        if let Some(target) = name.parse().ok().and_then(|id: u32| self.ids.get(&id)) {
+            return target;
+        }
+        name
+    }
+
+    /// Allow [register][Self::register] to silently replace a module already
+    /// registered under the same name.
+    pub fn set_allow_overwrite(&mut self, allow: bool) {
+        self.allow_overwrite = allow;
+    }
+
+    /// Reject instantiate, execute, and query payloads containing fields
+    /// that the module's message type does not know, as with serde's
+    /// `deny_unknown_fields`, so that typos fail loudly instead of being
+    /// silently dropped.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// Skip the keys of instantiate messages that match no registered module
+    /// or alias instead of failing, reporting each with a `skipped_module`
+    /// attribute. This lets a deployment script send the same message to
+    /// builds of a contract registering different modules. Funds may still
+    /// only be allocated to registered modules.
+    pub fn set_lenient_instantiate(&mut self, lenient: bool) {
+        self.lenient_instantiate = lenient;
+    }
+
+    /// Check instantiate, execute, and query payloads against the
+    /// [schemas][crate::module::Module::schemas] of the module they are
+    /// addressed to before parsing them, failing with every violation and
+    /// its path rather than serde's first error; see
+    /// [validation][crate::validation]. Validation costs gas, so this suits
+    /// contracts whose integrators benefit from precise errors.
+    pub fn set_validate_schemas(&mut self, validate: bool) {
+        self.validate_schemas = validate;
+    }
+
+    /// Report the failures of instantiate, execute, query, migrate, and reply
+    /// as [Structured][Error::Structured] errors, whose message is the
+    /// JSON-encoded [ErrorEnvelope][crate::error::ErrorEnvelope] of the
+    /// failure, so frontends can branch on its code.
+    pub fn set_structured_errors(&mut self, structured: bool) {
+        self.structured_errors = structured;
+    }
+
+    /// Record the failures the Manager swallows, such as the entries skipped
+    /// by a [PARTIAL_BATCH_KEY] message, as `glue-diagnostic` events on the
+    /// response, so indexers and dashboards can see them. Each event names
+    /// the failing module and carries the code and message of the failure.
+    pub fn set_diagnostics(&mut self, diagnostics: bool) {
+        self.diagnostics = diagnostics;
+    }
+
+    /// Reject messages larger than `limit` bytes with
+    /// [PayloadTooLarge][Error::PayloadTooLarge] before parsing them.
+    pub fn set_max_payload_bytes(&mut self, limit: Option<usize>) {
+        self.max_payload_bytes = limit;
+    }
+
+    /// Reject messages nesting arrays and objects more than `limit` levels
+    /// deep with [PayloadTooDeep][Error::PayloadTooDeep] before parsing them.
+    pub fn set_max_depth(&mut self, limit: Option<usize>) {
+        self.max_depth = limit;
+    }
+
+    /// Tag the attributes each module adds to the main `wasm` event with the
+    /// module's name, either by moving them into a dedicated `glue-{module}`
+    /// event or by prefixing their keys; see [AttributeTagging].
+    pub fn set_attribute_tagging(&mut self, tagging: AttributeTagging) {
+        self.attribute_tagging = tagging;
+    }
+
+    /// Encode the data of the aggregated instantiate and migrate responses,
+    /// and of execute responses if [wrapped][Self::set_wrap_execute_data],
+    /// with `envelope` instead of a JSON object keyed by module name.
+    pub fn set_data_envelope(&mut self, envelope: DataEnvelope) {
+        self.data_envelope = envelope;
+    }
+
+    /// Wrap the data of execute messages addressed to a single module in the
+    /// same envelope as instantiate data, e.g. `{ "sale": { ... } }`, so
+    /// clients parse the data of both the same way; see
+    /// [set_data_envelope][Self::set_data_envelope]. Batches, messages to the
+    /// Manager, and messages routed through a token receive hook or to the
+    /// [fallback][Self::set_fallback] module are left as they are.
+    pub fn set_wrap_execute_data(&mut self, wrap: bool) {
+        self.wrap_execute_data = wrap;
+    }
+
+    /// Match the names of the fields and variants of payloads in the
+    /// snake_case expected by modules, so camelCase is accepted too, and,
+    /// with [KeyCase::CamelCaseOut], convert those of query responses back to
+    /// camelCase; see [case][crate::case].
+    pub fn set_key_case(&mut self, key_case: KeyCase) {
+        self.key_case = key_case;
+    }
+
+    /// Check `msg` against the configured limits, then parse it.
+    fn parse(&self, msg: &[u8]) -> Result<Value, Error> {
+        Ok(self.parse_encoded(msg)?.0)
+    }
+
+    /// Like [parse][Self::parse], returning the encoding of the message as
+    /// well; see [MSGPACK_KEY].
+    fn parse_encoded(&self, msg: &[u8]) -> Result<(Value, Encoding), Error> {
+        self.check_limits(msg)?;
+        self.decode(json::parse_message(msg)?)
+    }
+
+    /// Check `msg` against the configured limits, then parse its root
+    /// envelope. The payload of a JSON message addressing a single registered
+    /// module is left unparsed, for the module to deserialize in a single
+    /// pass rather than through a `Value`; any other message is parsed in
+    /// full.
+    fn parse_envelope<'m>(&self, msg: &'m [u8]) -> Result<Parsed<'m>, Error> {
+        self.check_limits(msg)?;
+        #[cfg(not(feature = "serde-json-wasm"))]
+        if let Ok(envelope) = serde_json::from_slice::<json::Envelope<&RawValue>>(msg) {
+            let mut envelope = envelope.into_map()?;
+            if envelope.len() == 1 && envelope.keys().all(|name| self.is_registered(name)) {
+                if let Some((name, payload)) = envelope.pop_first() {
+                    return Ok(Parsed::Single(name, payload));
                 }
-                _ => {
-                    let err = Error::ParseError {
-                        msg: Some("too many module payloads".to_string()),
-                    };
-                    return Err(format!("{:?}", err));
+            }
+            let val = envelope
+                .into_iter()
+                .map(|(name, payload)| {
+                    let payload = json::parse_entry(&name, payload.get())?;
+                    Ok((name, payload))
+                })
+                .collect::<Result<serde_json::Map<String, Value>, Error>>()?;
+            let (val, encoding) = self.decode(Object(val))?;
+            return Ok(Parsed::Value(val, encoding));
+        }
+        let (val, encoding) = self.decode(json::parse_message(msg)?)?;
+        Ok(Parsed::Value(val, encoding))
+    }
+
+    /// Fail if `msg` exceeds the configured limits on the size and depth of
+    /// messages.
+    fn check_limits(&self, msg: &[u8]) -> Result<(), Error> {
+        if let Some(limit) = self.max_payload_bytes {
+            if msg.len() > limit {
+                return Err(Error::PayloadTooLarge {
+                    size: msg.len(),
+                    limit,
+                });
+            }
+        }
+        if let Some(limit) = self.max_depth {
+            json::check_depth(msg, limit)?;
+        }
+        Ok(())
+    }
+
+    /// The parsed message `val`, unpacked if it wraps a message encoded with
+    /// MessagePack, along with its encoding.
+    fn decode(&self, val: Value) -> Result<(Value, Encoding), Error> {
+        #[cfg(feature = "msgpack")]
+        if let Object(obj) = &val {
+            if let (1, Some(Value::String(packed))) = (obj.len(), obj.get(MSGPACK_KEY)) {
+                let packed = Binary::from_base64(packed)?;
+                let val = json::parse_msgpack_message(&packed)?;
+                if let Some(limit) = self.max_depth {
+                    json::check_value_depth(&val, limit)?;
                 }
+                return Ok((val, Encoding::MessagePack));
             }
-        } else {
-            let err = Error::ParseError { msg: None };
-            Err(format!("{:?}", err))
         }
+        Ok((val, Encoding::Json))
     }
 
-    /// Dispatch a JSON-encoded query message to the appropriate module
-    /// registered within the `Manager` instance.
-    pub fn query(&mut self, deps: &Deps, env: Env, msg: &str) -> StdResult<Binary> {
-        let val: Value =
-            serde_json::from_str(msg).map_err(|e| StdError::generic_err(e.to_string()))?;
-        if let Object(obj) = val {
-            let vals: Vec<(String, Value)> = obj.into_iter().collect();
-            match &vals[..] {
-                [(module_name, payload)] => {
-                    if let Some(module) = self.modules.get(module_name) {
-                        module.borrow().query_value(deps, env, payload)
-                    } else {
-                        let err = Error::NotFoundError {
-                            module: module_name.to_string(),
-                        };
-                        Err(StdError::generic_err(err.to_string()))
-                    }
+    /// Remove the module registered under `name`, returning it.
+    pub fn unregister(&mut self, name: &str) -> Result<Registered<C, Q>, Error> {
+        let module = self.modules.remove(name).ok_or_else(|| self.not_found(name))?;
+        self.options.remove(name);
+        self.aliases.retain(|_, target| target != name);
+        self.ids.retain(|_, target| target != name);
+        for versions in self.versions.values_mut() {
+            versions.retain(|_, versioned| versioned != name);
+        }
+        self.versions.retain(|_, versions| !versions.is_empty());
+        if self.fallback.as_deref() == Some(name) {
+            self.fallback = None;
+        }
+        for subscribers in self.subscriptions.values_mut() {
+            subscribers.retain(|s| s != name);
+        }
+        Ok(module.into_registered())
+    }
+
+    /// Replace the module registered under `name` with `module`, returning the
+    /// module that was previously registered. The new module's
+    /// [on_register][crate::module::Module::on_register] hook runs first.
+    pub fn replace(
+        &mut self,
+        name: &str,
+        module: Shared<dyn GenericModule<C, Q>>,
+    ) -> Result<Registered<C, Q>, Error> {
+        self.replace_slot(name, ModuleSlot::Shared(module))
+    }
+
+    /// Replace the module registered under `name` with the owned `module`
+    /// like [replace][Self::replace].
+    pub fn replace_boxed(
+        &mut self,
+        name: &str,
+        module: Box<dyn GenericModule<C, Q>>,
+    ) -> Result<Registered<C, Q>, Error> {
+        self.replace_slot(name, ModuleSlot::Owned(shared::shared(module)))
+    }
+
+    fn replace_slot(
+        &mut self,
+        name: &str,
+        module: ModuleSlot<C, Q>,
+    ) -> Result<Registered<C, Q>, Error> {
+        if !self.modules.contains_key(name) {
+            return Err(self.not_found(name));
+        }
+        self.check_registration(name, &module)?;
+        match self.modules.get_mut(name) {
+            Some(existing) => Ok(std::mem::replace(existing, module).into_registered()),
+            None => Err(self.not_found(name)),
+        }
+    }
+
+    /// The names of all modules registered with the manager, in sorted order.
+    pub fn module_names(&self) -> Vec<String> {
+        self.modules.keys().cloned().collect()
+    }
+
+    /// The names of all registered modules, ordered so that every module
+    /// comes after the modules it [depends on][ModuleOptions::depends_on],
+    /// and otherwise by name. Fails with
+    /// [DependencyCycle][Error::DependencyCycle] if the dependencies form a
+    /// cycle.
+    pub fn dependency_order(&self) -> Result<Vec<String>, Error> {
+        let mut remaining: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        for name in self.modules.keys() {
+            let mut dependencies = Vec::new();
+            for dependency in self.options.get(name).into_iter().flat_map(|o| &o.depends_on) {
+                let target = self.resolve(dependency);
+                if !self.modules.contains_key(target) {
+                    return Err(self.not_found(dependency));
                 }
-                _ => {
-                    let err = Error::ParseError {
-                        msg: Some("too many module payloads".to_string()),
-                    };
-                    Err(StdError::generic_err(err.to_string()))
+                dependencies.push(target);
+            }
+            remaining.insert(name, dependencies);
+        }
+        let mut order = Vec::with_capacity(remaining.len());
+        while !remaining.is_empty() {
+            let ready = remaining
+                .iter()
+                .find(|(_, dependencies)| dependencies.iter().all(|d| !remaining.contains_key(d)))
+                .map(|(name, _)| *name);
+            match ready {
+                Some(name) => {
+                    remaining.remove(name);
+                    order.push(name.to_string());
+                }
+                None => {
+                    return Err(Error::DependencyCycle {
+                        modules: remaining.keys().map(|m| m.to_string()).collect(),
+                    });
                 }
             }
-        } else {
-            let err = Error::ParseError { msg: None };
-            Err(StdError::generic_err(err.to_string()))
         }
+        Ok(order)
     }
 
-    /// Dispatch JSON-encoded instantiate messages to modules registered within
-    /// the Manager.
-    pub fn instantiate(
+    fn not_found(&self, module: &str) -> Error {
+        Error::not_found(module, self.module_names())
+    }
+
+    /// Dispatch a JSON-encoded execute message to the appropriate module
+    /// registered within the `Manager` instance.
+    ///
+    /// A message addresses a single module, and fails with
+    /// [DuplicateModuleKey][Error::DuplicateModuleKey] if it repeats its key.
+    /// To execute messages on several modules atomically, list them under
+    /// the reserved [BATCH_KEY]:
+    ///
+    /// ```javascript
+    /// { "_batch": [{ "token": { ... } }, { "sale": { ... } }] }
+    /// ```
+    ///
+    /// The entries are dispatched in order, and the first failure aborts the
+    /// whole batch. The messages, attributes, and events of the responses are
+    /// concatenated, and the response data is an array holding the data of
+    /// each entry.
+    ///
+    /// Entries see no funds unless they attach their share of `info.funds`
+    /// under [FUNDS_KEY], next to their module key, and the shares must add
+    /// up to exactly the attached funds, failing with
+    /// [FundsOverallocated][Error::FundsOverallocated] or
+    /// [FundsUnallocated][Error::FundsUnallocated] otherwise:
+    ///
+    /// ```javascript
+    /// {
+    ///     "_batch": [
+    ///         { "_funds": [{ "denom": "uxion", "amount": "100" }], "escrow": { ... } },
+    ///         { "token": { ... } }
+    ///     ]
+    /// }
+    /// ```
+    ///
+    /// Entries listed under [PARTIAL_BATCH_KEY] instead are validated before
+    /// being dispatched, and those addressing an unknown, paused, or disabled
+    /// module, attaching funds to a non-payable one, or failing the module's
+    /// [validate_execute][crate::module::Module::validate_execute] are
+    /// skipped, with `null` as their data. Failures while executing an entry
+    /// still abort the batch, since its writes cannot be undone. Skipped
+    /// entries are reported with [diagnostics][Self::set_diagnostics].
+    /// Funds are split across entries as in a [BATCH_KEY] message, and an
+    /// entry attaching funds is never skipped: failing its validation aborts
+    /// the batch, rather than leaving its funds with the contract.
+    ///
+    /// Only the root of a message addressing a single module is parsed by
+    /// the Manager; the module deserializes its payload straight from the
+    /// message, in a single pass. Middleware, [strict][Self::set_strict]
+    /// parsing, schema validation, and key normalization need the payload
+    /// as a `Value`, so with any of them it is parsed twice.
+    pub fn execute(
         &mut self,
-        mut deps: DepsMut,
+        deps: &mut DepsMut<Q>,
         env: Env,
         info: MessageInfo,
-        msgs: &str,
-    ) -> Result<cosmwasm_std::Response<Binary>, String> {
-        let mut aggregator: Aggregator = Aggregator::new();
-        let val: Value = serde_json::from_str(msgs).map_err(|e| e.to_string())?;
-        if let Object(obj) = val {
-            let vals: Vec<(String, Value)> = obj.into_iter().collect();
-            for (module_name, payload) in &vals {
-                if let Some(module) = self.modules.get(module_name) {
-                    let resp = module
-                        .deref()
-                        .borrow_mut()
-                        .instantiate_value(&mut deps, &env, &info, payload)?;
-                    aggregator.fold_response(module_name.clone(), resp);
-                } else {
-                    let err = Error::NotFoundError {
-                        module: module_name.to_string(),
-                    };
-                    return Err(format!("{:?}", err));
-                }
-            }
-            Ok(aggregator.aggregate())
-        } else {
-            let err = Error::ParseError { msg: None };
-            Err(format!("{:?}", err))
-        }
+        msg: &str,
+    ) -> Result<cosmwasm_std::Response<C>, Error> {
+        self.execute_raw(deps, env, info, msg.as_bytes())
+    }
+
+    /// Dispatch a JSON-encoded execute message, given as raw bytes (e.g. the
+    /// contents of a `Binary`), to the appropriate module registered within
+    /// the `Manager` instance.
+    pub fn execute_raw(
+        &mut self,
+        deps: &mut DepsMut<Q>,
+        env: Env,
+        info: MessageInfo,
+        msg: &[u8],
+    ) -> Result<cosmwasm_std::Response<C>, Error> {
+        let result = self.execute_bytes(deps, env, info, msg);
+        self.report(result)
+    }
+
+    /// Dispatch an execute message that was already parsed, e.g. by a
+    /// hand-written contract routing some variants of its own `ExecuteMsg`
+    /// to glue:
+    ///
+    /// ```ignore
+    /// match msg {
+    ///     ExecuteMsg::Glue(msg) => manager.execute_value(&mut deps, env, info, msg),
+    ///     // ...
+    /// }
+    /// ```
+    ///
+    /// `msg` is the same envelope [execute][Self::execute] accepts, e.g. a
+    /// [DispatchEnvelope] converted into a `Value`. The limits on the size
+    /// and depth of messages only apply to encoded messages, so they are not
+    /// checked.
+    pub fn execute_value(
+        &mut self,
+        deps: &mut DepsMut<Q>,
+        env: Env,
+        info: MessageInfo,
+        msg: Value,
+    ) -> Result<cosmwasm_std::Response<C>, Error> {
+        let result = self.execute_parsed(deps, &env, &info, msg);
+        self.report(result)
+    }
+
+    fn execute_bytes(
+        &mut self,
+        deps: &mut DepsMut<Q>,
+        env: Env,
+        info: MessageInfo,
+        msg: &[u8],
+    ) -> Result<cosmwasm_std::Response<C>, Error> {
+        let (val, encoding) = match self.parse_envelope(msg)? {
+            Parsed::Single(name, payload) => {
+                let resp = self.dispatch_raw(deps, &env, &info, &name, payload)?;
+                let module = self.wrap_execute_data.then(|| self.resolve(&name).to_string());
+                return self.finish_execute(deps, module, resp);
+            }
+            Parsed::Value(val, encoding) => (val, encoding),
+        };
+        let resp = self.execute_parsed(deps, &env, &info, val)?;
+        encode_response(encoding, resp)
+    }
+
+    fn execute_parsed(
+        &self,
+        deps: &mut DepsMut<Q>,
+        env: &Env,
+        info: &MessageInfo,
+        val: Value,
+    ) -> Result<cosmwasm_std::Response<C>, Error> {
+        let module = match &val {
+            Object(obj) if self.wrap_execute_data && obj.len() == 1 => obj
+                .keys()
+                .find(|key| self.is_registered(key))
+                .map(|key| self.resolve(key).to_string()),
+            _ => None,
+        };
+        let resp = self.dispatch_value(deps, env, info, val)?;
+        self.finish_execute(deps, module, resp)
+    }
+
+    /// Turn `resp`, the response to an execute message, into the contract's
+    /// response, wrapping its data under the name of `module`, the module
+    /// the message addressed, if set.
+    fn finish_execute(
+        &self,
+        deps: &mut DepsMut<Q>,
+        module: Option<String>,
+        resp: Response<C>,
+    ) -> Result<cosmwasm_std::Response<C>, Error> {
+        if let Some(module) = module {
+            let mut aggregator = Aggregator::with_envelope(self.data_envelope.clone());
+            aggregator.fold_response(module, resp)?;
+            return self.aggregate(deps.storage, &mut aggregator);
+        }
+        let resp: cosmwasm_std::Response<C> = resp.try_into()?;
+        if reply::expects_reply(&resp.messages) {
+            reply::set_pending_data(deps.storage, None)?;
+        }
+        Ok(resp)
+    }
+
+    /// Dispatch the glue message in the ibc-hooks `memo` of an ICS-20
+    /// transfer to the module it addresses, exposing the transferred funds
+    /// as [Received::Ics20]; see [ibc_hooks][crate::ibc_hooks]. The memo
+    /// must target this contract.
+    pub fn execute_ibc_memo(
+        &mut self,
+        deps: &mut DepsMut<Q>,
+        env: Env,
+        info: MessageInfo,
+        memo: &[u8],
+    ) -> Result<cosmwasm_std::Response<C>, Error> {
+        let result = self.execute_memo(deps, env, info, memo);
+        self.report(result)
+    }
+
+    fn execute_memo(
+        &mut self,
+        deps: &mut DepsMut<Q>,
+        env: Env,
+        info: MessageInfo,
+        memo: &[u8],
+    ) -> Result<cosmwasm_std::Response<C>, Error> {
+        let memo = Memo::deserialize(self.parse(memo)?).map_err(|e| Error::ParseError {
+            msg: Some(e.to_string()),
+        })?;
+        if memo.wasm.contract != env.contract.address.as_str() {
+            return Err(Error::ParseError {
+                msg: Some(format!("memo targets {}", memo.wasm.contract)),
+            });
+        }
+        let received = Received::Ics20 {
+            funds: info.funds.clone(),
+        };
+        self.dispatch_received(deps, &env, &info, memo.wasm.msg, received)?.try_into()
+    }
+
+    /// End a dispatch, dropping the query responses cached during it, and
+    /// return `result`, with its error turned into a
+    /// [Structured][Error::Structured] one if
+    /// [structured errors][Self::set_structured_errors] are enabled.
+    fn report<T>(&self, result: Result<T, Error>) -> Result<T, Error> {
+        if let Some(mut cache) = shared::try_borrow_mut(&self.query_cache) {
+            cache.clear();
+        }
+        match result {
+            Err(err) if self.structured_errors => Err(Error::Structured(err.envelope())),
+            result => result,
+        }
+    }
+
+    /// The response cached for the query `payload` to the module `module`
+    /// during the current dispatch, if any.
+    pub(crate) fn cached_query(&self, module: &str, payload: &str) -> Option<Binary> {
+        let key = (self.resolve(module).to_string(), payload.to_string());
+        shared::try_borrow(&self.query_cache)?.get(&key).cloned()
+    }
+
+    /// Cache `resp`, the response to the query `payload` to the module
+    /// `module`, until the current dispatch ends.
+    pub(crate) fn cache_query(&self, module: &str, payload: String, resp: Binary) {
+        if let Some(mut cache) = shared::try_borrow_mut(&self.query_cache) {
+            cache.insert((self.resolve(module).to_string(), payload), resp);
+        }
+    }
+
+    /// Route a parsed execute message, i.e. a glue envelope, to its handler.
+    pub(crate) fn dispatch_value(
+        &self,
+        deps: &mut DepsMut<Q>,
+        env: &Env,
+        info: &MessageInfo,
+        msg: Value,
+    ) -> Result<Response<C>, Error> {
+        match single_payload(msg)? {
+            (key, Value::Array(entries)) if key == BATCH_KEY => {
+                self.execute_batch(deps, env, info, entries)
+            }
+            (key, Value::Array(entries)) if key == PARTIAL_BATCH_KEY => {
+                self.execute_partial_batch(deps, env, info, entries)
+            }
+            (key, _) if key == BATCH_KEY || key == PARTIAL_BATCH_KEY => Err(Error::ParseError {
+                msg: Some(format!("{} must be an array of module payloads", key)),
+            }),
+            (key, payload) if key == CW20_RECEIVE_KEY && !self.is_registered(&key) => {
+                self.receive_cw20(deps, env, info, &payload)
+            }
+            (key, payload) if key == CW721_RECEIVE_KEY && !self.is_registered(&key) => {
+                self.receive_cw721(deps, env, info, &payload)
+            }
+            (module_name, payload) => {
+                self.execute_entry(deps, env, info, &module_name, &payload)
+            }
+        }
+    }
+
+    /// Whether `name` is the name or alias of a registered module.
+    fn is_registered(&self, name: &str) -> bool {
+        self.modules.contains_key(self.resolve(name))
+    }
+
+    /// Dispatch the glue message embedded in a [Cw20ReceiveMsg] to the
+    /// module it addresses; see [receive][crate::receive].
+    fn receive_cw20(
+        &self,
+        deps: &mut DepsMut<Q>,
+        env: &Env,
+        info: &MessageInfo,
+        payload: &Value,
+    ) -> Result<Response<C>, Error> {
+        let msg = Cw20ReceiveMsg::deserialize(payload).map_err(|e| Error::ParseError {
+            msg: Some(e.to_string()),
+        })?;
+        let received = Received::Cw20 {
+            token: info.sender.clone(),
+            sender: msg.sender,
+            amount: msg.amount,
+        };
+        self.dispatch_received(deps, env, info, self.parse(&msg.msg)?, received)
+    }
+
+    /// Dispatch the glue message embedded in a [Cw721ReceiveMsg] to the
+    /// module it addresses; see [receive][crate::receive].
+    fn receive_cw721(
+        &self,
+        deps: &mut DepsMut<Q>,
+        env: &Env,
+        info: &MessageInfo,
+        payload: &Value,
+    ) -> Result<Response<C>, Error> {
+        let msg = Cw721ReceiveMsg::deserialize(payload).map_err(|e| Error::ParseError {
+            msg: Some(e.to_string()),
+        })?;
+        let received = Received::Cw721 {
+            collection: info.sender.clone(),
+            sender: msg.sender,
+            token_id: msg.token_id,
+        };
+        self.dispatch_received(deps, env, info, self.parse(&msg.msg)?, received)
+    }
+
+    /// Dispatch the glue message `msg`, embedded in a token receive hook, to
+    /// the module it addresses.
+    fn dispatch_received(
+        &self,
+        deps: &mut DepsMut<Q>,
+        env: &Env,
+        info: &MessageInfo,
+        msg: Value,
+        received: Received,
+    ) -> Result<Response<C>, Error> {
+        let (name, payload) = single_payload(msg)?;
+        if name.starts_with('_') {
+            return Err(Error::ParseError {
+                msg: Some(format!("received tokens cannot be routed to {:?}", name)),
+            });
+        }
+        self.dispatch(deps, env, info, &name, &payload, Some(received))
+    }
+
+    /// Dispatch the single payload of an execute message, either to the
+    /// Manager itself or to a module.
+    fn execute_entry(
+        &self,
+        deps: &mut DepsMut<Q>,
+        env: &Env,
+        info: &MessageInfo,
+        name: &str,
+        payload: &Value,
+    ) -> Result<Response<C>, Error> {
+        if name == MANAGER_NAMESPACE {
+            self.execute_manager(deps, env, info, payload)
+        } else if name == PERMIT_KEY {
+            self.execute_permit(deps, info, payload)
+        } else {
+            self.dispatch_execute(deps, env, info, name, payload)
+        }
+    }
+
+    /// Dispatch the entries of a [BATCH_KEY] message in order, concatenating
+    /// their responses.
+    fn execute_batch(
+        &self,
+        deps: &mut DepsMut<Q>,
+        env: &Env,
+        info: &MessageInfo,
+        entries: Vec<Value>,
+    ) -> Result<Response<C>, Error> {
+        let mut resp = Response::new();
+        let mut data = Vec::with_capacity(entries.len());
+        for (entry, info) in split_batch_funds(info, entries)? {
+            let (name, payload) = batch_entry(entry)?;
+            let mut entry_resp = self.execute_entry(deps, env, &info, &name, &payload)?;
+            data.push(entry_resp.take_data_value()?);
+            resp.merge(entry_resp)?;
+        }
+        Ok(resp.try_set_data(data)?)
+    }
+
+    /// Dispatch the entries of a [PARTIAL_BATCH_KEY] message in order,
+    /// skipping those that fail validation.
+    fn execute_partial_batch(
+        &self,
+        deps: &mut DepsMut<Q>,
+        env: &Env,
+        info: &MessageInfo,
+        entries: Vec<Value>,
+    ) -> Result<Response<C>, Error> {
+        let mut resp = Response::new();
+        let mut data = Vec::with_capacity(entries.len());
+        for (index, (entry, info)) in split_batch_funds(info, entries)?.into_iter().enumerate() {
+            let (name, payload) = batch_entry(entry)?;
+            if let Err(err) = self.validate_entry(deps.as_ref(), env, &info, &name, &payload) {
+                if !info.funds.is_empty() {
+                    return Err(err);
+                }
+                if self.diagnostics {
+                    let event = diagnostic_event(&err, err.module().unwrap_or(&name))
+                        .add_attribute("batch_index", index.to_string());
+                    resp = resp.add_event(event);
+                }
+                data.push(Value::Null);
+                continue;
+            }
+            let mut entry_resp = self.execute_entry(deps, env, &info, &name, &payload)?;
+            data.push(entry_resp.take_data_value()?);
+            resp.merge(entry_resp)?;
+        }
+        Ok(resp.try_set_data(data)?)
+    }
+
+    /// Check that the execute payload `payload` addressed to `name` would be
+    /// dispatched to a module accepting it, without executing it.
+    fn validate_entry(
+        &self,
+        deps: Deps<Q>,
+        env: &Env,
+        info: &MessageInfo,
+        name: &str,
+        payload: &Value,
+    ) -> Result<(), Error> {
+        if name == MANAGER_NAMESPACE || name == PERMIT_KEY {
+            return Ok(());
+        }
+        let target = self.resolve(name);
+        let module = match self.modules.get(target) {
+            Some(module) => module,
+            None => {
+                if self.successor(deps.storage)?.is_some() {
+                    return Self::check_forwardable(deps.storage);
+                }
+                let (fallback, payload) = self.fallback(name, payload)?;
+                return self.validate_entry(deps, env, info, fallback, &payload);
+            }
+        };
+        self.check_available(deps.storage, target)?;
+        let module = module.borrow(target)?;
+        let info = &self.module_info(info, target)?;
+        if !info.funds.is_empty() && !module.capabilities().payable {
+            return Err(Error::NonPayable {
+                module: target.to_string(),
+            });
+        }
+        let ctx = Context::new(self, target);
+        module
+            .validate_execute_value(&ctx, &deps, env, info, payload)
+            .map_err(|err| Error::ExecutionError {
+                module: target.to_string(),
+                err,
+            })
+    }
+
+    /// Fail if execute messages to the module registered under `target` are
+    /// currently rejected, because it or the contract is paused or disabled.
+    fn check_available(&self, storage: &dyn Storage, target: &str) -> Result<(), Error> {
+        if let Some(GlobalPause { emergency_modules }) = pause::global_pause(storage)? {
+            if !emergency_modules.iter().any(|m| m == target) {
+                return Err(Error::ContractPaused {});
+            }
+        }
+        if pause::disabled(storage, target)?.is_some() {
+            return Err(Error::ModuleDisabled {
+                module: target.to_string(),
+            });
+        }
+        if pause::is_paused(storage, target)? {
+            return Err(Error::ModulePaused {
+                module: target.to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Dispatch an execute payload to the module registered under `name`,
+    /// which may be an alias, then deliver any hooks it published.
+    fn dispatch_execute(
+        &self,
+        deps: &mut DepsMut<Q>,
+        env: &Env,
+        info: &MessageInfo,
+        name: &str,
+        payload: &Value,
+    ) -> Result<Response<C>, Error> {
+        self.dispatch(deps, env, info, name, payload, None)
+    }
+
+    /// Like [dispatch_execute][Self::dispatch_execute], exposing the tokens
+    /// `received` along with the payload to the module.
+    fn dispatch(
+        &self,
+        deps: &mut DepsMut<Q>,
+        env: &Env,
+        info: &MessageInfo,
+        name: &str,
+        payload: &Value,
+        received: Option<Received>,
+    ) -> Result<Response<C>, Error> {
+        let target = self.resolve(name);
+        let module = match self.modules.get(target) {
+            Some(module) => module,
+            None => {
+                if let Some(successor) = self.successor(deps.storage)? {
+                    Self::check_forwardable(deps.storage)?;
+                    return successor::forward_execute(&successor, info, name, payload);
+                }
+                let (fallback, payload) = self.fallback(name, payload)?;
+                return self.dispatch(deps, env, info, fallback, &payload, received);
+            }
+        };
+        self.check_available(deps.storage, target)?;
+        let mut module = module.borrow_mut(target)?;
+        for middleware in &self.middleware {
+            middleware.before_execute(deps, env, info, target, payload)?;
+        }
+        let info = self.module_info(info, target)?;
+        if !info.funds.is_empty() && !module.capabilities().payable {
+            return Err(Error::NonPayable {
+                module: target.to_string(),
+            });
+        }
+        let ctx = Context::new(self, target).with_received(received);
+        let resp = module
+            .execute_value(&ctx, deps, env.clone(), info.clone(), payload)
+            .map_err(|err| Error::ExecutionError {
+                module: target.to_string(),
+                err,
+            })?;
+        drop(module);
+        self.finish_dispatch(&ctx, deps, env, &info, name, resp)
+    }
+
+    /// `info`, with the funds the module registered under `target` sees once
+    /// the middleware took their share, e.g. a fee.
+    fn module_info(&self, info: &MessageInfo, target: &str) -> Result<MessageInfo, Error> {
+        let mut info = info.clone();
+        for middleware in &self.middleware {
+            info.funds = middleware.module_funds(target, info.funds)?;
+        }
+        Ok(info)
+    }
+
+    /// Like [dispatch_execute][Self::dispatch_execute] for a registered
+    /// module, with the payload still encoded; see
+    /// [execute_raw][GenericModule::execute_raw]. Middleware work on a
+    /// `Value`, so if any were added the payload is parsed first.
+    fn dispatch_raw(
+        &self,
+        deps: &mut DepsMut<Q>,
+        env: &Env,
+        info: &MessageInfo,
+        name: &str,
+        payload: &RawValue,
+    ) -> Result<Response<C>, Error> {
+        if !self.middleware.is_empty() {
+            let payload: Value = serde_json::from_str(payload.get())?;
+            return self.dispatch_execute(deps, env, info, name, &payload);
+        }
+        let target = self.resolve(name);
+        let module = self.modules.get(target).ok_or_else(|| self.not_found(name))?;
+        self.check_available(deps.storage, target)?;
+        let mut module = module.borrow_mut(target)?;
+        if !info.funds.is_empty() && !module.capabilities().payable {
+            return Err(Error::NonPayable {
+                module: target.to_string(),
+            });
+        }
+        let ctx = Context::new(self, target);
+        let resp = module
+            .execute_raw(&ctx, deps, env.clone(), info.clone(), payload)
+            .map_err(|err| Error::ExecutionError {
+                module: target.to_string(),
+                err,
+            })?;
+        drop(module);
+        self.finish_dispatch(&ctx, deps, env, info, name, resp)
+    }
+
+    /// Complete the dispatch of an execute payload addressed to `name`, to
+    /// which the module of `ctx` responded with `resp`: tag its attributes,
+    /// deliver any hooks it published, and run the middleware.
+    fn finish_dispatch(
+        &self,
+        ctx: &Context<C, Q>,
+        deps: &mut DepsMut<Q>,
+        env: &Env,
+        info: &MessageInfo,
+        name: &str,
+        mut resp: Response<C>,
+    ) -> Result<Response<C>, Error> {
+        let target = ctx.module();
+        resp.tag_attributes(target, self.attribute_tagging);
+        if self.aliases.contains_key(name) {
+            resp = resp.add_event(deprecated_alias_event(name, target));
+        }
+        self.deliver_hooks(ctx, deps, env, info, &mut resp)?;
+        for middleware in self.middleware.iter().rev() {
+            middleware.after_execute(deps, env, info, target, &mut resp)?;
+        }
+        Ok(resp)
+    }
+
+    /// Handle an execute message addressed to the reserved
+    /// [MANAGER_NAMESPACE]. Only the [admin][crate::admin] may send these.
+    fn execute_manager(
+        &self,
+        deps: &mut DepsMut<Q>,
+        env: &Env,
+        info: &MessageInfo,
+        payload: &Value,
+    ) -> Result<Response<C>, Error> {
+        admin::assert_admin(deps.storage, &info.sender)?;
+        let msg = ManagerExecute::deserialize(payload).map_err(|e| Error::ParseError {
+            msg: Some(e.to_string()),
+        })?;
+        let resp = Response::new().add_attribute("module", MANAGER_NAMESPACE);
+        match msg {
+            ManagerExecute::UpdateAdmin { admin } => {
+                let admin = deps.api.addr_validate(&admin)?;
+                admin::set_admin(deps.storage, &admin)?;
+                Ok(resp
+                    .add_attribute("action", "update_admin")
+                    .add_attribute("admin", admin))
+            }
+            ManagerExecute::SetSuccessor { successor } => {
+                let successor = deps.api.addr_validate(&successor)?;
+                successor::set_successor(deps.storage, &successor)?;
+                Ok(resp
+                    .add_attribute("action", "set_successor")
+                    .add_attribute("successor", successor))
+            }
+            ManagerExecute::ClearSuccessor {} => {
+                successor::clear_successor(deps.storage);
+                Ok(resp.add_attribute("action", "clear_successor"))
+            }
+            ManagerExecute::PauseModule { module } => {
+                self.pause_module(deps.storage, &module)?;
+                Ok(resp
+                    .add_attribute("action", "pause_module")
+                    .add_attribute("paused_module", module))
+            }
+            ManagerExecute::UnpauseModule { module } => {
+                self.unpause_module(deps.storage, &module)?;
+                Ok(resp
+                    .add_attribute("action", "unpause_module")
+                    .add_attribute("unpaused_module", module))
+            }
+            ManagerExecute::DisableModule {
+                module,
+                keep_queries,
+            } => {
+                self.disable_module(deps.storage, &module, keep_queries)?;
+                Ok(resp
+                    .add_attribute("action", "disable_module")
+                    .add_attribute("disabled_module", module))
+            }
+            ManagerExecute::EnableModule { module } => {
+                self.enable_module(deps.storage, &module)?;
+                Ok(resp
+                    .add_attribute("action", "enable_module")
+                    .add_attribute("enabled_module", module))
+            }
+            ManagerExecute::PauseAll { emergency_modules } => {
+                let emergency_modules = emergency_modules
+                    .iter()
+                    .map(|module| match self.resolve(module) {
+                        target if self.modules.contains_key(target) => Ok(target.to_string()),
+                        _ => Err(self.not_found(module)),
+                    })
+                    .collect::<Result<_, _>>()?;
+                pause::pause_all(deps.storage, &GlobalPause { emergency_modules })?;
+                Ok(resp.add_attribute("action", "pause_all"))
+            }
+            ManagerExecute::UnpauseAll {} => {
+                pause::unpause_all(deps.storage);
+                Ok(resp.add_attribute("action", "unpause_all"))
+            }
+            ManagerExecute::Broadcast { msg } => {
+                let mut broadcast = self.dispatch_broadcast(deps, env, info, &msg)?;
+                broadcast.merge(resp.add_attribute("action", "broadcast"))?;
+                Ok(broadcast)
+            }
+        }
+    }
+
+    /// Handle an execute message addressed to the reserved [PERMIT_KEY], on
+    /// behalf of the sender.
+    fn execute_permit(
+        &self,
+        deps: &mut DepsMut<Q>,
+        info: &MessageInfo,
+        payload: &Value,
+    ) -> Result<Response<C>, Error> {
+        let msg = PermitExecute::deserialize(payload).map_err(|e| Error::ParseError {
+            msg: Some(e.to_string()),
+        })?;
+        let resp = Response::new()
+            .add_attribute("module", PERMIT_KEY)
+            .add_attribute("signer", info.sender.clone());
+        match msg {
+            PermitExecute::RegisterPubkey { pub_key } => {
+                if pub_key.len() != 33 && pub_key.len() != 65 {
+                    return Err(Error::InvalidPermit {
+                        reason: "expected a 33 or 65 byte secp256k1 public key".to_string(),
+                    });
+                }
+                permit::set_pubkey(deps.storage, &info.sender, &pub_key)?;
+                Ok(resp.add_attribute("action", "register_pubkey"))
+            }
+            PermitExecute::Revoke { permit_name } => {
+                permit::revoke(deps.storage, &info.sender, &permit_name)?;
+                Ok(resp
+                    .add_attribute("action", "revoke_permit")
+                    .add_attribute("permit_name", permit_name))
+            }
+        }
+    }
+
+    /// Send `msg` to every registered module, in
+    /// [dependency order][Self::dependency_order], through their
+    /// [on_broadcast][crate::module::Module::on_broadcast] handlers. The
+    /// responses of the modules that opt in are aggregated like instantiate
+    /// responses, with data keyed by module name. Broadcasts reach paused
+    /// modules too.
+    ///
+    /// The [admin][crate::admin] can also broadcast with a `broadcast`
+    /// message under the reserved [MANAGER_NAMESPACE].
+    pub fn broadcast(
+        &mut self,
+        deps: &mut DepsMut<Q>,
+        env: Env,
+        info: MessageInfo,
+        msg: &impl Serialize,
+    ) -> Result<cosmwasm_std::Response<C>, Error> {
+        let result = self.broadcast_value(deps, env, info, msg);
+        self.report(result)
+    }
+
+    fn broadcast_value(
+        &mut self,
+        deps: &mut DepsMut<Q>,
+        env: Env,
+        info: MessageInfo,
+        msg: &impl Serialize,
+    ) -> Result<cosmwasm_std::Response<C>, Error> {
+        let msg = serde_json::to_value(msg)?;
+        self.dispatch_broadcast(deps, &env, &info, &msg)?.try_into()
+    }
+
+    fn dispatch_broadcast(
+        &self,
+        deps: &mut DepsMut<Q>,
+        env: &Env,
+        info: &MessageInfo,
+        msg: &Value,
+    ) -> Result<Response<C>, Error> {
+        let mut aggregated = Response::new();
+        let mut data: BTreeMap<String, Value> = BTreeMap::new();
+        for name in &self.dependency_order()? {
+            let module = &self.modules[name];
+            let ctx = Context::new(self, name);
+            let resp = module
+                .borrow_mut(name)?
+                .on_broadcast_value(&ctx, deps, env, info, msg)
+                .map_err(|err| Error::ExecutionError {
+                    module: name.clone(),
+                    err,
+                })?;
+            if let Some(mut resp) = resp {
+                resp.tag_attributes(name, self.attribute_tagging);
+                self.deliver_hooks(&ctx, deps, env, info, &mut resp)?;
+                data.insert(name.clone(), resp.take_data_value()?);
+                aggregated.merge(resp)?;
+            }
+        }
+        if data.is_empty() {
+            return Ok(aggregated);
+        }
+        Ok(aggregated.try_set_data(data)?)
+    }
+
+    /// Deliver the hooks published through `publisher` to their subscribers,
+    /// merging the subscribers' responses into `resp`. Hooks published by
+    /// subscribers are delivered in turn.
+    fn deliver_hooks(
+        &self,
+        publisher: &Context<C, Q>,
+        deps: &mut DepsMut<Q>,
+        env: &Env,
+        info: &MessageInfo,
+        resp: &mut Response<C>,
+    ) -> Result<(), Error> {
+        let mut queue: VecDeque<(String, Value)> = publisher.take_published().into();
+        let mut deliveries = 0;
+        while let Some((topic, payload)) = queue.pop_front() {
+            for subscriber in self.subscriptions.get(&topic).into_iter().flatten() {
+                deliveries += 1;
+                if deliveries > MAX_HOOK_DELIVERIES {
+                    return Err(Error::HookLimitExceeded {
+                        limit: MAX_HOOK_DELIVERIES,
+                    });
+                }
+                let module = self
+                    .modules
+                    .get(subscriber)
+                    .ok_or_else(|| self.not_found(subscriber))?;
+                let ctx = Context::new(self, subscriber);
+                let mut hook_resp = module
+                    .borrow_mut(subscriber)?
+                    .on_hook_value(&ctx, deps, env, info, &topic, &payload)
+                    .map_err(|err| Error::HookError {
+                        module: subscriber.clone(),
+                        topic: topic.clone(),
+                        err,
+                    })?;
+                hook_resp.tag_attributes(subscriber, self.attribute_tagging);
+                resp.merge(hook_resp)?;
+                queue.extend(ctx.take_published());
+            }
+        }
+        Ok(())
+    }
+
+    /// Dispatch a JSON-encoded query message to the appropriate module
+    /// registered within the `Manager` instance.
+    ///
+    /// To query several modules in one call, key their payloads by module
+    /// under the reserved [MULTI_KEY]:
+    ///
+    /// ```javascript
+    /// { "_multi": { "token": { ... }, "sale": { ... } } }
+    /// ```
+    ///
+    /// The result is a JSON object holding each module's response under the
+    /// same key. If any query fails, the whole query fails.
+    pub fn query(&mut self, deps: &Deps<Q>, env: Env, msg: &str) -> StdResult<Binary> {
+        self.query_raw(deps, env, msg.as_bytes())
+    }
+
+    /// Dispatch a JSON-encoded query message, given as raw bytes, to the
+    /// appropriate module registered within the `Manager` instance.
+    pub fn query_raw(&mut self, deps: &Deps<Q>, env: Env, msg: &[u8]) -> StdResult<Binary> {
+        let result = self.query_bytes(deps, env, msg);
+        Ok(self.report(result)?)
+    }
+
+    /// Dispatch a query message that was already parsed; see
+    /// [execute_value][Self::execute_value].
+    pub fn query_value(&mut self, deps: &Deps<Q>, env: Env, msg: Value) -> StdResult<Binary> {
+        let result = self.query_parsed(deps, env, msg);
+        Ok(self.report(result)?)
+    }
+
+    fn query_bytes(&mut self, deps: &Deps<Q>, env: Env, msg: &[u8]) -> Result<Binary, Error> {
+        let (val, encoding) = match self.parse_envelope(msg)? {
+            Parsed::Single(name, payload) => {
+                return self.query_entry_raw(deps, &env, &name, payload);
+            }
+            Parsed::Value(val, encoding) => (val, encoding),
+        };
+        let result = self.query_parsed(deps, env, val)?;
+        encode_data(encoding, result)
+    }
+
+    fn query_parsed(&self, deps: &Deps<Q>, env: Env, val: Value) -> Result<Binary, Error> {
+        match single_payload(val)? {
+            (key, Object(queries)) if key == MULTI_KEY => {
+                let mut results: BTreeMap<String, Value> = BTreeMap::new();
+                for (name, payload) in queries {
+                    if name == MULTI_KEY {
+                        let err = Error::ParseError {
+                            msg: Some(format!("{} queries cannot be nested", MULTI_KEY)),
+                        };
+                        return Err(err);
+                    }
+                    let result = self.query_entry(deps, &env, &name, &payload, None)?;
+                    let result = match json::from_slice(&result) {
+                        Ok(result) => result,
+                        Err(_) => Value::String(result.to_base64()),
+                    };
+                    results.insert(name, result);
+                }
+                Ok(json::to_vec(&results)?.into())
+            }
+            (key, _) if key == MULTI_KEY => {
+                let err = Error::ParseError {
+                    msg: Some(format!("{} must be an object of module payloads", MULTI_KEY)),
+                };
+                Err(err)
+            }
+            (key, payload) if key == PERMIT_KEY => self.query_with_permit(deps, &env, payload),
+            (module_name, payload) => {
+                self.query_entry(deps, &env, &module_name, &payload, None)
+            }
+        }
+    }
+
+    /// Verify the permit of a [PERMIT_KEY] query and dispatch its query on
+    /// behalf of the signer; see [permit][crate::permit].
+    fn query_with_permit(
+        &self,
+        deps: &Deps<Q>,
+        env: &Env,
+        payload: Value,
+    ) -> Result<Binary, Error> {
+        let PermitQuery { permit, query } =
+            PermitQuery::deserialize(payload).map_err(|e| Error::ParseError {
+                msg: Some(e.to_string()),
+            })?;
+        let signer = permit::verify(deps, env, &permit)?;
+        let (name, payload) = single_payload(query)?;
+        let target = self.resolve(&name);
+        let allowed = permit
+            .params
+            .allowed_modules
+            .iter()
+            .any(|module| self.resolve(module) == target);
+        if name.starts_with('_') || !allowed {
+            return Err(Error::InvalidPermit {
+                reason: format!("the permit does not allow querying {:?}", name),
+            });
+        }
+        self.query_entry(deps, env, &name, &payload, Some(signer))
+    }
+
+    /// Dispatch the single payload of a query message, either to the Manager
+    /// itself or to a module, on behalf of the `signer` of a permit if any.
+    fn query_entry(
+        &self,
+        deps: &Deps<Q>,
+        env: &Env,
+        name: &str,
+        payload: &Value,
+        signer: Option<Addr>,
+    ) -> Result<Binary, Error> {
+        if name == MANAGER_NAMESPACE {
+            return Ok(self.query_manager(deps, env, payload)?);
+        }
+        let target = self.resolve(name);
+        for middleware in &self.middleware {
+            middleware.before_query(deps, env, target, payload)?;
+        }
+        let converts = self.key_case.converts_responses();
+        self.query_module_as(deps, env.clone(), name, payload, signer, converts)
+    }
+
+    /// Like [query_entry][Self::query_entry] for a registered module, with
+    /// the payload still encoded; see [dispatch_raw][Self::dispatch_raw].
+    fn query_entry_raw(
+        &self,
+        deps: &Deps<Q>,
+        env: &Env,
+        name: &str,
+        payload: &RawValue,
+    ) -> Result<Binary, Error> {
+        if !self.middleware.is_empty() {
+            let payload: Value = serde_json::from_str(payload.get())?;
+            return self.query_entry(deps, env, name, &payload, None);
+        }
+        let target = self.resolve(name);
+        let module = self.modules.get(target).ok_or_else(|| self.not_found(name))?;
+        if let Some(Disabled { keep_queries: false }) = pause::disabled(deps.storage, target)? {
+            return Err(Error::ModuleDisabled {
+                module: target.to_string(),
+            });
+        }
+        let module = module.borrow(target)?;
+        let ctx =
+            Context::new(self, target).with_converted_response(self.key_case.converts_responses());
+        module
+            .query_raw(&ctx, deps, env.clone(), payload)
+            .map_err(|err| Error::QueryError {
+                module: target.to_string(),
+                err,
+            })
+    }
+
+    /// Dispatch a query payload to the module registered under `name`, which
+    /// may be an alias.
+    pub(crate) fn query_module(
+        &self,
+        deps: &Deps<Q>,
+        env: Env,
+        name: &str,
+        payload: &Value,
+    ) -> Result<Binary, Error> {
+        self.query_module_as(deps, env, name, payload, None, false)
+    }
+
+    /// Like [query_module][Self::query_module], exposing the `signer` of the
+    /// permit the query was authenticated with to the module, and encoding
+    /// the response in camelCase if `converts`; see [KeyCase::CamelCaseOut].
+    fn query_module_as(
+        &self,
+        deps: &Deps<Q>,
+        env: Env,
+        name: &str,
+        payload: &Value,
+        signer: Option<Addr>,
+        converts: bool,
+    ) -> Result<Binary, Error> {
+        let target = self.resolve(name);
+        let module = match self.modules.get(target) {
+            Some(module) => module,
+            None => {
+                if let Some(successor) = self.successor(deps.storage)? {
+                    return successor::forward_query(deps, &successor, name, payload);
+                }
+                let (fallback, payload) = self.fallback(name, payload)?;
+                return self.query_module_as(deps, env, fallback, &payload, signer, converts);
+            }
+        };
+        if let Some(Disabled { keep_queries: false }) = pause::disabled(deps.storage, target)? {
+            return Err(Error::ModuleDisabled {
+                module: target.to_string(),
+            });
+        }
+        let module = module.borrow(target)?;
+        let ctx = Context::new(self, target)
+            .with_signer(signer)
+            .with_converted_response(converts);
+        module
+            .query_value(&ctx, deps, env, payload)
+            .map_err(|err| Error::QueryError {
+                module: target.to_string(),
+                err,
+            })
+    }
+
+    /// Answer a query addressed to the reserved [MANAGER_NAMESPACE].
+    fn query_manager(&self, deps: &Deps<Q>, env: &Env, payload: &Value) -> StdResult<Binary> {
+        let msg = ManagerQuery::deserialize(payload)
+            .map_err(|e| StdError::parse_err("ManagerQuery", e))?;
+        match msg {
+            ManagerQuery::ListModules {} => {
+                let modules = self
+                    .modules
+                    .iter()
+                    .map(|(name, module)| {
+                        let module = module.borrow(name)?;
+                        Ok(ModuleInfo {
+                            name: name.clone(),
+                            aliases: self
+                                .aliases
+                                .iter()
+                                .filter(|(_, target)| *target == name)
+                                .map(|(alias, _)| alias.clone())
+                                .collect(),
+                            id: self.module_id(name),
+                            version: module.version(),
+                            capabilities: module.capabilities(),
+                            paused: pause::is_paused(deps.storage, name)?,
+                            disabled: pause::disabled(deps.storage, name)?,
+                        })
+                    })
+                    .collect::<StdResult<Vec<ModuleInfo>>>()?;
+                cosmwasm_std::to_json_binary(&ListModulesResponse { modules })
+            }
+            ManagerQuery::Admin {} => cosmwasm_std::to_json_binary(&AdminResponse {
+                admin: admin::get_admin(deps.storage)?,
+            }),
+            ManagerQuery::GlobalPause {} => cosmwasm_std::to_json_binary(&GlobalPauseResponse {
+                paused: pause::global_pause(deps.storage)?,
+            }),
+            ManagerQuery::Successor {} => cosmwasm_std::to_json_binary(&SuccessorResponse {
+                successor: successor::successor(deps.storage)?,
+            }),
+            ManagerQuery::ModuleMetadata { module } => {
+                let name = self.resolve(&module).to_string();
+                let metadata = match self.modules.get(&name) {
+                    Some(m) => m.borrow(&name)?.metadata(),
+                    None => return Err(StdError::generic_err(self.not_found(&module).to_string())),
+                };
+                cosmwasm_std::to_json_binary(&ModuleMetadataResponse { name, metadata })
+            }
+            ManagerQuery::Child { module, label } => cosmwasm_std::to_json_binary(&ChildResponse {
+                child: factory::child(deps.storage, self.resolve(&module), &label)?,
+            }),
+            ManagerQuery::Children {
+                module,
+                start_after,
+                limit,
+            } => {
+                let children = factory::children(
+                    deps.storage,
+                    self.resolve(&module),
+                    start_after.as_deref(),
+                    limit,
+                )?;
+                cosmwasm_std::to_json_binary(&ChildrenResponse { children })
+            }
+            ManagerQuery::Health {} => {
+                let modules = self
+                    .modules
+                    .iter()
+                    .map(|(name, module)| {
+                        let mut health = ModuleHealth {
+                            name: name.clone(),
+                            version: None,
+                            paused: pause::is_paused(deps.storage, name)?,
+                            disabled: pause::disabled(deps.storage, name)?,
+                            status: None,
+                            error: None,
+                        };
+                        // A module failing to report its status, or busy
+                        // with the dispatch of this query, must not hide the
+                        // status of the others.
+                        match module.borrow(name) {
+                            Ok(module) => {
+                                health.version = module.version();
+                                let ctx = Context::new(self, name);
+                                match module.on_health_value(&ctx, deps, env) {
+                                    Ok(status) => health.status = status,
+                                    Err(err) => health.error = Some(err.to_string()),
+                                }
+                            }
+                            Err(err) => health.error = Some(err.to_string()),
+                        }
+                        Ok(health)
+                    })
+                    .collect::<StdResult<Vec<ModuleHealth>>>()?;
+                cosmwasm_std::to_json_binary(&HealthResponse {
+                    global_pause: pause::global_pause(deps.storage)?,
+                    modules,
+                })
+            }
+        }
+    }
+
+    /// Dispatch JSON-encoded instantiate messages to modules registered within
+    /// the Manager.
+    ///
+    /// Modules are instantiated in [dependency order][Self::dependency_order],
+    /// which falls back to the order of their names, independent of the
+    /// order of keys in `msgs`, so the aggregated response is byte-for-byte
+    /// stable.
+    ///
+    /// Before any module is instantiated, every payload is parsed and
+    /// [validated][crate::module::Module::validate_instantiate], failing with
+    /// a single [InstantiateValidationError][Error::InstantiateValidationError]
+    /// listing every invalid payload. Messages addressing a module more than
+    /// once fail with [DuplicateModuleKey][Error::DuplicateModuleKey], rather
+    /// than silently dropping all but the last of its payloads.
+    /// Keys matching no registered module fail with
+    /// [NotFoundError][Error::NotFoundError], unless the Manager is
+    /// [lenient][Self::set_lenient_instantiate].
+    ///
+    /// Modules registered with [ModuleOptions] are instantiated with their
+    /// default payload if `msgs` has none for them, and instantiation fails
+    /// if a required module has neither.
+    ///
+    /// By default every module sees all of `info.funds`. To hand each module
+    /// only its share, split the funds under the reserved [FUNDS_KEY]:
+    ///
+    /// ```javascript
+    /// {
+    ///     "_funds": { "escrow": [{ "denom": "uxion", "amount": "100" }] },
+    ///     "escrow": { ... },
+    ///     "token": { ... }
+    /// }
+    /// ```
+    ///
+    /// Modules without an allocation then receive no funds. As with
+    /// [batches][Self::execute], the allocations must add up to exactly the
+    /// attached funds, failing with
+    /// [FundsOverallocated][Error::FundsOverallocated] or
+    /// [FundsUnallocated][Error::FundsUnallocated] otherwise.
+    pub fn instantiate(
+        &mut self,
+        deps: DepsMut<Q>,
+        env: Env,
+        info: MessageInfo,
+        msgs: &str,
+    ) -> Result<cosmwasm_std::Response<C>, Error> {
+        self.instantiate_raw(deps, env, info, msgs.as_bytes())
+    }
+
+    /// Dispatch JSON-encoded instantiate messages, given as raw bytes, to
+    /// modules registered within the Manager.
+    pub fn instantiate_raw(
+        &mut self,
+        deps: DepsMut<Q>,
+        env: Env,
+        info: MessageInfo,
+        msgs: &[u8],
+    ) -> Result<cosmwasm_std::Response<C>, Error> {
+        let result = self.instantiate_bytes(deps, env, info, msgs);
+        self.report(result)
+    }
+
+    /// Dispatch instantiate messages that were already parsed; see
+    /// [execute_value][Self::execute_value].
+    pub fn instantiate_value(
+        &mut self,
+        deps: DepsMut<Q>,
+        env: Env,
+        info: MessageInfo,
+        msgs: Value,
+    ) -> Result<cosmwasm_std::Response<C>, Error> {
+        let result = self.instantiate_parsed(deps, env, info, msgs);
+        self.report(result)
+    }
+
+    fn instantiate_bytes(
+        &mut self,
+        deps: DepsMut<Q>,
+        env: Env,
+        info: MessageInfo,
+        msgs: &[u8],
+    ) -> Result<cosmwasm_std::Response<C>, Error> {
+        let (val, encoding) = self.parse_encoded(msgs)?;
+        let resp = self.instantiate_parsed(deps, env, info, val)?;
+        encode_response(encoding, resp)
+    }
+
+    fn instantiate_parsed(
+        &self,
+        mut deps: DepsMut<Q>,
+        env: Env,
+        info: MessageInfo,
+        val: Value,
+    ) -> Result<cosmwasm_std::Response<C>, Error> {
+        let mut aggregator: Aggregator<C> = Aggregator::with_envelope(self.data_envelope.clone());
+        if let Object(obj) = val {
+            let mut vals: BTreeMap<String, Value> = obj.into_iter().collect();
+            let funds = vals.remove(FUNDS_KEY);
+            let mut skipped = Vec::new();
+            if self.lenient_instantiate {
+                vals.retain(|name, _| {
+                    let registered = self.is_registered(name);
+                    if !registered {
+                        skipped.push(name.clone());
+                    }
+                    registered
+                });
+            }
+            for (name, options) in &self.options {
+                if vals.keys().any(|key| self.resolve(key) == name) {
+                    continue;
+                }
+                match &options.default_instantiate {
+                    Some(payload) => {
+                        vals.insert(name.clone(), payload.clone());
+                    }
+                    None if options.required => {
+                        return Err(Error::MissingInstantiateMsg {
+                            module: name.clone(),
+                        });
+                    }
+                    None => {}
+                }
+            }
+            let allocations = match funds {
+                Some(funds) => Some(self.allocate_funds(&info.funds, &funds, &vals)?),
+                None => None,
+            };
+            let mut payloads: BTreeMap<&str, (&str, &Value)> = BTreeMap::new();
+            for (module_name, payload) in &vals {
+                let target = self.resolve(module_name);
+                if !self.modules.contains_key(target) {
+                    return Err(self.not_found(module_name));
+                }
+                if payloads.insert(target, (module_name, payload)).is_some() {
+                    return Err(Error::DuplicateModuleKey {
+                        module: target.to_string(),
+                    });
+                }
+            }
+            let mut infos: BTreeMap<&str, MessageInfo> = BTreeMap::new();
+            for target in payloads.keys() {
+                let mut info = info.clone();
+                if let Some(allocations) = &allocations {
+                    info.funds = allocations.get(*target).cloned().unwrap_or_default();
+                }
+                infos.insert(*target, info);
+            }
+            let mut errors: BTreeMap<String, String> = BTreeMap::new();
+            for (target, (_, payload)) in &payloads {
+                let ctx = Context::new(self, target);
+                let validated = self.modules[*target]
+                    .borrow(target)?
+                    .validate_instantiate_value(
+                        &ctx,
+                        &deps.as_ref(),
+                        &env,
+                        &infos[target],
+                        payload,
+                    );
+                if let Err(err) = validated {
+                    errors.insert(target.to_string(), err.to_string());
+                }
+            }
+            if !errors.is_empty() {
+                return Err(Error::InstantiateValidationError { errors });
+            }
+            for target in &self.dependency_order()? {
+                let (module_name, payload) = match payloads.get(target.as_str()) {
+                    Some(entry) => *entry,
+                    None => continue,
+                };
+                let module = &self.modules[target];
+                let ctx = Context::new(self, target);
+                let info = &infos[target.as_str()];
+                let mut resp = module
+                    .borrow_mut(target)?
+                    .instantiate_value(&ctx, &mut deps, &env, info, payload)
+                    .map_err(|err| Error::InstantiateError {
+                        module: target.to_string(),
+                        err,
+                    })?;
+                resp.tag_attributes(target, self.attribute_tagging);
+                if self.aliases.contains_key(module_name) {
+                    resp = resp.add_event(deprecated_alias_event(module_name, target));
+                }
+                if let Some(version) = module.borrow(target)?.version() {
+                    version::set_module_version(deps.storage, target, &version)?;
+                }
+                aggregator.fold_response(target.to_string(), resp)?;
+            }
+            let resp = self.aggregate(deps.storage, &mut aggregator)?;
+            Ok(resp.add_attributes(skipped.into_iter().map(|name| ("skipped_module", name))))
+        } else {
+            Err(Error::ParseError { msg: None })
+        }
+    }
+
+    /// Parse the allocation of `funds` under [FUNDS_KEY], keyed by the names
+    /// modules are registered under. Every allocation must target a module
+    /// instantiated by `payloads`, and the allocations must add up to exactly
+    /// the attached `funds`.
+    fn allocate_funds(
+        &self,
+        funds: &[Coin],
+        allocation: &Value,
+        payloads: &BTreeMap<String, Value>,
+    ) -> Result<BTreeMap<String, Vec<Coin>>, Error> {
+        let allocation = BTreeMap::<String, Vec<Coin>>::deserialize(allocation).map_err(|e| {
+            Error::ParseError {
+                msg: Some(format!("invalid {}: {}", FUNDS_KEY, e)),
+            }
+        })?;
+        let mut allocated: BTreeMap<String, Uint128> = BTreeMap::new();
+        let mut slices: BTreeMap<String, Vec<Coin>> = BTreeMap::new();
+        for (module_name, coins) in allocation {
+            let target = self.resolve(&module_name);
+            if !self.modules.contains_key(target) {
+                return Err(self.not_found(&module_name));
+            }
+            if !payloads.keys().any(|name| self.resolve(name) == target) {
+                return Err(Error::ParseError {
+                    msg: Some(format!(
+                        "{} allocates funds to {:?}, which is not instantiated",
+                        FUNDS_KEY, module_name
+                    )),
+                });
+            }
+            for coin in &coins {
+                let total = allocated.entry(coin.denom.clone()).or_default();
+                *total = total.checked_add(coin.amount).map_err(StdError::from)?;
+            }
+            slices.entry(target.to_string()).or_default().extend(coins);
+        }
+        check_allocation(funds, &allocated)?;
+        Ok(slices)
+    }
+
+    /// Dispatch JSON-encoded migrate messages to modules registered within the
+    /// Manager.
+    ///
+    /// Like instantiate, `msgs` may contain a key for each module to migrate,
+    /// and modules are migrated in dependency order. Messages addressing a
+    /// module more than once, e.g. through both its name and an alias, fail
+    /// with [DuplicateModuleKey][Error::DuplicateModuleKey].
+    /// Every registered module reporting a [version][GenericModule::version]
+    /// has it checked against the version stored at instantiation or the last
+    /// migration, refusing downgrades, and the stored version is updated.
+    /// The module's [migration steps][crate::module::Module::migrations]
+    /// leading from the stored version are run first; see
+    /// [version][crate::version].
+    pub fn migrate(
+        &mut self,
+        deps: DepsMut<Q>,
+        env: Env,
+        msgs: &str,
+    ) -> Result<cosmwasm_std::Response<C>, Error> {
+        self.migrate_raw(deps, env, msgs.as_bytes())
+    }
+
+    /// Dispatch JSON-encoded migrate messages, given as raw bytes, to modules
+    /// registered within the Manager.
+    pub fn migrate_raw(
+        &mut self,
+        deps: DepsMut<Q>,
+        env: Env,
+        msgs: &[u8],
+    ) -> Result<cosmwasm_std::Response<C>, Error> {
+        let result = self.migrate_bytes(deps, env, msgs);
+        self.report(result)
+    }
+
+    fn migrate_bytes(
+        &mut self,
+        mut deps: DepsMut<Q>,
+        env: Env,
+        msgs: &[u8],
+    ) -> Result<cosmwasm_std::Response<C>, Error> {
+        let mut payloads: BTreeMap<String, Value> = BTreeMap::new();
+        match self.parse(msgs)? {
+            Object(obj) => {
+                for (module_name, payload) in obj {
+                    let target = self.resolve(&module_name);
+                    if !self.modules.contains_key(target) {
+                        return Err(self.not_found(&module_name));
+                    }
+                    if payloads.insert(target.to_string(), payload).is_some() {
+                        return Err(Error::DuplicateModuleKey {
+                            module: target.to_string(),
+                        });
+                    }
+                }
+            }
+            _ => return Err(Error::ParseError { msg: None }),
+        }
+
+        let mut aggregator: Aggregator<C> = Aggregator::with_envelope(self.data_envelope.clone());
+        for module_name in &self.dependency_order()? {
+            let module = &self.modules[module_name];
+            let version = module.borrow(module_name)?.version();
+            if let Some(version) = &version {
+                version::assert_not_downgrade(deps.storage, module_name, version)?;
+                let stored = version::get_module_version(deps.storage, module_name)?;
+                if let Some(stored) = stored {
+                    let steps = module.borrow(module_name)?.migration_steps();
+                    let path =
+                        version::migration_path(module_name, &steps, &stored.version, version)?;
+                    for index in path {
+                        let (from, to) = &steps[index];
+                        let mut resp = module
+                            .borrow_mut(module_name)?
+                            .migrate_step_value(&mut deps, &env, index)
+                            .map_err(|err| Error::MigrationError {
+                                module: module_name.to_string(),
+                                err,
+                            })?;
+                        resp.tag_attributes(module_name, self.attribute_tagging);
+                        let resp = resp.add_event(migration_event(module_name, from, to));
+                        aggregator.fold_response(module_name.clone(), resp)?;
+                    }
+                }
+            }
+            if let Some(payload) = payloads.get(module_name) {
+                let mut resp = module
+                    .borrow_mut(module_name)?
+                    .migrate_value(&mut deps, &env, payload)
+                    .map_err(|err| Error::MigrationError {
+                        module: module_name.to_string(),
+                        err,
+                    })?;
+                resp.tag_attributes(module_name, self.attribute_tagging);
+                aggregator.fold_response(module_name.clone(), resp)?;
+            }
+            if let Some(version) = &version {
+                version::set_module_version(deps.storage, module_name, version)?;
+            }
+        }
+        self.aggregate(deps.storage, &mut aggregator)
+    }
+
+    /// The response aggregated by `aggregator`. If it sends submessages
+    /// expecting a reply, the data of the modules is kept for the replies to
+    /// update; see [reply][crate::reply].
+    fn aggregate(
+        &self,
+        storage: &mut dyn Storage,
+        aggregator: &mut Aggregator<C>,
+    ) -> Result<cosmwasm_std::Response<C>, Error> {
+        let resp = aggregator.aggregate()?;
+        if reply::expects_reply(&resp.messages) {
+            reply::set_pending_data(storage, Some(aggregator.module_data()))?;
+        }
+        Ok(resp)
+    }
+
+    /// Dispatch the reply to a submessage to the module that sent it, as
+    /// identified by the [reply id][crate::context::Context::reply_id]. Data
+    /// set by the module's reply handler becomes the module's data; when the
+    /// submessage was sent from an aggregated response, it replaces the
+    /// module's entry in the aggregated data. See [reply][crate::reply].
+    pub fn reply(
+        &mut self,
+        deps: DepsMut<Q>,
+        env: Env,
+        msg: Reply,
+    ) -> Result<cosmwasm_std::Response<C>, Error> {
+        let result = self.handle_reply(deps, env, msg);
+        self.report(result)
+    }
+
+    fn handle_reply(
+        &mut self,
+        mut deps: DepsMut<Q>,
+        env: Env,
+        msg: Reply,
+    ) -> Result<cosmwasm_std::Response<C>, Error> {
+        let (hash, id) = reply::split_reply_id(msg.id);
+        let callback = if id & reply::CALLBACK_FLAG != 0 {
+            let pending = reply::take_callback(deps.storage, id)?.ok_or_else(|| {
+                Error::ParseError {
+                    msg: Some(format!("no callback for reply id {}", msg.id)),
+                }
+            })?;
+            Some(pending)
+        } else {
+            None
+        };
+        let callback = match callback {
+            Some(PendingCallback {
+                module,
+                child: Some(child),
+                ..
+            }) => {
+                let child = factory::save_child(deps.storage, &module, child, msg.result)?;
+                return Ok(cosmwasm_std::Response::new().add_event(factory::child_event(&child)));
+            }
+            callback => callback,
+        };
+        let target = match &callback {
+            Some(pending) => match self.modules.get_key_value(&pending.module) {
+                Some((target, _)) => target.as_str(),
+                None => return Err(self.not_found(&pending.module)),
+            },
+            None => {
+                let mut targets = self
+                    .modules
+                    .keys()
+                    .filter(|name| reply::module_hash(name) == hash);
+                match (targets.next(), targets.next()) {
+                    (Some(target), None) => target.as_str(),
+                    _ => {
+                        return Err(Error::ParseError {
+                            msg: Some(format!("no module for reply id {}", msg.id)),
+                        })
+                    }
+                }
+            }
+        };
+        let ctx = Context::new(self, target);
+        let mut module = self.modules[target].borrow_mut(target)?;
+        let resp = match callback {
+            Some(pending) => {
+                let callback = Callback::new(pending.callback);
+                module.on_callback_value(&ctx, &mut deps, &env, callback, msg.result)
+            }
+            None => {
+                let msg = Reply {
+                    id: id.into(),
+                    ..msg
+                };
+                module.reply_value(&ctx, &mut deps, &env, msg)
+            }
+        };
+        drop(module);
+        let mut resp = resp.map_err(|err| Error::ReplyError {
+            module: target.to_string(),
+            err,
+        })?;
+        resp.tag_attributes(target, self.attribute_tagging);
+        let data = resp.take_module_data()?;
+        let mut resp: cosmwasm_std::Response<C> = resp.try_into()?;
+        let pending = reply::pending_data(deps.storage)?;
+        resp.data = match pending {
+            _ if data.is_empty() => None,
+            Some(pending) => {
+                let mut aggregator: Aggregator<C> =
+                    Aggregator::with_data(self.data_envelope.clone(), pending);
+                aggregator.set_module_data(target.to_string(), data);
+                reply::set_pending_data(deps.storage, Some(aggregator.module_data()))?;
+                aggregator.encode_data()?
+            }
+            None => Some(data.to_bytes()?.into()),
+        };
+        Ok(resp)
+    }
+}
+
+/// A fluent builder for [Manager], validating each module registration as it
+/// is added:
+///
+/// ```ignore
+/// let manager = Manager::builder()
+///     .with_module("token", token)?
+///     .with_module("sale", sale)?
+///     .build();
+/// ```
+pub struct ManagerBuilder<C = Binary, Q: CustomQuery = Empty> {
+    manager: Manager<C, Q>,
+}
+
+impl<C, Q: CustomQuery> Default for ManagerBuilder<C, Q> {
+    fn default() -> Self {
+        ManagerBuilder {
+            manager: Manager::default(),
+        }
+    }
+}
+
+impl<C: Clone, Q: CustomQuery> ManagerBuilder<C, Q> {
+    /// Register `module` under `name`. Fails if the name is invalid or
+    /// already taken; see [Manager::register].
+    pub fn with_module(
+        mut self,
+        name: impl Into<String>,
+        module: Shared<dyn GenericModule<C, Q>>,
+    ) -> Result<Self, Error> {
+        self.manager.register(name.into(), module)?;
+        Ok(self)
+    }
+
+    /// Register `module` under `name` with `options`; see
+    /// [Manager::register_with_options].
+    pub fn with_module_options(
+        mut self,
+        name: impl Into<String>,
+        module: Shared<dyn GenericModule<C, Q>>,
+        options: ModuleOptions,
+    ) -> Result<Self, Error> {
+        self.manager.register_with_options(name.into(), module, options)?;
+        Ok(self)
+    }
+
+    /// Register the owned `module` under `name`; see
+    /// [Manager::register_boxed].
+    pub fn with_boxed_module(
+        mut self,
+        name: impl Into<String>,
+        module: Box<dyn GenericModule<C, Q>>,
+    ) -> Result<Self, Error> {
+        self.manager.register_boxed(name.into(), module)?;
+        Ok(self)
+    }
+
+    /// Register the owned `module` under `name` with `options`; see
+    /// [Manager::register_boxed_with_options].
+    pub fn with_boxed_module_options(
+        mut self,
+        name: impl Into<String>,
+        module: Box<dyn GenericModule<C, Q>>,
+        options: ModuleOptions,
+    ) -> Result<Self, Error> {
+        self.manager.register_boxed_with_options(name.into(), module, options)?;
+        Ok(self)
+    }
+
+    /// Make `value` available to every module handler; see
+    /// [Manager::insert_extension].
+    pub fn with_extension<T: MaybeSendSync + 'static>(mut self, value: T) -> Self {
+        self.manager.insert_extension(value);
+        self
+    }
+
+    /// Register `id` as a compact identifier of the module registered under
+    /// `target`; see [Manager::register_id].
+    pub fn with_module_id(mut self, id: u32, target: &str) -> Result<Self, Error> {
+        self.manager.register_id(id, target)?;
+        Ok(self)
+    }
+
+    /// Allow later registrations to replace modules registered under the same
+    /// name; see [Manager::set_allow_overwrite].
+    pub fn allow_overwrite(mut self, allow: bool) -> Self {
+        self.manager.set_allow_overwrite(allow);
+        self
+    }
+
+    /// Limit the size of messages; see [Manager::set_max_payload_bytes].
+    pub fn max_payload_bytes(mut self, limit: usize) -> Self {
+        self.manager.set_max_payload_bytes(Some(limit));
+        self
+    }
+
+    /// Limit the nesting depth of messages; see [Manager::set_max_depth].
+    pub fn max_depth(mut self, limit: usize) -> Self {
+        self.manager.set_max_depth(Some(limit));
+        self
+    }
+
+    /// Tag module attributes with their module's name; see
+    /// [Manager::set_attribute_tagging].
+    pub fn attribute_tagging(mut self, tagging: AttributeTagging) -> Self {
+        self.manager.set_attribute_tagging(tagging);
+        self
+    }
+
+    /// Encode aggregated data with `envelope`; see
+    /// [Manager::set_data_envelope].
+    pub fn data_envelope(mut self, envelope: DataEnvelope) -> Self {
+        self.manager.set_data_envelope(envelope);
+        self
+    }
+
+    /// Wrap the data of single-module execute messages in the data envelope;
+    /// see [Manager::set_wrap_execute_data].
+    pub fn wrap_execute_data(mut self, wrap: bool) -> Self {
+        self.manager.set_wrap_execute_data(wrap);
+        self
+    }
+
+    /// Convert the case of payload keys; see [Manager::set_key_case].
+    pub fn key_case(mut self, key_case: KeyCase) -> Self {
+        self.manager.set_key_case(key_case);
+        self
+    }
+
+    /// Reject payloads with unknown fields; see [Manager::set_strict].
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.manager.set_strict(strict);
+        self
+    }
+
+    /// Skip unknown keys of instantiate messages; see
+    /// [Manager::set_lenient_instantiate].
+    pub fn lenient_instantiate(mut self, lenient: bool) -> Self {
+        self.manager.set_lenient_instantiate(lenient);
+        self
+    }
+
+    /// Report failures as error envelopes; see
+    /// [Manager::set_structured_errors].
+    pub fn structured_errors(mut self, structured: bool) -> Self {
+        self.manager.set_structured_errors(structured);
+        self
+    }
+
+    /// Record swallowed failures as events; see [Manager::set_diagnostics].
+    pub fn diagnostics(mut self, diagnostics: bool) -> Self {
+        self.manager.set_diagnostics(diagnostics);
+        self
+    }
+
+    /// Validate payloads against module schemas; see
+    /// [Manager::set_validate_schemas].
+    pub fn validate_schemas(mut self, validate: bool) -> Self {
+        self.manager.set_validate_schemas(validate);
+        self
+    }
+
+    /// Route messages addressed to unknown keys to the module registered
+    /// under `module`; see [Manager::set_fallback].
+    pub fn with_fallback(mut self, module: &str) -> Result<Self, Error> {
+        self.manager.set_fallback(module)?;
+        Ok(self)
+    }
+
+    /// Add `middleware` to run around module dispatch; see
+    /// [Manager::add_middleware].
+    pub fn with_middleware(mut self, middleware: Box<dyn Middleware<C, Q>>) -> Self {
+        self.manager.add_middleware(middleware);
+        self
+    }
+
+    /// Finish building the Manager.
+    pub fn build(self) -> Manager<C, Q> {
+        self.manager
+    }
+}
+
+/// Split a message addressing a single key into that key and its payload.
+fn single_payload(msg: Value) -> Result<(String, Value), Error> {
+    match msg {
+        Object(obj) if obj.len() == 1 => Ok(obj.into_iter().next().unwrap()),
+        Object(_) => Err(Error::ParseError {
+            msg: Some("too many module payloads".to_string()),
+        }),
+        _ => Err(Error::ParseError { msg: None }),
+    }
+}
+
+/// `data`, re-encoded with `encoding` if it is JSON.
+fn encode_data(encoding: Encoding, data: Binary) -> Result<Binary, Error> {
+    match encoding {
+        Encoding::Json => Ok(data),
+        #[cfg(feature = "msgpack")]
+        Encoding::MessagePack => match json::from_slice::<Value>(&data) {
+            Ok(value) => Ok(json::to_msgpack(&value)?.into()),
+            Err(_) => Ok(data),
+        },
+    }
+}
+
+/// `resp`, with its data re-encoded with `encoding`.
+fn encode_response<C>(
+    encoding: Encoding,
+    mut resp: cosmwasm_std::Response<C>,
+) -> Result<cosmwasm_std::Response<C>, Error> {
+    resp.data = resp.data.map(|data| encode_data(encoding, data)).transpose()?;
+    Ok(resp)
+}
+
+fn deprecated_alias_event(alias: &str, module: &str) -> Event {
+    Event::new("glue-deprecated-alias")
+        .add_attribute("alias", alias)
+        .add_attribute("module", module)
+}
+
+fn diagnostic_event(err: &Error, module: &str) -> Event {
+    Event::new("glue-diagnostic")
+        .add_attribute("module", module)
+        .add_attribute("code", err.code())
+        .add_attribute("message", error_chain(err))
+}
+
+/// The single payload of an entry of a batch, which cannot itself be a
+/// batch.
+fn batch_entry(entry: Value) -> Result<(String, Value), Error> {
+    let (name, payload) = single_payload(entry)?;
+    if name == BATCH_KEY || name == PARTIAL_BATCH_KEY {
+        return Err(Error::ParseError {
+            msg: Some(format!("{} entries cannot be nested", name)),
+        });
+    }
+    Ok((name, payload))
+}
+
+/// The entries of a batch, each with the share of `info.funds` it attaches
+/// under [FUNDS_KEY]. The shares must add up to exactly `info.funds`, so no
+/// entry sees the funds of another.
+fn split_batch_funds(
+    info: &MessageInfo,
+    entries: Vec<Value>,
+) -> Result<Vec<(Value, MessageInfo)>, Error> {
+    let mut split = Vec::with_capacity(entries.len());
+    let mut allocated: BTreeMap<String, Uint128> = BTreeMap::new();
+    for mut entry in entries {
+        let funds = match &mut entry {
+            Object(obj) => obj.remove(FUNDS_KEY),
+            _ => None,
+        };
+        let funds = match funds {
+            Some(funds) => Vec::<Coin>::deserialize(funds).map_err(|e| Error::ParseError {
+                msg: Some(format!("invalid {}: {}", FUNDS_KEY, e)),
+            })?,
+            None => vec![],
+        };
+        for coin in &funds {
+            let total = allocated.entry(coin.denom.clone()).or_default();
+            *total = total.checked_add(coin.amount).map_err(StdError::from)?;
+        }
+        let info = MessageInfo {
+            sender: info.sender.clone(),
+            funds,
+        };
+        split.push((entry, info));
+    }
+    check_allocation(&info.funds, &allocated)?;
+    Ok(split)
+}
+
+/// Check that the funds `allocated` by denom add up to exactly the attached
+/// `funds`, failing with [FundsOverallocated][Error::FundsOverallocated] or
+/// [FundsUnallocated][Error::FundsUnallocated] otherwise.
+fn check_allocation(funds: &[Coin], allocated: &BTreeMap<String, Uint128>) -> Result<(), Error> {
+    let mut sent: BTreeMap<String, Uint128> = BTreeMap::new();
+    for coin in funds {
+        let total = sent.entry(coin.denom.clone()).or_default();
+        *total = total.checked_add(coin.amount).map_err(StdError::from)?;
+    }
+    for denom in allocated.keys().chain(sent.keys()) {
+        let allocated = allocated.get(denom).copied().unwrap_or_default();
+        let sent = sent.get(denom).copied().unwrap_or_default();
+        if allocated > sent {
+            return Err(Error::FundsOverallocated {
+                denom: denom.clone(),
+                allocated: allocated.to_string(),
+                sent: sent.to_string(),
+            });
+        }
+        if allocated < sent {
+            return Err(Error::FundsUnallocated {
+                denom: denom.clone(),
+                allocated: allocated.to_string(),
+                sent: sent.to_string(),
+            });
+        }
+    }
+    Ok(())
+}
+
+fn migration_event(module: &str, from: &str, to: &str) -> Event {
+    Event::new("glue-migration")
+        .add_attribute("module", module)
+        .add_attribute("from", from)
+        .add_attribute("to", to)
+}
+
+fn validate_module_name(name: &str) -> Result<(), Error> {
+    let reason = if name.is_empty() {
+        "name is empty"
+    } else if name.trim() != name {
+        "name has leading or trailing whitespace"
+    } else if name.chars().any(char::is_control) {
+        "name contains control characters"
+    } else if name.starts_with('_') {
+        "names beginning with an underscore are reserved"
+    } else {
+        return Ok(());
+    };
+    Err(Error::InvalidModuleName {
+        module: name.to_string(),
+        reason: reason.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{dispatch, funds_seen, info, instantiate_all, Deposits};
+    use cosmwasm_std::testing::{mock_dependencies, mock_env};
+    use cosmwasm_std::{coins, from_json};
+
+    fn manager() -> Manager {
+        Manager::builder()
+            .with_boxed_module("a", Box::new(Deposits))
+            .unwrap()
+            .with_boxed_module("b", Box::new(Deposits))
+            .unwrap()
+            .build()
+    }
+
+    #[test]
+    fn batch_splits_funds_across_entries() {
+        let msg = r#"{"_batch": [
+            {"_funds": [{"denom": "uxion", "amount": "60"}], "a": {}},
+            {"b": {}},
+            {"_funds": [{"denom": "uxion", "amount": "40"}], "b": {}}
+        ]}"#;
+        let resp = dispatch(manager(), &coins(100, "uxion"), msg).unwrap();
+        assert_eq!(funds_seen(&resp), ["60uxion", "", "40uxion"]);
+    }
+
+    #[test]
+    fn batch_rejects_unallocated_funds() {
+        let msg = r#"{"_batch": [{"a": {}}, {"a": {}}]}"#;
+        let err = dispatch(manager(), &coins(100, "uxion"), msg).unwrap_err();
+        assert!(matches!(err, Error::FundsUnallocated { .. }), "{:?}", err);
+    }
+
+    #[test]
+    fn batch_rejects_overallocated_funds() {
+        let msg = r#"{"_batch": [
+            {"_funds": [{"denom": "uxion", "amount": "100"}], "a": {}},
+            {"_funds": [{"denom": "uxion", "amount": "100"}], "a": {}}
+        ]}"#;
+        let err = dispatch(manager(), &coins(100, "uxion"), msg).unwrap_err();
+        assert!(matches!(err, Error::FundsOverallocated { .. }), "{:?}", err);
+    }
+
+    #[test]
+    fn single_message_sees_all_funds() {
+        let resp = dispatch(manager(), &coins(100, "uxion"), r#"{"a": {}}"#).unwrap();
+        assert_eq!(funds_seen(&resp), ["100uxion"]);
+    }
+
+    #[test]
+    fn partial_batch_splits_funds_and_skips_invalid_entries() {
+        let msg = r#"{"_partial_batch": [
+            {"missing": {}},
+            {"_funds": [{"denom": "uxion", "amount": "100"}], "a": {}},
+            {"b": {}}
+        ]}"#;
+        let resp = dispatch(manager(), &coins(100, "uxion"), msg).unwrap();
+        assert_eq!(funds_seen(&resp), ["100uxion", ""]);
+        let data: Value = from_json(resp.data.unwrap()).unwrap();
+        assert_eq!(data[0], Value::Null);
+    }
+
+    #[test]
+    fn partial_batch_rejects_unallocated_funds() {
+        let msg = r#"{"_partial_batch": [{"a": {}}, {"b": {}}]}"#;
+        let err = dispatch(manager(), &coins(100, "uxion"), msg).unwrap_err();
+        assert!(matches!(err, Error::FundsUnallocated { .. }), "{:?}", err);
+    }
+
+    #[test]
+    fn partial_batch_does_not_skip_entries_with_funds() {
+        let msg = r#"{"_partial_batch": [
+            {"_funds": [{"denom": "uxion", "amount": "100"}], "missing": {}},
+            {"a": {}}
+        ]}"#;
+        let err = dispatch(manager(), &coins(100, "uxion"), msg).unwrap_err();
+        assert!(matches!(err, Error::NotFoundError { .. }), "{:?}", err);
+    }
+
+    #[test]
+    fn instantiate_splits_funds_across_modules() {
+        let msg = r#"{
+            "_funds": {"a": [{"denom": "uxion", "amount": "60"}], "b": [{"denom": "uxion", "amount": "40"}]},
+            "a": {},
+            "b": {}
+        }"#;
+        let resp = instantiate_all(manager(), &coins(100, "uxion"), msg).unwrap();
+        assert_eq!(funds_seen(&resp), ["60uxion", "40uxion"]);
+    }
+
+    #[test]
+    fn instantiate_rejects_overallocated_funds() {
+        let msg = r#"{"_funds": {"a": [{"denom": "uxion", "amount": "101"}]}, "a": {}, "b": {}}"#;
+        let err = instantiate_all(manager(), &coins(100, "uxion"), msg).unwrap_err();
+        assert!(matches!(err, Error::FundsOverallocated { .. }), "{:?}", err);
+    }
+
+    #[test]
+    fn instantiate_rejects_unallocated_funds() {
+        let msg = r#"{"_funds": {"a": [{"denom": "uxion", "amount": "60"}]}, "a": {}, "b": {}}"#;
+        let err = instantiate_all(manager(), &coins(100, "uxion"), msg).unwrap_err();
+        assert!(matches!(err, Error::FundsUnallocated { .. }), "{:?}", err);
+        let msg = r#"{"_funds": {"a": []}, "a": {}}"#;
+        let err = instantiate_all(manager(), &coins(100, "ustake"), msg).unwrap_err();
+        assert!(matches!(err, Error::FundsUnallocated { .. }), "{:?}", err);
+    }
+
+    fn assert_duplicate(err: &Error, module: &str) {
+        assert!(
+            matches!(err, Error::DuplicateModuleKey { module: m } if m == module),
+            "{:?}",
+            err
+        );
+    }
+
+    #[test]
+    fn batch_entries_reject_repeated_keys() {
+        let err = dispatch(manager(), &[], r#"{"a": {}, "a": {}}"#).unwrap_err();
+        assert_duplicate(&err, "a");
+        let err = dispatch(
+            manager(),
+            &[],
+            r#"{"_batch": [{"b": {}}, {"a": {}, "a": {}}]}"#,
+        )
+        .unwrap_err();
+        assert_duplicate(&err, "a");
+        let msg = r#"{"_partial_batch": [{"a": {"x": 1}, "b": {}, "a": {}}]}"#;
+        let err = dispatch(manager(), &[], msg).unwrap_err();
+        assert_duplicate(&err, "a");
+    }
+
+    #[test]
+    fn instantiate_rejects_a_module_addressed_twice() {
+        for msg in [r#"{"a": {}, "legacy": {}}"#, r#"{"1": {}, "a": {}}"#] {
+            let mut manager = manager();
+            manager.register_alias("legacy".to_string(), "a").unwrap();
+            manager.register_id(1, "a").unwrap();
+            let err = instantiate_all(manager, &[], msg).unwrap_err();
+            assert_duplicate(&err, "a");
+        }
+    }
+
+    #[test]
+    fn payloads_may_repeat_reserved_keys() {
+        // Only the envelope is checked; module payloads are the module's.
+        let msg = r#"{"_batch": [{"a": {"_funds": 1, "_funds": 2}}]}"#;
+        dispatch(manager(), &[], msg).unwrap();
+    }
+
+    #[test]
+    fn multi_queries_and_fund_allocations_reject_repeated_keys() {
+        let deps = mock_dependencies();
+        let err = manager()
+            .query(
+                &deps.as_ref(),
+                mock_env(),
+                r#"{"_multi": {"a": {}, "a": {}}}"#,
+            )
+            .unwrap_err();
+        assert!(err.to_string().contains("more than once"), "{}", err);
+
+        let mut deps = mock_dependencies();
+        let msg = r#"{
+            "_funds": {
+                "a": [{"denom": "uxion", "amount": "50"}],
+                "a": [{"denom": "uxion", "amount": "50"}]
+            },
+            "a": {}
+        }"#;
+        let err = manager()
+            .instantiate(
+                deps.as_mut(),
+                mock_env(),
+                info("sender", &coins(100, "uxion")),
+                msg,
+            )
+            .unwrap_err();
+        assert_duplicate(&err, "a");
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn msgpack_messages_reject_repeated_keys() {
+        // {"a": {}, "a": {}}
+        let packed = Binary::from(vec![0x82, 0xa1, b'a', 0x80, 0xa1, b'a', 0x80]);
+        let msg = format!(r#"{{"{}": "{}"}}"#, MSGPACK_KEY, packed.to_base64());
+        let err = dispatch(manager(), &[], &msg).unwrap_err();
+        assert_duplicate(&err, "a");
+    }
+
+    #[test]
+    fn suggestions_skip_keys_out_of_reach() {
+        let err = dispatch(manager(), &[], r#"{"aa": {}}"#).unwrap_err();
+        assert!(
+            matches!(&err, Error::NotFoundError { suggestion: Some(s), .. } if s == "a"),
+            "{:?}",
+            err
+        );
+        let msg = format!(r#"{{"{}": {{}}}}"#, "a".repeat(10_000));
+        let err = dispatch(manager(), &[], &msg).unwrap_err();
+        assert!(
+            matches!(
+                err,
+                Error::NotFoundError {
+                    suggestion: None,
+                    ..
+                }
+            ),
+            "{:?}",
+            err
+        );
+    }
+
+    #[test]
+    fn names_with_colliding_reply_ids_are_rejected() {
+        assert_eq!(reply::module_hash("glbvs"), reply::module_hash("yacxa"));
+        let mut manager = manager();
+        manager
+            .register_boxed("glbvs".to_string(), Box::new(Deposits))
+            .unwrap();
+        let err = manager
+            .register_boxed("yacxa".to_string(), Box::new(Deposits))
+            .unwrap_err();
+        assert!(
+            matches!(&err, Error::InvalidModuleName { module, .. } if module == "yacxa"),
+            "{:?}",
+            err
+        );
+    }
+
+    #[test]
+    fn ids_and_module_names_do_not_shadow_each_other() {
+        let mut manager = manager();
+        manager.register_id(3, "a").unwrap();
+        let taken = |res: Result<(), Error>| matches!(res, Err(Error::ModuleAlreadyRegistered { module }) if module == "3");
+        assert!(taken(
+            manager.register_boxed("3".to_string(), Box::new(Deposits))
+        ));
+        assert!(taken(manager.register_alias("3".to_string(), "b")));
+
+        manager
+            .register_boxed("4".to_string(), Box::new(Deposits))
+            .unwrap();
+        assert!(matches!(
+            manager.register_id(4, "b"),
+            Err(Error::ModuleAlreadyRegistered { module }) if module == "4"
+        ));
+    }
+
+    #[test]
+    fn aliases_route_to_their_module_and_flag_the_deprecation() {
+        let mut manager = manager();
+        manager.register_alias("old_a".to_string(), "a").unwrap();
+        assert!(matches!(
+            manager.register_alias("b".to_string(), "a"),
+            Err(Error::ModuleAlreadyRegistered { module }) if module == "b"
+        ));
+        assert!(manager.register_alias("old_c".to_string(), "c").is_err());
+
+        let resp = dispatch(manager, &coins(5, "uxion"), r#"{"old_a": {}}"#).unwrap();
+        assert_eq!(funds_seen(&resp), ["5uxion"]);
+        let event = &resp.events[0];
+        assert_eq!(event.ty, "glue-deprecated-alias");
+        let attributes: Vec<(&str, &str)> = event
+            .attributes
+            .iter()
+            .map(|attr| (attr.key.as_str(), attr.value.as_str()))
+            .collect();
+        assert_eq!(attributes, [("alias", "old_a"), ("module", "a")]);
     }
 }
@@ -0,0 +1,332 @@
+//! Per-module sender allowlists.
+//!
+//! [AccessControl] is both a [Module] and a [Middleware]. Registered as a
+//! module, it lets an admin configure which addresses may execute messages on
+//! each module. Added as middleware, it rejects execute messages from senders
+//! not on the addressed module's allowlist. Modules without an allowlist are
+//! unrestricted.
+//!
+//! ```ignore
+//! manager.register(
+//!     "access_control".to_string(),
+//!     shared(AccessControl::new()),
+//! )?;
+//! manager.add_middleware(Box::new(AccessControl::new()));
+//! ```
+//!
+//! The admin is set at instantiation and configures allowlists with, e.g.:
+//!
+//! ```javascript
+//! { "access_control": { "set_allowlist": { "module": "admin_ops", "addresses": ["..."] } } }
+//! ```
+//!
+//! A module can also be guarded by a list kept in the
+//! [AddressList][crate::modules::address_list::AddressList] module, used
+//! either as an allowlist or as a denylist:
+//!
+//! ```javascript
+//! { "access_control": { "use_address_list": {
+//!     "module": "mint", "list": "blocked", "mode": "deny"
+//! } } }
+//! ```
+
+use crate::error::Error;
+use crate::middleware::Middleware;
+use crate::module::Module;
+use crate::modules::address_list;
+use crate::response::Response;
+use crate::storage;
+use cosmwasm_std::{Addr, CustomQuery, Deps, DepsMut, Env, MessageInfo, StdResult, Storage};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+const NAMESPACE: &[u8] = b"access_control";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct InstantiateMsg {
+    pub admin: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecuteMsg {
+    /// Restrict execute messages to `module` to the given addresses.
+    SetAllowlist {
+        module: String,
+        addresses: Vec<String>,
+    },
+    /// Lift the restrictions on `module`.
+    ClearAllowlist { module: String },
+    /// Guard `module` with the address list `list`.
+    UseAddressList {
+        module: String,
+        list: String,
+        mode: ListMode,
+    },
+    /// Stop guarding `module` with an address list.
+    ClearAddressList { module: String },
+    /// Hand the admin role to `admin`.
+    UpdateAdmin { admin: String },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMsg {
+    Admin {},
+    Allowlist { module: String },
+    AddressList { module: String },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum QueryResp {
+    Admin { admin: Addr },
+    Allowlist { addresses: Option<Vec<Addr>> },
+    AddressList { address_list: Option<AddressListGuard> },
+}
+
+/// How an address list guards a module.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ListMode {
+    /// Only addresses on the list may execute messages.
+    Allow,
+    /// Addresses on the list may not execute messages.
+    Deny,
+}
+
+/// An address list guarding a module.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct AddressListGuard {
+    pub list: String,
+    pub mode: ListMode,
+}
+
+/// A module and middleware enforcing per-module sender allowlists.
+#[derive(Clone, Debug, Default)]
+pub struct AccessControl {}
+
+impl AccessControl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+fn admin_key() -> Vec<u8> {
+    storage::key(&[NAMESPACE], b"admin")
+}
+
+fn allowlist_key(module: &str) -> Vec<u8> {
+    storage::key(&[NAMESPACE, b"allowlist"], module.as_bytes())
+}
+
+fn guard_key(module: &str) -> Vec<u8> {
+    storage::key(&[NAMESPACE, b"address_list"], module.as_bytes())
+}
+
+/// Load the address list guarding `module`, if any.
+pub fn address_list_guard(
+    storage: &dyn Storage,
+    module: &str,
+) -> StdResult<Option<AddressListGuard>> {
+    storage::load(storage, &guard_key(module))
+}
+
+/// Load the allowlist for `module`, if it is restricted.
+pub fn allowlist(storage: &dyn Storage, module: &str) -> StdResult<Option<Vec<Addr>>> {
+    storage::load(storage, &allowlist_key(module))
+}
+
+fn assert_admin(storage: &dyn Storage, sender: &Addr) -> Result<(), Error> {
+    let admin: Option<Addr> = storage::load(storage, &admin_key())?;
+    match admin {
+        Some(admin) if admin == *sender => Ok(()),
+        _ => Err(Error::Unauthorized {
+            sender: sender.to_string(),
+        }),
+    }
+}
+
+impl<C: Clone, Q: CustomQuery> Module<C, Q> for AccessControl {
+    type InstantiateMsg = InstantiateMsg;
+    type ExecuteMsg = ExecuteMsg;
+    type QueryMsg = QueryMsg;
+    type QueryResp = QueryResp;
+    type Error = Error;
+
+    fn instantiate(
+        &mut self,
+        deps: &mut DepsMut<Q>,
+        _env: &Env,
+        _info: &MessageInfo,
+        msg: InstantiateMsg,
+    ) -> Result<Response<C>, Error> {
+        let admin = deps.api.addr_validate(&msg.admin)?;
+        storage::save(deps.storage, &admin_key(), &admin)?;
+        Ok(Response::new().add_attribute("admin", admin))
+    }
+
+    fn execute(
+        &mut self,
+        deps: &mut DepsMut<Q>,
+        _env: Env,
+        info: MessageInfo,
+        msg: ExecuteMsg,
+    ) -> Result<Response<C>, Error> {
+        assert_admin(deps.storage, &info.sender)?;
+        match msg {
+            ExecuteMsg::SetAllowlist { module, addresses } => {
+                let addresses = addresses
+                    .iter()
+                    .map(|a| deps.api.addr_validate(a))
+                    .collect::<StdResult<Vec<Addr>>>()?;
+                storage::save(deps.storage, &allowlist_key(&module), &addresses)?;
+                Ok(Response::new()
+                    .add_attribute("action", "set_allowlist")
+                    .add_attribute("module", module))
+            }
+            ExecuteMsg::ClearAllowlist { module } => {
+                storage::remove(deps.storage, &allowlist_key(&module));
+                Ok(Response::new()
+                    .add_attribute("action", "clear_allowlist")
+                    .add_attribute("module", module))
+            }
+            ExecuteMsg::UseAddressList { module, list, mode } => {
+                storage::save(deps.storage, &guard_key(&module), &AddressListGuard { list, mode })?;
+                Ok(Response::new()
+                    .add_attribute("action", "use_address_list")
+                    .add_attribute("module", module))
+            }
+            ExecuteMsg::ClearAddressList { module } => {
+                storage::remove(deps.storage, &guard_key(&module));
+                Ok(Response::new()
+                    .add_attribute("action", "clear_address_list")
+                    .add_attribute("module", module))
+            }
+            ExecuteMsg::UpdateAdmin { admin } => {
+                let admin = deps.api.addr_validate(&admin)?;
+                storage::save(deps.storage, &admin_key(), &admin)?;
+                Ok(Response::new()
+                    .add_attribute("action", "update_admin")
+                    .add_attribute("admin", admin))
+            }
+        }
+    }
+
+    fn query(&self, deps: &Deps<Q>, _env: Env, msg: QueryMsg) -> Result<QueryResp, Error> {
+        match msg {
+            QueryMsg::Admin {} => {
+                let admin: Option<Addr> = storage::load(deps.storage, &admin_key())?;
+                let admin = admin.ok_or_else(|| Error::NotInstantiated {
+                    module: "access_control".to_string(),
+                })?;
+                Ok(QueryResp::Admin { admin })
+            }
+            QueryMsg::Allowlist { module } => Ok(QueryResp::Allowlist {
+                addresses: allowlist(deps.storage, &module)?,
+            }),
+            QueryMsg::AddressList { module } => Ok(QueryResp::AddressList {
+                address_list: address_list_guard(deps.storage, &module)?,
+            }),
+        }
+    }
+}
+
+impl<C, Q: CustomQuery> Middleware<C, Q> for AccessControl {
+    fn before_execute(
+        &self,
+        deps: &mut DepsMut<Q>,
+        _env: &Env,
+        info: &MessageInfo,
+        module: &str,
+        _msg: &Value,
+    ) -> Result<(), Error> {
+        let unauthorized = || Error::Unauthorized {
+            sender: info.sender.to_string(),
+        };
+        if let Some(addresses) = allowlist(deps.storage, module)? {
+            if !addresses.contains(&info.sender) {
+                return Err(unauthorized());
+            }
+        }
+        if let Some(guard) = address_list_guard(deps.storage, module)? {
+            let listed = address_list::contains(deps.storage, &guard.list, &info.sender)?;
+            if listed != (guard.mode == ListMode::Allow) {
+                return Err(unauthorized());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manager::Manager;
+    use crate::test_utils::{addr, info, instantiate, Deposits, MockDeps};
+    use cosmwasm_std::testing::{mock_dependencies, mock_env};
+    use serde_json::json;
+
+    struct Setup {
+        deps: MockDeps,
+        manager: Manager,
+    }
+
+    impl Setup {
+        /// A contract whose `admin_ops` module only alice may execute.
+        fn new() -> Self {
+            let manager = Manager::builder()
+                .with_boxed_module("access_control", Box::new(AccessControl::new()))
+                .unwrap()
+                .with_boxed_module("admin_ops", Box::new(Deposits))
+                .unwrap()
+                .with_boxed_module("open", Box::new(Deposits))
+                .unwrap()
+                .with_middleware(Box::new(AccessControl::new()))
+                .build();
+            let mut setup = Setup {
+                deps: mock_dependencies(),
+                manager,
+            };
+            let msg = InstantiateMsg {
+                admin: addr("admin").to_string(),
+            };
+            instantiate::<AccessControl>(&mut setup.deps, "admin", msg).unwrap();
+            let allow = json!({"access_control": {"set_allowlist": {
+                "module": "admin_ops",
+                "addresses": [addr("alice")],
+            }}});
+            setup.execute("admin", &allow.to_string()).unwrap();
+            setup
+        }
+
+        fn execute(&mut self, sender: &str, msg: &str) -> Result<(), Error> {
+            self.manager
+                .execute(&mut self.deps.as_mut(), mock_env(), info(sender, &[]), msg)
+                .map(|_| ())
+        }
+    }
+
+    #[test]
+    fn allowlists_gate_execute_messages() {
+        let mut setup = Setup::new();
+        setup.execute("alice", r#"{"admin_ops": {}}"#).unwrap();
+        let err = setup.execute("bob", r#"{"admin_ops": {}}"#).unwrap_err();
+        assert!(matches!(err, Error::Unauthorized { .. }), "{:?}", err);
+        setup.execute("bob", r#"{"open": {}}"#).unwrap();
+
+        let clear = r#"{"access_control": {"clear_allowlist": {"module": "admin_ops"}}}"#;
+        setup.execute("admin", clear).unwrap();
+        setup.execute("bob", r#"{"admin_ops": {}}"#).unwrap();
+    }
+
+    #[test]
+    fn only_the_admin_configures_allowlists() {
+        let mut setup = Setup::new();
+        let clear = r#"{"access_control": {"clear_allowlist": {"module": "admin_ops"}}}"#;
+        let err = setup.execute("alice", clear).unwrap_err();
+        assert!(err.to_string().contains("is not authorized"), "{}", err);
+        let allowlist = allowlist(&setup.deps.storage, "admin_ops").unwrap();
+        assert_eq!(allowlist, Some(vec![addr("alice")]));
+    }
+}
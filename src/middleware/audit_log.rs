@@ -0,0 +1,284 @@
+//! An on-chain log of execute messages.
+//!
+//! [AuditLog] is both a [Module] and a [Middleware]. Added as middleware, it
+//! appends a compact record of every execute message dispatched to a module
+//! to contract storage: the module, the sender, the block height and the
+//! SHA-256 hash of the payload. Registered as a module, it exposes the log
+//! through paginated queries, for contracts whose history must be auditable
+//! on chain, without an indexer:
+//!
+//! ```ignore
+//! manager.register("audit_log".to_string(), shared(AuditLog::new()))?;
+//! manager.add_middleware(Box::new(AuditLog::new()));
+//! ```
+//!
+//! ```javascript
+//! { "audit_log": { "records": { "start_after": 41, "limit": 10 } } }
+//! ```
+//!
+//! Records are numbered from 0 in the order the messages were dispatched.
+//! Only successful execute messages are logged: a failed execute reverts
+//! every state change of the transaction, including the record.
+
+use crate::context::Context;
+use crate::error::Error;
+use crate::json;
+use crate::middleware::Middleware;
+use crate::module::{Capabilities, Health, Module};
+use crate::modules::signatures;
+use crate::pagination;
+use crate::response::Response;
+use crate::storage;
+use cosmwasm_std::{Addr, Binary, CustomQuery, Deps, DepsMut, Env, MessageInfo, StdResult, Storage};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+const NAMESPACE: &[u8] = b"audit_log";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct InstantiateMsg {}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecuteMsg {}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMsg {
+    Record {
+        seq: u64,
+    },
+    /// List the records in the order they were logged.
+    Records {
+        #[serde(default)]
+        start_after: Option<u64>,
+        #[serde(default)]
+        limit: Option<u32>,
+    },
+    /// The number of records logged.
+    Count {},
+}
+
+/// The record of an execute message.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Record {
+    pub seq: u64,
+    pub module: String,
+    pub sender: Addr,
+    pub height: u64,
+    /// The SHA-256 hash of the JSON encoding of the payload sent to the
+    /// module.
+    pub msg_hash: Binary,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum QueryResp {
+    Record { record: Option<Record> },
+    Records { records: Vec<Record> },
+    Count { count: u64 },
+}
+
+/// A module and middleware logging execute messages.
+#[derive(Clone, Debug, Default)]
+pub struct AuditLog {}
+
+impl AuditLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+fn count_key() -> Vec<u8> {
+    storage::key(&[NAMESPACE], b"count")
+}
+
+fn record_key(seq: u64) -> Vec<u8> {
+    storage::key(&[NAMESPACE, b"records"], &seq.to_be_bytes())
+}
+
+/// The number of records logged.
+pub fn count(storage: &dyn Storage) -> StdResult<u64> {
+    Ok(storage::load(storage, &count_key())?.unwrap_or_default())
+}
+
+/// Load the record `seq`, if it was logged.
+pub fn record(storage: &dyn Storage, seq: u64) -> StdResult<Option<Record>> {
+    storage::load(storage, &record_key(seq))
+}
+
+impl<C: Clone, Q: CustomQuery> Module<C, Q> for AuditLog {
+    type InstantiateMsg = InstantiateMsg;
+    type ExecuteMsg = ExecuteMsg;
+    type QueryMsg = QueryMsg;
+    type QueryResp = QueryResp;
+    type Error = Error;
+
+    fn instantiate(
+        &mut self,
+        _deps: &mut DepsMut<Q>,
+        _env: &Env,
+        _info: &MessageInfo,
+        _msg: InstantiateMsg,
+    ) -> Result<Response<C>, Error> {
+        Ok(Response::new())
+    }
+
+    fn execute(
+        &mut self,
+        _deps: &mut DepsMut<Q>,
+        _env: Env,
+        _info: MessageInfo,
+        msg: ExecuteMsg,
+    ) -> Result<Response<C>, Error> {
+        match msg {}
+    }
+
+    fn query(&self, deps: &Deps<Q>, _env: Env, msg: QueryMsg) -> Result<QueryResp, Error> {
+        match msg {
+            QueryMsg::Record { seq } => Ok(QueryResp::Record {
+                record: record(deps.storage, seq)?,
+            }),
+            QueryMsg::Records { start_after, limit } => {
+                let limit = pagination::limit(limit);
+                let start_after = start_after.map(u64::to_be_bytes);
+                let records = storage::range(
+                    deps.storage,
+                    &[NAMESPACE, b"records"],
+                    start_after.as_ref().map(|seq| &seq[..]),
+                    limit,
+                )?;
+                Ok(QueryResp::Records {
+                    records: records.into_iter().map(|(_, record)| record).collect(),
+                })
+            }
+            QueryMsg::Count {} => Ok(QueryResp::Count {
+                count: count(deps.storage)?,
+            }),
+        }
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            execute: false,
+            ..Capabilities::default()
+        }
+    }
+
+    fn on_health(
+        &self,
+        _ctx: &Context<C, Q>,
+        deps: &Deps<Q>,
+        _env: &Env,
+    ) -> Result<Option<Health>, Error> {
+        Ok(Some(Health::new().with_count("records", count(deps.storage)?)))
+    }
+}
+
+impl<C, Q: CustomQuery> Middleware<C, Q> for AuditLog {
+    fn before_execute(
+        &self,
+        deps: &mut DepsMut<Q>,
+        env: &Env,
+        info: &MessageInfo,
+        module: &str,
+        msg: &Value,
+    ) -> Result<(), Error> {
+        let seq = count(deps.storage)?;
+        let record = Record {
+            seq,
+            module: module.to_string(),
+            sender: info.sender.clone(),
+            height: env.block.height,
+            msg_hash: signatures::sha256(&json::to_vec(msg)?).into(),
+        };
+        storage::save(deps.storage, &record_key(seq), &record)?;
+        Ok(storage::save(deps.storage, &count_key(), &(seq + 1))?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manager::Manager;
+    use crate::test_utils::{addr, info, Deposits, MockDeps};
+    use cosmwasm_std::from_json;
+    use cosmwasm_std::testing::{mock_dependencies, mock_env};
+    use serde_json::json;
+
+    struct Setup {
+        deps: MockDeps,
+        manager: Manager,
+    }
+
+    impl Setup {
+        fn new() -> Self {
+            let manager = Manager::builder()
+                .with_boxed_module("audit_log", Box::new(AuditLog::new()))
+                .unwrap()
+                .with_boxed_module("sale", Box::new(Deposits))
+                .unwrap()
+                .with_middleware(Box::new(AuditLog::new()))
+                .build();
+            Setup {
+                deps: mock_dependencies(),
+                manager,
+            }
+        }
+
+        fn execute(&mut self, sender: &str, msg: Value) {
+            self.manager
+                .execute(
+                    &mut self.deps.as_mut(),
+                    mock_env(),
+                    info(sender, &[]),
+                    &msg.to_string(),
+                )
+                .unwrap();
+        }
+
+        fn query(&mut self, msg: Value) -> Value {
+            let msg = json!({ "audit_log": msg }).to_string();
+            let resp = self
+                .manager
+                .query(&self.deps.as_ref(), mock_env(), &msg)
+                .unwrap();
+            from_json(resp).unwrap()
+        }
+    }
+
+    fn json_of(resp: QueryResp) -> Value {
+        serde_json::to_value(resp).unwrap()
+    }
+
+    #[test]
+    fn execute_messages_are_logged_in_order() {
+        let mut setup = Setup::new();
+        setup.execute("alice", json!({"sale": {"buy": {}}}));
+        setup.execute("bob", json!({"sale": {"sell": {}}}));
+
+        assert_eq!(
+            setup.query(json!({"count": {}})),
+            json_of(QueryResp::Count { count: 2 })
+        );
+        let sell = Record {
+            seq: 1,
+            module: "sale".to_string(),
+            sender: addr("bob"),
+            height: mock_env().block.height,
+            msg_hash: signatures::sha256(&json::to_vec(&json!({"sell": {}})).unwrap()).into(),
+        };
+        assert_eq!(
+            setup.query(json!({"records": {"start_after": 0}})),
+            json_of(QueryResp::Records {
+                records: vec![sell.clone()]
+            })
+        );
+        assert_eq!(
+            setup.query(json!({"record": {"seq": 1}})),
+            json_of(QueryResp::Record { record: Some(sell) })
+        );
+        let first = record(&setup.deps.storage, 0).unwrap().unwrap();
+        assert_eq!(first.sender, addr("alice"));
+    }
+}
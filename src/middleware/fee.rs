@@ -0,0 +1,233 @@
+//! Per-module call fees.
+//!
+//! [Fees] is a [Middleware] requiring a fee in `info.funds` to execute
+//! messages on specific modules. After the module handled the message, the
+//! fee is either forwarded to a collector or burned, so modules need no fee
+//! logic of their own.
+//!
+//! ```ignore
+//! manager.add_middleware(Box::new(Fees::new().with_fee(
+//!     "mint",
+//!     Fee {
+//!         amount: coin(1_000, "uxion"),
+//!         destination: FeeDestination::Forward(Addr::unchecked("treasury")),
+//!     },
+//! )));
+//! ```
+//!
+//! The fee is taken out of `info.funds` before the module sees them, so a
+//! module only sees what was attached beyond the fee, and is never handed
+//! the fee as if it were its own deposit. Each entry of a
+//! [batch][crate::manager::BATCH_KEY] pays the fee from the funds it attaches,
+//! so a fee is never collected from the contract's own balance.
+
+use crate::error::Error;
+use crate::middleware::Middleware;
+use crate::response::Response;
+use cosmwasm_std::{Addr, BankMsg, Coin, CustomQuery, DepsMut, Env, MessageInfo};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// What happens to a collected fee.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FeeDestination {
+    /// Send the fee to the given address.
+    Forward(Addr),
+    /// Burn the fee.
+    Burn,
+}
+
+/// The fee charged for executing messages on a module.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Fee {
+    pub amount: Coin,
+    pub destination: FeeDestination,
+}
+
+/// A middleware charging fees for executing messages on modules.
+#[derive(Clone, Debug, Default)]
+pub struct Fees {
+    fees: BTreeMap<String, Fee>,
+}
+
+impl Fees {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Charge `fee` for every execute message dispatched to `module`, which
+    /// must be the name the module is registered under.
+    pub fn with_fee(mut self, module: impl Into<String>, fee: Fee) -> Self {
+        self.fees.insert(module.into(), fee);
+        self
+    }
+}
+
+impl<C: Clone, Q: CustomQuery> Middleware<C, Q> for Fees {
+    fn before_execute(
+        &self,
+        _deps: &mut DepsMut<Q>,
+        _env: &Env,
+        info: &MessageInfo,
+        module: &str,
+        _msg: &Value,
+    ) -> Result<(), Error> {
+        let fee = match self.fees.get(module) {
+            Some(fee) => fee,
+            None => return Ok(()),
+        };
+        let paid = info
+            .funds
+            .iter()
+            .any(|c| c.denom == fee.amount.denom && c.amount >= fee.amount.amount);
+        if !paid {
+            return Err(Error::InsufficientFee {
+                module: module.to_string(),
+                fee: fee.amount.to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    fn module_funds(&self, module: &str, mut funds: Vec<Coin>) -> Result<Vec<Coin>, Error> {
+        let fee = match self.fees.get(module) {
+            Some(fee) => fee,
+            None => return Ok(funds),
+        };
+        let insufficient = || Error::InsufficientFee {
+            module: module.to_string(),
+            fee: fee.amount.to_string(),
+        };
+        let index = funds
+            .iter()
+            .position(|c| c.denom == fee.amount.denom)
+            .ok_or_else(insufficient)?;
+        let coin = &mut funds[index];
+        coin.amount = coin
+            .amount
+            .checked_sub(fee.amount.amount)
+            .map_err(|_| insufficient())?;
+        if coin.amount.is_zero() {
+            funds.remove(index);
+        }
+        Ok(funds)
+    }
+
+    fn after_execute(
+        &self,
+        _deps: &mut DepsMut<Q>,
+        _env: &Env,
+        _info: &MessageInfo,
+        module: &str,
+        resp: &mut Response<C>,
+    ) -> Result<(), Error> {
+        let fee = match self.fees.get(module) {
+            Some(fee) => fee,
+            None => return Ok(()),
+        };
+        let amount = vec![fee.amount.clone()];
+        let msg = match &fee.destination {
+            FeeDestination::Forward(to) => BankMsg::Send {
+                to_address: to.to_string(),
+                amount,
+            },
+            FeeDestination::Burn => BankMsg::Burn { amount },
+        };
+        *resp = std::mem::take(resp)
+            .add_message(msg)
+            .add_attribute("fee", fee.amount.to_string());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manager::Manager;
+    use crate::test_utils::{dispatch, funds_seen, Deposits};
+    use cosmwasm_std::{coin, coins, Binary, CosmosMsg};
+
+    fn manager() -> Manager {
+        let fees = Fees::new().with_fee(
+            "paid",
+            Fee {
+                amount: coin(10, "uxion"),
+                destination: FeeDestination::Forward(Addr::unchecked("treasury")),
+            },
+        );
+        Manager::builder()
+            .with_boxed_module("paid", Box::new(Deposits))
+            .unwrap()
+            .with_boxed_module("free", Box::new(Deposits))
+            .unwrap()
+            .with_middleware(Box::new(fees))
+            .build()
+    }
+
+    /// The fees forwarded to the treasury.
+    fn fees_forwarded(resp: &cosmwasm_std::Response<Binary>) -> Vec<Vec<Coin>> {
+        resp.messages
+            .iter()
+            .filter_map(|sub| match &sub.msg {
+                CosmosMsg::Bank(BankMsg::Send { to_address, amount })
+                    if to_address == "treasury" =>
+                {
+                    Some(amount.clone())
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn fee_is_taken_out_of_module_funds() {
+        let resp = dispatch(manager(), &coins(100, "uxion"), r#"{"paid": {}}"#).unwrap();
+        assert_eq!(funds_seen(&resp), ["90uxion"]);
+        assert_eq!(fees_forwarded(&resp), [coins(10, "uxion")]);
+    }
+
+    #[test]
+    fn exact_fee_leaves_module_no_funds() {
+        let resp = dispatch(manager(), &coins(10, "uxion"), r#"{"paid": {}}"#).unwrap();
+        assert_eq!(funds_seen(&resp), [""]);
+    }
+
+    #[test]
+    fn insufficient_fee_is_rejected() {
+        let err = dispatch(manager(), &coins(9, "uxion"), r#"{"paid": {}}"#).unwrap_err();
+        assert!(matches!(err, Error::InsufficientFee { .. }), "{:?}", err);
+        let err = dispatch(manager(), &coins(100, "ustake"), r#"{"paid": {}}"#).unwrap_err();
+        assert!(matches!(err, Error::InsufficientFee { .. }), "{:?}", err);
+    }
+
+    #[test]
+    fn modules_without_fee_are_not_charged() {
+        let resp = dispatch(manager(), &coins(100, "uxion"), r#"{"free": {}}"#).unwrap();
+        assert_eq!(funds_seen(&resp), ["100uxion"]);
+        assert!(fees_forwarded(&resp).is_empty());
+    }
+
+    #[test]
+    fn batch_entries_pay_the_fee_from_their_own_funds() {
+        let msg = r#"{"_batch": [
+            {"_funds": [{"denom": "uxion", "amount": "10"}], "paid": {}},
+            {"_funds": [{"denom": "uxion", "amount": "15"}], "paid": {}}
+        ]}"#;
+        let resp = dispatch(manager(), &coins(25, "uxion"), msg).unwrap();
+        assert_eq!(funds_seen(&resp), ["", "5uxion"]);
+        assert_eq!(
+            fees_forwarded(&resp),
+            [coins(10, "uxion"), coins(10, "uxion")]
+        );
+    }
+
+    #[test]
+    fn batch_entries_cannot_share_a_fee() {
+        let msg = r#"{"_batch": [
+            {"_funds": [{"denom": "uxion", "amount": "10"}], "paid": {}},
+            {"paid": {}}
+        ]}"#;
+        let err = dispatch(manager(), &coins(10, "uxion"), msg).unwrap_err();
+        assert!(matches!(err, Error::InsufficientFee { .. }), "{:?}", err);
+    }
+}
@@ -0,0 +1,273 @@
+//! Per-module usage counters.
+//!
+//! [Metrics] is both a [Module] and a [Middleware]. Added as middleware, it
+//! counts the execute messages each module handles, in contract storage.
+//! Registered as a module, it exposes the counters through queries:
+//!
+//! ```ignore
+//! manager.register("metrics".to_string(), shared(Metrics::new()))?;
+//! manager.add_middleware(Box::new(Metrics::new()));
+//! ```
+//!
+//! ```javascript
+//! { "metrics": { "module": { "module": "token" } } }
+//! { "metrics": { "all": {} } }
+//! ```
+//!
+//! Only successful execute messages are counted: a failed execute reverts
+//! every state change of the transaction, including the counter, and queries
+//! can't write to storage at all.
+
+use crate::context::Context;
+use crate::error::Error;
+use crate::middleware::Middleware;
+use crate::module::{Capabilities, Module};
+use crate::response::Response;
+use crate::storage;
+use cosmwasm_std::{CustomQuery, Deps, DepsMut, Env, MessageInfo, StdResult, Storage};
+use serde::{Deserialize, Serialize};
+
+const NAMESPACE: &[u8] = b"metrics";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct InstantiateMsg {}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecuteMsg {}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMsg {
+    /// The counters of a single module.
+    Module { module: String },
+    /// The counters of every registered module.
+    All {},
+}
+
+/// The usage counters of a module.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ModuleMetrics {
+    pub module: String,
+    /// The number of execute messages the module handled.
+    pub executes: u64,
+    /// The height of the block in which the module last handled an execute
+    /// message.
+    pub last_executed_height: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum QueryResp {
+    Module(ModuleMetrics),
+    All(Vec<ModuleMetrics>),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+struct Counters {
+    executes: u64,
+    last_executed_height: Option<u64>,
+}
+
+/// A module and middleware counting dispatches per module.
+#[derive(Clone, Debug, Default)]
+pub struct Metrics {}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+fn key(module: &str) -> Vec<u8> {
+    storage::key(&[NAMESPACE], module.as_bytes())
+}
+
+/// Load the counters of `module`.
+pub fn module_metrics(storage: &dyn Storage, module: &str) -> StdResult<ModuleMetrics> {
+    let counters: Counters = storage::load(storage, &key(module))?.unwrap_or_default();
+    Ok(ModuleMetrics {
+        module: module.to_string(),
+        executes: counters.executes,
+        last_executed_height: counters.last_executed_height,
+    })
+}
+
+impl<C: Clone, Q: CustomQuery> Module<C, Q> for Metrics {
+    type InstantiateMsg = InstantiateMsg;
+    type ExecuteMsg = ExecuteMsg;
+    type QueryMsg = QueryMsg;
+    type QueryResp = QueryResp;
+    type Error = Error;
+
+    fn instantiate(
+        &mut self,
+        _deps: &mut DepsMut<Q>,
+        _env: &Env,
+        _info: &MessageInfo,
+        _msg: InstantiateMsg,
+    ) -> Result<Response<C>, Error> {
+        Ok(Response::new())
+    }
+
+    fn execute(
+        &mut self,
+        _deps: &mut DepsMut<Q>,
+        _env: Env,
+        _info: MessageInfo,
+        msg: ExecuteMsg,
+    ) -> Result<Response<C>, Error> {
+        match msg {}
+    }
+
+    fn query(&self, deps: &Deps<Q>, _env: Env, msg: QueryMsg) -> Result<QueryResp, Error> {
+        match msg {
+            QueryMsg::Module { module } => {
+                Ok(QueryResp::Module(module_metrics(deps.storage, &module)?))
+            }
+            QueryMsg::All {} => Err(Error::ParseError {
+                msg: Some("querying all modules requires the dispatch context".to_string()),
+            }),
+        }
+    }
+
+    fn query_with_context(
+        &self,
+        ctx: &Context<C, Q>,
+        deps: &Deps<Q>,
+        env: Env,
+        msg: QueryMsg,
+    ) -> Result<QueryResp, Error> {
+        match msg {
+            QueryMsg::All {} => Ok(QueryResp::All(
+                ctx.manager()
+                    .module_names()
+                    .iter()
+                    .map(|module| module_metrics(deps.storage, module))
+                    .collect::<StdResult<_>>()?,
+            )),
+            msg => <Self as Module<C, Q>>::query(self, deps, env, msg),
+        }
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            execute: false,
+            ..Capabilities::default()
+        }
+    }
+}
+
+impl<C, Q: CustomQuery> Middleware<C, Q> for Metrics {
+    fn after_execute(
+        &self,
+        deps: &mut DepsMut<Q>,
+        env: &Env,
+        _info: &MessageInfo,
+        module: &str,
+        _resp: &mut Response<C>,
+    ) -> Result<(), Error> {
+        let key = key(module);
+        let mut counters: Counters = storage::load(deps.storage, &key)?.unwrap_or_default();
+        counters.executes += 1;
+        counters.last_executed_height = Some(env.block.height);
+        Ok(storage::save(deps.storage, &key, &counters)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manager::Manager;
+    use crate::test_utils::{info, Deposits, MockDeps};
+    use cosmwasm_std::from_json;
+    use cosmwasm_std::testing::{mock_dependencies, mock_env};
+    use serde_json::{json, Value};
+
+    struct Setup {
+        deps: MockDeps,
+        manager: Manager,
+    }
+
+    impl Setup {
+        fn new() -> Self {
+            let manager = Manager::builder()
+                .with_boxed_module("metrics", Box::new(Metrics::new()))
+                .unwrap()
+                .with_boxed_module("sale", Box::new(Deposits))
+                .unwrap()
+                .with_middleware(Box::new(Metrics::new()))
+                .build();
+            Setup {
+                deps: mock_dependencies(),
+                manager,
+            }
+        }
+
+        /// Execute `{"sale": {}}`, `blocks` blocks after the mock block.
+        fn buy(&mut self, blocks: u64) {
+            let mut env = mock_env();
+            env.block.height += blocks;
+            self.manager
+                .execute(
+                    &mut self.deps.as_mut(),
+                    env,
+                    info("alice", &[]),
+                    r#"{"sale": {}}"#,
+                )
+                .unwrap();
+        }
+
+        fn query(&mut self, msg: Value) -> Result<QueryResp, Error> {
+            let msg = json!({ "metrics": msg }).to_string();
+            let resp = self.manager.query(&self.deps.as_ref(), mock_env(), &msg)?;
+            Ok(from_json(resp)?)
+        }
+    }
+
+    #[test]
+    fn executes_are_counted_per_module() {
+        let mut setup = Setup::new();
+        setup.buy(0);
+        setup.buy(3);
+
+        let height = mock_env().block.height;
+        let sale = ModuleMetrics {
+            module: "sale".to_string(),
+            executes: 2,
+            last_executed_height: Some(height + 3),
+        };
+        assert_eq!(
+            setup.query(json!({"module": {"module": "sale"}})).unwrap(),
+            QueryResp::Module(sale.clone())
+        );
+        let metrics = ModuleMetrics {
+            module: "metrics".to_string(),
+            ..ModuleMetrics::default()
+        };
+        assert_eq!(
+            setup.query(json!({"all": {}})).unwrap(),
+            QueryResp::All(vec![metrics, sale])
+        );
+    }
+
+    #[test]
+    fn the_metrics_module_takes_no_execute_messages() {
+        let mut setup = Setup::new();
+        setup
+            .manager
+            .execute(
+                &mut setup.deps.as_mut(),
+                mock_env(),
+                info("alice", &[]),
+                r#"{"metrics": {"reset": {}}}"#,
+            )
+            .unwrap_err();
+        assert_eq!(
+            module_metrics(&setup.deps.storage, "metrics")
+                .unwrap()
+                .executes,
+            0
+        );
+    }
+}
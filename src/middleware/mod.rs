@@ -0,0 +1,73 @@
+//! Middleware run by the Manager around module dispatch.
+//!
+//! Middleware implement cross-cutting concerns, such as access checks,
+//! metering, or logging attributes, without touching every module. They are
+//! added to a Manager with
+//! [add_middleware][crate::manager::Manager::add_middleware] and run in the
+//! order they were added before dispatch, and in reverse order after it.
+
+pub mod access_control;
+pub mod audit_log;
+pub mod fee;
+pub mod metrics;
+pub mod rate_limit;
+
+use crate::error::Error;
+use crate::response::Response;
+use crate::shared::MaybeSendSync;
+use cosmwasm_std::{Binary, Coin, CustomQuery, Deps, DepsMut, Empty, Env, MessageInfo};
+use serde_json::Value;
+
+/// Hooks run by the Manager around module dispatch. Every hook defaults to a
+/// no-op, so implementations only need to provide the ones they use.
+///
+/// `module` is always the name the module is registered under, even if the
+/// message was addressed to an alias.
+pub trait Middleware<C = Binary, Q: CustomQuery = Empty>: MaybeSendSync {
+    /// Called before an execute message is dispatched to `module`. Returning an
+    /// error aborts the dispatch.
+    fn before_execute(
+        &self,
+        _deps: &mut DepsMut<Q>,
+        _env: &Env,
+        _info: &MessageInfo,
+        _module: &str,
+        _msg: &Value,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// The share of `funds` that `module` sees of an execute message, e.g.
+    /// without a fee the middleware collects. Called after every
+    /// middleware's [before_execute][Self::before_execute], with the funds
+    /// left by the middleware added before this one.
+    fn module_funds(&self, _module: &str, funds: Vec<Coin>) -> Result<Vec<Coin>, Error> {
+        Ok(funds)
+    }
+
+    /// Called after `module` handled an execute message, including any hooks
+    /// it published, with the response, which the middleware may amend.
+    fn after_execute(
+        &self,
+        _deps: &mut DepsMut<Q>,
+        _env: &Env,
+        _info: &MessageInfo,
+        _module: &str,
+        _resp: &mut Response<C>,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Called before a query is dispatched to `module`. Returning an error
+    /// aborts the query. Queries between modules through a
+    /// [Context][crate::context::Context] are not intercepted.
+    fn before_query(
+        &self,
+        _deps: &Deps<Q>,
+        _env: &Env,
+        _module: &str,
+        _msg: &Value,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+}
@@ -0,0 +1,340 @@
+//! Call limits over block or time windows.
+//!
+//! [RateLimiter] is both a [Module] and a [Middleware]. Registered as a
+//! module, it lets the [admin][crate::admin] set a limit on the execute
+//! messages each module accepts per window, either per sender or across all
+//! senders. Added as middleware, it rejects messages beyond the limit with
+//! [RateLimited][Error::RateLimited]:
+//!
+//! ```javascript
+//! { "rate_limit": { "set_limit": { "module": "mint", "limit": {
+//!     "max_calls": 5, "window": { "seconds": 86400 }, "scope": "per_address"
+//! } } } }
+//! ```
+//!
+//! Windows are fixed: they start at multiples of their length, counted in
+//! blocks or seconds since genesis, and the count resets when a new window
+//! starts.
+
+use crate::admin;
+use crate::error::Error;
+use crate::middleware::Middleware;
+use crate::module::Module;
+use crate::response::Response;
+use crate::storage;
+use cosmwasm_std::{
+    Addr, CustomQuery, Deps, DepsMut, Env, MessageInfo, StdError, StdResult, Storage,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+const NAMESPACE: &[u8] = b"rate_limit";
+
+/// The length of a rate limiting window.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Window {
+    Blocks(u64),
+    Seconds(u64),
+}
+
+/// Whose calls are counted together.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Scope {
+    /// Each sender has their own allowance.
+    PerAddress,
+    /// All senders share one allowance.
+    Global,
+}
+
+/// A limit on the execute messages a module accepts.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct RateLimit {
+    pub max_calls: u64,
+    pub window: Window,
+    pub scope: Scope,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct InstantiateMsg {}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecuteMsg {
+    /// Limit execute messages to `module`.
+    SetLimit { module: String, limit: RateLimit },
+    /// Lift the limit on `module`.
+    RemoveLimit { module: String },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMsg {
+    Limit {
+        module: String,
+    },
+    /// The calls `address` has left in the current window. For global
+    /// limits, `address` is ignored.
+    Remaining {
+        module: String,
+        #[serde(default)]
+        address: Option<String>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum QueryResp {
+    Limit { limit: Option<RateLimit> },
+    Remaining { remaining: Option<u64> },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+struct Usage {
+    window: u64,
+    calls: u64,
+}
+
+/// A module and middleware enforcing per-module call limits.
+#[derive(Clone, Debug, Default)]
+pub struct RateLimiter {}
+
+impl RateLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+fn limit_key(module: &str) -> Vec<u8> {
+    storage::key(&[NAMESPACE, b"limits"], module.as_bytes())
+}
+
+fn usage_key(module: &str, limit: &RateLimit, sender: Option<&Addr>) -> Vec<u8> {
+    let caller: &[u8] = match (limit.scope, sender) {
+        (Scope::PerAddress, Some(sender)) => sender.as_bytes(),
+        _ => b"",
+    };
+    storage::key(&[NAMESPACE, b"usage", module.as_bytes()], caller)
+}
+
+/// Load the limit on `module`, if it is limited.
+pub fn limit(storage: &dyn Storage, module: &str) -> StdResult<Option<RateLimit>> {
+    storage::load(storage, &limit_key(module))
+}
+
+/// The index of the window `env` falls in.
+fn current_window(env: &Env, window: Window) -> StdResult<u64> {
+    let (now, length) = match window {
+        Window::Blocks(length) => (env.block.height, length),
+        Window::Seconds(length) => (env.block.time.seconds(), length),
+    };
+    now.checked_div(length)
+        .ok_or_else(|| StdError::generic_err("rate limit window must not be empty"))
+}
+
+/// The calls left in the current window.
+fn remaining(
+    storage: &dyn Storage,
+    env: &Env,
+    module: &str,
+    limit: &RateLimit,
+    sender: Option<&Addr>,
+) -> StdResult<u64> {
+    let window = current_window(env, limit.window)?;
+    let usage: Usage = storage::load(storage, &usage_key(module, limit, sender))?
+        .filter(|usage: &Usage| usage.window == window)
+        .unwrap_or_default();
+    Ok(limit.max_calls.saturating_sub(usage.calls))
+}
+
+impl<C: Clone, Q: CustomQuery> Module<C, Q> for RateLimiter {
+    type InstantiateMsg = InstantiateMsg;
+    type ExecuteMsg = ExecuteMsg;
+    type QueryMsg = QueryMsg;
+    type QueryResp = QueryResp;
+    type Error = Error;
+
+    fn instantiate(
+        &mut self,
+        _deps: &mut DepsMut<Q>,
+        _env: &Env,
+        _info: &MessageInfo,
+        _msg: InstantiateMsg,
+    ) -> Result<Response<C>, Error> {
+        Ok(Response::new())
+    }
+
+    fn execute(
+        &mut self,
+        deps: &mut DepsMut<Q>,
+        _env: Env,
+        info: MessageInfo,
+        msg: ExecuteMsg,
+    ) -> Result<Response<C>, Error> {
+        admin::assert_admin(deps.storage, &info.sender)?;
+        match msg {
+            ExecuteMsg::SetLimit { module, limit } => {
+                if let Window::Blocks(0) | Window::Seconds(0) = limit.window {
+                    return Err(StdError::generic_err("rate limit window must not be empty").into());
+                }
+                storage::save(deps.storage, &limit_key(&module), &limit)?;
+                Ok(Response::new()
+                    .add_attribute("action", "set_limit")
+                    .add_attribute("module", module))
+            }
+            ExecuteMsg::RemoveLimit { module } => {
+                storage::remove(deps.storage, &limit_key(&module));
+                Ok(Response::new()
+                    .add_attribute("action", "remove_limit")
+                    .add_attribute("module", module))
+            }
+        }
+    }
+
+    fn query(&self, deps: &Deps<Q>, env: Env, msg: QueryMsg) -> Result<QueryResp, Error> {
+        match msg {
+            QueryMsg::Limit { module } => Ok(QueryResp::Limit {
+                limit: limit(deps.storage, &module)?,
+            }),
+            QueryMsg::Remaining { module, address } => {
+                let address = address.map(|a| deps.api.addr_validate(&a)).transpose()?;
+                let remaining = match limit(deps.storage, &module)? {
+                    Some(limit) => Some(remaining(
+                        deps.storage,
+                        &env,
+                        &module,
+                        &limit,
+                        address.as_ref(),
+                    )?),
+                    None => None,
+                };
+                Ok(QueryResp::Remaining { remaining })
+            }
+        }
+    }
+}
+
+impl<C, Q: CustomQuery> Middleware<C, Q> for RateLimiter {
+    fn before_execute(
+        &self,
+        deps: &mut DepsMut<Q>,
+        env: &Env,
+        info: &MessageInfo,
+        module: &str,
+        _msg: &Value,
+    ) -> Result<(), Error> {
+        let limit = match limit(deps.storage, module)? {
+            Some(limit) => limit,
+            None => return Ok(()),
+        };
+        let window = current_window(env, limit.window)?;
+        let key = usage_key(module, &limit, Some(&info.sender));
+        let mut usage: Usage = storage::load(deps.storage, &key)?
+            .filter(|usage: &Usage| usage.window == window)
+            .unwrap_or(Usage { window, calls: 0 });
+        if usage.calls >= limit.max_calls {
+            return Err(Error::RateLimited {
+                module: module.to_string(),
+            });
+        }
+        usage.calls += 1;
+        Ok(storage::save(deps.storage, &key, &usage)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manager::Manager;
+    use crate::test_utils::{addr, info, Deposits, MockDeps};
+    use cosmwasm_std::testing::{mock_dependencies, mock_env};
+    use serde_json::json;
+
+    struct Setup {
+        deps: MockDeps,
+        manager: Manager,
+    }
+
+    impl Setup {
+        /// A contract whose `mint` module accepts two calls every ten blocks
+        /// within `scope`.
+        fn new(scope: Scope) -> Self {
+            let manager = Manager::builder()
+                .with_boxed_module("rate_limit", Box::new(RateLimiter::new()))
+                .unwrap()
+                .with_boxed_module("mint", Box::new(Deposits))
+                .unwrap()
+                .with_middleware(Box::new(RateLimiter::new()))
+                .build();
+            let mut setup = Setup {
+                deps: mock_dependencies(),
+                manager,
+            };
+            admin::set_admin(&mut setup.deps.storage, &addr("admin")).unwrap();
+            let limit = RateLimit {
+                max_calls: 2,
+                window: Window::Blocks(10),
+                scope,
+            };
+            let msg = json!({"rate_limit": {"set_limit": {"module": "mint", "limit": limit}}});
+            setup.execute(0, "admin", &msg.to_string()).unwrap();
+            setup
+        }
+
+        /// Execute `msg` as `sender`, `blocks` blocks after the mock block.
+        fn execute(&mut self, blocks: u64, sender: &str, msg: &str) -> Result<(), Error> {
+            let mut env = mock_env();
+            env.block.height += blocks;
+            self.manager
+                .execute(&mut self.deps.as_mut(), env, info(sender, &[]), msg)
+                .map(|_| ())
+        }
+
+        fn mint(&mut self, blocks: u64, sender: &str) -> Result<(), Error> {
+            self.execute(blocks, sender, r#"{"mint": {}}"#)
+        }
+    }
+
+    #[test]
+    fn per_address_limits_count_each_sender() {
+        let mut setup = Setup::new(Scope::PerAddress);
+        let start = 10 - mock_env().block.height % 10;
+        setup.mint(start, "alice").unwrap();
+        setup.mint(start + 1, "alice").unwrap();
+        let err = setup.mint(start + 2, "alice").unwrap_err();
+        assert!(matches!(err, Error::RateLimited { .. }), "{:?}", err);
+        setup.mint(start + 2, "bob").unwrap();
+
+        setup.mint(start + 10, "alice").unwrap();
+    }
+
+    #[test]
+    fn global_limits_are_shared() {
+        let mut setup = Setup::new(Scope::Global);
+        let start = 10 - mock_env().block.height % 10;
+        setup.mint(start, "alice").unwrap();
+        setup.mint(start, "bob").unwrap();
+        let err = setup.mint(start, "carol").unwrap_err();
+        assert!(matches!(err, Error::RateLimited { .. }), "{:?}", err);
+
+        let remove = r#"{"rate_limit": {"remove_limit": {"module": "mint"}}}"#;
+        setup.execute(start, "admin", remove).unwrap();
+        setup.mint(start, "carol").unwrap();
+    }
+
+    #[test]
+    fn empty_windows_are_rejected() {
+        let mut setup = Setup::new(Scope::Global);
+        let empty = json!({"max_calls": 1, "window": {"seconds": 0}, "scope": "global"});
+        let msg = json!({"rate_limit": {"set_limit": {"module": "mint", "limit": empty}}});
+        setup.execute(0, "admin", &msg.to_string()).unwrap_err();
+        assert_eq!(
+            limit(&setup.deps.storage, "mint")
+                .unwrap()
+                .unwrap()
+                .max_calls,
+            2
+        );
+    }
+}
@@ -1,10 +1,22 @@
 //! Traits for reusable, composable CosmWasm modules.
 
+use crate::case;
+use crate::context::Context;
+use crate::error::{ErrorCode, ModuleError};
+use crate::manager::Manager;
 use crate::response::Response;
-use cosmwasm_std::{Binary, Deps, DepsMut, Env, MessageInfo, StdError, StdResult};
-use serde::{Deserialize, Serialize};
+use crate::reply::Callback;
+use crate::shared::MaybeSendSync;
+use crate::validation;
+use cosmwasm_std::{
+    Binary, CustomQuery, Deps, DepsMut, Empty, Env, MessageInfo, Reply, StdError, StdResult,
+    SubMsgResult,
+};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::value::RawValue;
 use serde_json::Value;
-use std::fmt::Display;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
 
 /// A well typed CosmWasm module
 ///
@@ -13,7 +25,11 @@ use std::fmt::Display;
 ///
 /// Programmers looking to implement reusable CosmWasm modules should create
 /// structs that implement Module.
-pub trait Module {
+///
+/// Modules are generic over the chain's custom message type, `C`, and custom
+/// query type, `Q`. Modules that don't emit custom messages or make custom
+/// queries through `deps.querier` can leave them as the defaults.
+pub trait Module<C = Binary, Q: CustomQuery = Empty> {
     /// The message sent to the module to instantiate its state.
     type InstantiateMsg: for<'a> Deserialize<'a>;
     /// The type of transaction messages this module can handle. For modules
@@ -26,104 +42,884 @@ pub trait Module {
     /// The response to queries dispatched to the module.
     type QueryResp: Serialize;
     /// The type of errors this module can generate. This must implement
-    /// Display for easy stringification.
-    type Error: Display;
+    /// std::error::Error, so the Manager can report the error's sources
+    /// along with its message.
+    type Error: std::error::Error + Send + Sync + 'static;
 
     /// The instantiate handler for the module. When a Manager with this
     /// module registered is instantiated, this method may be called.
     fn instantiate(
         &mut self,
-        deps: &mut DepsMut,
+        deps: &mut DepsMut<Q>,
         env: &Env,
         info: &MessageInfo,
         msg: Self::InstantiateMsg,
-    ) -> Result<Response, Self::Error>;
+    ) -> Result<Response<C>, Self::Error>;
     /// The transaction handler for this module. Messages to this contract
     /// will be dispatched by the Manager.
     fn execute(
         &mut self,
-        deps: &mut DepsMut,
+        deps: &mut DepsMut<Q>,
         env: Env,
         info: MessageInfo,
         msg: Self::ExecuteMsg,
-    ) -> Result<Response, Self::Error>;
+    ) -> Result<Response<C>, Self::Error>;
     /// The query handler for this module. Messages to this contract will be
     /// dispatched by the Manager.
     fn query(
         &self,
-        deps: &Deps,
+        deps: &Deps<Q>,
         env: Env,
         msg: Self::QueryMsg,
     ) -> Result<Self::QueryResp, Self::Error>;
+
+    /// Check an instantiate message before any module is instantiated. The
+    /// Manager parses and validates the payloads of all modules first, and
+    /// reports every failure at once instead of stopping at the first
+    /// module whose handler fails. Defaults to accepting any message that
+    /// parses.
+    fn validate_instantiate(
+        &self,
+        _deps: &Deps<Q>,
+        _env: &Env,
+        _info: &MessageInfo,
+        _msg: &Self::InstantiateMsg,
+    ) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Check an execute message without executing it. The Manager validates
+    /// the entries of a [partial batch][crate::manager::PARTIAL_BATCH_KEY]
+    /// up front, and skips those that fail instead of aborting the batch.
+    /// Defaults to accepting any message that parses.
+    fn validate_execute(
+        &self,
+        _deps: &Deps<Q>,
+        _env: &Env,
+        _info: &MessageInfo,
+        _msg: &Self::ExecuteMsg,
+    ) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Like [instantiate][Self::instantiate], with access to the dispatch
+    /// [Context]. Modules that need to interact with their sibling modules
+    /// override this instead; by default the context is ignored.
+    fn instantiate_with_context(
+        &mut self,
+        _ctx: &Context<C, Q>,
+        deps: &mut DepsMut<Q>,
+        env: &Env,
+        info: &MessageInfo,
+        msg: Self::InstantiateMsg,
+    ) -> Result<Response<C>, Self::Error> {
+        self.instantiate(deps, env, info, msg)
+    }
+    /// Like [execute][Self::execute], with access to the dispatch [Context].
+    /// By default the context is ignored.
+    fn execute_with_context(
+        &mut self,
+        _ctx: &Context<C, Q>,
+        deps: &mut DepsMut<Q>,
+        env: Env,
+        info: MessageInfo,
+        msg: Self::ExecuteMsg,
+    ) -> Result<Response<C>, Self::Error> {
+        self.execute(deps, env, info, msg)
+    }
+    /// Like [query][Self::query], with access to the dispatch [Context]. By
+    /// default the context is ignored.
+    fn query_with_context(
+        &self,
+        _ctx: &Context<C, Q>,
+        deps: &Deps<Q>,
+        env: Env,
+        msg: Self::QueryMsg,
+    ) -> Result<Self::QueryResp, Self::Error> {
+        self.query(deps, env, msg)
+    }
+
+    /// The hook handler for this module, called when another module publishes
+    /// on a `topic` this module is subscribed to; see
+    /// [Manager::subscribe][crate::manager::Manager::subscribe]. The
+    /// messages, attributes, and events of the response are merged into the
+    /// response of the execute that published the hook. Defaults to a no-op.
+    fn on_hook(
+        &mut self,
+        _ctx: &Context<C, Q>,
+        _deps: &mut DepsMut<Q>,
+        _env: &Env,
+        _info: &MessageInfo,
+        _topic: &str,
+        _payload: &Value,
+    ) -> Result<Response<C>, Self::Error> {
+        Ok(Response::default())
+    }
+
+    /// The broadcast handler for this module, called when a message is
+    /// [broadcast][crate::manager::Manager::broadcast] to every module, e.g.
+    /// to prepare for a migration or collect status. Modules opt in by
+    /// returning `Some` response, which is aggregated with those of the other
+    /// modules. Defaults to `None`, ignoring broadcasts.
+    fn on_broadcast(
+        &mut self,
+        _ctx: &Context<C, Q>,
+        _deps: &mut DepsMut<Q>,
+        _env: &Env,
+        _info: &MessageInfo,
+        _msg: &Value,
+    ) -> Result<Option<Response<C>>, Self::Error> {
+        Ok(None)
+    }
+
+    /// The reply handler for this module, called by the Manager with the
+    /// reply to a submessage the module sent with an id from
+    /// [Context::reply_id]. The id of the reply is the module's own id. Data
+    /// set on the response becomes the data of the module; see
+    /// [reply][crate::reply]. Defaults to a no-op.
+    fn reply(
+        &mut self,
+        _ctx: &Context<C, Q>,
+        _deps: &mut DepsMut<Q>,
+        _env: &Env,
+        _reply: Reply,
+    ) -> Result<Response<C>, Self::Error> {
+        Ok(Response::default())
+    }
+
+    /// The callback handler for this module, called by the Manager with the
+    /// `callback` the module attached to a submessage with
+    /// [Context::send_with_callback], once the submessage has completed with
+    /// `result`. Like [reply][Self::reply], data set on the response becomes
+    /// the data of the module. Defaults to a no-op.
+    fn on_callback(
+        &mut self,
+        _ctx: &Context<C, Q>,
+        _deps: &mut DepsMut<Q>,
+        _env: &Env,
+        _callback: Callback,
+        _result: SubMsgResult,
+    ) -> Result<Response<C>, Self::Error> {
+        Ok(Response::default())
+    }
+
+    /// The migrate handler for this module, called by the Manager when the
+    /// migrate message addresses this module. The payload is handed over
+    /// as-is for the module to parse. Modules overriding this should also
+    /// report the `migrate` [capability][Self::capabilities]. Defaults to a
+    /// no-op.
+    fn migrate(
+        &mut self,
+        _deps: &mut DepsMut<Q>,
+        _env: &Env,
+        _msg: &Value,
+    ) -> Result<Response<C>, Self::Error> {
+        Ok(Response::default())
+    }
+
+    /// The version of this module, reported by the Manager's introspection
+    /// queries and recorded in storage to guard migrations. Should be a
+    /// semantic version. Defaults to `None`.
+    fn version(&self) -> Option<String> {
+        None
+    }
+    /// The steps migrating the module's state between versions. During
+    /// migrate, the Manager chains the steps leading from the module's stored
+    /// version to its current [version][Self::version] and runs them in
+    /// order, before the [migrate][Self::migrate] handler. Defaults to none.
+    fn migrations(&self) -> Vec<Migration<Self, C, Q>>
+    where
+        Self: Sized,
+    {
+        Vec::new()
+    }
+    /// Encode `resp`, the response to a query, as the bytes returned to the
+    /// querier. Defaults to JSON; see [raw][crate::raw] for modules answering
+    /// with bytes of their own.
+    fn encode_query_resp(&self, resp: &Self::QueryResp) -> StdResult<Binary> {
+        cosmwasm_std::to_json_binary(resp)
+    }
+    /// Encode a query response for a Manager converting the names of the
+    /// fields and variants of responses to camelCase; see
+    /// [KeyCase::CamelCaseOut][crate::case::KeyCase::CamelCaseOut]. Modules
+    /// overriding [encode_query_resp][Self::encode_query_resp] should
+    /// override this as well.
+    fn encode_camel_case_query_resp(&self, resp: &Self::QueryResp) -> StdResult<Binary> {
+        let resp = case::to_camel_case_value(resp)
+            .map_err(|e| StdError::generic_err(format!("Error serializing response: {}", e)))?;
+        cosmwasm_std::to_json_binary(&resp)
+    }
+    /// The stable, machine-readable code of `err`, reported along with the
+    /// module's name in the Manager's [error envelopes][crate::error]. Codes
+    /// should be snake case, e.g. `"sold_out"`, and stay the same across
+    /// versions of the module. Defaults to no code.
+    fn error_code(&self, _err: &Self::Error) -> Option<ErrorCode> {
+        None
+    }
+    /// JSON schemas of the module's messages. When
+    /// [schema validation][crate::manager::Manager::set_validate_schemas] is
+    /// enabled, the Manager checks payloads against them before parsing,
+    /// reporting every violation with its path; see
+    /// [validation][crate::validation]. Defaults to no schemas.
+    fn schemas(&self) -> Schemas {
+        Schemas::default()
+    }
+    /// The kinds of messages this module handles, reported by the Manager's
+    /// introspection queries. Defaults to execute and query, accepting funds.
+    ///
+    /// Modules whose execute handlers never expect funds should report
+    /// themselves as not [payable][Capabilities::payable], so the Manager
+    /// rejects messages carrying funds instead of locking them in the
+    /// contract.
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
+    /// Called when the module is registered with `manager` under `name`,
+    /// before the module is added to it. Modules check that their
+    /// configuration fits the Manager here, e.g. that an interface they
+    /// depend on is registered; a failure aborts the registration with
+    /// [RegistrationError][crate::error::Error::RegistrationError]. Defaults
+    /// to accepting any Manager.
+    fn on_register(&self, _manager: &Manager<C, Q>, _name: &str) -> Result<(), Self::Error> {
+        Ok(())
+    }
+    /// Structured information describing this module, reported by the
+    /// Manager's introspection queries. Defaults to the module's
+    /// [version][Self::version] and nothing else.
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            version: <Self as Module<C, Q>>::version(self),
+            ..Metadata::default()
+        }
+    }
+    /// The status of this module, reported along with its version and pause
+    /// state by the Manager's `health` query, e.g. the number of items it
+    /// holds and a digest of its configuration. An error is reported for the
+    /// module without failing the query. Defaults to `None`, reporting
+    /// nothing beyond what the Manager knows.
+    fn on_health(
+        &self,
+        _ctx: &Context<C, Q>,
+        _deps: &Deps<Q>,
+        _env: &Env,
+    ) -> Result<Option<Health>, Self::Error> {
+        Ok(None)
+    }
+}
+
+/// The kinds of messages a module handles.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Capabilities {
+    pub execute: bool,
+    pub query: bool,
+    pub migrate: bool,
+    /// Whether execute messages may carry funds. The Manager rejects
+    /// messages with funds to modules that are not payable with
+    /// [NonPayable][crate::error::Error::NonPayable].
+    #[serde(default = "default_payable")]
+    pub payable: bool,
+}
+
+fn default_payable() -> bool {
+    true
+}
+
+/// The handler of a [Migration].
+pub type MigrationHandler<M, C, Q> =
+    fn(&mut M, &mut DepsMut<Q>, &Env) -> Result<Response<C>, <M as Module<C, Q>>::Error>;
+
+/// A step migrating the state of a module of type `M` from the semantic
+/// version `from` to the later version `to`.
+///
+/// ```ignore
+/// fn migrations(&self) -> Vec<Migration<Self, C, Q>> {
+///     vec![
+///         Migration::new("1.0.0", "1.1.0", Self::add_fee),
+///         Migration::new("1.1.0", "2.0.0", Self::split_config),
+///     ]
+/// }
+/// ```
+pub struct Migration<M: Module<C, Q>, C, Q: CustomQuery> {
+    pub from: String,
+    pub to: String,
+    handler: MigrationHandler<M, C, Q>,
+}
+
+impl<M: Module<C, Q>, C, Q: CustomQuery> Migration<M, C, Q> {
+    pub fn new(
+        from: impl Into<String>,
+        to: impl Into<String>,
+        handler: MigrationHandler<M, C, Q>,
+    ) -> Self {
+        Migration {
+            from: from.into(),
+            to: to.into(),
+            handler,
+        }
+    }
+}
+
+/// The JSON schemas of a module's messages. Messages without a schema are
+/// not validated.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Schemas {
+    pub instantiate: Option<Value>,
+    pub execute: Option<Value>,
+    pub query: Option<Value>,
+}
+
+#[cfg(feature = "schema")]
+impl Schemas {
+    /// The schemas of the message types `I`, `E`, and `M`, as generated by
+    /// `schemars`.
+    pub fn of<I, E, M>() -> Self
+    where
+        I: cosmwasm_schema::schemars::JsonSchema,
+        E: cosmwasm_schema::schemars::JsonSchema,
+        M: cosmwasm_schema::schemars::JsonSchema,
+    {
+        Schemas {
+            instantiate: serde_json::to_value(cosmwasm_schema::schema_for!(I)).ok(),
+            execute: serde_json::to_value(cosmwasm_schema::schema_for!(E)).ok(),
+            query: serde_json::to_value(cosmwasm_schema::schema_for!(M)).ok(),
+        }
+    }
+}
+
+/// Structured information describing a module.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Metadata {
+    /// The semantic version of the module.
+    pub version: Option<String>,
+    /// A human readable description of the module.
+    pub description: Option<String>,
+    /// The names of the interfaces the module supports, e.g. `"cw20"`.
+    pub interfaces: Vec<String>,
+    /// A hash of the module's message schemas, allowing clients to detect
+    /// changes to its interface.
+    pub schema_hash: Option<String>,
+}
+
+/// The status a module reports through [on_health][Module::on_health].
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Health {
+    /// The number of items of each kind the module holds, e.g.
+    /// `{ "proposals": 12 }`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub counts: BTreeMap<String, u64>,
+    /// The SHA-256 hash of the module's configuration, allowing monitoring
+    /// to detect changes to it; see [with_config][Health::with_config].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config_digest: Option<Binary>,
+    /// Any other status the module reports.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<Value>,
+}
+
+impl Health {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Report `count` items of the kind `name`.
+    pub fn with_count(mut self, name: impl Into<String>, count: u64) -> Self {
+        self.counts.insert(name.into(), count);
+        self
+    }
+
+    /// Report the digest of `config`, the hash of its JSON encoding.
+    pub fn with_config(mut self, config: &impl Serialize) -> StdResult<Self> {
+        let config = cosmwasm_std::to_json_vec(config)?;
+        self.config_digest = Some(Sha256::digest(config).to_vec().into());
+        Ok(self)
+    }
+
+    /// Report `details`, in addition to counts and the config digest.
+    pub fn with_details(mut self, details: Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Capabilities {
+            execute: true,
+            query: true,
+            migrate: false,
+            payable: true,
+        }
+    }
 }
 
 /// A dynamically typed module.
 ///
 /// GenericModules accept JSON values as their messages and return them as
-/// their results. Errors returned by GenericModules are strings. This trait
+/// their results. Errors returned by GenericModules are [ModuleError]s,
+/// holding the message of the module's error and its code, if any. This trait
 /// was created to enable a simple dynamic dispatch of messages sent to the
 /// contract by the `Manager`.
-pub trait GenericModule {
-    /// A generic implementation of Module::instantiate
+pub trait GenericModule<C = Binary, Q: CustomQuery = Empty>: MaybeSendSync {
+    /// A generic implementation of Module::validate_instantiate
+    fn validate_instantiate_value(
+        &self,
+        ctx: &Context<C, Q>,
+        deps: &Deps<Q>,
+        env: &Env,
+        info: &MessageInfo,
+        msg: &Value,
+    ) -> Result<(), ModuleError>;
+    /// A generic implementation of Module::instantiate_with_context
     fn instantiate_value(
         &mut self,
-        deps: &mut DepsMut,
+        ctx: &Context<C, Q>,
+        deps: &mut DepsMut<Q>,
         env: &Env,
         info: &MessageInfo,
         msg: &Value,
-    ) -> Result<Response, String>;
-    /// A generic implementation of Module::execute
+    ) -> Result<Response<C>, ModuleError>;
+    /// A generic implementation of Module::validate_execute
+    fn validate_execute_value(
+        &self,
+        ctx: &Context<C, Q>,
+        deps: &Deps<Q>,
+        env: &Env,
+        info: &MessageInfo,
+        msg: &Value,
+    ) -> Result<(), ModuleError>;
+    /// A generic implementation of Module::execute_with_context
     fn execute_value(
         &mut self,
-        deps: &mut DepsMut,
+        ctx: &Context<C, Q>,
+        deps: &mut DepsMut<Q>,
+        env: Env,
+        info: MessageInfo,
+        msg: &Value,
+    ) -> Result<Response<C>, ModuleError>;
+    /// A generic implementation of Module::query_with_context
+    fn query_value(
+        &self,
+        ctx: &Context<C, Q>,
+        deps: &Deps<Q>,
+        env: Env,
+        msg: &Value,
+    ) -> Result<Binary, ModuleError>;
+    /// Like [execute_value][Self::execute_value], with the payload still
+    /// encoded, so it can be deserialized into the module's message in a
+    /// single pass. Defaults to parsing it into a `Value` first.
+    fn execute_raw(
+        &mut self,
+        ctx: &Context<C, Q>,
+        deps: &mut DepsMut<Q>,
         env: Env,
         info: MessageInfo,
+        msg: &RawValue,
+    ) -> Result<Response<C>, ModuleError> {
+        let msg: Value = serde_json::from_str(msg.get()).map_err(ModuleError::from_error)?;
+        self.execute_value(ctx, deps, env, info, &msg)
+    }
+    /// Like [query_value][Self::query_value], with the payload still
+    /// encoded; see [execute_raw][Self::execute_raw].
+    fn query_raw(
+        &self,
+        ctx: &Context<C, Q>,
+        deps: &Deps<Q>,
+        env: Env,
+        msg: &RawValue,
+    ) -> Result<Binary, ModuleError> {
+        let msg: Value = serde_json::from_str(msg.get()).map_err(ModuleError::from_error)?;
+        self.query_value(ctx, deps, env, &msg)
+    }
+    /// A generic implementation of Module::on_hook
+    fn on_hook_value(
+        &mut self,
+        ctx: &Context<C, Q>,
+        deps: &mut DepsMut<Q>,
+        env: &Env,
+        info: &MessageInfo,
+        topic: &str,
+        payload: &Value,
+    ) -> Result<Response<C>, ModuleError>;
+    /// A generic implementation of Module::on_broadcast
+    fn on_broadcast_value(
+        &mut self,
+        ctx: &Context<C, Q>,
+        deps: &mut DepsMut<Q>,
+        env: &Env,
+        info: &MessageInfo,
         msg: &Value,
-    ) -> Result<Response, String>;
-    /// A generic implementation of Module::query
-    fn query_value(&self, deps: &Deps, env: Env, msg: &Value) -> StdResult<Binary>;
+    ) -> Result<Option<Response<C>>, ModuleError>;
+    /// A generic implementation of Module::reply
+    fn reply_value(
+        &mut self,
+        ctx: &Context<C, Q>,
+        deps: &mut DepsMut<Q>,
+        env: &Env,
+        reply: Reply,
+    ) -> Result<Response<C>, ModuleError>;
+    /// A generic implementation of Module::on_callback
+    fn on_callback_value(
+        &mut self,
+        ctx: &Context<C, Q>,
+        deps: &mut DepsMut<Q>,
+        env: &Env,
+        callback: Callback,
+        result: SubMsgResult,
+    ) -> Result<Response<C>, ModuleError>;
+    /// A generic implementation of Module::migrate
+    fn migrate_value(
+        &mut self,
+        deps: &mut DepsMut<Q>,
+        env: &Env,
+        msg: &Value,
+    ) -> Result<Response<C>, ModuleError>;
+    /// The versions each of the steps of Module::migrations migrates between
+    fn migration_steps(&self) -> Vec<(String, String)>;
+    /// Run the step at `index` of Module::migrations
+    fn migrate_step_value(
+        &mut self,
+        deps: &mut DepsMut<Q>,
+        env: &Env,
+        index: usize,
+    ) -> Result<Response<C>, ModuleError>;
+    /// A generic implementation of Module::version
+    fn version(&self) -> Option<String>;
+    /// A generic implementation of Module::schemas
+    fn schemas(&self) -> Schemas;
+    /// A generic implementation of Module::capabilities
+    fn capabilities(&self) -> Capabilities;
+    /// A generic implementation of Module::on_register
+    fn on_register_value(&self, manager: &Manager<C, Q>, name: &str) -> Result<(), ModuleError>;
+    /// A generic implementation of Module::metadata
+    fn metadata(&self) -> Metadata;
+    /// A generic implementation of Module::on_health
+    fn on_health_value(
+        &self,
+        ctx: &Context<C, Q>,
+        deps: &Deps<Q>,
+        env: &Env,
+    ) -> Result<Option<Health>, ModuleError>;
+}
+
+/// Deserialize a message from its payload. When the Manager is
+/// [strict][crate::manager::Manager::set_strict], fields ignored by the
+/// message type are reported as an error. When it
+/// [normalizes][crate::manager::Manager::set_key_case] names, fields and
+/// variants are matched in snake_case.
+fn parse<M, C, Q>(ctx: &Context<C, Q>, msg: &Value) -> Result<M, ModuleError>
+where
+    M: for<'a> Deserialize<'a>,
+    Q: CustomQuery,
+{
+    if ctx.key_case().converts_payloads() {
+        parse_from(ctx, case::SnakeCase(msg))
+    } else {
+        parse_from(ctx, msg)
+    }
+}
+
+fn parse_from<'de, M, C, Q, D>(ctx: &Context<C, Q>, msg: D) -> Result<M, ModuleError>
+where
+    M: Deserialize<'de>,
+    Q: CustomQuery,
+    D: Deserializer<'de, Error = serde_json::Error>,
+{
+    if !ctx.is_strict() {
+        return M::deserialize(msg).map_err(ModuleError::from_error);
+    }
+    let mut unknown = Vec::new();
+    let parsed = serde_ignored::deserialize(msg, |path| unknown.push(path.to_string()))
+        .map_err(ModuleError::from_error)?;
+    if !unknown.is_empty() {
+        return Err(format!("unknown fields: {}", unknown.join(", ")).into());
+    }
+    Ok(parsed)
+}
+
+/// Whether payloads can be deserialized straight from their encoding, i.e.
+/// the Manager neither normalizes their names, checks them against schemas,
+/// nor reports the fields they ignore, all of which work on a `Value`.
+fn parses_raw<C, Q>(ctx: &Context<C, Q>) -> bool
+where
+    Q: CustomQuery,
+{
+    !ctx.is_strict() && !ctx.validates_schemas() && !ctx.key_case().converts_payloads()
+}
+
+/// Check a payload against the schema returned by `schema` when the Manager
+/// [validates schemas][crate::manager::Manager::set_validate_schemas],
+/// matching its keys in snake_case if it
+/// [normalizes][crate::manager::Manager::set_key_case] them.
+fn check_schema<C, Q>(
+    ctx: &Context<C, Q>,
+    schema: impl FnOnce() -> Option<Value>,
+    msg: &Value,
+) -> Result<(), String>
+where
+    Q: CustomQuery,
+{
+    if !ctx.validates_schemas() {
+        return Ok(());
+    }
+    let errors = match schema() {
+        Some(schema) if ctx.key_case().converts_payloads() => {
+            validation::validate_named(&schema, msg, case::to_snake_case)
+        }
+        Some(schema) => validation::validate(&schema, msg),
+        None => return Ok(()),
+    };
+    if errors.is_empty() {
+        return Ok(());
+    }
+    let errors: Vec<String> = errors.iter().map(ToString::to_string).collect();
+    Err(format!("invalid payload: {}", errors.join("; ")))
+}
+
+/// Encode `resp`, the response of `module` to a query, with the names of its
+/// fields and variants in camelCase if the query came from outside the
+/// contract and the Manager [converts][crate::manager::Manager::set_key_case]
+/// them.
+fn encode_query_resp<T, C, Q>(
+    module: &T,
+    ctx: &Context<C, Q>,
+    resp: &T::QueryResp,
+) -> Result<Binary, ModuleError>
+where
+    Q: CustomQuery,
+    T: Module<C, Q>,
+{
+    let encoded = if ctx.converts_response() {
+        module.encode_camel_case_query_resp(resp)
+    } else {
+        module.encode_query_resp(resp)
+    };
+    encoded.map_err(ModuleError::from_error)
 }
 
 /// An implementation of GenericModule for all valid implementations of Module.
-impl<T, A, B, C, D, E> GenericModule for T
+///
+/// Payloads are deserialized directly from the borrowed `Value`, so the JSON
+/// tree is never cloned on dispatch. Payloads handed over still encoded are deserialized straight into the module's
+/// messages, unless the Manager needs them as a `Value` first.
+impl<T, C, Q> GenericModule<C, Q> for T
 where
-    A: for<'de> Deserialize<'de>,
-    B: for<'de> Deserialize<'de>,
-    C: for<'de> Deserialize<'de>,
-    D: Serialize,
-    E: Display,
-    T: Module<InstantiateMsg = A, ExecuteMsg = B, QueryMsg = C, QueryResp = D, Error = E>,
+    Q: CustomQuery,
+    T: Module<C, Q> + MaybeSendSync,
 {
+    fn validate_instantiate_value(
+        &self,
+        ctx: &Context<C, Q>,
+        deps: &Deps<Q>,
+        env: &Env,
+        info: &MessageInfo,
+        msg: &Value,
+    ) -> Result<(), ModuleError> {
+        check_schema(ctx, || <T as Module<C, Q>>::schemas(self).instantiate, msg)?;
+        let parsed_msg: T::InstantiateMsg = parse(ctx, msg)?;
+        self.validate_instantiate(deps, env, info, &parsed_msg)
+            .map_err(|e| ModuleError::from_module::<T, C, Q>(self, e))
+    }
+
     fn instantiate_value(
         &mut self,
-        deps: &mut DepsMut,
+        ctx: &Context<C, Q>,
+        deps: &mut DepsMut<Q>,
         env: &Env,
         info: &MessageInfo,
         msg: &Value,
-    ) -> Result<Response, String> {
-        let parsed_msg = serde_json::from_value(msg.clone()).map_err(|e| e.to_string())?;
-        self.instantiate(deps, env, info, parsed_msg)
-            .map_err(|e| e.to_string())
+    ) -> Result<Response<C>, ModuleError> {
+        check_schema(ctx, || <T as Module<C, Q>>::schemas(self).instantiate, msg)?;
+        let parsed_msg: T::InstantiateMsg = parse(ctx, msg)?;
+        self.instantiate_with_context(ctx, deps, env, info, parsed_msg)
+            .map_err(|e| ModuleError::from_module::<T, C, Q>(self, e))
+    }
+
+    fn validate_execute_value(
+        &self,
+        ctx: &Context<C, Q>,
+        deps: &Deps<Q>,
+        env: &Env,
+        info: &MessageInfo,
+        msg: &Value,
+    ) -> Result<(), ModuleError> {
+        check_schema(ctx, || <T as Module<C, Q>>::schemas(self).execute, msg)?;
+        let parsed_msg: T::ExecuteMsg = parse(ctx, msg)?;
+        self.validate_execute(deps, env, info, &parsed_msg)
+            .map_err(|e| ModuleError::from_module::<T, C, Q>(self, e))
     }
 
     fn execute_value(
         &mut self,
-        deps: &mut DepsMut,
+        ctx: &Context<C, Q>,
+        deps: &mut DepsMut<Q>,
         env: Env,
         info: MessageInfo,
         msg: &Value,
-    ) -> Result<Response, String> {
-        let parsed_msg = serde_json::from_value(msg.clone()).map_err(|e| e.to_string())?;
-        self.execute(deps, env, info, parsed_msg)
-            .map_err(|e| e.to_string())
+    ) -> Result<Response<C>, ModuleError> {
+        check_schema(ctx, || <T as Module<C, Q>>::schemas(self).execute, msg)?;
+        let parsed_msg: T::ExecuteMsg = parse(ctx, msg)?;
+        self.execute_with_context(ctx, deps, env, info, parsed_msg)
+            .map_err(|e| ModuleError::from_module::<T, C, Q>(self, e))
+    }
+
+    fn query_value(
+        &self,
+        ctx: &Context<C, Q>,
+        deps: &Deps<Q>,
+        env: Env,
+        msg: &Value,
+    ) -> Result<Binary, ModuleError> {
+        check_schema(ctx, || <T as Module<C, Q>>::schemas(self).query, msg)?;
+        let parsed_msg: T::QueryMsg = parse(ctx, msg)?;
+        let res = self
+            .query_with_context(ctx, deps, env, parsed_msg)
+            .map_err(|e| ModuleError::from_module::<T, C, Q>(self, e))?;
+        encode_query_resp(self, ctx, &res)
+    }
+
+    fn execute_raw(
+        &mut self,
+        ctx: &Context<C, Q>,
+        deps: &mut DepsMut<Q>,
+        env: Env,
+        info: MessageInfo,
+        msg: &RawValue,
+    ) -> Result<Response<C>, ModuleError> {
+        if !parses_raw(ctx) {
+            let msg: Value = serde_json::from_str(msg.get()).map_err(ModuleError::from_error)?;
+            return self.execute_value(ctx, deps, env, info, &msg);
+        }
+        let parsed_msg: T::ExecuteMsg =
+            serde_json::from_str(msg.get()).map_err(ModuleError::from_error)?;
+        self.execute_with_context(ctx, deps, env, info, parsed_msg)
+            .map_err(|e| ModuleError::from_module::<T, C, Q>(self, e))
     }
 
-    fn query_value(&self, deps: &Deps, env: Env, msg: &Value) -> StdResult<Binary> {
-        let parsed_msg = serde_json::from_value(msg.clone())
-            .map_err(|e| StdError::generic_err(e.to_string()))?;
+    fn query_raw(
+        &self,
+        ctx: &Context<C, Q>,
+        deps: &Deps<Q>,
+        env: Env,
+        msg: &RawValue,
+    ) -> Result<Binary, ModuleError> {
+        if !parses_raw(ctx) {
+            let msg: Value = serde_json::from_str(msg.get()).map_err(ModuleError::from_error)?;
+            return self.query_value(ctx, deps, env, &msg);
+        }
+        let parsed_msg: T::QueryMsg =
+            serde_json::from_str(msg.get()).map_err(ModuleError::from_error)?;
         let res = self
-            .query(deps, env, parsed_msg)
-            .map_err(|e| StdError::generic_err(e.to_string()))?;
-        cosmwasm_std::to_binary(&res)
+            .query_with_context(ctx, deps, env, parsed_msg)
+            .map_err(|e| ModuleError::from_module::<T, C, Q>(self, e))?;
+        encode_query_resp(self, ctx, &res)
+    }
+
+    fn on_hook_value(
+        &mut self,
+        ctx: &Context<C, Q>,
+        deps: &mut DepsMut<Q>,
+        env: &Env,
+        info: &MessageInfo,
+        topic: &str,
+        payload: &Value,
+    ) -> Result<Response<C>, ModuleError> {
+        <T as Module<C, Q>>::on_hook(self, ctx, deps, env, info, topic, payload)
+            .map_err(|e| ModuleError::from_module::<T, C, Q>(self, e))
+    }
+
+    fn on_broadcast_value(
+        &mut self,
+        ctx: &Context<C, Q>,
+        deps: &mut DepsMut<Q>,
+        env: &Env,
+        info: &MessageInfo,
+        msg: &Value,
+    ) -> Result<Option<Response<C>>, ModuleError> {
+        <T as Module<C, Q>>::on_broadcast(self, ctx, deps, env, info, msg)
+            .map_err(|e| ModuleError::from_module::<T, C, Q>(self, e))
+    }
+
+    fn reply_value(
+        &mut self,
+        ctx: &Context<C, Q>,
+        deps: &mut DepsMut<Q>,
+        env: &Env,
+        reply: Reply,
+    ) -> Result<Response<C>, ModuleError> {
+        <T as Module<C, Q>>::reply(self, ctx, deps, env, reply)
+            .map_err(|e| ModuleError::from_module::<T, C, Q>(self, e))
+    }
+
+    fn on_callback_value(
+        &mut self,
+        ctx: &Context<C, Q>,
+        deps: &mut DepsMut<Q>,
+        env: &Env,
+        callback: Callback,
+        result: SubMsgResult,
+    ) -> Result<Response<C>, ModuleError> {
+        <T as Module<C, Q>>::on_callback(self, ctx, deps, env, callback, result)
+            .map_err(|e| ModuleError::from_module::<T, C, Q>(self, e))
+    }
+
+    fn migrate_value(
+        &mut self,
+        deps: &mut DepsMut<Q>,
+        env: &Env,
+        msg: &Value,
+    ) -> Result<Response<C>, ModuleError> {
+        <T as Module<C, Q>>::migrate(self, deps, env, msg)
+            .map_err(|e| ModuleError::from_module::<T, C, Q>(self, e))
+    }
+
+    fn migration_steps(&self) -> Vec<(String, String)> {
+        <T as Module<C, Q>>::migrations(self)
+            .into_iter()
+            .map(|step| (step.from, step.to))
+            .collect()
+    }
+
+    fn migrate_step_value(
+        &mut self,
+        deps: &mut DepsMut<Q>,
+        env: &Env,
+        index: usize,
+    ) -> Result<Response<C>, ModuleError> {
+        let step = <T as Module<C, Q>>::migrations(self)
+            .into_iter()
+            .nth(index)
+            .ok_or_else(|| format!("no migration step {}", index))?;
+        (step.handler)(self, deps, env).map_err(|e| ModuleError::from_module::<T, C, Q>(self, e))
+    }
+
+    fn version(&self) -> Option<String> {
+        <T as Module<C, Q>>::version(self)
+    }
+
+    fn schemas(&self) -> Schemas {
+        <T as Module<C, Q>>::schemas(self)
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        <T as Module<C, Q>>::capabilities(self)
+    }
+
+    fn on_register_value(&self, manager: &Manager<C, Q>, name: &str) -> Result<(), ModuleError> {
+        <T as Module<C, Q>>::on_register(self, manager, name)
+            .map_err(|e| ModuleError::from_module::<T, C, Q>(self, e))
+    }
+
+    fn metadata(&self) -> Metadata {
+        <T as Module<C, Q>>::metadata(self)
+    }
+
+    fn on_health_value(
+        &self,
+        ctx: &Context<C, Q>,
+        deps: &Deps<Q>,
+        env: &Env,
+    ) -> Result<Option<Health>, ModuleError> {
+        <T as Module<C, Q>>::on_health(self, ctx, deps, env)
+            .map_err(|e| ModuleError::from_module::<T, C, Q>(self, e))
     }
 }
@@ -0,0 +1,282 @@
+//! Named lists of addresses.
+//!
+//! [AddressList] keeps any number of named address lists, maintained by the
+//! [admin][crate::admin], for use as allowlists, denylists, or anything else
+//! that needs a set of accounts:
+//!
+//! ```javascript
+//! { "address_list": { "add": { "list": "blocked", "addresses": ["...", "..."] } } }
+//! ```
+//!
+//! Sibling modules check membership over the query bus with
+//! [Context::query][crate::context::Context::query]:
+//!
+//! ```ignore
+//! let resp: ContainsResponse = ctx.query(
+//!     &deps.as_ref(),
+//!     &env,
+//!     "address_list",
+//!     &QueryMsg::Contains { list: "blocked".to_string(), address: sender },
+//! )?;
+//! ```
+//!
+//! [AccessControl][crate::middleware::access_control::AccessControl] can
+//! also use a list to guard a module.
+
+use crate::admin;
+use crate::error::Error;
+use crate::module::Module;
+use crate::pagination;
+use crate::response::Response;
+use crate::storage;
+use cosmwasm_std::{Addr, CustomQuery, Deps, DepsMut, Env, MessageInfo, StdResult, Storage};
+use serde::{Deserialize, Serialize};
+
+const NAMESPACE: &[u8] = b"address_list";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct InstantiateMsg {}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecuteMsg {
+    /// Add `addresses` to `list`, creating it if needed.
+    Add { list: String, addresses: Vec<String> },
+    /// Remove `addresses` from `list`.
+    Remove { list: String, addresses: Vec<String> },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMsg {
+    Contains {
+        list: String,
+        address: String,
+    },
+    /// List the addresses on `list` in ascending order.
+    Addresses {
+        list: String,
+        #[serde(default)]
+        start_after: Option<String>,
+        #[serde(default)]
+        limit: Option<u32>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ContainsResponse {
+    pub contains: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct AddressesResponse {
+    pub addresses: Vec<Addr>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum QueryResp {
+    Contains(ContainsResponse),
+    Addresses(AddressesResponse),
+}
+
+/// A module maintaining named address lists.
+#[derive(Clone, Debug, Default)]
+pub struct AddressList {}
+
+impl AddressList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+fn entry_key(list: &str, address: &Addr) -> Vec<u8> {
+    storage::key(&[NAMESPACE, list.as_bytes()], address.as_bytes())
+}
+
+/// Whether `address` is on `list`.
+pub fn contains(storage: &dyn Storage, list: &str, address: &Addr) -> StdResult<bool> {
+    Ok(storage::load::<bool>(storage, &entry_key(list, address))?.is_some())
+}
+
+impl<C: Clone, Q: CustomQuery> Module<C, Q> for AddressList {
+    type InstantiateMsg = InstantiateMsg;
+    type ExecuteMsg = ExecuteMsg;
+    type QueryMsg = QueryMsg;
+    type QueryResp = QueryResp;
+    type Error = Error;
+
+    fn instantiate(
+        &mut self,
+        _deps: &mut DepsMut<Q>,
+        _env: &Env,
+        _info: &MessageInfo,
+        _msg: InstantiateMsg,
+    ) -> Result<Response<C>, Error> {
+        Ok(Response::new())
+    }
+
+    fn execute(
+        &mut self,
+        deps: &mut DepsMut<Q>,
+        _env: Env,
+        info: MessageInfo,
+        msg: ExecuteMsg,
+    ) -> Result<Response<C>, Error> {
+        admin::assert_admin(deps.storage, &info.sender)?;
+        let (add, list, addresses) = match msg {
+            ExecuteMsg::Add { list, addresses } => (true, list, addresses),
+            ExecuteMsg::Remove { list, addresses } => (false, list, addresses),
+        };
+        for address in &addresses {
+            let key = entry_key(&list, &deps.api.addr_validate(address)?);
+            if add {
+                storage::save(deps.storage, &key, &true)?;
+            } else {
+                storage::remove(deps.storage, &key);
+            }
+        }
+        Ok(Response::new()
+            .add_attribute("action", if add { "add" } else { "remove" })
+            .add_attribute("list", list)
+            .add_attribute("count", addresses.len().to_string()))
+    }
+
+    fn query(&self, deps: &Deps<Q>, _env: Env, msg: QueryMsg) -> Result<QueryResp, Error> {
+        match msg {
+            QueryMsg::Contains { list, address } => {
+                let address = deps.api.addr_validate(&address)?;
+                Ok(QueryResp::Contains(ContainsResponse {
+                    contains: contains(deps.storage, &list, &address)?,
+                }))
+            }
+            QueryMsg::Addresses {
+                list,
+                start_after,
+                limit,
+            } => {
+                let limit = pagination::limit(limit);
+                let entries = storage::range::<bool>(
+                    deps.storage,
+                    &[NAMESPACE, list.as_bytes()],
+                    start_after.as_ref().map(|a| a.as_bytes()),
+                    limit,
+                )?;
+                let addresses = entries
+                    .into_iter()
+                    .map(|(key, _)| Addr::unchecked(String::from_utf8_lossy(&key)))
+                    .collect();
+                Ok(QueryResp::Addresses(AddressesResponse { addresses }))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manager::Manager;
+    use crate::middleware::access_control::{self, AccessControl};
+    use crate::test_utils::{addr, info, instantiate, Deposits, MockDeps};
+    use cosmwasm_std::from_json;
+    use cosmwasm_std::testing::{mock_dependencies, mock_env};
+    use serde_json::{json, Value};
+
+    struct Setup {
+        deps: MockDeps,
+        manager: Manager,
+    }
+
+    impl Setup {
+        /// A contract whose `mint` module may be guarded by an address list.
+        fn new() -> Self {
+            let manager = Manager::builder()
+                .with_boxed_module("address_list", Box::new(AddressList::new()))
+                .unwrap()
+                .with_boxed_module("access_control", Box::new(AccessControl::new()))
+                .unwrap()
+                .with_boxed_module("mint", Box::new(Deposits))
+                .unwrap()
+                .with_middleware(Box::new(AccessControl::new()))
+                .build();
+            let mut setup = Setup {
+                deps: mock_dependencies(),
+                manager,
+            };
+            admin::set_admin(&mut setup.deps.storage, &addr("admin")).unwrap();
+            let msg = access_control::InstantiateMsg {
+                admin: addr("admin").to_string(),
+            };
+            instantiate::<AccessControl>(&mut setup.deps, "admin", msg).unwrap();
+            setup
+        }
+
+        fn execute(&mut self, sender: &str, msg: Value) -> Result<(), Error> {
+            self.manager
+                .execute(
+                    &mut self.deps.as_mut(),
+                    mock_env(),
+                    info(sender, &[]),
+                    &msg.to_string(),
+                )
+                .map(|_| ())
+        }
+
+        fn update(&mut self, sender: &str, action: &str, addresses: &[&str]) -> Result<(), Error> {
+            let addresses: Vec<Addr> = addresses.iter().map(|a| addr(a)).collect();
+            let msg =
+                json!({"address_list": {action: {"list": "blocked", "addresses": addresses}}});
+            self.execute(sender, msg)
+        }
+
+        fn query(&mut self, msg: Value) -> Value {
+            let msg = json!({ "address_list": msg }).to_string();
+            let resp = self
+                .manager
+                .query(&self.deps.as_ref(), mock_env(), &msg)
+                .unwrap();
+            from_json(resp).unwrap()
+        }
+    }
+
+    #[test]
+    fn the_admin_maintains_lists() {
+        let mut setup = Setup::new();
+        setup.update("admin", "add", &["bob", "carol"]).unwrap();
+        setup.update("admin", "remove", &["carol"]).unwrap();
+        let err = setup.update("bob", "remove", &["bob"]).unwrap_err();
+        assert!(err.to_string().contains("is not authorized"), "{}", err);
+
+        let contains = |setup: &mut Setup, name: &str| {
+            let msg = json!({"contains": {"list": "blocked", "address": addr(name)}});
+            setup.query(msg)["contains"].clone()
+        };
+        assert_eq!(contains(&mut setup, "bob"), json!(true));
+        assert_eq!(contains(&mut setup, "carol"), json!(false));
+        assert_eq!(
+            setup.query(json!({"addresses": {"list": "blocked"}})),
+            json!({"addresses": [addr("bob")]})
+        );
+    }
+
+    #[test]
+    fn lists_guard_modules_through_access_control() {
+        let mut setup = Setup::new();
+        setup.update("admin", "add", &["bob"]).unwrap();
+        let guard = |mode: &str| {
+            json!({"access_control": {"use_address_list": {
+                "module": "mint", "list": "blocked", "mode": mode,
+            }}})
+        };
+
+        setup.execute("admin", guard("deny")).unwrap();
+        setup.execute("alice", json!({"mint": {}})).unwrap();
+        let err = setup.execute("bob", json!({"mint": {}})).unwrap_err();
+        assert!(matches!(err, Error::Unauthorized { .. }), "{:?}", err);
+
+        setup.execute("admin", guard("allow")).unwrap();
+        setup.execute("bob", json!({"mint": {}})).unwrap();
+        let err = setup.execute("alice", json!({"mint": {}})).unwrap_err();
+        assert!(matches!(err, Error::Unauthorized { .. }), "{:?}", err);
+    }
+}
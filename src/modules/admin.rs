@@ -0,0 +1,155 @@
+//! Contract ownership with a two-step transfer.
+//!
+//! [Admin] keeps the owner in the same slot as the
+//! [contract admin][crate::admin], so the owner is also the account allowed
+//! to send the Manager's own `_manager` messages. Other modules and
+//! middleware consult it with [assert_admin][crate::admin::assert_admin].
+//!
+//! Ownership is handed over in two steps: the owner proposes a new owner, who
+//! then accepts. This protects against transferring the contract to a mistyped
+//! address.
+//!
+//! ```javascript
+//! { "admin": { "propose_owner": { "owner": "..." } } }
+//! { "admin": { "accept_ownership": {} } }
+//! ```
+
+use crate::admin;
+use crate::error::Error;
+use crate::module::Module;
+use crate::response::Response;
+use crate::storage;
+use cosmwasm_std::{Addr, CustomQuery, Deps, DepsMut, Env, MessageInfo, StdResult, Storage};
+use serde::{Deserialize, Serialize};
+
+const NAMESPACE: &[u8] = b"admin";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct InstantiateMsg {
+    /// The initial owner. Defaults to the instantiating account.
+    #[serde(default)]
+    pub owner: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecuteMsg {
+    /// Propose `owner` as the new owner. Replaces any earlier proposal.
+    ProposeOwner { owner: String },
+    /// Withdraw the pending proposal.
+    CancelProposal {},
+    /// Accept a proposal made to the sender.
+    AcceptOwnership {},
+    /// Give up ownership for good, leaving owner-only actions unavailable.
+    RenounceOwnership {},
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMsg {
+    Ownership {},
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct OwnershipResponse {
+    pub owner: Option<Addr>,
+    pub pending_owner: Option<Addr>,
+}
+
+/// A module managing the owner of the contract.
+#[derive(Clone, Debug, Default)]
+pub struct Admin {}
+
+impl Admin {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+fn pending_key() -> Vec<u8> {
+    storage::key(&[NAMESPACE], b"pending")
+}
+
+/// Load the proposed owner, if a transfer is pending.
+pub fn pending_owner(storage: &dyn Storage) -> StdResult<Option<Addr>> {
+    storage::load(storage, &pending_key())
+}
+
+impl<C: Clone, Q: CustomQuery> Module<C, Q> for Admin {
+    type InstantiateMsg = InstantiateMsg;
+    type ExecuteMsg = ExecuteMsg;
+    type QueryMsg = QueryMsg;
+    type QueryResp = OwnershipResponse;
+    type Error = Error;
+
+    fn instantiate(
+        &mut self,
+        deps: &mut DepsMut<Q>,
+        _env: &Env,
+        info: &MessageInfo,
+        msg: InstantiateMsg,
+    ) -> Result<Response<C>, Error> {
+        let owner = match msg.owner {
+            Some(owner) => deps.api.addr_validate(&owner)?,
+            None => info.sender.clone(),
+        };
+        admin::set_admin(deps.storage, &owner)?;
+        Ok(Response::new().add_attribute("owner", owner))
+    }
+
+    fn execute(
+        &mut self,
+        deps: &mut DepsMut<Q>,
+        _env: Env,
+        info: MessageInfo,
+        msg: ExecuteMsg,
+    ) -> Result<Response<C>, Error> {
+        match msg {
+            ExecuteMsg::ProposeOwner { owner } => {
+                admin::assert_admin(deps.storage, &info.sender)?;
+                let owner = deps.api.addr_validate(&owner)?;
+                storage::save(deps.storage, &pending_key(), &owner)?;
+                Ok(Response::new()
+                    .add_attribute("action", "propose_owner")
+                    .add_attribute("pending_owner", owner))
+            }
+            ExecuteMsg::CancelProposal {} => {
+                admin::assert_admin(deps.storage, &info.sender)?;
+                storage::remove(deps.storage, &pending_key());
+                Ok(Response::new().add_attribute("action", "cancel_proposal"))
+            }
+            ExecuteMsg::AcceptOwnership {} => {
+                if pending_owner(deps.storage)?.as_ref() != Some(&info.sender) {
+                    return Err(Error::Unauthorized {
+                        sender: info.sender.to_string(),
+                    });
+                }
+                storage::remove(deps.storage, &pending_key());
+                admin::set_admin(deps.storage, &info.sender)?;
+                Ok(Response::new()
+                    .add_attribute("action", "accept_ownership")
+                    .add_attribute("owner", info.sender))
+            }
+            ExecuteMsg::RenounceOwnership {} => {
+                admin::assert_admin(deps.storage, &info.sender)?;
+                storage::remove(deps.storage, &pending_key());
+                admin::clear_admin(deps.storage);
+                Ok(Response::new().add_attribute("action", "renounce_ownership"))
+            }
+        }
+    }
+
+    fn query(
+        &self,
+        deps: &Deps<Q>,
+        _env: Env,
+        msg: QueryMsg,
+    ) -> Result<OwnershipResponse, Error> {
+        match msg {
+            QueryMsg::Ownership {} => Ok(OwnershipResponse {
+                owner: admin::get_admin(deps.storage)?,
+                pending_owner: pending_owner(deps.storage)?,
+            }),
+        }
+    }
+}
@@ -0,0 +1,619 @@
+//! A cw1155 multi-token as a module.
+//!
+//! [Cw1155] implements the messages and queries of `cw1155-base`: balances
+//! per token id, batch mint, transfer and burn, and operator approvals.
+//! Messages use the cw1155 format inside the glue envelope:
+//!
+//! ```javascript
+//! { "items": { "batch_send_from": {
+//!     "from": "...", "to": "...", "batch": [["sword", "1"], ["potion", "5"]]
+//! } } }
+//! ```
+//!
+//! When a transfer or mint includes a `msg`, the recipient contract is
+//! notified with a [Cw1155ReceiveMsg] or [Cw1155BatchReceiveMsg]. State
+//! lives in a fixed namespace, so a contract holds at most one [Cw1155]
+//! module.
+
+use crate::error::Error;
+use crate::module::Module;
+use crate::modules::expiration::Expiration;
+use crate::pagination;
+use crate::response::Response;
+use crate::storage;
+use cosmwasm_std::{
+    to_json_binary, Addr, Binary, BlockInfo, CosmosMsg, CustomQuery, Deps, DepsMut, Env,
+    MessageInfo, StdError, StdResult, Storage, Uint128, WasmMsg,
+};
+use serde::{Deserialize, Serialize};
+
+const NAMESPACE: &[u8] = b"cw1155";
+
+/// Token ids with their amounts.
+pub type TokenBatch = Vec<(String, Uint128)>;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct InstantiateMsg {
+    /// The account allowed to mint.
+    pub minter: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecuteMsg {
+    SendFrom {
+        from: String,
+        to: String,
+        token_id: String,
+        value: Uint128,
+        #[serde(default)]
+        msg: Option<Binary>,
+    },
+    BatchSendFrom {
+        from: String,
+        to: String,
+        batch: TokenBatch,
+        #[serde(default)]
+        msg: Option<Binary>,
+    },
+    Mint {
+        to: String,
+        token_id: String,
+        value: Uint128,
+        #[serde(default)]
+        msg: Option<Binary>,
+    },
+    BatchMint {
+        to: String,
+        batch: TokenBatch,
+        #[serde(default)]
+        msg: Option<Binary>,
+    },
+    Burn {
+        from: String,
+        token_id: String,
+        value: Uint128,
+    },
+    BatchBurn {
+        from: String,
+        batch: TokenBatch,
+    },
+    /// Allow `operator` to move and burn all of the sender's tokens.
+    ApproveAll {
+        operator: String,
+        #[serde(default)]
+        expires: Option<Expiration>,
+    },
+    RevokeAll {
+        operator: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMsg {
+    Balance {
+        owner: String,
+        token_id: String,
+    },
+    BatchBalance {
+        owner: String,
+        token_ids: Vec<String>,
+    },
+    IsApprovedForAll {
+        owner: String,
+        operator: String,
+    },
+    /// List the token ids `owner` holds.
+    Tokens {
+        owner: String,
+        #[serde(default)]
+        start_after: Option<String>,
+        #[serde(default)]
+        limit: Option<u32>,
+    },
+    /// List the token ids with a non-zero supply.
+    AllTokens {
+        #[serde(default)]
+        start_after: Option<String>,
+        #[serde(default)]
+        limit: Option<u32>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct BalanceResponse {
+    pub balance: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct BatchBalanceResponse {
+    pub balances: Vec<Uint128>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct IsApprovedForAllResponse {
+    pub approved: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct TokensResponse {
+    pub tokens: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum QueryResp {
+    Balance(BalanceResponse),
+    BatchBalance(BatchBalanceResponse),
+    IsApprovedForAll(IsApprovedForAllResponse),
+    Tokens(TokensResponse),
+}
+
+/// The message notifying a contract of a single token transfer, mirroring
+/// `cw1155::Cw1155ReceiveMsg`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Cw1155ReceiveMsg {
+    pub operator: String,
+    /// The previous owner, or `None` for mints.
+    pub from: Option<String>,
+    pub token_id: String,
+    pub amount: Uint128,
+    pub msg: Binary,
+}
+
+/// The message notifying a contract of a batch transfer, mirroring
+/// `cw1155::Cw1155BatchReceiveMsg`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Cw1155BatchReceiveMsg {
+    pub operator: String,
+    pub from: Option<String>,
+    pub batch: TokenBatch,
+    pub msg: Binary,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum ReceiverExecuteMsg {
+    Receive(Cw1155ReceiveMsg),
+    BatchReceive(Cw1155BatchReceiveMsg),
+}
+
+/// A module implementing a cw1155 multi-token.
+#[derive(Clone, Debug, Default)]
+pub struct Cw1155 {}
+
+impl Cw1155 {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+fn minter_key() -> Vec<u8> {
+    storage::key(&[NAMESPACE], b"minter")
+}
+
+fn balance_key(owner: &Addr, token_id: &str) -> Vec<u8> {
+    storage::key(&[NAMESPACE, b"balances", owner.as_bytes()], token_id.as_bytes())
+}
+
+fn supply_key(token_id: &str) -> Vec<u8> {
+    storage::key(&[NAMESPACE, b"supply"], token_id.as_bytes())
+}
+
+fn operator_key(owner: &Addr, operator: &Addr) -> Vec<u8> {
+    storage::key(&[NAMESPACE, b"operators", owner.as_bytes()], operator.as_bytes())
+}
+
+/// The balance `owner` holds of `token_id`.
+pub fn balance(storage: &dyn Storage, owner: &Addr, token_id: &str) -> StdResult<Uint128> {
+    Ok(storage::load(storage, &balance_key(owner, token_id))?.unwrap_or_default())
+}
+
+fn update(storage: &mut dyn Storage, key: &[u8], amount: Uint128, add: bool) -> StdResult<()> {
+    let current: Uint128 = storage::load(storage, key)?.unwrap_or_default();
+    let updated = if add {
+        current.checked_add(amount)?
+    } else {
+        current.checked_sub(amount)?
+    };
+    if updated.is_zero() {
+        storage::remove(storage, key);
+        Ok(())
+    } else {
+        storage::save(storage, key, &updated)
+    }
+}
+
+/// Move `amount` of `token_id` from `from` to `to`. Without `from`, the
+/// tokens are minted; without `to`, they are burned.
+fn transfer(
+    storage: &mut dyn Storage,
+    from: Option<&Addr>,
+    to: Option<&Addr>,
+    token_id: &str,
+    amount: Uint128,
+) -> StdResult<()> {
+    if let Some(from) = from {
+        update(storage, &balance_key(from, token_id), amount, false)?;
+    }
+    if let Some(to) = to {
+        update(storage, &balance_key(to, token_id), amount, true)?;
+    }
+    match (from, to) {
+        (None, Some(_)) => update(storage, &supply_key(token_id), amount, true),
+        (Some(_), None) => update(storage, &supply_key(token_id), amount, false),
+        _ => Ok(()),
+    }
+}
+
+fn is_approved_for_all(
+    storage: &dyn Storage,
+    block: &BlockInfo,
+    owner: &Addr,
+    operator: &Addr,
+) -> StdResult<bool> {
+    let expires: Option<Expiration> = storage::load(storage, &operator_key(owner, operator))?;
+    Ok(matches!(expires, Some(expires) if !expires.is_expired(block)))
+}
+
+fn check_can_send(
+    storage: &dyn Storage,
+    block: &BlockInfo,
+    owner: &Addr,
+    sender: &Addr,
+) -> Result<(), Error> {
+    if owner == sender || is_approved_for_all(storage, block, owner, sender)? {
+        Ok(())
+    } else {
+        Err(Error::Unauthorized {
+            sender: sender.to_string(),
+        })
+    }
+}
+
+fn check_minter(storage: &dyn Storage, sender: &Addr) -> Result<(), Error> {
+    let minter: Option<Addr> = storage::load(storage, &minter_key())?;
+    if minter.as_ref() == Some(sender) {
+        Ok(())
+    } else {
+        Err(Error::Unauthorized {
+            sender: sender.to_string(),
+        })
+    }
+}
+
+fn receive_msg<C>(to: &Addr, msg: ReceiverExecuteMsg) -> StdResult<CosmosMsg<C>> {
+    Ok(WasmMsg::Execute {
+        contract_addr: to.to_string(),
+        msg: to_json_binary(&msg)?,
+        funds: vec![],
+    }
+    .into())
+}
+
+/// Move a batch of tokens, notifying the recipient if `msg` is given.
+#[allow(clippy::too_many_arguments)]
+fn execute_transfer<C: Clone>(
+    storage: &mut dyn Storage,
+    operator: &Addr,
+    from: Option<&Addr>,
+    to: Option<&Addr>,
+    batch: TokenBatch,
+    msg: Option<Binary>,
+    single: bool,
+    action: &str,
+) -> Result<Response<C>, Error> {
+    let mut resp = Response::new().add_attribute("action", action);
+    for (token_id, amount) in &batch {
+        transfer(storage, from, to, token_id, *amount)?;
+        resp = resp
+            .add_attribute("token_id", token_id)
+            .add_attribute("amount", amount.to_string());
+    }
+    if let Some(from) = from {
+        resp = resp.add_attribute("from", from);
+    }
+    if let Some(to) = to {
+        resp = resp.add_attribute("to", to);
+    }
+    if let (Some(to), Some(msg)) = (to, msg) {
+        let from = from.map(Addr::to_string);
+        let receive = if single {
+            let (token_id, amount) = batch.into_iter().next().unwrap_or_default();
+            ReceiverExecuteMsg::Receive(Cw1155ReceiveMsg {
+                operator: operator.to_string(),
+                from,
+                token_id,
+                amount,
+                msg,
+            })
+        } else {
+            ReceiverExecuteMsg::BatchReceive(Cw1155BatchReceiveMsg {
+                operator: operator.to_string(),
+                from,
+                batch,
+                msg,
+            })
+        };
+        resp = resp.add_message(receive_msg(to, receive)?);
+    }
+    Ok(resp)
+}
+
+impl<C: Clone, Q: CustomQuery> Module<C, Q> for Cw1155 {
+    type InstantiateMsg = InstantiateMsg;
+    type ExecuteMsg = ExecuteMsg;
+    type QueryMsg = QueryMsg;
+    type QueryResp = QueryResp;
+    type Error = Error;
+
+    fn instantiate(
+        &mut self,
+        deps: &mut DepsMut<Q>,
+        _env: &Env,
+        _info: &MessageInfo,
+        msg: InstantiateMsg,
+    ) -> Result<Response<C>, Error> {
+        let minter = deps.api.addr_validate(&msg.minter)?;
+        storage::save(deps.storage, &minter_key(), &minter)?;
+        Ok(Response::new().add_attribute("minter", minter))
+    }
+
+    fn execute(
+        &mut self,
+        deps: &mut DepsMut<Q>,
+        env: Env,
+        info: MessageInfo,
+        msg: ExecuteMsg,
+    ) -> Result<Response<C>, Error> {
+        let sender = info.sender;
+        let (from, to, batch, msg, single, action) = match msg {
+            ExecuteMsg::SendFrom {
+                from,
+                to,
+                token_id,
+                value,
+                msg,
+            } => (Some(from), Some(to), vec![(token_id, value)], msg, true, "transfer"),
+            ExecuteMsg::BatchSendFrom {
+                from,
+                to,
+                batch,
+                msg,
+            } => (Some(from), Some(to), batch, msg, false, "transfer"),
+            ExecuteMsg::Mint {
+                to,
+                token_id,
+                value,
+                msg,
+            } => (None, Some(to), vec![(token_id, value)], msg, true, "mint"),
+            ExecuteMsg::BatchMint { to, batch, msg } => (None, Some(to), batch, msg, false, "mint"),
+            ExecuteMsg::Burn {
+                from,
+                token_id,
+                value,
+            } => (Some(from), None, vec![(token_id, value)], None, true, "burn"),
+            ExecuteMsg::BatchBurn { from, batch } => (Some(from), None, batch, None, false, "burn"),
+            ExecuteMsg::ApproveAll { operator, expires } => {
+                let operator = deps.api.addr_validate(&operator)?;
+                let expires = expires.unwrap_or_default();
+                if expires.is_expired(&env.block) {
+                    return Err(StdError::generic_err("expiration is in the past").into());
+                }
+                storage::save(deps.storage, &operator_key(&sender, &operator), &expires)?;
+                return Ok(Response::new()
+                    .add_attribute("action", "approve_all")
+                    .add_attribute("operator", operator));
+            }
+            ExecuteMsg::RevokeAll { operator } => {
+                let operator = deps.api.addr_validate(&operator)?;
+                storage::remove(deps.storage, &operator_key(&sender, &operator));
+                return Ok(Response::new()
+                    .add_attribute("action", "revoke_all")
+                    .add_attribute("operator", operator));
+            }
+        };
+        let from = from.map(|from| deps.api.addr_validate(&from)).transpose()?;
+        let to = to.map(|to| deps.api.addr_validate(&to)).transpose()?;
+        match &from {
+            Some(from) => check_can_send(deps.storage, &env.block, from, &sender)?,
+            None => check_minter(deps.storage, &sender)?,
+        }
+        execute_transfer(
+            deps.storage,
+            &sender,
+            from.as_ref(),
+            to.as_ref(),
+            batch,
+            msg,
+            single,
+            action,
+        )
+    }
+
+    fn query(&self, deps: &Deps<Q>, env: Env, msg: QueryMsg) -> Result<QueryResp, Error> {
+        match msg {
+            QueryMsg::Balance { owner, token_id } => {
+                let owner = deps.api.addr_validate(&owner)?;
+                Ok(QueryResp::Balance(BalanceResponse {
+                    balance: balance(deps.storage, &owner, &token_id)?,
+                }))
+            }
+            QueryMsg::BatchBalance { owner, token_ids } => {
+                let owner = deps.api.addr_validate(&owner)?;
+                let balances = token_ids
+                    .iter()
+                    .map(|token_id| balance(deps.storage, &owner, token_id))
+                    .collect::<StdResult<_>>()?;
+                Ok(QueryResp::BatchBalance(BatchBalanceResponse { balances }))
+            }
+            QueryMsg::IsApprovedForAll { owner, operator } => {
+                let owner = deps.api.addr_validate(&owner)?;
+                let operator = deps.api.addr_validate(&operator)?;
+                Ok(QueryResp::IsApprovedForAll(IsApprovedForAllResponse {
+                    approved: is_approved_for_all(deps.storage, &env.block, &owner, &operator)?,
+                }))
+            }
+            QueryMsg::Tokens {
+                owner,
+                start_after,
+                limit,
+            } => {
+                let owner = deps.api.addr_validate(&owner)?;
+                let namespaces: &[&[u8]] = &[NAMESPACE, b"balances", owner.as_bytes()];
+                let tokens = token_ids(deps.storage, namespaces, start_after, limit)?;
+                Ok(QueryResp::Tokens(TokensResponse { tokens }))
+            }
+            QueryMsg::AllTokens { start_after, limit } => {
+                let namespaces: &[&[u8]] = &[NAMESPACE, b"supply"];
+                let tokens = token_ids(deps.storage, namespaces, start_after, limit)?;
+                Ok(QueryResp::Tokens(TokensResponse { tokens }))
+            }
+        }
+    }
+}
+
+fn token_ids(
+    storage: &dyn Storage,
+    namespaces: &[&[u8]],
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<Vec<String>> {
+    let limit = pagination::limit(limit);
+    let entries = storage::range::<Uint128>(
+        storage,
+        namespaces,
+        start_after.as_ref().map(|id| id.as_bytes()),
+        limit,
+    )?;
+    Ok(entries
+        .into_iter()
+        .map(|(key, _)| String::from_utf8_lossy(&key).into_owned())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{addr, execute, instantiate, MockDeps};
+    use cosmwasm_std::from_json;
+    use cosmwasm_std::testing::mock_dependencies;
+
+    fn setup() -> MockDeps {
+        let mut deps = mock_dependencies();
+        let msg = InstantiateMsg {
+            minter: addr("minter").to_string(),
+        };
+        instantiate::<Cw1155>(&mut deps, "creator", msg).unwrap();
+        let msg = ExecuteMsg::BatchMint {
+            to: addr("alice").to_string(),
+            batch: vec![
+                ("gold".to_string(), Uint128::new(100)),
+                ("silver".to_string(), Uint128::new(50)),
+            ],
+            msg: None,
+        };
+        execute::<Cw1155>(&mut deps, "minter", msg).unwrap();
+        deps
+    }
+
+    fn balance_of(deps: &MockDeps, owner: &str, token_id: &str) -> u128 {
+        balance(&deps.storage, &addr(owner), token_id)
+            .unwrap()
+            .u128()
+    }
+
+    fn supply(deps: &MockDeps, token_id: &str) -> u128 {
+        storage::load::<Uint128>(&deps.storage, &supply_key(token_id))
+            .unwrap()
+            .unwrap_or_default()
+            .u128()
+    }
+
+    fn send(from: &str, value: u128, msg: Option<Binary>) -> ExecuteMsg {
+        ExecuteMsg::SendFrom {
+            from: addr(from).to_string(),
+            to: addr("bob").to_string(),
+            token_id: "gold".to_string(),
+            value: Uint128::new(value),
+            msg,
+        }
+    }
+
+    #[test]
+    fn only_minter_mints() {
+        let mut deps = setup();
+        assert_eq!(balance_of(&deps, "alice", "gold"), 100);
+        assert_eq!(supply(&deps, "silver"), 50);
+        let msg = ExecuteMsg::Mint {
+            to: addr("alice").to_string(),
+            token_id: "gold".to_string(),
+            value: Uint128::new(1),
+            msg: None,
+        };
+        execute::<Cw1155>(&mut deps, "alice", msg).unwrap_err();
+        assert_eq!(supply(&deps, "gold"), 100);
+    }
+
+    #[test]
+    fn owner_and_operators_transfer() {
+        let mut deps = setup();
+        execute::<Cw1155>(&mut deps, "alice", send("alice", 30, None)).unwrap();
+        assert_eq!(balance_of(&deps, "alice", "gold"), 70);
+        assert_eq!(balance_of(&deps, "bob", "gold"), 30);
+
+        execute::<Cw1155>(&mut deps, "carol", send("alice", 10, None)).unwrap_err();
+        let approve = ExecuteMsg::ApproveAll {
+            operator: addr("carol").to_string(),
+            expires: None,
+        };
+        execute::<Cw1155>(&mut deps, "alice", approve).unwrap();
+        execute::<Cw1155>(&mut deps, "carol", send("alice", 10, None)).unwrap();
+        assert_eq!(balance_of(&deps, "alice", "gold"), 60);
+        assert_eq!(supply(&deps, "gold"), 100);
+    }
+
+    #[test]
+    fn transfer_beyond_balance_fails() {
+        let mut deps = setup();
+        execute::<Cw1155>(&mut deps, "alice", send("alice", 101, None)).unwrap_err();
+        assert_eq!(balance_of(&deps, "alice", "gold"), 100);
+        assert_eq!(balance_of(&deps, "bob", "gold"), 0);
+    }
+
+    #[test]
+    fn send_notifies_recipient() {
+        let mut deps = setup();
+        let msg = Some(Binary::from(b"{}".as_slice()));
+        let resp = execute::<Cw1155>(&mut deps, "alice", send("alice", 5, msg)).unwrap();
+        let receive = match &resp.response.messages[0].msg {
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr, msg, ..
+            }) if contract_addr == addr("bob").as_str() => msg.clone(),
+            msg => panic!("unexpected message {:?}", msg),
+        };
+        let receive: serde_json::Value = from_json(receive).unwrap();
+        assert_eq!(receive["receive"]["operator"], addr("alice").as_str());
+        assert_eq!(receive["receive"]["token_id"], "gold");
+        assert_eq!(receive["receive"]["amount"], "5");
+    }
+
+    #[test]
+    fn burn_reduces_supply() {
+        let mut deps = setup();
+        let burn = |value| ExecuteMsg::BatchBurn {
+            from: addr("alice").to_string(),
+            batch: vec![
+                ("gold".to_string(), Uint128::new(value)),
+                ("silver".to_string(), Uint128::new(value)),
+            ],
+        };
+        execute::<Cw1155>(&mut deps, "bob", burn(10)).unwrap_err();
+        execute::<Cw1155>(&mut deps, "alice", burn(10)).unwrap();
+        assert_eq!(balance_of(&deps, "alice", "gold"), 90);
+        assert_eq!(supply(&deps, "gold"), 90);
+        assert_eq!(supply(&deps, "silver"), 40);
+        execute::<Cw1155>(&mut deps, "alice", burn(41)).unwrap_err();
+    }
+}
@@ -0,0 +1,681 @@
+//! A cw20 fungible token as a module.
+//!
+//! [Cw20] implements the messages and queries of `cw20-base`, minus
+//! marketing, so a token can be one part of a composed contract. Messages use
+//! the cw20 format inside the glue envelope:
+//!
+//! ```javascript
+//! { "token": { "transfer": { "recipient": "...", "amount": "100" } } }
+//! ```
+//!
+//! Other contracts and tools see the module as a cw20 token only if they
+//! wrap their messages accordingly. Token state lives in a fixed namespace,
+//! so a contract holds at most one [Cw20] module. Sibling modules can read
+//! and move balances directly with [balance], [total_supply], and
+//! [transfer].
+
+use crate::error::Error;
+use crate::module::Module;
+use crate::modules::expiration::Expiration;
+use crate::pagination;
+use crate::receive::Cw20ReceiveMsg;
+use crate::response::Response;
+use crate::storage;
+use cosmwasm_std::{
+    Addr, Binary, CustomQuery, Deps, DepsMut, Env, MessageInfo, StdError, StdResult, Storage,
+    Uint128,
+};
+use serde::{Deserialize, Serialize};
+
+const NAMESPACE: &[u8] = b"cw20";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Cw20Coin {
+    pub address: String,
+    pub amount: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct MinterResponse {
+    pub minter: String,
+    /// The maximum total supply minting may reach.
+    pub cap: Option<Uint128>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct InstantiateMsg {
+    pub name: String,
+    pub symbol: String,
+    pub decimals: u8,
+    pub initial_balances: Vec<Cw20Coin>,
+    #[serde(default)]
+    pub mint: Option<MinterResponse>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecuteMsg {
+    Transfer {
+        recipient: String,
+        amount: Uint128,
+    },
+    Burn {
+        amount: Uint128,
+    },
+    /// Transfer `amount` to `contract` and notify it with a
+    /// [Cw20ReceiveMsg] carrying `msg`.
+    Send {
+        contract: String,
+        amount: Uint128,
+        msg: Binary,
+    },
+    IncreaseAllowance {
+        spender: String,
+        amount: Uint128,
+        #[serde(default)]
+        expires: Option<Expiration>,
+    },
+    DecreaseAllowance {
+        spender: String,
+        amount: Uint128,
+        #[serde(default)]
+        expires: Option<Expiration>,
+    },
+    TransferFrom {
+        owner: String,
+        recipient: String,
+        amount: Uint128,
+    },
+    SendFrom {
+        owner: String,
+        contract: String,
+        amount: Uint128,
+        msg: Binary,
+    },
+    BurnFrom {
+        owner: String,
+        amount: Uint128,
+    },
+    Mint {
+        recipient: String,
+        amount: Uint128,
+    },
+    /// Hand minting to `new_minter`, or disable it for good.
+    UpdateMinter {
+        new_minter: Option<String>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMsg {
+    Balance {
+        address: String,
+    },
+    TokenInfo {},
+    Minter {},
+    Allowance {
+        owner: String,
+        spender: String,
+    },
+    AllAccounts {
+        #[serde(default)]
+        start_after: Option<String>,
+        #[serde(default)]
+        limit: Option<u32>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct BalanceResponse {
+    pub balance: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct TokenInfoResponse {
+    pub name: String,
+    pub symbol: String,
+    pub decimals: u8,
+    pub total_supply: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct AllowanceResponse {
+    pub allowance: Uint128,
+    pub expires: Expiration,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct AllAccountsResponse {
+    pub accounts: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum QueryResp {
+    Balance(BalanceResponse),
+    TokenInfo(TokenInfoResponse),
+    Minter(Option<MinterResponse>),
+    Allowance(AllowanceResponse),
+    AllAccounts(AllAccountsResponse),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+struct MinterData {
+    minter: Addr,
+    cap: Option<Uint128>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+struct TokenInfo {
+    name: String,
+    symbol: String,
+    decimals: u8,
+    total_supply: Uint128,
+    mint: Option<MinterData>,
+}
+
+/// A module implementing a cw20 token.
+#[derive(Clone, Debug, Default)]
+pub struct Cw20 {}
+
+impl Cw20 {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+fn token_info_key() -> Vec<u8> {
+    storage::key(&[NAMESPACE], b"token_info")
+}
+
+fn balance_key(address: &Addr) -> Vec<u8> {
+    storage::key(&[NAMESPACE, b"balances"], address.as_bytes())
+}
+
+fn allowance_key(owner: &Addr, spender: &Addr) -> Vec<u8> {
+    storage::key(&[NAMESPACE, b"allowances", owner.as_bytes()], spender.as_bytes())
+}
+
+fn token_info(storage: &dyn Storage) -> StdResult<TokenInfo> {
+    storage::load(storage, &token_info_key())?.ok_or_else(|| StdError::not_found("cw20 token"))
+}
+
+/// The token balance of `address`.
+pub fn balance(storage: &dyn Storage, address: &Addr) -> StdResult<Uint128> {
+    Ok(storage::load(storage, &balance_key(address))?.unwrap_or_default())
+}
+
+/// The total supply of the token.
+pub fn total_supply(storage: &dyn Storage) -> StdResult<Uint128> {
+    Ok(token_info(storage)?.total_supply)
+}
+
+fn set_balance(storage: &mut dyn Storage, address: &Addr, amount: Uint128) -> StdResult<()> {
+    if amount.is_zero() {
+        storage::remove(storage, &balance_key(address));
+        Ok(())
+    } else {
+        storage::save(storage, &balance_key(address), &amount)
+    }
+}
+
+/// Move `amount` tokens from `from` to `to`.
+pub fn transfer(
+    storage: &mut dyn Storage,
+    from: &Addr,
+    to: &Addr,
+    amount: Uint128,
+) -> StdResult<()> {
+    let from_balance = balance(storage, from)?.checked_sub(amount)?;
+    set_balance(storage, from, from_balance)?;
+    let to_balance = balance(storage, to)?.checked_add(amount)?;
+    set_balance(storage, to, to_balance)
+}
+
+fn burn(storage: &mut dyn Storage, from: &Addr, amount: Uint128) -> StdResult<()> {
+    let remaining = balance(storage, from)?.checked_sub(amount)?;
+    set_balance(storage, from, remaining)?;
+    let mut info = token_info(storage)?;
+    info.total_supply = info.total_supply.checked_sub(amount)?;
+    storage::save(storage, &token_info_key(), &info)
+}
+
+/// Load the allowance `spender` has over the tokens of `owner`.
+pub fn allowance(
+    storage: &dyn Storage,
+    owner: &Addr,
+    spender: &Addr,
+) -> StdResult<AllowanceResponse> {
+    Ok(storage::load(storage, &allowance_key(owner, spender))?.unwrap_or_default())
+}
+
+fn set_allowance(
+    storage: &mut dyn Storage,
+    owner: &Addr,
+    spender: &Addr,
+    allowance: &AllowanceResponse,
+) -> StdResult<()> {
+    if allowance.allowance.is_zero() {
+        storage::remove(storage, &allowance_key(owner, spender));
+        Ok(())
+    } else {
+        storage::save(storage, &allowance_key(owner, spender), allowance)
+    }
+}
+
+/// Deduct `amount` from the allowance `spender` has over the tokens of
+/// `owner`.
+fn spend_allowance(
+    storage: &mut dyn Storage,
+    env: &Env,
+    owner: &Addr,
+    spender: &Addr,
+    amount: Uint128,
+) -> Result<(), Error> {
+    let mut current = allowance(storage, owner, spender)?;
+    if current.expires.is_expired(&env.block) {
+        return Err(StdError::generic_err("allowance is expired").into());
+    }
+    current.allowance = current.allowance.checked_sub(amount).map_err(StdError::from)?;
+    Ok(set_allowance(storage, owner, spender, &current)?)
+}
+
+fn validate_spender<Q: CustomQuery>(
+    deps: &DepsMut<Q>,
+    owner: &Addr,
+    spender: &str,
+) -> Result<Addr, Error> {
+    let spender = deps.api.addr_validate(spender)?;
+    if spender == *owner {
+        return Err(StdError::generic_err("cannot set an allowance on one's own tokens").into());
+    }
+    Ok(spender)
+}
+
+fn send_msg<C: Clone>(
+    resp: Response<C>,
+    sender: &Addr,
+    contract: &Addr,
+    amount: Uint128,
+    msg: Binary,
+) -> StdResult<Response<C>> {
+    let receive = Cw20ReceiveMsg {
+        sender: sender.to_string(),
+        amount,
+        msg,
+    };
+    Ok(resp.add_message(receive.into_cosmos_msg(contract)?))
+}
+
+impl<C: Clone, Q: CustomQuery> Module<C, Q> for Cw20 {
+    type InstantiateMsg = InstantiateMsg;
+    type ExecuteMsg = ExecuteMsg;
+    type QueryMsg = QueryMsg;
+    type QueryResp = QueryResp;
+    type Error = Error;
+
+    fn validate_instantiate(
+        &self,
+        _deps: &Deps<Q>,
+        _env: &Env,
+        _info: &MessageInfo,
+        msg: &InstantiateMsg,
+    ) -> Result<(), Error> {
+        if msg.decimals > 18 {
+            return Err(StdError::generic_err("decimals must not exceed 18").into());
+        }
+        if !(3..=12).contains(&msg.symbol.len()) {
+            return Err(StdError::generic_err("symbol must be 3 to 12 characters").into());
+        }
+        Ok(())
+    }
+
+    fn instantiate(
+        &mut self,
+        deps: &mut DepsMut<Q>,
+        _env: &Env,
+        _info: &MessageInfo,
+        msg: InstantiateMsg,
+    ) -> Result<Response<C>, Error> {
+        let mut total_supply = Uint128::zero();
+        for coin in &msg.initial_balances {
+            let address = deps.api.addr_validate(&coin.address)?;
+            let amount = balance(deps.storage, &address)?.checked_add(coin.amount);
+            set_balance(deps.storage, &address, amount.map_err(StdError::from)?)?;
+            total_supply = total_supply.checked_add(coin.amount).map_err(StdError::from)?;
+        }
+        let mint = match msg.mint {
+            Some(mint) => {
+                if matches!(mint.cap, Some(cap) if total_supply > cap) {
+                    return Err(StdError::generic_err("initial supply exceeds the cap").into());
+                }
+                Some(MinterData {
+                    minter: deps.api.addr_validate(&mint.minter)?,
+                    cap: mint.cap,
+                })
+            }
+            None => None,
+        };
+        let info = TokenInfo {
+            name: msg.name,
+            symbol: msg.symbol,
+            decimals: msg.decimals,
+            total_supply,
+            mint,
+        };
+        storage::save(deps.storage, &token_info_key(), &info)?;
+        Ok(Response::new()
+            .add_attribute("symbol", info.symbol)
+            .add_attribute("total_supply", total_supply.to_string()))
+    }
+
+    fn execute(
+        &mut self,
+        deps: &mut DepsMut<Q>,
+        env: Env,
+        info: MessageInfo,
+        msg: ExecuteMsg,
+    ) -> Result<Response<C>, Error> {
+        let sender = info.sender;
+        match msg {
+            ExecuteMsg::Transfer { recipient, amount } => {
+                let recipient = deps.api.addr_validate(&recipient)?;
+                transfer(deps.storage, &sender, &recipient, amount)?;
+                Ok(Response::new()
+                    .add_attribute("action", "transfer")
+                    .add_attribute("from", sender)
+                    .add_attribute("to", recipient)
+                    .add_attribute("amount", amount.to_string()))
+            }
+            ExecuteMsg::Burn { amount } => {
+                burn(deps.storage, &sender, amount)?;
+                Ok(Response::new()
+                    .add_attribute("action", "burn")
+                    .add_attribute("from", sender)
+                    .add_attribute("amount", amount.to_string()))
+            }
+            ExecuteMsg::Send {
+                contract,
+                amount,
+                msg,
+            } => {
+                let contract = deps.api.addr_validate(&contract)?;
+                transfer(deps.storage, &sender, &contract, amount)?;
+                let resp = Response::new()
+                    .add_attribute("action", "send")
+                    .add_attribute("from", &sender)
+                    .add_attribute("to", &contract)
+                    .add_attribute("amount", amount.to_string());
+                Ok(send_msg(resp, &sender, &contract, amount, msg)?)
+            }
+            ExecuteMsg::IncreaseAllowance {
+                spender,
+                amount,
+                expires,
+            } => {
+                let spender = validate_spender(deps, &sender, &spender)?;
+                let mut current = allowance(deps.storage, &sender, &spender)?;
+                current.allowance = current.allowance.checked_add(amount).map_err(StdError::from)?;
+                if let Some(expires) = expires {
+                    if expires.is_expired(&env.block) {
+                        return Err(StdError::generic_err("expiration is in the past").into());
+                    }
+                    current.expires = expires;
+                }
+                set_allowance(deps.storage, &sender, &spender, &current)?;
+                Ok(Response::new()
+                    .add_attribute("action", "increase_allowance")
+                    .add_attribute("owner", sender)
+                    .add_attribute("spender", spender)
+                    .add_attribute("amount", amount.to_string()))
+            }
+            ExecuteMsg::DecreaseAllowance {
+                spender,
+                amount,
+                expires,
+            } => {
+                let spender = validate_spender(deps, &sender, &spender)?;
+                let mut current = allowance(deps.storage, &sender, &spender)?;
+                current.allowance = current.allowance.saturating_sub(amount);
+                if let Some(expires) = expires {
+                    current.expires = expires;
+                }
+                set_allowance(deps.storage, &sender, &spender, &current)?;
+                Ok(Response::new()
+                    .add_attribute("action", "decrease_allowance")
+                    .add_attribute("owner", sender)
+                    .add_attribute("spender", spender)
+                    .add_attribute("amount", amount.to_string()))
+            }
+            ExecuteMsg::TransferFrom {
+                owner,
+                recipient,
+                amount,
+            } => {
+                let owner = deps.api.addr_validate(&owner)?;
+                let recipient = deps.api.addr_validate(&recipient)?;
+                spend_allowance(deps.storage, &env, &owner, &sender, amount)?;
+                transfer(deps.storage, &owner, &recipient, amount)?;
+                Ok(Response::new()
+                    .add_attribute("action", "transfer_from")
+                    .add_attribute("from", owner)
+                    .add_attribute("to", recipient)
+                    .add_attribute("by", sender)
+                    .add_attribute("amount", amount.to_string()))
+            }
+            ExecuteMsg::SendFrom {
+                owner,
+                contract,
+                amount,
+                msg,
+            } => {
+                let owner = deps.api.addr_validate(&owner)?;
+                let contract = deps.api.addr_validate(&contract)?;
+                spend_allowance(deps.storage, &env, &owner, &sender, amount)?;
+                transfer(deps.storage, &owner, &contract, amount)?;
+                let resp = Response::new()
+                    .add_attribute("action", "send_from")
+                    .add_attribute("from", &owner)
+                    .add_attribute("to", &contract)
+                    .add_attribute("by", &sender)
+                    .add_attribute("amount", amount.to_string());
+                Ok(send_msg(resp, &sender, &contract, amount, msg)?)
+            }
+            ExecuteMsg::BurnFrom { owner, amount } => {
+                let owner = deps.api.addr_validate(&owner)?;
+                spend_allowance(deps.storage, &env, &owner, &sender, amount)?;
+                burn(deps.storage, &owner, amount)?;
+                Ok(Response::new()
+                    .add_attribute("action", "burn_from")
+                    .add_attribute("from", owner)
+                    .add_attribute("by", sender)
+                    .add_attribute("amount", amount.to_string()))
+            }
+            ExecuteMsg::Mint { recipient, amount } => {
+                let mut info = token_info(deps.storage)?;
+                let cap = match &info.mint {
+                    Some(mint) if mint.minter == sender => mint.cap,
+                    _ => {
+                        return Err(Error::Unauthorized {
+                            sender: sender.to_string(),
+                        })
+                    }
+                };
+                info.total_supply = info.total_supply.checked_add(amount).map_err(StdError::from)?;
+                if matches!(cap, Some(cap) if info.total_supply > cap) {
+                    return Err(StdError::generic_err("minting exceeds the cap").into());
+                }
+                storage::save(deps.storage, &token_info_key(), &info)?;
+                let recipient = deps.api.addr_validate(&recipient)?;
+                let amount_after = balance(deps.storage, &recipient)?.checked_add(amount);
+                set_balance(deps.storage, &recipient, amount_after.map_err(StdError::from)?)?;
+                Ok(Response::new()
+                    .add_attribute("action", "mint")
+                    .add_attribute("to", recipient)
+                    .add_attribute("amount", amount.to_string()))
+            }
+            ExecuteMsg::UpdateMinter { new_minter } => {
+                let mut info = token_info(deps.storage)?;
+                let cap = match &info.mint {
+                    Some(mint) if mint.minter == sender => mint.cap,
+                    _ => {
+                        return Err(Error::Unauthorized {
+                            sender: sender.to_string(),
+                        })
+                    }
+                };
+                info.mint = match &new_minter {
+                    Some(minter) => Some(MinterData {
+                        minter: deps.api.addr_validate(minter)?,
+                        cap,
+                    }),
+                    None => None,
+                };
+                storage::save(deps.storage, &token_info_key(), &info)?;
+                Ok(Response::new()
+                    .add_attribute("action", "update_minter")
+                    .add_attribute("new_minter", new_minter.unwrap_or_default()))
+            }
+        }
+    }
+
+    fn query(&self, deps: &Deps<Q>, _env: Env, msg: QueryMsg) -> Result<QueryResp, Error> {
+        match msg {
+            QueryMsg::Balance { address } => {
+                let address = deps.api.addr_validate(&address)?;
+                Ok(QueryResp::Balance(BalanceResponse {
+                    balance: balance(deps.storage, &address)?,
+                }))
+            }
+            QueryMsg::TokenInfo {} => {
+                let info = token_info(deps.storage)?;
+                Ok(QueryResp::TokenInfo(TokenInfoResponse {
+                    name: info.name,
+                    symbol: info.symbol,
+                    decimals: info.decimals,
+                    total_supply: info.total_supply,
+                }))
+            }
+            QueryMsg::Minter {} => {
+                let mint = token_info(deps.storage)?.mint.map(|mint| MinterResponse {
+                    minter: mint.minter.to_string(),
+                    cap: mint.cap,
+                });
+                Ok(QueryResp::Minter(mint))
+            }
+            QueryMsg::Allowance { owner, spender } => {
+                let owner = deps.api.addr_validate(&owner)?;
+                let spender = deps.api.addr_validate(&spender)?;
+                Ok(QueryResp::Allowance(allowance(deps.storage, &owner, &spender)?))
+            }
+            QueryMsg::AllAccounts { start_after, limit } => {
+                let limit = pagination::limit(limit);
+                let balances = storage::range::<Uint128>(
+                    deps.storage,
+                    &[NAMESPACE, b"balances"],
+                    start_after.as_ref().map(|a| a.as_bytes()),
+                    limit,
+                )?;
+                let accounts = balances
+                    .into_iter()
+                    .map(|(key, _)| String::from_utf8_lossy(&key).into_owned())
+                    .collect();
+                Ok(QueryResp::AllAccounts(AllAccountsResponse { accounts }))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{addr, execute, instantiate, MockDeps};
+    use cosmwasm_std::testing::mock_dependencies;
+    use cosmwasm_std::{from_json, CosmosMsg, WasmMsg};
+
+    fn setup() -> MockDeps {
+        let mut deps = mock_dependencies();
+        let msg = InstantiateMsg {
+            name: "Token".to_string(),
+            symbol: "TKN".to_string(),
+            decimals: 6,
+            initial_balances: vec![Cw20Coin {
+                address: addr("alice").to_string(),
+                amount: Uint128::new(100),
+            }],
+            mint: None,
+        };
+        instantiate::<Cw20>(&mut deps, "creator", msg).unwrap();
+        deps
+    }
+
+    fn balance_of(deps: &MockDeps, address: &str) -> u128 {
+        balance(&deps.storage, &addr(address))
+            .unwrap()
+            .u128()
+    }
+
+    #[test]
+    fn transfer_moves_balance() {
+        let mut deps = setup();
+        let msg = ExecuteMsg::Transfer {
+            recipient: addr("bob").to_string(),
+            amount: Uint128::new(30),
+        };
+        execute::<Cw20>(&mut deps, "alice", msg).unwrap();
+        assert_eq!(balance_of(&deps, "alice"), 70);
+        assert_eq!(balance_of(&deps, "bob"), 30);
+        assert_eq!(token_info(&deps.storage).unwrap().total_supply.u128(), 100);
+    }
+
+    #[test]
+    fn transfer_beyond_balance_fails() {
+        let mut deps = setup();
+        let msg = ExecuteMsg::Transfer {
+            recipient: addr("bob").to_string(),
+            amount: Uint128::new(101),
+        };
+        execute::<Cw20>(&mut deps, "alice", msg).unwrap_err();
+        assert_eq!(balance_of(&deps, "alice"), 100);
+        assert_eq!(balance_of(&deps, "bob"), 0);
+    }
+
+    #[test]
+    fn burn_reduces_supply() {
+        let mut deps = setup();
+        let msg = ExecuteMsg::Burn {
+            amount: Uint128::new(40),
+        };
+        execute::<Cw20>(&mut deps, "alice", msg).unwrap();
+        assert_eq!(balance_of(&deps, "alice"), 60);
+        assert_eq!(token_info(&deps.storage).unwrap().total_supply.u128(), 60);
+
+        let msg = ExecuteMsg::Burn {
+            amount: Uint128::new(61),
+        };
+        execute::<Cw20>(&mut deps, "alice", msg).unwrap_err();
+        assert_eq!(token_info(&deps.storage).unwrap().total_supply.u128(), 60);
+    }
+
+    #[test]
+    fn send_notifies_contract() {
+        let mut deps = setup();
+        let msg = ExecuteMsg::Send {
+            contract: addr("market").to_string(),
+            amount: Uint128::new(25),
+            msg: Binary::from(b"{}".as_slice()),
+        };
+        let resp = execute::<Cw20>(&mut deps, "alice", msg).unwrap();
+        assert_eq!(balance_of(&deps, "market"), 25);
+        let receive = match &resp.response.messages[0].msg {
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr, msg, ..
+            }) if contract_addr == addr("market").as_str() => msg.clone(),
+            msg => panic!("unexpected message {:?}", msg),
+        };
+        let receive: serde_json::Value = from_json(receive).unwrap();
+        assert_eq!(receive["receive"]["sender"], addr("alice").as_str());
+        assert_eq!(receive["receive"]["amount"], "25");
+    }
+}
@@ -0,0 +1,635 @@
+//! A cw721 NFT collection as a module.
+//!
+//! [Cw721] implements the core messages and queries of `cw721-base`, so an
+//! NFT collection can live in a composed contract alongside, e.g., sale and
+//! [royalties][crate::modules::royalties] modules. Messages use the cw721
+//! format inside the glue envelope:
+//!
+//! ```javascript
+//! { "nft": { "transfer_nft": { "recipient": "...", "token_id": "1" } } }
+//! ```
+//!
+//! Token metadata beyond the `token_uri` goes into the free-form JSON
+//! `extension`. Collection state lives in a fixed namespace, so a contract
+//! holds at most one [Cw721] module. Sibling modules can look up owners
+//! directly with [owner_of].
+
+use crate::error::Error;
+use crate::module::Module;
+use crate::modules::expiration::Expiration;
+use crate::receive::Cw721ReceiveMsg;
+use crate::response::Response;
+use crate::storage;
+use cosmwasm_std::{
+    Addr, Binary, BlockInfo, CustomQuery, Deps, DepsMut, Env, MessageInfo, StdError, StdResult,
+    Storage, Uint64,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+const NAMESPACE: &[u8] = b"cw721";
+const DEFAULT_LIMIT: u32 = 10;
+const MAX_LIMIT: u32 = 100;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct InstantiateMsg {
+    pub name: String,
+    pub symbol: String,
+    /// The account allowed to mint. Defaults to the instantiating account.
+    #[serde(default)]
+    pub minter: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecuteMsg {
+    TransferNft {
+        recipient: String,
+        token_id: String,
+    },
+    /// Transfer `token_id` to `contract` and notify it with a
+    /// [Cw721ReceiveMsg] carrying `msg`.
+    SendNft {
+        contract: String,
+        token_id: String,
+        msg: Binary,
+    },
+    /// Allow `spender` to transfer or send `token_id`.
+    Approve {
+        spender: String,
+        token_id: String,
+        #[serde(default)]
+        expires: Option<Expiration>,
+    },
+    Revoke {
+        spender: String,
+        token_id: String,
+    },
+    /// Allow `operator` to transfer, send, and approve all of the sender's
+    /// tokens.
+    ApproveAll {
+        operator: String,
+        #[serde(default)]
+        expires: Option<Expiration>,
+    },
+    RevokeAll {
+        operator: String,
+    },
+    Mint {
+        token_id: String,
+        owner: String,
+        #[serde(default)]
+        token_uri: Option<String>,
+        #[serde(default)]
+        extension: Value,
+    },
+    Burn {
+        token_id: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMsg {
+    OwnerOf {
+        token_id: String,
+        #[serde(default)]
+        include_expired: Option<bool>,
+    },
+    NftInfo {
+        token_id: String,
+    },
+    ContractInfo {},
+    NumTokens {},
+    Minter {},
+    /// List the tokens owned by `owner`.
+    Tokens {
+        owner: String,
+        #[serde(default)]
+        start_after: Option<String>,
+        #[serde(default)]
+        limit: Option<u32>,
+    },
+    AllTokens {
+        #[serde(default)]
+        start_after: Option<String>,
+        #[serde(default)]
+        limit: Option<u32>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Approval {
+    pub spender: String,
+    pub expires: Expiration,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct OwnerOfResponse {
+    pub owner: String,
+    pub approvals: Vec<Approval>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct NftInfoResponse {
+    pub token_uri: Option<String>,
+    pub extension: Value,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ContractInfoResponse {
+    pub name: String,
+    pub symbol: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct NumTokensResponse {
+    pub count: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct MinterResponse {
+    pub minter: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct TokensResponse {
+    pub tokens: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum QueryResp {
+    OwnerOf(OwnerOfResponse),
+    NftInfo(NftInfoResponse),
+    ContractInfo(ContractInfoResponse),
+    NumTokens(NumTokensResponse),
+    Minter(MinterResponse),
+    Tokens(TokensResponse),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+struct CollectionInfo {
+    name: String,
+    symbol: String,
+    minter: Addr,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+struct Token {
+    owner: Addr,
+    approvals: Vec<(Addr, Expiration)>,
+    token_uri: Option<String>,
+    extension: Value,
+}
+
+/// A module implementing a cw721 collection.
+#[derive(Clone, Debug, Default)]
+pub struct Cw721 {}
+
+impl Cw721 {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+fn info_key() -> Vec<u8> {
+    storage::key(&[NAMESPACE], b"info")
+}
+
+fn count_key() -> Vec<u8> {
+    storage::key(&[NAMESPACE], b"num_tokens")
+}
+
+fn token_key(token_id: &str) -> Vec<u8> {
+    storage::key(&[NAMESPACE, b"tokens"], token_id.as_bytes())
+}
+
+fn owned_key(owner: &Addr, token_id: &str) -> Vec<u8> {
+    storage::key(&[NAMESPACE, b"owned", owner.as_bytes()], token_id.as_bytes())
+}
+
+fn operator_key(owner: &Addr, operator: &Addr) -> Vec<u8> {
+    storage::key(&[NAMESPACE, b"operators", owner.as_bytes()], operator.as_bytes())
+}
+
+fn collection_info(storage: &dyn Storage) -> StdResult<CollectionInfo> {
+    storage::load(storage, &info_key())?.ok_or_else(|| StdError::not_found("cw721 collection"))
+}
+
+fn num_tokens(storage: &dyn Storage) -> StdResult<u64> {
+    Ok(storage::load(storage, &count_key())?.unwrap_or_default())
+}
+
+fn increment_num_tokens(storage: &mut dyn Storage) -> StdResult<()> {
+    let updated = Uint64::new(num_tokens(storage)?).checked_add(Uint64::one())?;
+    storage::save(storage, &count_key(), &updated.u64())
+}
+
+fn decrement_num_tokens(storage: &mut dyn Storage) -> StdResult<()> {
+    let updated = Uint64::new(num_tokens(storage)?).checked_sub(Uint64::one())?;
+    storage::save(storage, &count_key(), &updated.u64())
+}
+
+fn load_token(storage: &dyn Storage, token_id: &str) -> StdResult<Token> {
+    storage::load(storage, &token_key(token_id))?
+        .ok_or_else(|| StdError::not_found(format!("token {:?}", token_id)))
+}
+
+/// The owner of `token_id`, if it exists.
+pub fn owner_of(storage: &dyn Storage, token_id: &str) -> StdResult<Option<Addr>> {
+    Ok(storage::load::<Token>(storage, &token_key(token_id))?.map(|token| token.owner))
+}
+
+fn is_operator(
+    storage: &dyn Storage,
+    block: &BlockInfo,
+    owner: &Addr,
+    operator: &Addr,
+) -> StdResult<bool> {
+    let expires: Option<Expiration> = storage::load(storage, &operator_key(owner, operator))?;
+    Ok(matches!(expires, Some(expires) if !expires.is_expired(block)))
+}
+
+/// Fail unless `sender` may approve others to move `token`.
+fn check_can_approve(
+    storage: &dyn Storage,
+    block: &BlockInfo,
+    token: &Token,
+    sender: &Addr,
+) -> Result<(), Error> {
+    if token.owner == *sender || is_operator(storage, block, &token.owner, sender)? {
+        Ok(())
+    } else {
+        Err(Error::Unauthorized {
+            sender: sender.to_string(),
+        })
+    }
+}
+
+/// Fail unless `sender` may move `token`.
+fn check_can_send(
+    storage: &dyn Storage,
+    block: &BlockInfo,
+    token: &Token,
+    sender: &Addr,
+) -> Result<(), Error> {
+    let approved = token
+        .approvals
+        .iter()
+        .any(|(spender, expires)| spender == sender && !expires.is_expired(block));
+    if approved {
+        return Ok(());
+    }
+    check_can_approve(storage, block, token, sender)
+}
+
+/// Move `token_id` to `recipient`, clearing its approvals.
+fn transfer(
+    storage: &mut dyn Storage,
+    block: &BlockInfo,
+    sender: &Addr,
+    recipient: &Addr,
+    token_id: &str,
+) -> Result<(), Error> {
+    let mut token = load_token(storage, token_id)?;
+    check_can_send(storage, block, &token, sender)?;
+    storage::remove(storage, &owned_key(&token.owner, token_id));
+    storage::save(storage, &owned_key(recipient, token_id), &true)?;
+    token.owner = recipient.clone();
+    token.approvals.clear();
+    Ok(storage::save(storage, &token_key(token_id), &token)?)
+}
+
+fn range_ids(
+    storage: &dyn Storage,
+    namespaces: &[&[u8]],
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<Vec<String>> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let entries = storage::range::<Value>(
+        storage,
+        namespaces,
+        start_after.as_ref().map(|id| id.as_bytes()),
+        limit,
+    )?;
+    Ok(entries
+        .into_iter()
+        .map(|(key, _)| String::from_utf8_lossy(&key).into_owned())
+        .collect())
+}
+
+impl<C: Clone, Q: CustomQuery> Module<C, Q> for Cw721 {
+    type InstantiateMsg = InstantiateMsg;
+    type ExecuteMsg = ExecuteMsg;
+    type QueryMsg = QueryMsg;
+    type QueryResp = QueryResp;
+    type Error = Error;
+
+    fn instantiate(
+        &mut self,
+        deps: &mut DepsMut<Q>,
+        _env: &Env,
+        info: &MessageInfo,
+        msg: InstantiateMsg,
+    ) -> Result<Response<C>, Error> {
+        let minter = match msg.minter {
+            Some(minter) => deps.api.addr_validate(&minter)?,
+            None => info.sender.clone(),
+        };
+        let info = CollectionInfo {
+            name: msg.name,
+            symbol: msg.symbol,
+            minter,
+        };
+        storage::save(deps.storage, &info_key(), &info)?;
+        Ok(Response::new()
+            .add_attribute("symbol", info.symbol)
+            .add_attribute("minter", info.minter))
+    }
+
+    fn execute(
+        &mut self,
+        deps: &mut DepsMut<Q>,
+        env: Env,
+        info: MessageInfo,
+        msg: ExecuteMsg,
+    ) -> Result<Response<C>, Error> {
+        let sender = info.sender;
+        match msg {
+            ExecuteMsg::TransferNft {
+                recipient,
+                token_id,
+            } => {
+                let recipient = deps.api.addr_validate(&recipient)?;
+                transfer(deps.storage, &env.block, &sender, &recipient, &token_id)?;
+                Ok(Response::new()
+                    .add_attribute("action", "transfer_nft")
+                    .add_attribute("sender", sender)
+                    .add_attribute("recipient", recipient)
+                    .add_attribute("token_id", token_id))
+            }
+            ExecuteMsg::SendNft {
+                contract,
+                token_id,
+                msg,
+            } => {
+                let contract = deps.api.addr_validate(&contract)?;
+                transfer(deps.storage, &env.block, &sender, &contract, &token_id)?;
+                let receive = Cw721ReceiveMsg {
+                    sender: sender.to_string(),
+                    token_id: token_id.clone(),
+                    msg,
+                };
+                Ok(Response::new()
+                    .add_message(receive.into_cosmos_msg(&contract)?)
+                    .add_attribute("action", "send_nft")
+                    .add_attribute("sender", sender)
+                    .add_attribute("recipient", contract)
+                    .add_attribute("token_id", token_id))
+            }
+            ExecuteMsg::Approve {
+                spender,
+                token_id,
+                expires,
+            } => {
+                let spender = deps.api.addr_validate(&spender)?;
+                let mut token = load_token(deps.storage, &token_id)?;
+                check_can_approve(deps.storage, &env.block, &token, &sender)?;
+                let expires = expires.unwrap_or_default();
+                if expires.is_expired(&env.block) {
+                    return Err(StdError::generic_err("expiration is in the past").into());
+                }
+                token.approvals.retain(|(approved, _)| *approved != spender);
+                token.approvals.push((spender.clone(), expires));
+                storage::save(deps.storage, &token_key(&token_id), &token)?;
+                Ok(Response::new()
+                    .add_attribute("action", "approve")
+                    .add_attribute("spender", spender)
+                    .add_attribute("token_id", token_id))
+            }
+            ExecuteMsg::Revoke { spender, token_id } => {
+                let spender = deps.api.addr_validate(&spender)?;
+                let mut token = load_token(deps.storage, &token_id)?;
+                check_can_approve(deps.storage, &env.block, &token, &sender)?;
+                token.approvals.retain(|(approved, _)| *approved != spender);
+                storage::save(deps.storage, &token_key(&token_id), &token)?;
+                Ok(Response::new()
+                    .add_attribute("action", "revoke")
+                    .add_attribute("spender", spender)
+                    .add_attribute("token_id", token_id))
+            }
+            ExecuteMsg::ApproveAll { operator, expires } => {
+                let operator = deps.api.addr_validate(&operator)?;
+                let expires = expires.unwrap_or_default();
+                if expires.is_expired(&env.block) {
+                    return Err(StdError::generic_err("expiration is in the past").into());
+                }
+                storage::save(deps.storage, &operator_key(&sender, &operator), &expires)?;
+                Ok(Response::new()
+                    .add_attribute("action", "approve_all")
+                    .add_attribute("operator", operator))
+            }
+            ExecuteMsg::RevokeAll { operator } => {
+                let operator = deps.api.addr_validate(&operator)?;
+                storage::remove(deps.storage, &operator_key(&sender, &operator));
+                Ok(Response::new()
+                    .add_attribute("action", "revoke_all")
+                    .add_attribute("operator", operator))
+            }
+            ExecuteMsg::Mint {
+                token_id,
+                owner,
+                token_uri,
+                extension,
+            } => {
+                if collection_info(deps.storage)?.minter != sender {
+                    return Err(Error::Unauthorized {
+                        sender: sender.to_string(),
+                    });
+                }
+                if owner_of(deps.storage, &token_id)?.is_some() {
+                    return Err(StdError::generic_err(format!(
+                        "token {:?} already exists",
+                        token_id
+                    ))
+                    .into());
+                }
+                let owner = deps.api.addr_validate(&owner)?;
+                let token = Token {
+                    owner: owner.clone(),
+                    approvals: vec![],
+                    token_uri,
+                    extension,
+                };
+                storage::save(deps.storage, &token_key(&token_id), &token)?;
+                storage::save(deps.storage, &owned_key(&owner, &token_id), &true)?;
+                increment_num_tokens(deps.storage)?;
+                Ok(Response::new()
+                    .add_attribute("action", "mint")
+                    .add_attribute("owner", owner)
+                    .add_attribute("token_id", token_id))
+            }
+            ExecuteMsg::Burn { token_id } => {
+                let token = load_token(deps.storage, &token_id)?;
+                check_can_send(deps.storage, &env.block, &token, &sender)?;
+                storage::remove(deps.storage, &token_key(&token_id));
+                storage::remove(deps.storage, &owned_key(&token.owner, &token_id));
+                decrement_num_tokens(deps.storage)?;
+                Ok(Response::new()
+                    .add_attribute("action", "burn")
+                    .add_attribute("token_id", token_id))
+            }
+        }
+    }
+
+    fn query(&self, deps: &Deps<Q>, env: Env, msg: QueryMsg) -> Result<QueryResp, Error> {
+        match msg {
+            QueryMsg::OwnerOf {
+                token_id,
+                include_expired,
+            } => {
+                let token = load_token(deps.storage, &token_id)?;
+                let include_expired = include_expired.unwrap_or(false);
+                let approvals = token
+                    .approvals
+                    .into_iter()
+                    .filter(|(_, expires)| include_expired || !expires.is_expired(&env.block))
+                    .map(|(spender, expires)| Approval {
+                        spender: spender.to_string(),
+                        expires,
+                    })
+                    .collect();
+                Ok(QueryResp::OwnerOf(OwnerOfResponse {
+                    owner: token.owner.to_string(),
+                    approvals,
+                }))
+            }
+            QueryMsg::NftInfo { token_id } => {
+                let token = load_token(deps.storage, &token_id)?;
+                Ok(QueryResp::NftInfo(NftInfoResponse {
+                    token_uri: token.token_uri,
+                    extension: token.extension,
+                }))
+            }
+            QueryMsg::ContractInfo {} => {
+                let info = collection_info(deps.storage)?;
+                Ok(QueryResp::ContractInfo(ContractInfoResponse {
+                    name: info.name,
+                    symbol: info.symbol,
+                }))
+            }
+            QueryMsg::NumTokens {} => Ok(QueryResp::NumTokens(NumTokensResponse {
+                count: num_tokens(deps.storage)?,
+            })),
+            QueryMsg::Minter {} => Ok(QueryResp::Minter(MinterResponse {
+                minter: collection_info(deps.storage)?.minter.to_string(),
+            })),
+            QueryMsg::Tokens {
+                owner,
+                start_after,
+                limit,
+            } => {
+                let owner = deps.api.addr_validate(&owner)?;
+                let tokens = range_ids(
+                    deps.storage,
+                    &[NAMESPACE, b"owned", owner.as_bytes()],
+                    start_after,
+                    limit,
+                )?;
+                Ok(QueryResp::Tokens(TokensResponse { tokens }))
+            }
+            QueryMsg::AllTokens { start_after, limit } => {
+                let tokens = range_ids(deps.storage, &[NAMESPACE, b"tokens"], start_after, limit)?;
+                Ok(QueryResp::Tokens(TokensResponse { tokens }))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{addr, execute, instantiate, MockDeps};
+    use cosmwasm_std::testing::mock_dependencies;
+
+    fn setup() -> MockDeps {
+        let mut deps = mock_dependencies();
+        let msg = InstantiateMsg {
+            name: "Collection".to_string(),
+            symbol: "NFT".to_string(),
+            minter: None,
+        };
+        instantiate::<Cw721>(&mut deps, "minter", msg).unwrap();
+        deps
+    }
+
+    fn mint(deps: &mut MockDeps, token_id: &str, owner: &str) {
+        let msg = ExecuteMsg::Mint {
+            token_id: token_id.to_string(),
+            owner: addr(owner).to_string(),
+            token_uri: None,
+            extension: Value::Null,
+        };
+        execute::<Cw721>(deps, "minter", msg).unwrap();
+    }
+
+    fn owner(deps: &MockDeps, token_id: &str) -> Option<Addr> {
+        owner_of(&deps.storage, token_id).unwrap()
+    }
+
+    #[test]
+    fn only_minter_mints() {
+        let mut deps = setup();
+        let msg = ExecuteMsg::Mint {
+            token_id: "1".to_string(),
+            owner: addr("alice").to_string(),
+            token_uri: None,
+            extension: Value::Null,
+        };
+        execute::<Cw721>(&mut deps, "alice", msg).unwrap_err();
+        assert_eq!(num_tokens(&deps.storage).unwrap(), 0);
+        mint(&mut deps, "1", "alice");
+        assert_eq!(owner(&deps, "1"), Some(addr("alice")));
+        assert_eq!(num_tokens(&deps.storage).unwrap(), 1);
+    }
+
+    #[test]
+    fn owner_transfers() {
+        let mut deps = setup();
+        mint(&mut deps, "1", "alice");
+        let transfer = |recipient: &str| ExecuteMsg::TransferNft {
+            recipient: addr(recipient).to_string(),
+            token_id: "1".to_string(),
+        };
+        execute::<Cw721>(&mut deps, "bob", transfer("bob")).unwrap_err();
+        assert_eq!(owner(&deps, "1"), Some(addr("alice")));
+        execute::<Cw721>(&mut deps, "alice", transfer("bob")).unwrap();
+        assert_eq!(owner(&deps, "1"), Some(addr("bob")));
+    }
+
+    #[test]
+    fn burn_updates_count() {
+        let mut deps = setup();
+        mint(&mut deps, "1", "alice");
+        mint(&mut deps, "2", "alice");
+        let burn = || ExecuteMsg::Burn {
+            token_id: "1".to_string(),
+        };
+        execute::<Cw721>(&mut deps, "bob", burn()).unwrap_err();
+        assert_eq!(num_tokens(&deps.storage).unwrap(), 2);
+        execute::<Cw721>(&mut deps, "alice", burn()).unwrap();
+        assert_eq!(owner(&deps, "1"), None);
+        assert_eq!(num_tokens(&deps.storage).unwrap(), 1);
+        execute::<Cw721>(&mut deps, "alice", burn()).unwrap_err();
+        assert_eq!(num_tokens(&deps.storage).unwrap(), 1);
+    }
+
+    #[test]
+    fn count_does_not_underflow() {
+        let mut deps = setup();
+        decrement_num_tokens(&mut deps.storage).unwrap_err();
+        assert_eq!(num_tokens(&deps.storage).unwrap(), 0);
+    }
+}
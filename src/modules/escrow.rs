@@ -0,0 +1,563 @@
+//! Native token escrow.
+//!
+//! [Escrow] holds the funds sent along with a `create` message until they are
+//! either released to the recipient or refunded to the depositor. How they
+//! are released is chosen per escrow:
+//!
+//! - `approval`: the approver, e.g. the buyer on delivery, releases the funds.
+//! - `arbiter`: the arbiter either releases or refunds the funds.
+//! - `timelock`: anyone can release the funds once `release_at` has passed.
+//!
+//! ```javascript
+//! { "escrow": { "create": {
+//!     "id": "order-42", "recipient": "...", "condition": { "arbiter": { "arbiter": "..." } }
+//! } } }
+//! { "escrow": { "release": { "id": "order-42" } } }
+//! ```
+//!
+//! The recipient can always decline, refunding the depositor. If an escrow
+//! has an `expires_at` time and is still open after it, anyone can refund it.
+//! Times are block times in seconds.
+//!
+//! An escrow holds the funds the module sees, i.e. the module's share of a
+//! [batch][crate::manager::BATCH_KEY], less any fee a middleware took.
+//! Escrows are kept under the name the module is registered under, so
+//! several instances of the module hold separate escrows. Driven without the
+//! dispatch context, e.g. by a [static manager][crate::static_manager], the
+//! module keeps its escrows in a namespace of its own instead, apart from the
+//! state of every registered module.
+
+use crate::context::Context;
+use crate::error::Error;
+use crate::module::Module;
+use crate::pagination;
+use crate::response::Response;
+use crate::storage::{self, Map};
+use cosmwasm_std::{
+    Addr, BankMsg, Coin, CustomQuery, Deps, DepsMut, Env, MessageInfo, StdError, StdResult, Storage,
+};
+use serde::{Deserialize, Serialize};
+
+const ESCROWS: Map<String, EscrowState> = Map::new("escrows");
+
+/// The namespace of the escrows of a module driven without the dispatch
+/// context.
+const NAMESPACE: &[u8] = b"escrow";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct InstantiateMsg {}
+
+/// How the funds of an escrow are released.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Condition<A = Addr> {
+    /// `approver` releases the funds.
+    Approval { approver: A },
+    /// `arbiter` releases or refunds the funds.
+    Arbiter { arbiter: A },
+    /// Anyone may release the funds from `release_at`.
+    Timelock { release_at: u64 },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecuteMsg {
+    /// Escrow the funds sent with the message under `id`.
+    Create {
+        id: String,
+        recipient: String,
+        condition: Condition<String>,
+        #[serde(default)]
+        expires_at: Option<u64>,
+    },
+    /// Send the funds of escrow `id` to its recipient.
+    Release { id: String },
+    /// Return the funds of escrow `id` to its depositor.
+    Refund { id: String },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMsg {
+    Escrow {
+        id: String,
+    },
+    /// List the open escrows in order of their id.
+    Escrows {
+        #[serde(default)]
+        start_after: Option<String>,
+        #[serde(default)]
+        limit: Option<u32>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum QueryResp {
+    Escrow { escrow: Option<EscrowState> },
+    Escrows { escrows: Vec<EscrowState> },
+}
+
+/// An open escrow.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct EscrowState {
+    pub id: String,
+    pub depositor: Addr,
+    pub recipient: Addr,
+    pub funds: Vec<Coin>,
+    pub condition: Condition,
+    pub expires_at: Option<u64>,
+}
+
+/// A module escrowing native tokens.
+#[derive(Clone, Debug, Default)]
+pub struct Escrow {}
+
+impl Escrow {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// Load escrow `id` of the module `ctx` dispatches to, if it is still open.
+pub fn escrow<C, Q: CustomQuery>(
+    ctx: &Context<C, Q>,
+    storage: &dyn Storage,
+    id: &str,
+) -> StdResult<Option<EscrowState>> {
+    load(Some(ctx), storage, id)
+}
+
+/// The key of escrow `id` under [NAMESPACE].
+fn key(id: &str) -> Vec<u8> {
+    storage::key(&[NAMESPACE], id.as_bytes())
+}
+
+/// Load escrow `id` of the module `ctx` dispatches to or, without a context,
+/// the escrow kept under [NAMESPACE].
+fn load<C, Q: CustomQuery>(
+    ctx: Option<&Context<C, Q>>,
+    storage: &dyn Storage,
+    id: &str,
+) -> StdResult<Option<EscrowState>> {
+    match ctx {
+        Some(ctx) => ESCROWS.may_load(ctx, storage, &id.to_string()),
+        None => storage::load(storage, &key(id)),
+    }
+}
+
+fn save<C, Q: CustomQuery>(
+    ctx: Option<&Context<C, Q>>,
+    storage: &mut dyn Storage,
+    escrow: &EscrowState,
+) -> StdResult<()> {
+    match ctx {
+        Some(ctx) => ESCROWS.save(ctx, storage, &escrow.id, escrow),
+        None => storage::save(storage, &key(&escrow.id), escrow),
+    }
+}
+
+fn remove<C, Q: CustomQuery>(ctx: Option<&Context<C, Q>>, storage: &mut dyn Storage, id: &str) {
+    match ctx {
+        Some(ctx) => ESCROWS.remove(ctx, storage, &id.to_string()),
+        None => storage::remove(storage, &key(id)),
+    }
+}
+
+fn range<C, Q: CustomQuery>(
+    ctx: Option<&Context<C, Q>>,
+    storage: &dyn Storage,
+    start_after: Option<String>,
+    limit: usize,
+) -> StdResult<Vec<EscrowState>> {
+    let escrows = match ctx {
+        Some(ctx) => ESCROWS
+            .range(ctx, storage, start_after.as_ref(), limit)?
+            .into_iter()
+            .map(|(_, escrow)| escrow)
+            .collect(),
+        None => {
+            let start_after = start_after.as_ref().map(String::as_bytes);
+            storage::range(storage, &[NAMESPACE], start_after, limit)?
+                .into_iter()
+                .map(|(_, escrow)| escrow)
+                .collect()
+        }
+    };
+    Ok(escrows)
+}
+
+fn must_load<C, Q: CustomQuery>(
+    ctx: Option<&Context<C, Q>>,
+    storage: &dyn Storage,
+    id: &str,
+) -> Result<EscrowState, Error> {
+    load(ctx, storage, id)?.ok_or_else(|| StdError::not_found(format!("escrow {:?}", id)).into())
+}
+
+impl EscrowState {
+    fn may_release(&self, env: &Env, sender: &Addr) -> bool {
+        match &self.condition {
+            Condition::Approval { approver } => approver == sender,
+            Condition::Arbiter { arbiter } => arbiter == sender,
+            Condition::Timelock { release_at } => env.block.time.seconds() >= *release_at,
+        }
+    }
+
+    fn may_refund(&self, env: &Env, sender: &Addr) -> bool {
+        let expired = matches!(self.expires_at, Some(at) if env.block.time.seconds() >= at);
+        let arbiter = match &self.condition {
+            Condition::Arbiter { arbiter } => arbiter == sender,
+            _ => false,
+        };
+        expired || arbiter || self.recipient == *sender
+    }
+}
+
+fn execute_in<C: Clone, Q: CustomQuery>(
+    ctx: Option<&Context<C, Q>>,
+    deps: &mut DepsMut<Q>,
+    env: Env,
+    info: MessageInfo,
+    msg: ExecuteMsg,
+) -> Result<Response<C>, Error> {
+    match msg {
+        ExecuteMsg::Create {
+            id,
+            recipient,
+            condition,
+            expires_at,
+        } => {
+            if info.funds.is_empty() {
+                return Err(StdError::generic_err("escrow requires funds").into());
+            }
+            if load(ctx, deps.storage, &id)?.is_some() {
+                return Err(StdError::generic_err(format!("escrow {:?} exists", id)).into());
+            }
+            let condition = match condition {
+                Condition::Approval { approver } => Condition::Approval {
+                    approver: deps.api.addr_validate(&approver)?,
+                },
+                Condition::Arbiter { arbiter } => Condition::Arbiter {
+                    arbiter: deps.api.addr_validate(&arbiter)?,
+                },
+                Condition::Timelock { release_at } => Condition::Timelock { release_at },
+            };
+            let escrow = EscrowState {
+                id: id.clone(),
+                depositor: info.sender,
+                recipient: deps.api.addr_validate(&recipient)?,
+                funds: info.funds,
+                condition,
+                expires_at,
+            };
+            save(ctx, deps.storage, &escrow)?;
+            Ok(Response::new()
+                .add_attribute("action", "create")
+                .add_attribute("id", id))
+        }
+        ExecuteMsg::Release { id } => {
+            let escrow = must_load(ctx, deps.storage, &id)?;
+            if !escrow.may_release(&env, &info.sender) {
+                return Err(Error::Unauthorized {
+                    sender: info.sender.to_string(),
+                });
+            }
+            remove(ctx, deps.storage, &id);
+            Ok(Response::new()
+                .add_message(BankMsg::Send {
+                    to_address: escrow.recipient.to_string(),
+                    amount: escrow.funds,
+                })
+                .add_attribute("action", "release")
+                .add_attribute("id", id))
+        }
+        ExecuteMsg::Refund { id } => {
+            let escrow = must_load(ctx, deps.storage, &id)?;
+            if !escrow.may_refund(&env, &info.sender) {
+                return Err(Error::Unauthorized {
+                    sender: info.sender.to_string(),
+                });
+            }
+            remove(ctx, deps.storage, &id);
+            Ok(Response::new()
+                .add_message(BankMsg::Send {
+                    to_address: escrow.depositor.to_string(),
+                    amount: escrow.funds,
+                })
+                .add_attribute("action", "refund")
+                .add_attribute("id", id))
+        }
+    }
+}
+
+fn query_in<C, Q: CustomQuery>(
+    ctx: Option<&Context<C, Q>>,
+    deps: &Deps<Q>,
+    msg: QueryMsg,
+) -> Result<QueryResp, Error> {
+    match msg {
+        QueryMsg::Escrow { id } => Ok(QueryResp::Escrow {
+            escrow: load(ctx, deps.storage, &id)?,
+        }),
+        QueryMsg::Escrows { start_after, limit } => Ok(QueryResp::Escrows {
+            escrows: range(ctx, deps.storage, start_after, pagination::limit(limit))?,
+        }),
+    }
+}
+
+impl<C: Clone, Q: CustomQuery> Module<C, Q> for Escrow {
+    type InstantiateMsg = InstantiateMsg;
+    type ExecuteMsg = ExecuteMsg;
+    type QueryMsg = QueryMsg;
+    type QueryResp = QueryResp;
+    type Error = Error;
+
+    fn instantiate(
+        &mut self,
+        _deps: &mut DepsMut<Q>,
+        _env: &Env,
+        _info: &MessageInfo,
+        _msg: InstantiateMsg,
+    ) -> Result<Response<C>, Error> {
+        Ok(Response::new())
+    }
+
+    fn execute(
+        &mut self,
+        deps: &mut DepsMut<Q>,
+        env: Env,
+        info: MessageInfo,
+        msg: ExecuteMsg,
+    ) -> Result<Response<C>, Error> {
+        execute_in(None, deps, env, info, msg)
+    }
+
+    fn execute_with_context(
+        &mut self,
+        ctx: &Context<C, Q>,
+        deps: &mut DepsMut<Q>,
+        env: Env,
+        info: MessageInfo,
+        msg: ExecuteMsg,
+    ) -> Result<Response<C>, Error> {
+        execute_in(Some(ctx), deps, env, info, msg)
+    }
+
+    fn query(&self, deps: &Deps<Q>, _env: Env, msg: QueryMsg) -> Result<QueryResp, Error> {
+        query_in::<C, Q>(None, deps, msg)
+    }
+
+    fn query_with_context(
+        &self,
+        ctx: &Context<C, Q>,
+        deps: &Deps<Q>,
+        _env: Env,
+        msg: QueryMsg,
+    ) -> Result<QueryResp, Error> {
+        query_in(Some(ctx), deps, msg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manager::Manager;
+    use crate::test_utils::{addr, info, MockDeps};
+    use cosmwasm_std::testing::{mock_dependencies, mock_env};
+    use cosmwasm_std::{coins, from_json, Binary, CosmosMsg};
+
+    struct Setup {
+        deps: MockDeps,
+        manager: Manager,
+    }
+
+    impl Setup {
+        fn new() -> Self {
+            let manager = Manager::builder()
+                .with_boxed_module("escrow", Box::new(Escrow::new()))
+                .unwrap()
+                .with_boxed_module("deposits", Box::new(Escrow::new()))
+                .unwrap()
+                .build();
+            Setup {
+                deps: mock_dependencies(),
+                manager,
+            }
+        }
+
+        fn execute(
+            &mut self,
+            sender: &str,
+            funds: &[Coin],
+            msg: &str,
+        ) -> Result<cosmwasm_std::Response<Binary>, Error> {
+            self.manager.execute(
+                &mut self.deps.as_mut(),
+                mock_env(),
+                info(sender, funds),
+                msg,
+            )
+        }
+
+        fn escrow(&mut self, module: &str, id: &str) -> Option<EscrowState> {
+            let msg = format!(r#"{{"{}": {{"escrow": {{"id": "{}"}}}}}}"#, module, id);
+            let resp = self
+                .manager
+                .query(&self.deps.as_ref(), mock_env(), &msg)
+                .unwrap();
+            match from_json(resp).unwrap() {
+                QueryResp::Escrow { escrow } => escrow,
+                resp => panic!("unexpected response {:?}", resp),
+            }
+        }
+    }
+
+    fn create(module: &str, id: &str) -> String {
+        format!(
+            r#"{{"{}": {{"create": {{
+                "id": "{}",
+                "recipient": "{}",
+                "condition": {{"approval": {{"approver": "{}"}}}}
+            }}}}}}"#,
+            module,
+            id,
+            addr("seller"),
+            addr("buyer")
+        )
+    }
+
+    fn create_payload(id: &str) -> String {
+        let msg: serde_json::Value = serde_json::from_str(&create("escrow", id)).unwrap();
+        msg["escrow"].to_string()
+    }
+
+    fn bank_sends(resp: &cosmwasm_std::Response<Binary>) -> Vec<(String, Vec<Coin>)> {
+        resp.messages
+            .iter()
+            .filter_map(|sub| match &sub.msg {
+                CosmosMsg::Bank(BankMsg::Send { to_address, amount }) => {
+                    Some((to_address.clone(), amount.clone()))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn create_and_release() {
+        let mut setup = Setup::new();
+        setup
+            .execute("buyer", &coins(100, "uxion"), &create("escrow", "order"))
+            .unwrap();
+        let escrow = setup.escrow("escrow", "order").unwrap();
+        assert_eq!(escrow.depositor, addr("buyer"));
+        assert_eq!(escrow.funds, coins(100, "uxion"));
+
+        let release = r#"{"escrow": {"release": {"id": "order"}}}"#;
+        let err = setup.execute("seller", &[], release).unwrap_err();
+        assert!(
+            matches!(&err, Error::ExecutionError { err, .. } if err.message.contains("authorized")),
+            "{:?}",
+            err
+        );
+
+        let resp = setup.execute("buyer", &[], release).unwrap();
+        assert_eq!(
+            bank_sends(&resp),
+            [(addr("seller").to_string(), coins(100, "uxion"))]
+        );
+        assert_eq!(setup.escrow("escrow", "order"), None);
+    }
+
+    #[test]
+    fn recipient_refunds_depositor() {
+        let mut setup = Setup::new();
+        setup
+            .execute("buyer", &coins(100, "uxion"), &create("escrow", "order"))
+            .unwrap();
+        let refund = r#"{"escrow": {"refund": {"id": "order"}}}"#;
+        let resp = setup.execute("seller", &[], refund).unwrap();
+        assert_eq!(
+            bank_sends(&resp),
+            [(addr("buyer").to_string(), coins(100, "uxion"))]
+        );
+        assert_eq!(setup.escrow("escrow", "order"), None);
+    }
+
+    #[test]
+    fn create_requires_funds() {
+        let mut setup = Setup::new();
+        let err = setup
+            .execute("buyer", &[], &create("escrow", "order"))
+            .unwrap_err();
+        assert!(matches!(err, Error::ExecutionError { .. }), "{:?}", err);
+    }
+
+    #[test]
+    fn instances_hold_separate_escrows() {
+        let mut setup = Setup::new();
+        setup
+            .execute("buyer", &coins(100, "uxion"), &create("escrow", "order"))
+            .unwrap();
+        assert_eq!(setup.escrow("deposits", "order"), None);
+        setup
+            .execute("buyer", &coins(50, "uxion"), &create("deposits", "order"))
+            .unwrap();
+        assert_eq!(
+            setup.escrow("escrow", "order").unwrap().funds,
+            coins(100, "uxion")
+        );
+        assert_eq!(
+            setup.escrow("deposits", "order").unwrap().funds,
+            coins(50, "uxion")
+        );
+    }
+
+    #[test]
+    fn batched_escrows_hold_their_share() {
+        let mut setup = Setup::new();
+        let msg = format!(
+            r#"{{"_batch": [
+                {{"_funds": [{{"denom": "uxion", "amount": "60"}}], "escrow": {}}},
+                {{"_funds": [{{"denom": "uxion", "amount": "40"}}], "escrow": {}}}
+            ]}}"#,
+            create_payload("first"),
+            create_payload("second")
+        );
+        setup.execute("buyer", &coins(100, "uxion"), &msg).unwrap();
+        assert_eq!(
+            setup.escrow("escrow", "first").unwrap().funds,
+            coins(60, "uxion")
+        );
+        assert_eq!(
+            setup.escrow("escrow", "second").unwrap().funds,
+            coins(40, "uxion")
+        );
+    }
+
+    #[test]
+    fn module_without_context_keeps_escrows_apart() {
+        let mut setup = Setup::new();
+        let msg: ExecuteMsg = serde_json::from_str(&create_payload("order")).unwrap();
+        <Escrow as Module>::execute(
+            &mut Escrow::new(),
+            &mut setup.deps.as_mut(),
+            mock_env(),
+            info("buyer", &coins(100, "uxion")),
+            msg,
+        )
+        .unwrap();
+        let query = QueryMsg::Escrow {
+            id: "order".to_string(),
+        };
+        let resp =
+            <Escrow as Module>::query(&Escrow::new(), &setup.deps.as_ref(), mock_env(), query);
+        match resp.unwrap() {
+            QueryResp::Escrow { escrow } => assert_eq!(escrow.unwrap().funds, coins(100, "uxion")),
+            resp => panic!("unexpected response {:?}", resp),
+        }
+        assert_eq!(setup.escrow("escrow", "order"), None);
+        assert_eq!(setup.escrow("deposits", "order"), None);
+    }
+}
@@ -0,0 +1,30 @@
+//! Expiration of allowances and approvals, mirroring `cw_utils::Expiration`.
+
+use cosmwasm_std::{BlockInfo, Timestamp};
+use serde::{Deserialize, Serialize};
+
+/// The point from which an allowance or approval no longer applies.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Expiration {
+    AtHeight(u64),
+    AtTime(Timestamp),
+    Never {},
+}
+
+impl Default for Expiration {
+    fn default() -> Self {
+        Expiration::Never {}
+    }
+}
+
+impl Expiration {
+    /// Whether the expiration has passed as of `block`.
+    pub fn is_expired(&self, block: &BlockInfo) -> bool {
+        match self {
+            Expiration::AtHeight(height) => block.height >= *height,
+            Expiration::AtTime(time) => block.time >= *time,
+            Expiration::Never {} => false,
+        }
+    }
+}
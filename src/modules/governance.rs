@@ -0,0 +1,547 @@
+//! Proposals and weighted voting.
+//!
+//! [Governance] lets holders of voting power propose glue messages for other
+//! modules, vote on them, and execute those that pass. Voting power comes
+//! from whatever implements [VotingPower] under the interface name the
+//! module was created with, e.g. the [cw20][crate::modules::cw20] module:
+//!
+//! ```ignore
+//! let token = shared(Cw20::new());
+//! manager.register("token".to_string(), token.clone())?;
+//! manager.register_interface::<dyn VotingPower>(VOTING_POWER_INTERFACE, token)?;
+//! manager.register("gov".to_string(), shared(Governance::new()))?;
+//! ```
+//!
+//! ```javascript
+//! { "gov": { "propose": { "title": "...", "description": "...", "msgs": [
+//!     { "treasury": { "withdraw": { ... } } }
+//! ] } } }
+//! { "gov": { "vote": { "id": 0, "vote": "yes" } } }
+//! { "gov": { "execute": { "id": 0 } } }
+//! ```
+//!
+//! Voting power is read when the vote is cast. If it can be moved during the
+//! voting period, as cw20 balances can, the same tokens can vote more than
+//! once; prefer a source whose power is locked, such as staked tokens with
+//! an unbonding period longer than the voting period. Passed proposals are
+//! dispatched on behalf of the contract itself.
+
+use crate::context::Context;
+use crate::error::Error;
+use crate::module::Module;
+use crate::modules::cw20::{self, Cw20};
+use crate::pagination;
+use crate::response::Response;
+use crate::shared::{self, MaybeSendSync};
+use crate::storage;
+use cosmwasm_std::{
+    Addr, CustomQuery, Decimal, Deps, DepsMut, Env, MessageInfo, StdError, StdResult, Storage,
+    Uint128,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+const NAMESPACE: &[u8] = b"governance";
+
+/// The interface name [Governance::new] looks up [VotingPower] under.
+pub const VOTING_POWER_INTERFACE: &str = "voting_power";
+
+/// A source of voting power.
+pub trait VotingPower: MaybeSendSync {
+    /// The voting power of `voter`.
+    fn voting_power(&self, storage: &dyn Storage, voter: &Addr) -> StdResult<Uint128>;
+    /// The voting power of all voters together.
+    fn total_power(&self, storage: &dyn Storage) -> StdResult<Uint128>;
+}
+
+impl VotingPower for Cw20 {
+    fn voting_power(&self, storage: &dyn Storage, voter: &Addr) -> StdResult<Uint128> {
+        cw20::balance(storage, voter)
+    }
+
+    fn total_power(&self, storage: &dyn Storage) -> StdResult<Uint128> {
+        cw20::total_supply(storage)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct InstantiateMsg {
+    /// How long proposals are open for voting, in seconds.
+    pub voting_period: u64,
+    /// The share of the total voting power that must vote for a proposal
+    /// to be valid.
+    pub quorum: Decimal,
+    /// The share of yes votes, among yes and no votes, a proposal needs to
+    /// pass.
+    pub threshold: Decimal,
+}
+
+/// The config of the module.
+pub type Config = InstantiateMsg;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Vote {
+    Yes,
+    No,
+    Abstain,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecuteMsg {
+    /// Propose the glue messages `msgs`.
+    Propose {
+        title: String,
+        description: String,
+        msgs: Vec<Value>,
+    },
+    Vote {
+        id: u64,
+        vote: Vote,
+    },
+    /// Dispatch the messages of proposal `id` once voting is over, if it
+    /// passed.
+    Execute {
+        id: u64,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMsg {
+    Config {},
+    Proposal {
+        id: u64,
+    },
+    Proposals {
+        #[serde(default)]
+        start_after: Option<u64>,
+        #[serde(default)]
+        limit: Option<u32>,
+    },
+    Ballot {
+        id: u64,
+        voter: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum QueryResp {
+    Config(Config),
+    Proposal { proposal: Option<Proposal> },
+    Proposals { proposals: Vec<Proposal> },
+    Ballot { ballot: Option<Ballot> },
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Open,
+    Executed,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Tally {
+    pub yes: Uint128,
+    pub no: Uint128,
+    pub abstain: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Proposal {
+    pub id: u64,
+    pub proposer: Addr,
+    pub title: String,
+    pub description: String,
+    pub msgs: Vec<Value>,
+    /// The block time, in seconds, at which voting ends.
+    pub voting_ends_at: u64,
+    pub tally: Tally,
+    pub status: Status,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Ballot {
+    pub vote: Vote,
+    pub power: Uint128,
+}
+
+/// A module executing glue messages passed by a weighted vote.
+#[derive(Clone, Debug)]
+pub struct Governance {
+    interface: String,
+}
+
+impl Default for Governance {
+    fn default() -> Self {
+        Self::with_interface(VOTING_POWER_INTERFACE)
+    }
+}
+
+impl Governance {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a module reading voting power from the [VotingPower]
+    /// implementation registered as `interface`.
+    pub fn with_interface(interface: impl Into<String>) -> Self {
+        Governance {
+            interface: interface.into(),
+        }
+    }
+
+    fn power<C: Clone, Q: CustomQuery>(
+        &self,
+        ctx: &Context<C, Q>,
+        storage: &dyn Storage,
+        voter: Option<&Addr>,
+    ) -> Result<Uint128, Error> {
+        let source = ctx.interface::<dyn VotingPower>(&self.interface)?;
+        let source = shared::try_borrow(&source).ok_or_else(|| Error::ModuleBusy {
+            module: self.interface.clone(),
+        })?;
+        Ok(match voter {
+            Some(voter) => source.voting_power(storage, voter)?,
+            None => source.total_power(storage)?,
+        })
+    }
+}
+
+fn config_key() -> Vec<u8> {
+    storage::key(&[NAMESPACE], b"config")
+}
+
+fn next_id_key() -> Vec<u8> {
+    storage::key(&[NAMESPACE], b"next_id")
+}
+
+fn proposal_key(id: u64) -> Vec<u8> {
+    storage::key(&[NAMESPACE, b"proposals"], &id.to_be_bytes())
+}
+
+fn ballot_key(id: u64, voter: &Addr) -> Vec<u8> {
+    storage::key(&[NAMESPACE, b"ballots", &id.to_be_bytes()], voter.as_bytes())
+}
+
+fn config(storage: &dyn Storage) -> StdResult<Config> {
+    storage::load(storage, &config_key())?.ok_or_else(|| StdError::not_found("governance config"))
+}
+
+/// Load proposal `id`.
+pub fn proposal(storage: &dyn Storage, id: u64) -> StdResult<Option<Proposal>> {
+    storage::load(storage, &proposal_key(id))
+}
+
+fn must_load(storage: &dyn Storage, id: u64) -> Result<Proposal, Error> {
+    proposal(storage, id)?
+        .ok_or_else(|| StdError::not_found(format!("governance proposal {}", id)).into())
+}
+
+/// Whether `tally` meets the quorum and threshold of `config`, given the
+/// `total` voting power.
+fn passed(config: &Config, tally: &Tally, total: Uint128) -> bool {
+    let voted = tally.yes + tally.no + tally.abstain;
+    if total.is_zero() || Decimal::from_ratio(voted, total) < config.quorum {
+        return false;
+    }
+    let decisive = tally.yes + tally.no;
+    !decisive.is_zero() && Decimal::from_ratio(tally.yes, decisive) >= config.threshold
+}
+
+impl<C: Clone, Q: CustomQuery> Module<C, Q> for Governance {
+    type InstantiateMsg = InstantiateMsg;
+    type ExecuteMsg = ExecuteMsg;
+    type QueryMsg = QueryMsg;
+    type QueryResp = QueryResp;
+    type Error = Error;
+
+    fn validate_instantiate(
+        &self,
+        _deps: &Deps<Q>,
+        _env: &Env,
+        _info: &MessageInfo,
+        msg: &InstantiateMsg,
+    ) -> Result<(), Error> {
+        if msg.quorum > Decimal::one() || msg.threshold > Decimal::one() {
+            return Err(StdError::generic_err("quorum and threshold must not exceed 1").into());
+        }
+        Ok(())
+    }
+
+    fn instantiate(
+        &mut self,
+        deps: &mut DepsMut<Q>,
+        _env: &Env,
+        _info: &MessageInfo,
+        msg: InstantiateMsg,
+    ) -> Result<Response<C>, Error> {
+        storage::save(deps.storage, &config_key(), &msg)?;
+        Ok(Response::new())
+    }
+
+    fn execute(
+        &mut self,
+        _deps: &mut DepsMut<Q>,
+        _env: Env,
+        _info: MessageInfo,
+        _msg: ExecuteMsg,
+    ) -> Result<Response<C>, Error> {
+        Err(Error::ParseError {
+            msg: Some("governance requires the dispatch context".to_string()),
+        })
+    }
+
+    fn execute_with_context(
+        &mut self,
+        ctx: &Context<C, Q>,
+        deps: &mut DepsMut<Q>,
+        env: Env,
+        info: MessageInfo,
+        msg: ExecuteMsg,
+    ) -> Result<Response<C>, Error> {
+        let now = env.block.time.seconds();
+        match msg {
+            ExecuteMsg::Propose {
+                title,
+                description,
+                msgs,
+            } => {
+                if self.power(ctx, deps.storage, Some(&info.sender))?.is_zero() {
+                    return Err(Error::Unauthorized {
+                        sender: info.sender.to_string(),
+                    });
+                }
+                let config = config(deps.storage)?;
+                let id: u64 = storage::load(deps.storage, &next_id_key())?.unwrap_or_default();
+                storage::save(deps.storage, &next_id_key(), &(id + 1))?;
+                let proposal = Proposal {
+                    id,
+                    proposer: info.sender,
+                    title,
+                    description,
+                    msgs,
+                    voting_ends_at: now.saturating_add(config.voting_period),
+                    tally: Tally::default(),
+                    status: Status::Open,
+                };
+                storage::save(deps.storage, &proposal_key(id), &proposal)?;
+                Ok(Response::new()
+                    .add_attribute("action", "propose")
+                    .add_attribute("id", id.to_string()))
+            }
+            ExecuteMsg::Vote { id, vote } => {
+                let mut proposal = must_load(deps.storage, id)?;
+                if proposal.status != Status::Open || now >= proposal.voting_ends_at {
+                    return Err(StdError::generic_err(format!("voting on {} is over", id)).into());
+                }
+                let key = ballot_key(id, &info.sender);
+                if storage::load::<Ballot>(deps.storage, &key)?.is_some() {
+                    return Err(StdError::generic_err(format!(
+                        "{} already voted on {}",
+                        info.sender, id
+                    ))
+                    .into());
+                }
+                let power = self.power(ctx, deps.storage, Some(&info.sender))?;
+                if power.is_zero() {
+                    return Err(Error::Unauthorized {
+                        sender: info.sender.to_string(),
+                    });
+                }
+                let count = match vote {
+                    Vote::Yes => &mut proposal.tally.yes,
+                    Vote::No => &mut proposal.tally.no,
+                    Vote::Abstain => &mut proposal.tally.abstain,
+                };
+                *count += power;
+                storage::save(deps.storage, &key, &Ballot { vote, power })?;
+                storage::save(deps.storage, &proposal_key(id), &proposal)?;
+                Ok(Response::new()
+                    .add_attribute("action", "vote")
+                    .add_attribute("id", id.to_string())
+                    .add_attribute("power", power.to_string()))
+            }
+            ExecuteMsg::Execute { id } => {
+                let mut proposal = must_load(deps.storage, id)?;
+                if proposal.status != Status::Open || now < proposal.voting_ends_at {
+                    return Err(StdError::generic_err(format!("{} is not executable", id)).into());
+                }
+                let total = self.power(ctx, deps.storage, None)?;
+                if !passed(&config(deps.storage)?, &proposal.tally, total) {
+                    return Err(StdError::generic_err(format!("{} did not pass", id)).into());
+                }
+                proposal.status = Status::Executed;
+                storage::save(deps.storage, &proposal_key(id), &proposal)?;
+                let contract = MessageInfo {
+                    sender: env.contract.address.clone(),
+                    funds: vec![],
+                };
+                let mut resp = Response::new()
+                    .add_attribute("action", "execute")
+                    .add_attribute("id", id.to_string());
+                for msg in &proposal.msgs {
+                    resp.merge(ctx.dispatch(deps, &env, &contract, msg)?)?;
+                }
+                Ok(resp)
+            }
+        }
+    }
+
+    fn query(&self, deps: &Deps<Q>, _env: Env, msg: QueryMsg) -> Result<QueryResp, Error> {
+        match msg {
+            QueryMsg::Config {} => Ok(QueryResp::Config(config(deps.storage)?)),
+            QueryMsg::Proposal { id } => Ok(QueryResp::Proposal {
+                proposal: proposal(deps.storage, id)?,
+            }),
+            QueryMsg::Proposals { start_after, limit } => {
+                let limit = pagination::limit(limit);
+                let start_after = start_after.map(u64::to_be_bytes);
+                let proposals = storage::range(
+                    deps.storage,
+                    &[NAMESPACE, b"proposals"],
+                    start_after.as_ref().map(|id| &id[..]),
+                    limit,
+                )?;
+                Ok(QueryResp::Proposals {
+                    proposals: proposals.into_iter().map(|(_, p)| p).collect(),
+                })
+            }
+            QueryMsg::Ballot { id, voter } => {
+                let voter = deps.api.addr_validate(&voter)?;
+                Ok(QueryResp::Ballot {
+                    ballot: storage::load(deps.storage, &ballot_key(id, &voter))?,
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manager::Manager;
+    use crate::shared::shared;
+    use crate::test_utils::{addr, info, MockDeps};
+    use cosmwasm_std::testing::{mock_dependencies, mock_env};
+
+    struct Setup {
+        deps: MockDeps,
+        manager: Manager,
+    }
+
+    impl Setup {
+        fn new() -> Self {
+            let token = shared(Cw20::new());
+            let mut manager = Manager::new();
+            manager
+                .register("token".to_string(), token.clone())
+                .unwrap();
+            manager
+                .register_interface::<dyn VotingPower>(VOTING_POWER_INTERFACE, token)
+                .unwrap();
+            manager
+                .register("gov".to_string(), shared(Governance::new()))
+                .unwrap();
+            let mut setup = Setup {
+                deps: mock_dependencies(),
+                manager,
+            };
+            let contract = mock_env().contract.address;
+            let msg = format!(
+                r#"{{
+                    "token": {{
+                        "name": "Token", "symbol": "TKN", "decimals": 6,
+                        "initial_balances": [
+                            {{"address": "{}", "amount": "60"}},
+                            {{"address": "{}", "amount": "40"}},
+                            {{"address": "{}", "amount": "10"}}
+                        ]
+                    }},
+                    "gov": {{"voting_period": 100, "quorum": "0.5", "threshold": "0.5"}}
+                }}"#,
+                addr("alice"),
+                addr("bob"),
+                contract
+            );
+            setup
+                .manager
+                .instantiate(setup.deps.as_mut(), mock_env(), info("creator", &[]), &msg)
+                .unwrap();
+            setup
+        }
+
+        fn execute(&mut self, sender: &str, later: u64, msg: &str) -> Result<(), Error> {
+            let mut env = mock_env();
+            env.block.time = env.block.time.plus_seconds(later);
+            self.manager
+                .execute(&mut self.deps.as_mut(), env, info(sender, &[]), msg)
+                .map(|_| ())
+        }
+
+        fn vote(&mut self, voter: &str, vote: &str) -> Result<(), Error> {
+            let msg = format!(r#"{{"gov": {{"vote": {{"id": 0, "vote": "{}"}}}}}}"#, vote);
+            self.execute(voter, 0, &msg)
+        }
+
+        fn balance(&self, address: &str) -> u128 {
+            cw20::balance(&self.deps.storage, &addr(address))
+                .unwrap()
+                .u128()
+        }
+    }
+
+    fn propose() -> String {
+        format!(
+            r#"{{"gov": {{"propose": {{
+                "title": "Pay carol", "description": "",
+                "msgs": [{{"token": {{"transfer": {{"recipient": "{}", "amount": "10"}}}}}}]
+            }}}}}}"#,
+            addr("carol")
+        )
+    }
+
+    const EXECUTE: &str = r#"{"gov": {"execute": {"id": 0}}}"#;
+
+    #[test]
+    fn passed_proposal_executes_after_voting() {
+        let mut setup = Setup::new();
+        setup.execute("alice", 0, &propose()).unwrap();
+        setup.vote("alice", "yes").unwrap();
+        setup.vote("bob", "no").unwrap();
+        setup.vote("alice", "yes").unwrap_err();
+
+        setup.execute("bob", 0, EXECUTE).unwrap_err();
+        setup.execute("bob", 100, EXECUTE).unwrap();
+        assert_eq!(setup.balance("carol"), 10);
+        let proposal = proposal(&setup.deps.storage, 0).unwrap().unwrap();
+        assert_eq!(proposal.status, Status::Executed);
+        assert_eq!(proposal.tally.yes.u128(), 60);
+        assert_eq!(proposal.tally.no.u128(), 40);
+        setup.execute("bob", 100, EXECUTE).unwrap_err();
+    }
+
+    #[test]
+    fn proposal_without_quorum_fails() {
+        let mut setup = Setup::new();
+        setup.execute("bob", 0, &propose()).unwrap();
+        setup.vote("bob", "yes").unwrap();
+        setup.execute("bob", 100, EXECUTE).unwrap_err();
+        assert_eq!(setup.balance("carol"), 0);
+    }
+
+    #[test]
+    fn only_holders_propose_and_vote() {
+        let mut setup = Setup::new();
+        let err = setup.execute("carol", 0, &propose()).unwrap_err();
+        assert!(
+            matches!(&err, Error::ExecutionError { err, .. } if err.message.contains("authorized")),
+            "{:?}",
+            err
+        );
+        setup.execute("alice", 0, &propose()).unwrap();
+        setup.vote("carol", "yes").unwrap_err();
+    }
+}
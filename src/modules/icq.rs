@@ -0,0 +1,468 @@
+//! Interchain queries.
+//!
+//! [Icq] manages key-value interchain queries on chains supporting them,
+//! e.g. Neutron. The [admin][crate::admin] registers queries by name; the
+//! chain-specific messages are built by an [IcqBackend]:
+//!
+//! ```javascript
+//! { "icq": { "register": { "name": "pool", "connection_id": "connection-0",
+//!            "keys": [{ "path": "bank", "key": "..." }], "update_period": 10 } } }
+//! ```
+//!
+//! The chain reports the id it assigned to a query, and later its results,
+//! to the contract's `reply` and `sudo` entrypoints, which hand them over
+//! with [set_query_id] and [set_result]. The registration message is sent
+//! with [REGISTER_REPLY_ID]. Sibling modules read the latest results through
+//! the [InterchainQueries] interface:
+//!
+//! ```ignore
+//! let icq = shared(Icq::new(NeutronBackend));
+//! manager.register("icq".to_string(), icq.clone())?;
+//! manager.register_interface::<dyn InterchainQueries>(ICQ_INTERFACE, icq)?;
+//! ```
+
+use crate::admin;
+use crate::error::Error;
+use crate::module::Module;
+use crate::pagination;
+use crate::response::Response;
+use crate::shared::MaybeSendSync;
+use crate::storage;
+use cosmwasm_std::{
+    Binary, CosmosMsg, CustomQuery, Deps, DepsMut, Env, MessageInfo, StdError, StdResult,
+    Storage, SubMsg,
+};
+use serde::{Deserialize, Serialize};
+
+const NAMESPACE: &[u8] = b"icq";
+
+/// The interface name [InterchainQueries] are looked up under.
+pub const ICQ_INTERFACE: &str = "icq";
+
+/// The id of the reply to the registration of a query.
+pub const REGISTER_REPLY_ID: u64 = 0x1c9;
+
+/// Access to the latest results of interchain queries.
+pub trait InterchainQueries: MaybeSendSync {
+    /// The latest result of the query registered as `name`.
+    fn result(&self, storage: &dyn Storage, name: &str) -> StdResult<Option<KvResult>>;
+}
+
+/// The chain-specific messages managing interchain queries.
+pub trait IcqBackend<C> {
+    /// The message registering `query`.
+    fn register(&self, query: &KvQuery) -> StdResult<CosmosMsg<C>>;
+    /// The message removing the query `query_id`.
+    fn remove(&self, query_id: u64) -> StdResult<CosmosMsg<C>>;
+}
+
+/// A key of the remote chain's storage.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct KvKey {
+    /// The store the key is in, e.g. `"bank"`.
+    pub path: String,
+    pub key: Binary,
+}
+
+/// A key-value interchain query.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct KvQuery {
+    pub connection_id: String,
+    pub keys: Vec<KvKey>,
+    /// How often the query is updated, in blocks of the remote chain.
+    pub update_period: u64,
+}
+
+/// A value read by an interchain query.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct StorageValue {
+    pub path: String,
+    pub key: Binary,
+    pub value: Binary,
+}
+
+/// The result of a key-value interchain query.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct KvResult {
+    /// The height of the remote chain the values were read at.
+    pub height: u64,
+    pub values: Vec<StorageValue>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct InstantiateMsg {}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecuteMsg {
+    Register {
+        name: String,
+        connection_id: String,
+        keys: Vec<KvKey>,
+        update_period: u64,
+    },
+    Remove {
+        name: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMsg {
+    Query {
+        name: String,
+    },
+    Queries {
+        #[serde(default)]
+        start_after: Option<String>,
+        #[serde(default)]
+        limit: Option<u32>,
+    },
+    Result {
+        name: String,
+    },
+}
+
+/// A query registered with the module.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct RegisteredQuery {
+    pub name: String,
+    pub query: KvQuery,
+    /// The id assigned by the chain, once it has been reported.
+    pub query_id: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum QueryResp {
+    Query(RegisteredQuery),
+    Queries { queries: Vec<RegisteredQuery> },
+    Result { result: Option<KvResult> },
+}
+
+/// A module managing interchain queries.
+#[derive(Clone, Debug, Default)]
+pub struct Icq<B> {
+    backend: B,
+}
+
+impl<B> Icq<B> {
+    pub fn new(backend: B) -> Self {
+        Icq { backend }
+    }
+}
+
+fn query_key(name: &str) -> Vec<u8> {
+    storage::key(&[NAMESPACE, b"queries"], name.as_bytes())
+}
+
+fn name_key(query_id: u64) -> Vec<u8> {
+    storage::key(&[NAMESPACE, b"ids"], &query_id.to_be_bytes())
+}
+
+fn result_key(name: &str) -> Vec<u8> {
+    storage::key(&[NAMESPACE, b"results"], name.as_bytes())
+}
+
+fn pending_key() -> Vec<u8> {
+    storage::key(&[NAMESPACE], b"pending")
+}
+
+/// Load the query registered as `name`.
+pub fn query(storage: &dyn Storage, name: &str) -> StdResult<Option<RegisteredQuery>> {
+    storage::load(storage, &query_key(name))
+}
+
+/// Load the latest result of the query registered as `name`.
+pub fn result(storage: &dyn Storage, name: &str) -> StdResult<Option<KvResult>> {
+    storage::load(storage, &result_key(name))
+}
+
+/// Record `query_id` as the id of the oldest registration awaiting one,
+/// returning the name of the query. Called from the contract's `reply`
+/// entrypoint on [REGISTER_REPLY_ID].
+pub fn set_query_id(storage: &mut dyn Storage, query_id: u64) -> StdResult<String> {
+    let mut pending: Vec<String> = storage::load(storage, &pending_key())?.unwrap_or_default();
+    if pending.is_empty() {
+        return Err(StdError::generic_err("no pending query registration"));
+    }
+    let name = pending.remove(0);
+    storage::save(storage, &pending_key(), &pending)?;
+    if let Some(mut registered) = query(storage, &name)? {
+        registered.query_id = Some(query_id);
+        storage::save(storage, &query_key(&name), &registered)?;
+        storage::save(storage, &name_key(query_id), &name)?;
+    }
+    Ok(name)
+}
+
+/// Record `result` as the latest result of the query `query_id`, returning
+/// the name of the query. Called from the contract's `sudo` entrypoint.
+pub fn set_result(
+    storage: &mut dyn Storage,
+    query_id: u64,
+    result: &KvResult,
+) -> StdResult<String> {
+    let name: String = storage::load(storage, &name_key(query_id))?
+        .ok_or_else(|| StdError::not_found(format!("interchain query {}", query_id)))?;
+    storage::save(storage, &result_key(&name), result)?;
+    Ok(name)
+}
+
+impl<B: MaybeSendSync> InterchainQueries for Icq<B> {
+    fn result(&self, storage: &dyn Storage, name: &str) -> StdResult<Option<KvResult>> {
+        result(storage, name)
+    }
+}
+
+impl<B, C, Q> Module<C, Q> for Icq<B>
+where
+    B: IcqBackend<C>,
+    C: Clone,
+    Q: CustomQuery,
+{
+    type InstantiateMsg = InstantiateMsg;
+    type ExecuteMsg = ExecuteMsg;
+    type QueryMsg = QueryMsg;
+    type QueryResp = QueryResp;
+    type Error = Error;
+
+    fn instantiate(
+        &mut self,
+        _deps: &mut DepsMut<Q>,
+        _env: &Env,
+        _info: &MessageInfo,
+        _msg: InstantiateMsg,
+    ) -> Result<Response<C>, Error> {
+        Ok(Response::new())
+    }
+
+    fn execute(
+        &mut self,
+        deps: &mut DepsMut<Q>,
+        _env: Env,
+        info: MessageInfo,
+        msg: ExecuteMsg,
+    ) -> Result<Response<C>, Error> {
+        admin::assert_admin(deps.storage, &info.sender)?;
+        match msg {
+            ExecuteMsg::Register {
+                name,
+                connection_id,
+                keys,
+                update_period,
+            } => {
+                if query(deps.storage, &name)?.is_some() {
+                    return Err(StdError::generic_err(format!(
+                        "interchain query {:?} already registered",
+                        name
+                    ))
+                    .into());
+                }
+                let registered = RegisteredQuery {
+                    name: name.clone(),
+                    query: KvQuery {
+                        connection_id,
+                        keys,
+                        update_period,
+                    },
+                    query_id: None,
+                };
+                let msg = self.backend.register(&registered.query)?;
+                storage::save(deps.storage, &query_key(&name), &registered)?;
+                let mut pending: Vec<String> =
+                    storage::load(deps.storage, &pending_key())?.unwrap_or_default();
+                pending.push(name.clone());
+                storage::save(deps.storage, &pending_key(), &pending)?;
+                Ok(Response::new()
+                    .add_submessage(SubMsg::reply_on_success(msg, REGISTER_REPLY_ID))
+                    .add_attribute("action", "register")
+                    .add_attribute("name", name))
+            }
+            ExecuteMsg::Remove { name } => {
+                let registered = query(deps.storage, &name)?
+                    .ok_or_else(|| StdError::not_found(format!("interchain query {:?}", name)))?;
+                let query_id = registered.query_id.ok_or_else(|| {
+                    StdError::generic_err(format!("interchain query {:?} is pending", name))
+                })?;
+                storage::remove(deps.storage, &query_key(&name));
+                storage::remove(deps.storage, &name_key(query_id));
+                storage::remove(deps.storage, &result_key(&name));
+                Ok(Response::new()
+                    .add_message(self.backend.remove(query_id)?)
+                    .add_attribute("action", "remove")
+                    .add_attribute("name", name))
+            }
+        }
+    }
+
+    fn query(&self, deps: &Deps<Q>, _env: Env, msg: QueryMsg) -> Result<QueryResp, Error> {
+        match msg {
+            QueryMsg::Query { name } => {
+                let registered = query(deps.storage, &name)?
+                    .ok_or_else(|| StdError::not_found(format!("interchain query {:?}", name)))?;
+                Ok(QueryResp::Query(registered))
+            }
+            QueryMsg::Queries { start_after, limit } => {
+                let limit = pagination::limit(limit);
+                let queries = storage::range::<RegisteredQuery>(
+                    deps.storage,
+                    &[NAMESPACE, b"queries"],
+                    start_after.as_ref().map(|name| name.as_bytes()),
+                    limit,
+                )?;
+                Ok(QueryResp::Queries {
+                    queries: queries.into_iter().map(|(_, query)| query).collect(),
+                })
+            }
+            QueryMsg::Result { name } => Ok(QueryResp::Result {
+                result: result(deps.storage, &name)?,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manager::Manager;
+    use crate::test_utils::{addr, info, MockDeps};
+    use cosmwasm_std::testing::{mock_dependencies, mock_env};
+    use cosmwasm_std::{from_json, to_json_binary, WasmMsg};
+    use serde_json::{json, Value};
+
+    /// A backend managing queries through a host contract.
+    struct Host;
+
+    impl IcqBackend<Binary> for Host {
+        fn register(&self, query: &KvQuery) -> StdResult<CosmosMsg<Binary>> {
+            host_msg(json!({ "register": query }))
+        }
+
+        fn remove(&self, query_id: u64) -> StdResult<CosmosMsg<Binary>> {
+            host_msg(json!({ "remove": query_id }))
+        }
+    }
+
+    fn host_msg(msg: Value) -> StdResult<CosmosMsg<Binary>> {
+        Ok(WasmMsg::Execute {
+            contract_addr: "host".to_string(),
+            msg: to_json_binary(&msg)?,
+            funds: vec![],
+        }
+        .into())
+    }
+
+    struct Setup {
+        deps: MockDeps,
+        manager: Manager,
+    }
+
+    impl Setup {
+        fn new() -> Self {
+            let manager = Manager::builder()
+                .with_boxed_module("icq", Box::new(Icq::new(Host)))
+                .unwrap()
+                .build();
+            let mut deps = mock_dependencies();
+            admin::set_admin(&mut deps.storage, &addr("admin")).unwrap();
+            Setup { deps, manager }
+        }
+
+        fn execute(
+            &mut self,
+            sender: &str,
+            msg: Value,
+        ) -> Result<cosmwasm_std::Response<Binary>, Error> {
+            let msg = json!({ "icq": msg }).to_string();
+            self.manager
+                .execute(&mut self.deps.as_mut(), mock_env(), info(sender, &[]), &msg)
+        }
+
+        fn register(&mut self, name: &str) -> Result<cosmwasm_std::Response<Binary>, Error> {
+            let msg = json!({"register": {
+                "name": name,
+                "connection_id": "connection-0",
+                "keys": [{"path": "bank", "key": Binary::from(name.as_bytes())}],
+                "update_period": 10,
+            }});
+            self.execute("admin", msg)
+        }
+
+        fn query(&mut self, msg: Value) -> Value {
+            let msg = json!({ "icq": msg }).to_string();
+            let resp = self
+                .manager
+                .query(&self.deps.as_ref(), mock_env(), &msg)
+                .unwrap();
+            from_json(resp).unwrap()
+        }
+    }
+
+    fn kv_result(height: u64) -> KvResult {
+        KvResult {
+            height,
+            values: vec![StorageValue {
+                path: "bank".to_string(),
+                key: Binary::from(b"pool".to_vec()),
+                value: Binary::from(b"42".to_vec()),
+            }],
+        }
+    }
+
+    #[test]
+    fn registrations_receive_their_ids_in_order() {
+        let mut setup = Setup::new();
+        let resp = setup.register("pool").unwrap();
+        assert_eq!(resp.messages[0].id, REGISTER_REPLY_ID);
+        setup.register("vault").unwrap();
+        let err = setup.register("pool").unwrap_err();
+        assert!(err.to_string().contains("already registered"), "{}", err);
+
+        assert_eq!(set_query_id(&mut setup.deps.storage, 7).unwrap(), "pool");
+        assert_eq!(set_query_id(&mut setup.deps.storage, 8).unwrap(), "vault");
+        set_query_id(&mut setup.deps.storage, 9).unwrap_err();
+
+        let pool = setup.query(json!({"query": {"name": "pool"}}));
+        assert_eq!(pool["query_id"], json!(7));
+        assert_eq!(
+            set_result(&mut setup.deps.storage, 8, &kv_result(100)).unwrap(),
+            "vault"
+        );
+        set_result(&mut setup.deps.storage, 9, &kv_result(100)).unwrap_err();
+        assert_eq!(
+            setup.query(json!({"result": {"name": "vault"}})),
+            json!({ "result": kv_result(100) })
+        );
+        assert_eq!(
+            Icq::new(Host).result(&setup.deps.storage, "pool").unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn removing_a_query_clears_its_results() {
+        let mut setup = Setup::new();
+        setup.register("pool").unwrap();
+        let err = setup
+            .execute("admin", json!({"remove": {"name": "pool"}}))
+            .unwrap_err();
+        assert!(err.to_string().contains("is pending"), "{}", err);
+
+        set_query_id(&mut setup.deps.storage, 7).unwrap();
+        set_result(&mut setup.deps.storage, 7, &kv_result(100)).unwrap();
+        let err = setup
+            .execute("alice", json!({"remove": {"name": "pool"}}))
+            .unwrap_err();
+        assert!(err.to_string().contains("is not authorized"), "{}", err);
+        let resp = setup
+            .execute("admin", json!({"remove": {"name": "pool"}}))
+            .unwrap();
+        assert_eq!(
+            resp.messages[0].msg,
+            host_msg(json!({"remove": 7})).unwrap()
+        );
+        assert_eq!(result(&setup.deps.storage, "pool").unwrap(), None);
+        assert_eq!(query(&setup.deps.storage, "pool").unwrap(), None);
+    }
+}
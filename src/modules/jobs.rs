@@ -0,0 +1,422 @@
+//! Scheduled execution of glue messages.
+//!
+//! [Jobs] keeps a queue of glue messages to dispatch once they are due, at a
+//! block height or time. Modules schedule follow-up work in process through
+//! the [JobScheduler] interface, or with [enqueue]:
+//!
+//! ```ignore
+//! let jobs = shared(Jobs::new());
+//! manager.register("jobs".to_string(), jobs.clone())?;
+//! manager.register_interface::<dyn JobScheduler>(JOBS_INTERFACE, jobs)?;
+//! ```
+//!
+//! Any account can also enqueue a job, attaching the reward for executing
+//! it as funds:
+//!
+//! ```javascript
+//! { "jobs": { "enqueue": {
+//!     "msg": { "vault": { "compound": {} } },
+//!     "due": { "at_height": 123456 }
+//! } } }
+//! ```
+//!
+//! Nothing happens on chain by itself, so keepers call `crank` to have the
+//! Manager dispatch the jobs that are due, collecting their rewards:
+//!
+//! ```javascript
+//! { "jobs": { "crank": { "limit": 5 } } }
+//! ```
+//!
+//! A job is dispatched on behalf of its owner, without funds, so it can only
+//! do what its owner could have done directly. Jobs enqueued with a message
+//! are owned by the sender; modules pick the owner of the jobs they enqueue,
+//! e.g. the contract itself. A job that fails makes the whole crank fail;
+//! keepers skip it with `start_after`, and its owner or the
+//! [admin][crate::admin] can cancel it.
+
+use crate::admin;
+use crate::context::Context;
+use crate::error::Error;
+use crate::module::Module;
+use crate::modules::expiration::Expiration;
+use crate::pagination;
+use crate::response::Response;
+use crate::shared::MaybeSendSync;
+use crate::storage;
+use cosmwasm_std::{
+    Addr, BankMsg, Coin, CustomQuery, Deps, DepsMut, Env, MessageInfo, StdError, StdResult,
+    Storage,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+const NAMESPACE: &[u8] = b"jobs";
+/// The number of jobs a crank looks at for due ones.
+const MAX_SCAN: usize = 100;
+
+/// The interface name a [JobScheduler] is looked up under.
+pub const JOBS_INTERFACE: &str = "jobs";
+
+/// Scheduling of jobs for sibling modules.
+pub trait JobScheduler: MaybeSendSync {
+    /// Enqueue `msg` to be dispatched on behalf of `owner` once `due` has
+    /// passed, returning the id of the job; see [enqueue].
+    fn enqueue(
+        &self,
+        storage: &mut dyn Storage,
+        owner: Addr,
+        msg: Value,
+        due: Expiration,
+        reward: Vec<Coin>,
+    ) -> Result<u64, Error> {
+        enqueue(storage, owner, msg, due, reward)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct InstantiateMsg {}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecuteMsg {
+    /// Enqueue the glue message `msg`, to be dispatched once `due` has
+    /// passed. The funds sent along are the reward for executing it.
+    Enqueue { msg: Value, due: Expiration },
+    /// Dispatch the due jobs among the next ones in order of their id,
+    /// paying their rewards to the sender.
+    Crank {
+        #[serde(default)]
+        start_after: Option<u64>,
+        #[serde(default)]
+        limit: Option<u32>,
+    },
+    /// Drop the job `id`, refunding its reward to its owner. Only the owner
+    /// and the admin may cancel jobs.
+    Cancel { id: u64 },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMsg {
+    Job {
+        id: u64,
+    },
+    /// List the queued jobs in order of their id.
+    Jobs {
+        #[serde(default)]
+        start_after: Option<u64>,
+        #[serde(default)]
+        limit: Option<u32>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum QueryResp {
+    Job { job: Option<Job> },
+    Jobs { jobs: Vec<Job> },
+}
+
+/// A queued job.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Job {
+    pub id: u64,
+    pub owner: Addr,
+    pub msg: Value,
+    /// The height or time from which the job may be executed.
+    pub due: Expiration,
+    /// Paid to the account executing the job.
+    pub reward: Vec<Coin>,
+}
+
+/// A module dispatching glue messages once they are due.
+#[derive(Clone, Debug, Default)]
+pub struct Jobs {}
+
+impl Jobs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+fn next_id_key() -> Vec<u8> {
+    storage::key(&[NAMESPACE], b"next_id")
+}
+
+fn job_key(id: u64) -> Vec<u8> {
+    storage::key(&[NAMESPACE, b"queue"], &id.to_be_bytes())
+}
+
+/// Load the job `id`, if it is still queued.
+pub fn job(storage: &dyn Storage, id: u64) -> StdResult<Option<Job>> {
+    storage::load(storage, &job_key(id))
+}
+
+fn must_load(storage: &dyn Storage, id: u64) -> Result<Job, Error> {
+    job(storage, id)?.ok_or_else(|| StdError::not_found(format!("job {}", id)).into())
+}
+
+/// Enqueue `msg` to be dispatched on behalf of `owner` once `due` has
+/// passed, returning the id of the job. `reward` is paid from the contract's
+/// balance to the account executing the job, so the caller must make sure
+/// the contract holds it.
+pub fn enqueue(
+    storage: &mut dyn Storage,
+    owner: Addr,
+    msg: Value,
+    due: Expiration,
+    reward: Vec<Coin>,
+) -> Result<u64, Error> {
+    if matches!(due, Expiration::Never {}) {
+        return Err(StdError::generic_err("a job must be due at a height or time").into());
+    }
+    let id: u64 = storage::load(storage, &next_id_key())?.unwrap_or_default();
+    storage::save(storage, &next_id_key(), &(id + 1))?;
+    let job = Job {
+        id,
+        owner,
+        msg,
+        due,
+        reward,
+    };
+    storage::save(storage, &job_key(id), &job)?;
+    Ok(id)
+}
+
+/// Add `coins` to `total`, merging amounts of the same denom.
+fn add_coins(total: &mut Vec<Coin>, coins: Vec<Coin>) {
+    for coin in coins {
+        match total.iter_mut().find(|c| c.denom == coin.denom) {
+            Some(c) => c.amount += coin.amount,
+            None => total.push(coin),
+        }
+    }
+}
+
+impl JobScheduler for Jobs {}
+
+impl<C: Clone, Q: CustomQuery> Module<C, Q> for Jobs {
+    type InstantiateMsg = InstantiateMsg;
+    type ExecuteMsg = ExecuteMsg;
+    type QueryMsg = QueryMsg;
+    type QueryResp = QueryResp;
+    type Error = Error;
+
+    fn instantiate(
+        &mut self,
+        _deps: &mut DepsMut<Q>,
+        _env: &Env,
+        _info: &MessageInfo,
+        _msg: InstantiateMsg,
+    ) -> Result<Response<C>, Error> {
+        Ok(Response::new())
+    }
+
+    fn execute(
+        &mut self,
+        deps: &mut DepsMut<Q>,
+        env: Env,
+        info: MessageInfo,
+        msg: ExecuteMsg,
+    ) -> Result<Response<C>, Error> {
+        match msg {
+            ExecuteMsg::Enqueue { msg, due } => {
+                let id = enqueue(deps.storage, info.sender, msg, due, info.funds)?;
+                Ok(Response::new()
+                    .add_attribute("action", "enqueue")
+                    .add_attribute("id", id.to_string()))
+            }
+            ExecuteMsg::Crank { .. } => Err(Error::ParseError {
+                msg: Some("cranking jobs requires the dispatch context".to_string()),
+            }),
+            ExecuteMsg::Cancel { id } => {
+                let job = must_load(deps.storage, id)?;
+                if job.owner != info.sender {
+                    admin::assert_admin(deps.storage, &info.sender)?;
+                }
+                storage::remove(deps.storage, &job_key(id));
+                let mut resp = Response::new()
+                    .add_attribute("action", "cancel")
+                    .add_attribute("id", id.to_string());
+                if !job.reward.is_empty() && job.owner != env.contract.address {
+                    resp = resp.add_message(BankMsg::Send {
+                        to_address: job.owner.to_string(),
+                        amount: job.reward,
+                    });
+                }
+                Ok(resp)
+            }
+        }
+    }
+
+    fn execute_with_context(
+        &mut self,
+        ctx: &Context<C, Q>,
+        deps: &mut DepsMut<Q>,
+        env: Env,
+        info: MessageInfo,
+        msg: ExecuteMsg,
+    ) -> Result<Response<C>, Error> {
+        match msg {
+            ExecuteMsg::Crank { start_after, limit } => {
+                let limit = pagination::limit(limit);
+                let start_after = start_after.map(u64::to_be_bytes);
+                let queued = storage::range::<Job>(
+                    deps.storage,
+                    &[NAMESPACE, b"queue"],
+                    start_after.as_ref().map(|id| &id[..]),
+                    MAX_SCAN,
+                )?;
+                let due = queued
+                    .into_iter()
+                    .map(|(_, job)| job)
+                    .filter(|job| job.due.is_expired(&env.block))
+                    .take(limit);
+                let mut resp = Response::new().add_attribute("action", "crank");
+                let mut reward = vec![];
+                let mut executed = 0;
+                for job in due {
+                    storage::remove(deps.storage, &job_key(job.id));
+                    let owner = MessageInfo {
+                        sender: job.owner,
+                        funds: vec![],
+                    };
+                    let dispatched = ctx.dispatch(deps, &env, &owner, &job.msg)?;
+                    resp.merge(dispatched)?;
+                    resp = resp.add_attribute("job", job.id.to_string());
+                    add_coins(&mut reward, job.reward);
+                    executed += 1;
+                }
+                resp = resp.add_attribute("executed", executed.to_string());
+                if !reward.is_empty() {
+                    resp = resp.add_message(BankMsg::Send {
+                        to_address: info.sender.to_string(),
+                        amount: reward,
+                    });
+                }
+                Ok(resp)
+            }
+            msg => <Self as Module<C, Q>>::execute(self, deps, env, info, msg),
+        }
+    }
+
+    fn query(&self, deps: &Deps<Q>, _env: Env, msg: QueryMsg) -> Result<QueryResp, Error> {
+        match msg {
+            QueryMsg::Job { id } => Ok(QueryResp::Job {
+                job: job(deps.storage, id)?,
+            }),
+            QueryMsg::Jobs { start_after, limit } => {
+                let limit = pagination::limit(limit);
+                let start_after = start_after.map(u64::to_be_bytes);
+                let jobs = storage::range(
+                    deps.storage,
+                    &[NAMESPACE, b"queue"],
+                    start_after.as_ref().map(|id| &id[..]),
+                    limit,
+                )?;
+                Ok(QueryResp::Jobs {
+                    jobs: jobs.into_iter().map(|(_, job)| job).collect(),
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manager::Manager;
+    use crate::test_utils::{addr, info, senders_seen, Deposits, MockDeps};
+    use cosmwasm_std::testing::{mock_dependencies, mock_env};
+    use cosmwasm_std::{coins, Binary, CosmosMsg};
+    use serde_json::json;
+
+    struct Setup {
+        deps: MockDeps,
+        manager: Manager,
+    }
+
+    impl Setup {
+        /// A contract with a `vault` module alice scheduled a compounding
+        /// job for, due five blocks from now with a reward of 3 uxion.
+        fn new() -> Self {
+            let manager = Manager::builder()
+                .with_boxed_module("jobs", Box::new(Jobs::new()))
+                .unwrap()
+                .with_boxed_module("vault", Box::new(Deposits))
+                .unwrap()
+                .build();
+            let mut setup = Setup {
+                deps: mock_dependencies(),
+                manager,
+            };
+            admin::set_admin(&mut setup.deps.storage, &addr("admin")).unwrap();
+            let due = Expiration::AtHeight(mock_env().block.height + 5);
+            let msg = json!({"enqueue": {"msg": {"vault": {"compound": {}}}, "due": due}});
+            setup.execute(0, "alice", &coins(3, "uxion"), msg).unwrap();
+            setup
+        }
+
+        /// Execute `msg` as `sender`, `blocks` blocks after the mock block.
+        fn execute(
+            &mut self,
+            blocks: u64,
+            sender: &str,
+            funds: &[Coin],
+            msg: Value,
+        ) -> Result<cosmwasm_std::Response<Binary>, Error> {
+            let mut env = mock_env();
+            env.block.height += blocks;
+            let msg = json!({ "jobs": msg }).to_string();
+            self.manager
+                .execute(&mut self.deps.as_mut(), env, info(sender, funds), &msg)
+        }
+
+        fn crank(&mut self, blocks: u64) -> cosmwasm_std::Response<Binary> {
+            self.execute(blocks, "keeper", &[], json!({"crank": {}}))
+                .unwrap()
+        }
+    }
+
+    fn payment(to: &str, amount: u128) -> CosmosMsg<Binary> {
+        CosmosMsg::Bank(BankMsg::Send {
+            to_address: addr(to).to_string(),
+            amount: coins(amount, "uxion"),
+        })
+    }
+
+    #[test]
+    fn due_jobs_run_as_their_owner_and_pay_the_keeper() {
+        let mut setup = Setup::new();
+        let resp = setup.crank(4);
+        assert!(senders_seen(&resp).is_empty());
+        assert!(job(&setup.deps.storage, 0).unwrap().is_some());
+
+        let resp = setup.crank(5);
+        assert_eq!(senders_seen(&resp), [addr("alice").as_str()]);
+        assert_eq!(resp.messages.len(), 1);
+        assert_eq!(resp.messages[0].msg, payment("keeper", 3));
+        assert_eq!(job(&setup.deps.storage, 0).unwrap(), None);
+    }
+
+    #[test]
+    fn only_the_owner_or_admin_cancels_jobs() {
+        let mut setup = Setup::new();
+        let cancel = json!({"cancel": {"id": 0}});
+        let err = setup.execute(0, "bob", &[], cancel.clone()).unwrap_err();
+        assert!(err.to_string().contains("is not authorized"), "{}", err);
+
+        let resp = setup.execute(0, "alice", &[], cancel.clone()).unwrap();
+        assert_eq!(resp.messages[0].msg, payment("alice", 3));
+        setup.execute(0, "admin", &[], cancel).unwrap_err();
+        assert!(senders_seen(&setup.crank(5)).is_empty());
+    }
+
+    #[test]
+    fn jobs_must_come_due() {
+        let mut setup = Setup::new();
+        let msg = json!({"enqueue": {"msg": {"vault": {}}, "due": Expiration::Never {}}});
+        let err = setup.execute(0, "alice", &[], msg).unwrap_err();
+        assert!(err.to_string().contains("must be due"), "{}", err);
+    }
+}
@@ -0,0 +1,253 @@
+//! Key/value metadata.
+//!
+//! [MetadataStore] stores JSON values, plain strings included, under keys
+//! grouped into namespaces, e.g. collection-level metadata, contract
+//! configuration, or hints for frontends. Only the [admin][crate::admin] can
+//! write:
+//!
+//! ```javascript
+//! { "metadata": { "set": { "namespace": "collection", "key": "banner", "value": "ipfs://..." } } }
+//! { "metadata": { "list": { "namespace": "collection" } } }
+//! ```
+
+use crate::admin;
+use crate::error::Error;
+use crate::module::Module;
+use crate::pagination;
+use crate::response::Response;
+use crate::storage;
+use cosmwasm_std::{CustomQuery, Deps, DepsMut, Env, MessageInfo, StdResult, Storage};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+const NAMESPACE: &[u8] = b"metadata";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct InstantiateMsg {
+    /// Entries to set at instantiation, by namespace and key.
+    #[serde(default)]
+    pub entries: BTreeMap<String, BTreeMap<String, Value>>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecuteMsg {
+    Set {
+        namespace: String,
+        key: String,
+        value: Value,
+    },
+    Remove {
+        namespace: String,
+        key: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMsg {
+    Get {
+        namespace: String,
+        key: String,
+    },
+    /// List the entries in `namespace` in order of their key.
+    List {
+        namespace: String,
+        #[serde(default)]
+        start_after: Option<String>,
+        #[serde(default)]
+        limit: Option<u32>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub key: String,
+    pub value: Value,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum QueryResp {
+    Get { value: Option<Value> },
+    List { entries: Vec<Entry> },
+}
+
+/// A module storing namespaced metadata.
+#[derive(Clone, Debug, Default)]
+pub struct MetadataStore {}
+
+impl MetadataStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+fn entry_key(namespace: &str, key: &str) -> Vec<u8> {
+    storage::key(&[NAMESPACE, namespace.as_bytes()], key.as_bytes())
+}
+
+/// Load the value stored under `key` in `namespace`.
+pub fn get(storage: &dyn Storage, namespace: &str, key: &str) -> StdResult<Option<Value>> {
+    storage::load(storage, &entry_key(namespace, key))
+}
+
+impl<C: Clone, Q: CustomQuery> Module<C, Q> for MetadataStore {
+    type InstantiateMsg = InstantiateMsg;
+    type ExecuteMsg = ExecuteMsg;
+    type QueryMsg = QueryMsg;
+    type QueryResp = QueryResp;
+    type Error = Error;
+
+    fn instantiate(
+        &mut self,
+        deps: &mut DepsMut<Q>,
+        _env: &Env,
+        _info: &MessageInfo,
+        msg: InstantiateMsg,
+    ) -> Result<Response<C>, Error> {
+        for (namespace, entries) in &msg.entries {
+            for (key, value) in entries {
+                storage::save(deps.storage, &entry_key(namespace, key), value)?;
+            }
+        }
+        Ok(Response::new())
+    }
+
+    fn execute(
+        &mut self,
+        deps: &mut DepsMut<Q>,
+        _env: Env,
+        info: MessageInfo,
+        msg: ExecuteMsg,
+    ) -> Result<Response<C>, Error> {
+        admin::assert_admin(deps.storage, &info.sender)?;
+        match msg {
+            ExecuteMsg::Set {
+                namespace,
+                key,
+                value,
+            } => {
+                storage::save(deps.storage, &entry_key(&namespace, &key), &value)?;
+                Ok(Response::new()
+                    .add_attribute("action", "set")
+                    .add_attribute("namespace", namespace)
+                    .add_attribute("key", key))
+            }
+            ExecuteMsg::Remove { namespace, key } => {
+                storage::remove(deps.storage, &entry_key(&namespace, &key));
+                Ok(Response::new()
+                    .add_attribute("action", "remove")
+                    .add_attribute("namespace", namespace)
+                    .add_attribute("key", key))
+            }
+        }
+    }
+
+    fn query(&self, deps: &Deps<Q>, _env: Env, msg: QueryMsg) -> Result<QueryResp, Error> {
+        match msg {
+            QueryMsg::Get { namespace, key } => Ok(QueryResp::Get {
+                value: get(deps.storage, &namespace, &key)?,
+            }),
+            QueryMsg::List {
+                namespace,
+                start_after,
+                limit,
+            } => {
+                let limit = pagination::limit(limit);
+                let entries = storage::range::<Value>(
+                    deps.storage,
+                    &[NAMESPACE, namespace.as_bytes()],
+                    start_after.as_ref().map(|key| key.as_bytes()),
+                    limit,
+                )?;
+                let entries = entries
+                    .into_iter()
+                    .map(|(key, value)| Entry {
+                        key: String::from_utf8_lossy(&key).into_owned(),
+                        value,
+                    })
+                    .collect();
+                Ok(QueryResp::List { entries })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{addr, execute, MockDeps};
+    use cosmwasm_std::testing::{mock_dependencies, mock_env};
+    use serde_json::json;
+
+    fn setup() -> MockDeps {
+        let mut deps = mock_dependencies();
+        admin::set_admin(&mut deps.storage, &addr("admin")).unwrap();
+        deps
+    }
+
+    fn set(namespace: &str, key: &str, value: Value) -> ExecuteMsg {
+        ExecuteMsg::Set {
+            namespace: namespace.to_string(),
+            key: key.to_string(),
+            value,
+        }
+    }
+
+    #[test]
+    fn admin_sets_and_removes_entries() {
+        let mut deps = setup();
+        execute::<MetadataStore>(
+            &mut deps,
+            "admin",
+            set("collection", "banner", json!("ipfs://a")),
+        )
+        .unwrap();
+        assert_eq!(
+            get(&deps.storage, "collection", "banner").unwrap(),
+            Some(json!("ipfs://a"))
+        );
+        let remove = ExecuteMsg::Remove {
+            namespace: "collection".to_string(),
+            key: "banner".to_string(),
+        };
+        execute::<MetadataStore>(&mut deps, "admin", remove).unwrap();
+        assert_eq!(get(&deps.storage, "collection", "banner").unwrap(), None);
+    }
+
+    #[test]
+    fn only_admin_writes() {
+        let mut deps = setup();
+        let err = execute::<MetadataStore>(&mut deps, "alice", set("collection", "banner", json!(1))).unwrap_err();
+        assert!(matches!(err, Error::Unauthorized { .. }), "{:?}", err);
+        assert_eq!(get(&deps.storage, "collection", "banner").unwrap(), None);
+    }
+
+    #[test]
+    fn list_is_scoped_to_namespace() {
+        let mut deps = setup();
+        execute::<MetadataStore>(&mut deps, "admin", set("collection", "b", json!(2))).unwrap();
+        execute::<MetadataStore>(&mut deps, "admin", set("collection", "a", json!(1))).unwrap();
+        execute::<MetadataStore>(&mut deps, "admin", set("frontend", "a", json!(3))).unwrap();
+        let msg = QueryMsg::List {
+            namespace: "collection".to_string(),
+            start_after: None,
+            limit: None,
+        };
+        let resp = <MetadataStore as Module>::query(
+            &MetadataStore::new(),
+            &deps.as_ref(),
+            mock_env(),
+            msg,
+        )
+        .unwrap();
+        let entries = match resp {
+            QueryResp::List { entries } => entries,
+            resp => panic!("unexpected response {:?}", resp),
+        };
+        let keys: Vec<&str> = entries.iter().map(|entry| entry.key.as_str()).collect();
+        assert_eq!(keys, ["a", "b"]);
+    }
+}
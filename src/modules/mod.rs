@@ -0,0 +1,21 @@
+//! Reusable modules for common contract concerns.
+
+pub mod address_list;
+pub mod admin;
+pub mod cw1155;
+pub mod cw20;
+pub mod cw721;
+pub mod escrow;
+pub mod expiration;
+pub mod governance;
+pub mod icq;
+pub mod jobs;
+pub mod metadata;
+pub mod multisig;
+pub mod nonces;
+pub mod rbac;
+pub mod royalties;
+pub mod signatures;
+pub mod snapshots;
+pub mod staking;
+pub mod timelock;
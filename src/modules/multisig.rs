@@ -0,0 +1,406 @@
+//! K-of-N approval of glue messages.
+//!
+//! [Multisig] lets a fixed set of members propose execute messages for other
+//! modules, given as ordinary glue envelopes, and approve them. Once a
+//! proposal has as many approvals as the threshold, the Manager dispatches
+//! it straight away:
+//!
+//! ```javascript
+//! { "multisig": { "propose": { "msg": { "treasury": { "withdraw": { ... } } } } } }
+//! { "multisig": { "approve": { "id": 0 } } }
+//! ```
+//!
+//! Approved messages are dispatched on behalf of the contract itself, i.e.
+//! with `env.contract.address` as the sender. Making the contract its own
+//! [admin][crate::admin] thus puts admin-only actions under the control of
+//! the members.
+
+use crate::context::Context;
+use crate::error::Error;
+use crate::module::Module;
+use crate::pagination;
+use crate::response::Response;
+use crate::storage;
+use cosmwasm_std::{
+    Addr, CustomQuery, Deps, DepsMut, Env, MessageInfo, StdError, StdResult, Storage,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+const NAMESPACE: &[u8] = b"multisig";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct InstantiateMsg {
+    pub members: Vec<String>,
+    /// The number of approvals needed to dispatch a proposal.
+    pub threshold: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecuteMsg {
+    /// Propose the glue message `msg`. Proposing counts as an approval.
+    Propose { msg: Value },
+    /// Approve proposal `id`, dispatching it if that reaches the threshold.
+    Approve { id: u64 },
+    /// Withdraw proposal `id`. Only its proposer may withdraw it.
+    Withdraw { id: u64 },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMsg {
+    Config {},
+    Proposal {
+        id: u64,
+    },
+    /// List the open proposals in order of their id.
+    Proposals {
+        #[serde(default)]
+        start_after: Option<u64>,
+        #[serde(default)]
+        limit: Option<u32>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum QueryResp {
+    Config(Config),
+    Proposal { proposal: Option<Proposal> },
+    Proposals { proposals: Vec<Proposal> },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Config {
+    pub members: Vec<Addr>,
+    pub threshold: u32,
+}
+
+/// An open proposal.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Proposal {
+    pub id: u64,
+    pub proposer: Addr,
+    pub msg: Value,
+    pub approvals: Vec<Addr>,
+}
+
+/// A module dispatching messages approved by a threshold of its members.
+#[derive(Clone, Debug, Default)]
+pub struct Multisig {}
+
+impl Multisig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+fn config_key() -> Vec<u8> {
+    storage::key(&[NAMESPACE], b"config")
+}
+
+fn next_id_key() -> Vec<u8> {
+    storage::key(&[NAMESPACE], b"next_id")
+}
+
+fn proposal_key(id: u64) -> Vec<u8> {
+    storage::key(&[NAMESPACE, b"proposals"], &id.to_be_bytes())
+}
+
+fn config(storage: &dyn Storage) -> StdResult<Config> {
+    storage::load(storage, &config_key())?
+        .ok_or_else(|| StdError::not_found("multisig config"))
+}
+
+/// Load proposal `id`, if it is still open.
+pub fn proposal(storage: &dyn Storage, id: u64) -> StdResult<Option<Proposal>> {
+    storage::load(storage, &proposal_key(id))
+}
+
+fn must_load(storage: &dyn Storage, id: u64) -> Result<Proposal, Error> {
+    proposal(storage, id)?
+        .ok_or_else(|| StdError::not_found(format!("multisig proposal {}", id)).into())
+}
+
+fn assert_member(config: &Config, sender: &Addr) -> Result<(), Error> {
+    if config.members.contains(sender) {
+        Ok(())
+    } else {
+        Err(Error::Unauthorized {
+            sender: sender.to_string(),
+        })
+    }
+}
+
+impl Multisig {
+    /// Store `proposal`, or dispatch it if it has reached the threshold.
+    fn settle<C: Clone, Q: CustomQuery>(
+        ctx: &Context<C, Q>,
+        deps: &mut DepsMut<Q>,
+        env: &Env,
+        config: &Config,
+        proposal: Proposal,
+        resp: Response<C>,
+    ) -> Result<Response<C>, Error> {
+        if (proposal.approvals.len() as u64) < u64::from(config.threshold) {
+            storage::save(deps.storage, &proposal_key(proposal.id), &proposal)?;
+            return Ok(resp);
+        }
+        storage::remove(deps.storage, &proposal_key(proposal.id));
+        let contract = MessageInfo {
+            sender: env.contract.address.clone(),
+            funds: vec![],
+        };
+        let dispatched = ctx.dispatch(deps, env, &contract, &proposal.msg)?;
+        let mut resp = resp.add_attribute("executed", "true");
+        resp.merge(dispatched)?;
+        Ok(resp)
+    }
+}
+
+impl<C: Clone, Q: CustomQuery> Module<C, Q> for Multisig {
+    type InstantiateMsg = InstantiateMsg;
+    type ExecuteMsg = ExecuteMsg;
+    type QueryMsg = QueryMsg;
+    type QueryResp = QueryResp;
+    type Error = Error;
+
+    fn instantiate(
+        &mut self,
+        deps: &mut DepsMut<Q>,
+        _env: &Env,
+        _info: &MessageInfo,
+        msg: InstantiateMsg,
+    ) -> Result<Response<C>, Error> {
+        let mut members = msg
+            .members
+            .iter()
+            .map(|member| deps.api.addr_validate(member))
+            .collect::<StdResult<Vec<_>>>()?;
+        members.sort();
+        members.dedup();
+        if msg.threshold == 0 || msg.threshold as usize > members.len() {
+            return Err(StdError::generic_err(format!(
+                "threshold must be between 1 and the {} members",
+                members.len()
+            ))
+            .into());
+        }
+        let config = Config {
+            members,
+            threshold: msg.threshold,
+        };
+        storage::save(deps.storage, &config_key(), &config)?;
+        Ok(Response::new().add_attribute("threshold", msg.threshold.to_string()))
+    }
+
+    fn execute(
+        &mut self,
+        deps: &mut DepsMut<Q>,
+        _env: Env,
+        info: MessageInfo,
+        msg: ExecuteMsg,
+    ) -> Result<Response<C>, Error> {
+        match msg {
+            ExecuteMsg::Propose { .. } | ExecuteMsg::Approve { .. } => Err(Error::ParseError {
+                msg: Some("proposals can only be approved with the dispatch context".to_string()),
+            }),
+            ExecuteMsg::Withdraw { id } => {
+                let proposal = must_load(deps.storage, id)?;
+                if proposal.proposer != info.sender {
+                    return Err(Error::Unauthorized {
+                        sender: info.sender.to_string(),
+                    });
+                }
+                storage::remove(deps.storage, &proposal_key(id));
+                Ok(Response::new()
+                    .add_attribute("action", "withdraw")
+                    .add_attribute("id", id.to_string()))
+            }
+        }
+    }
+
+    fn execute_with_context(
+        &mut self,
+        ctx: &Context<C, Q>,
+        deps: &mut DepsMut<Q>,
+        env: Env,
+        info: MessageInfo,
+        msg: ExecuteMsg,
+    ) -> Result<Response<C>, Error> {
+        match msg {
+            ExecuteMsg::Propose { msg } => {
+                let config = config(deps.storage)?;
+                assert_member(&config, &info.sender)?;
+                let id: u64 = storage::load(deps.storage, &next_id_key())?.unwrap_or_default();
+                storage::save(deps.storage, &next_id_key(), &(id + 1))?;
+                let proposal = Proposal {
+                    id,
+                    proposer: info.sender.clone(),
+                    msg,
+                    approvals: vec![info.sender],
+                };
+                let resp = Response::new()
+                    .add_attribute("action", "propose")
+                    .add_attribute("id", id.to_string());
+                Self::settle(ctx, deps, &env, &config, proposal, resp)
+            }
+            ExecuteMsg::Approve { id } => {
+                let config = config(deps.storage)?;
+                assert_member(&config, &info.sender)?;
+                let mut proposal = must_load(deps.storage, id)?;
+                if proposal.approvals.contains(&info.sender) {
+                    return Err(StdError::generic_err(format!(
+                        "{} already approved proposal {}",
+                        info.sender, id
+                    ))
+                    .into());
+                }
+                proposal.approvals.push(info.sender);
+                let resp = Response::new()
+                    .add_attribute("action", "approve")
+                    .add_attribute("id", id.to_string());
+                Self::settle(ctx, deps, &env, &config, proposal, resp)
+            }
+            msg => <Self as Module<C, Q>>::execute(self, deps, env, info, msg),
+        }
+    }
+
+    fn query(&self, deps: &Deps<Q>, _env: Env, msg: QueryMsg) -> Result<QueryResp, Error> {
+        match msg {
+            QueryMsg::Config {} => Ok(QueryResp::Config(config(deps.storage)?)),
+            QueryMsg::Proposal { id } => Ok(QueryResp::Proposal {
+                proposal: proposal(deps.storage, id)?,
+            }),
+            QueryMsg::Proposals { start_after, limit } => {
+                let limit = pagination::limit(limit);
+                let start_after = start_after.map(u64::to_be_bytes);
+                let proposals = storage::range(
+                    deps.storage,
+                    &[NAMESPACE, b"proposals"],
+                    start_after.as_ref().map(|id| &id[..]),
+                    limit,
+                )?;
+                Ok(QueryResp::Proposals {
+                    proposals: proposals.into_iter().map(|(_, p)| p).collect(),
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manager::Manager;
+    use crate::test_utils::{addr, info, instantiate, senders_seen, Deposits, MockDeps};
+    use cosmwasm_std::testing::{mock_dependencies, mock_env};
+    use cosmwasm_std::Binary;
+    use serde_json::json;
+
+    struct Setup {
+        deps: MockDeps,
+        manager: Manager,
+    }
+
+    impl Setup {
+        /// A contract whose treasury is controlled by two of alice, bob and
+        /// carol.
+        fn new() -> Self {
+            let manager = Manager::builder()
+                .with_boxed_module("multisig", Box::new(Multisig::new()))
+                .unwrap()
+                .with_boxed_module("treasury", Box::new(Deposits))
+                .unwrap()
+                .build();
+            let mut setup = Setup {
+                deps: mock_dependencies(),
+                manager,
+            };
+            let msg = InstantiateMsg {
+                members: ["alice", "bob", "carol"]
+                    .map(|m| addr(m).to_string())
+                    .to_vec(),
+                threshold: 2,
+            };
+            instantiate::<Multisig>(&mut setup.deps, "alice", msg).unwrap();
+            setup
+        }
+
+        fn execute(
+            &mut self,
+            sender: &str,
+            msg: Value,
+        ) -> Result<cosmwasm_std::Response<Binary>, Error> {
+            let msg = json!({ "multisig": msg }).to_string();
+            self.manager
+                .execute(&mut self.deps.as_mut(), mock_env(), info(sender, &[]), &msg)
+        }
+
+        fn propose(&mut self, sender: &str) -> Result<cosmwasm_std::Response<Binary>, Error> {
+            let msg = json!({"treasury": {"withdraw": {}}});
+            self.execute(sender, json!({"propose": {"msg": msg}}))
+        }
+    }
+
+    #[test]
+    fn proposals_run_as_the_contract_once_approved() {
+        let mut setup = Setup::new();
+        let resp = setup.propose("alice").unwrap();
+        assert!(senders_seen(&resp).is_empty());
+
+        let approve = json!({"approve": {"id": 0}});
+        let err = setup.execute("alice", approve.clone()).unwrap_err();
+        assert!(err.to_string().contains("already approved"), "{}", err);
+
+        let resp = setup.execute("bob", approve.clone()).unwrap();
+        let contract = mock_env().contract.address;
+        assert_eq!(senders_seen(&resp), [contract.as_str()]);
+        assert_eq!(proposal(&setup.deps.storage, 0).unwrap(), None);
+        setup.execute("carol", approve).unwrap_err();
+    }
+
+    #[test]
+    fn only_members_propose_and_approve() {
+        let mut setup = Setup::new();
+        let err = setup.propose("mallory").unwrap_err();
+        assert!(err.to_string().contains("is not authorized"), "{}", err);
+
+        setup.propose("alice").unwrap();
+        let err = setup
+            .execute("mallory", json!({"approve": {"id": 0}}))
+            .unwrap_err();
+        assert!(err.to_string().contains("is not authorized"), "{}", err);
+        let proposal = proposal(&setup.deps.storage, 0).unwrap().unwrap();
+        assert_eq!(proposal.approvals, [addr("alice")]);
+    }
+
+    #[test]
+    fn only_the_proposer_withdraws() {
+        let mut setup = Setup::new();
+        setup.propose("alice").unwrap();
+        let withdraw = json!({"withdraw": {"id": 0}});
+        setup.execute("bob", withdraw.clone()).unwrap_err();
+        setup.execute("alice", withdraw).unwrap();
+        setup
+            .execute("bob", json!({"approve": {"id": 0}}))
+            .unwrap_err();
+    }
+
+    #[test]
+    fn thresholds_must_be_reachable() {
+        let mut deps = mock_dependencies();
+        for threshold in [0, 3] {
+            let msg = InstantiateMsg {
+                members: vec![
+                    addr("alice").to_string(),
+                    addr("alice").to_string(),
+                    addr("bob").to_string(),
+                ],
+                threshold,
+            };
+            instantiate::<Multisig>(&mut deps, "alice", msg).unwrap_err();
+        }
+    }
+}
@@ -0,0 +1,282 @@
+//! Replay protection.
+//!
+//! [Nonces] keeps a nonce per address, starting at 0, that increases by one
+//! each time it is consumed. Modules accepting signed messages, e.g.
+//! meta-transactions relayed on behalf of their signer, have the signer sign
+//! its next nonce along with the message and consume it before acting on
+//! the message, so that it can't be replayed:
+//!
+//! ```ignore
+//! let nonces = ctx.interface::<dyn NonceTracker>(NONCES_INTERFACE)?;
+//! let nonces = shared::try_borrow(&nonces).ok_or_else(|| Error::ModuleBusy {
+//!     module: NONCES_INTERFACE.to_string(),
+//! })?;
+//! nonces.consume_nonce(deps.storage, &signer, msg.nonce)?;
+//! ```
+//!
+//! or, without going through the interface registry, with
+//! [consume_nonce]:
+//!
+//! ```ignore
+//! nonces::consume_nonce(deps.storage, &signer, msg.nonce)?;
+//! ```
+//!
+//! Register it as both a module and the [NonceTracker] interface:
+//!
+//! ```ignore
+//! let nonces = shared(Nonces::new());
+//! manager.register("nonces".to_string(), nonces.clone())?;
+//! manager.register_interface::<dyn NonceTracker>(NONCES_INTERFACE, nonces)?;
+//! ```
+//!
+//! Clients read the next nonce of an address with a query, and an address
+//! can invalidate the messages it signed but that were not relayed yet:
+//!
+//! ```javascript
+//! { "nonces": { "invalidate": { "nonce": 7 } } }
+//! ```
+
+use crate::error::Error;
+use crate::module::Module;
+use crate::response::Response;
+use crate::shared::MaybeSendSync;
+use crate::storage;
+use cosmwasm_std::{
+    Addr, CustomQuery, Deps, DepsMut, Env, MessageInfo, OverflowError, OverflowOperation,
+    StdResult, Storage,
+};
+use serde::{Deserialize, Serialize};
+
+const NAMESPACE: &[u8] = b"nonces";
+
+/// The interface name a [NonceTracker] is looked up under.
+pub const NONCES_INTERFACE: &str = "nonces";
+
+/// Per-address nonces for sibling modules.
+pub trait NonceTracker: MaybeSendSync {
+    /// The next nonce of `address`.
+    fn nonce(&self, storage: &dyn Storage, address: &Addr) -> StdResult<u64> {
+        nonce(storage, address)
+    }
+    /// Consume the next nonce of `address`, which must be `nonce`.
+    fn consume_nonce(
+        &self,
+        storage: &mut dyn Storage,
+        address: &Addr,
+        nonce: u64,
+    ) -> Result<(), Error> {
+        consume_nonce(storage, address, nonce)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct InstantiateMsg {}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecuteMsg {
+    /// Consume the sender's nonces up to and including `nonce`, invalidating
+    /// any message signed with them.
+    Invalidate { nonce: u64 },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMsg {
+    /// The next nonce of `address`.
+    Nonce { address: String },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct NonceResponse {
+    pub nonce: u64,
+}
+
+/// A module tracking per-address nonces.
+#[derive(Clone, Debug, Default)]
+pub struct Nonces {}
+
+impl Nonces {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+fn nonce_key(address: &Addr) -> Vec<u8> {
+    storage::key(&[NAMESPACE], address.as_bytes())
+}
+
+/// The nonce after `nonce`.
+fn next(nonce: u64) -> StdResult<u64> {
+    nonce
+        .checked_add(1)
+        .ok_or_else(|| overflow(nonce).into())
+}
+
+#[cfg(not(feature = "cosmwasm-2"))]
+fn overflow(nonce: u64) -> OverflowError {
+    OverflowError::new(OverflowOperation::Add, nonce, 1)
+}
+
+// cosmwasm-std 2.x no longer records the operands.
+#[cfg(feature = "cosmwasm-2")]
+fn overflow(_nonce: u64) -> OverflowError {
+    OverflowError::new(OverflowOperation::Add)
+}
+
+/// The next nonce of `address`.
+pub fn nonce(storage: &dyn Storage, address: &Addr) -> StdResult<u64> {
+    Ok(storage::load(storage, &nonce_key(address))?.unwrap_or_default())
+}
+
+/// Consume the next nonce of `address`, failing with
+/// [InvalidNonce][Error::InvalidNonce] unless it is `nonce`.
+pub fn consume_nonce(storage: &mut dyn Storage, address: &Addr, nonce: u64) -> Result<(), Error> {
+    let expected = self::nonce(storage, address)?;
+    if nonce != expected {
+        return Err(Error::InvalidNonce {
+            address: address.to_string(),
+            expected,
+            nonce,
+        });
+    }
+    storage::save(storage, &nonce_key(address), &next(expected)?)?;
+    Ok(())
+}
+
+impl NonceTracker for Nonces {}
+
+impl<C: Clone, Q: CustomQuery> Module<C, Q> for Nonces {
+    type InstantiateMsg = InstantiateMsg;
+    type ExecuteMsg = ExecuteMsg;
+    type QueryMsg = QueryMsg;
+    type QueryResp = NonceResponse;
+    type Error = Error;
+
+    fn instantiate(
+        &mut self,
+        _deps: &mut DepsMut<Q>,
+        _env: &Env,
+        _info: &MessageInfo,
+        _msg: InstantiateMsg,
+    ) -> Result<Response<C>, Error> {
+        Ok(Response::new())
+    }
+
+    fn execute(
+        &mut self,
+        deps: &mut DepsMut<Q>,
+        _env: Env,
+        info: MessageInfo,
+        msg: ExecuteMsg,
+    ) -> Result<Response<C>, Error> {
+        match msg {
+            ExecuteMsg::Invalidate { nonce } => {
+                let expected = self::nonce(deps.storage, &info.sender)?;
+                if nonce < expected {
+                    return Err(Error::InvalidNonce {
+                        address: info.sender.to_string(),
+                        expected,
+                        nonce,
+                    });
+                }
+                storage::save(deps.storage, &nonce_key(&info.sender), &next(nonce)?)?;
+                Ok(Response::new()
+                    .add_attribute("action", "invalidate")
+                    .add_attribute("address", info.sender)
+                    .add_attribute("nonce", nonce.to_string()))
+            }
+        }
+    }
+
+    fn query(&self, deps: &Deps<Q>, _env: Env, msg: QueryMsg) -> Result<NonceResponse, Error> {
+        match msg {
+            QueryMsg::Nonce { address } => {
+                let address = deps.api.addr_validate(&address)?;
+                Ok(NonceResponse {
+                    nonce: nonce(deps.storage, &address)?,
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manager::Manager;
+    use crate::test_utils::{addr, info, MockDeps};
+    use cosmwasm_std::from_json;
+    use cosmwasm_std::testing::{mock_dependencies, mock_env};
+    use serde_json::json;
+
+    struct Setup {
+        deps: MockDeps,
+        manager: Manager,
+    }
+
+    impl Setup {
+        fn new() -> Self {
+            let manager = Manager::builder()
+                .with_boxed_module("nonces", Box::new(Nonces::new()))
+                .unwrap()
+                .build();
+            Setup {
+                deps: mock_dependencies(),
+                manager,
+            }
+        }
+
+        fn invalidate(&mut self, sender: &str, nonce: u64) -> Result<(), Error> {
+            let msg = json!({"nonces": {"invalidate": {"nonce": nonce}}}).to_string();
+            self.manager
+                .execute(&mut self.deps.as_mut(), mock_env(), info(sender, &[]), &msg)
+                .map(|_| ())
+        }
+
+        fn nonce(&mut self, name: &str) -> u64 {
+            let msg = json!({"nonces": {"nonce": {"address": addr(name)}}}).to_string();
+            let resp = self
+                .manager
+                .query(&self.deps.as_ref(), mock_env(), &msg)
+                .unwrap();
+            from_json::<NonceResponse>(resp).unwrap().nonce
+        }
+    }
+
+    #[test]
+    fn nonces_are_consumed_once_and_in_order() {
+        let mut setup = Setup::new();
+        let alice = addr("alice");
+        let tracker = Nonces::new();
+        tracker
+            .consume_nonce(&mut setup.deps.storage, &alice, 0)
+            .unwrap();
+        for replayed in [0, 2] {
+            let err = tracker
+                .consume_nonce(&mut setup.deps.storage, &alice, replayed)
+                .unwrap_err();
+            assert!(
+                matches!(err, Error::InvalidNonce { expected: 1, nonce, .. } if nonce == replayed),
+                "{:?}",
+                err
+            );
+        }
+        assert_eq!(setup.nonce("alice"), 1);
+        assert_eq!(setup.nonce("bob"), 0);
+    }
+
+    #[test]
+    fn senders_invalidate_their_pending_nonces() {
+        let mut setup = Setup::new();
+        setup.invalidate("alice", 5).unwrap();
+        assert_eq!(setup.nonce("alice"), 6);
+        let err = setup.invalidate("alice", 5).unwrap_err();
+        assert!(err.to_string().contains("expected 6"), "{}", err);
+        consume_nonce(&mut setup.deps.storage, &addr("alice"), 6).unwrap();
+        assert_eq!(setup.nonce("bob"), 0);
+
+        let err = setup.invalidate("bob", u64::MAX).unwrap_err();
+        assert!(err.to_string().contains("verflow"), "{}", err);
+    }
+}
@@ -0,0 +1,473 @@
+//! Role-based access control.
+//!
+//! [Rbac] keeps named roles and their members, maintained by the
+//! [admin][crate::admin]. Roles come into existence when first granted:
+//!
+//! ```javascript
+//! { "rbac": { "grant": { "role": "minter", "addresses": ["...", "..."] } } }
+//! ```
+//!
+//! Other modules guard their handlers with [require_role]:
+//!
+//! ```ignore
+//! rbac::require_role(deps.storage, &info.sender, "minter")?;
+//! ```
+//!
+//! Permissions can also be granted over (module, action) pairs, where the
+//! action is the name of an execute message, or `*` for every message of the
+//! module without a permission of its own:
+//!
+//! ```javascript
+//! { "rbac": { "set_permission": { "module": "cw20", "action": "mint", "roles": ["minter"] } } }
+//! ```
+//!
+//! Added as middleware, [Rbac] rejects execute messages from senders holding
+//! none of the roles permitted to send them. Actions without a permission are
+//! unrestricted.
+//!
+//! ```ignore
+//! manager.register("rbac".to_string(), shared(Rbac::new()))?;
+//! manager.add_middleware(Box::new(Rbac::new()));
+//! ```
+
+use crate::admin;
+use crate::error::Error;
+use crate::middleware::Middleware;
+use crate::module::Module;
+use crate::pagination;
+use crate::response::Response;
+use crate::storage;
+use cosmwasm_std::{Addr, CustomQuery, Deps, DepsMut, Env, MessageInfo, StdResult, Storage};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+const NAMESPACE: &[u8] = b"rbac";
+
+/// The action of a permission covering every message of a module.
+pub const ANY_ACTION: &str = "*";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct InstantiateMsg {}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecuteMsg {
+    /// Give `role` to `addresses`.
+    Grant { role: String, addresses: Vec<String> },
+    /// Take `role` away from `addresses`.
+    Revoke { role: String, addresses: Vec<String> },
+    /// Give up `role`. Unlike the other messages, sent by the member rather
+    /// than the admin.
+    Renounce { role: String },
+    /// Allow only members of `roles` to send `action` to `module`.
+    SetPermission {
+        module: String,
+        action: String,
+        roles: Vec<String>,
+    },
+    /// Lift the restrictions on sending `action` to `module`.
+    ClearPermission { module: String, action: String },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMsg {
+    /// List the roles with at least one member in ascending order.
+    Roles {
+        #[serde(default)]
+        start_after: Option<String>,
+        #[serde(default)]
+        limit: Option<u32>,
+    },
+    /// List the members of `role` in ascending order.
+    Members {
+        role: String,
+        #[serde(default)]
+        start_after: Option<String>,
+        #[serde(default)]
+        limit: Option<u32>,
+    },
+    HasRole {
+        role: String,
+        address: String,
+    },
+    Permission {
+        module: String,
+        action: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct RolesResponse {
+    pub roles: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct MembersResponse {
+    pub members: Vec<Addr>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct HasRoleResponse {
+    pub has_role: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PermissionResponse {
+    pub roles: Option<Vec<String>>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum QueryResp {
+    Roles(RolesResponse),
+    Members(MembersResponse),
+    HasRole(HasRoleResponse),
+    Permission(PermissionResponse),
+}
+
+/// A module and middleware maintaining roles and their permissions.
+#[derive(Clone, Debug, Default)]
+pub struct Rbac {}
+
+impl Rbac {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+fn role_key(role: &str) -> Vec<u8> {
+    storage::key(&[NAMESPACE, b"role"], role.as_bytes())
+}
+
+fn member_key(role: &str, address: &Addr) -> Vec<u8> {
+    storage::key(&[NAMESPACE, b"member", role.as_bytes()], address.as_bytes())
+}
+
+fn permission_key(module: &str, action: &str) -> Vec<u8> {
+    storage::key(&[NAMESPACE, b"permission", module.as_bytes()], action.as_bytes())
+}
+
+/// Whether `address` has `role`.
+pub fn has_role(storage: &dyn Storage, address: &Addr, role: &str) -> StdResult<bool> {
+    Ok(storage::load::<bool>(storage, &member_key(role, address))?.is_some())
+}
+
+/// Fail with [MissingRole][Error::MissingRole] unless `sender` has `role`.
+pub fn require_role(storage: &dyn Storage, sender: &Addr, role: &str) -> Result<(), Error> {
+    if has_role(storage, sender, role)? {
+        Ok(())
+    } else {
+        Err(Error::MissingRole {
+            sender: sender.to_string(),
+            role: role.to_string(),
+        })
+    }
+}
+
+/// Load the roles permitted to send `action` to `module`, if it is
+/// restricted.
+pub fn permission(
+    storage: &dyn Storage,
+    module: &str,
+    action: &str,
+) -> StdResult<Option<Vec<String>>> {
+    storage::load(storage, &permission_key(module, action))
+}
+
+/// Give `role` to `address`, returning whether it did not have it yet.
+fn grant(storage: &mut dyn Storage, role: &str, address: &Addr) -> StdResult<bool> {
+    if has_role(storage, address, role)? {
+        return Ok(false);
+    }
+    let members: u32 = storage::load(storage, &role_key(role))?.unwrap_or_default();
+    storage::save(storage, &role_key(role), &(members + 1))?;
+    storage::save(storage, &member_key(role, address), &true)?;
+    Ok(true)
+}
+
+/// Take `role` away from `address`, returning whether it had it.
+fn revoke(storage: &mut dyn Storage, role: &str, address: &Addr) -> StdResult<bool> {
+    if !has_role(storage, address, role)? {
+        return Ok(false);
+    }
+    let members: u32 = storage::load(storage, &role_key(role))?.unwrap_or_default();
+    if members > 1 {
+        storage::save(storage, &role_key(role), &(members - 1))?;
+    } else {
+        storage::remove(storage, &role_key(role));
+    }
+    storage::remove(storage, &member_key(role, address));
+    Ok(true)
+}
+
+/// The name of the execute message `msg`, e.g. `mint` for
+/// `{ "mint": { ... } }` or `"mint"`.
+fn action(msg: &Value) -> Option<&str> {
+    match msg {
+        Value::Object(obj) if obj.len() == 1 => obj.keys().next().map(String::as_str),
+        Value::String(action) => Some(action),
+        _ => None,
+    }
+}
+
+impl<C: Clone, Q: CustomQuery> Module<C, Q> for Rbac {
+    type InstantiateMsg = InstantiateMsg;
+    type ExecuteMsg = ExecuteMsg;
+    type QueryMsg = QueryMsg;
+    type QueryResp = QueryResp;
+    type Error = Error;
+
+    fn instantiate(
+        &mut self,
+        _deps: &mut DepsMut<Q>,
+        _env: &Env,
+        _info: &MessageInfo,
+        _msg: InstantiateMsg,
+    ) -> Result<Response<C>, Error> {
+        Ok(Response::new())
+    }
+
+    fn execute(
+        &mut self,
+        deps: &mut DepsMut<Q>,
+        _env: Env,
+        info: MessageInfo,
+        msg: ExecuteMsg,
+    ) -> Result<Response<C>, Error> {
+        if !matches!(msg, ExecuteMsg::Renounce { .. }) {
+            admin::assert_admin(deps.storage, &info.sender)?;
+        }
+        match msg {
+            ExecuteMsg::Grant { role, addresses } => {
+                let mut granted = 0;
+                for address in &addresses {
+                    let address = deps.api.addr_validate(address)?;
+                    if grant(deps.storage, &role, &address)? {
+                        granted += 1;
+                    }
+                }
+                Ok(Response::new()
+                    .add_attribute("action", "grant")
+                    .add_attribute("role", role)
+                    .add_attribute("count", granted.to_string()))
+            }
+            ExecuteMsg::Revoke { role, addresses } => {
+                let mut revoked = 0;
+                for address in &addresses {
+                    let address = deps.api.addr_validate(address)?;
+                    if revoke(deps.storage, &role, &address)? {
+                        revoked += 1;
+                    }
+                }
+                Ok(Response::new()
+                    .add_attribute("action", "revoke")
+                    .add_attribute("role", role)
+                    .add_attribute("count", revoked.to_string()))
+            }
+            ExecuteMsg::SetPermission {
+                module,
+                action,
+                roles,
+            } => {
+                storage::save(deps.storage, &permission_key(&module, &action), &roles)?;
+                Ok(Response::new()
+                    .add_attribute("action", "set_permission")
+                    .add_attribute("module", module)
+                    .add_attribute("module_action", action))
+            }
+            ExecuteMsg::ClearPermission { module, action } => {
+                storage::remove(deps.storage, &permission_key(&module, &action));
+                Ok(Response::new()
+                    .add_attribute("action", "clear_permission")
+                    .add_attribute("module", module)
+                    .add_attribute("module_action", action))
+            }
+            ExecuteMsg::Renounce { role } => {
+                require_role(deps.storage, &info.sender, &role)?;
+                revoke(deps.storage, &role, &info.sender)?;
+                Ok(Response::new()
+                    .add_attribute("action", "renounce")
+                    .add_attribute("role", role)
+                    .add_attribute("address", info.sender))
+            }
+        }
+    }
+
+    fn query(&self, deps: &Deps<Q>, _env: Env, msg: QueryMsg) -> Result<QueryResp, Error> {
+        match msg {
+            QueryMsg::Roles { start_after, limit } => {
+                let limit = pagination::limit(limit);
+                let entries = storage::range::<u32>(
+                    deps.storage,
+                    &[NAMESPACE, b"role"],
+                    start_after.as_ref().map(|r| r.as_bytes()),
+                    limit,
+                )?;
+                let roles = entries
+                    .into_iter()
+                    .map(|(key, _)| String::from_utf8_lossy(&key).into_owned())
+                    .collect();
+                Ok(QueryResp::Roles(RolesResponse { roles }))
+            }
+            QueryMsg::Members {
+                role,
+                start_after,
+                limit,
+            } => {
+                let limit = pagination::limit(limit);
+                let entries = storage::range::<bool>(
+                    deps.storage,
+                    &[NAMESPACE, b"member", role.as_bytes()],
+                    start_after.as_ref().map(|a| a.as_bytes()),
+                    limit,
+                )?;
+                let members = entries
+                    .into_iter()
+                    .map(|(key, _)| Addr::unchecked(String::from_utf8_lossy(&key)))
+                    .collect();
+                Ok(QueryResp::Members(MembersResponse { members }))
+            }
+            QueryMsg::HasRole { role, address } => {
+                let address = deps.api.addr_validate(&address)?;
+                Ok(QueryResp::HasRole(HasRoleResponse {
+                    has_role: has_role(deps.storage, &address, &role)?,
+                }))
+            }
+            QueryMsg::Permission { module, action } => Ok(QueryResp::Permission(PermissionResponse {
+                roles: permission(deps.storage, &module, &action)?,
+            })),
+        }
+    }
+}
+
+impl<C, Q: CustomQuery> Middleware<C, Q> for Rbac {
+    fn before_execute(
+        &self,
+        deps: &mut DepsMut<Q>,
+        _env: &Env,
+        info: &MessageInfo,
+        module: &str,
+        msg: &Value,
+    ) -> Result<(), Error> {
+        let specific = match action(msg) {
+            Some(action) => permission(deps.storage, module, action)?,
+            None => None,
+        };
+        let roles = match specific {
+            Some(roles) => roles,
+            None => match permission(deps.storage, module, ANY_ACTION)? {
+                Some(roles) => roles,
+                None => return Ok(()),
+            },
+        };
+        for role in &roles {
+            if has_role(deps.storage, &info.sender, role)? {
+                return Ok(());
+            }
+        }
+        Err(Error::Unauthorized {
+            sender: info.sender.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manager::Manager;
+    use crate::test_utils::{addr, info, Deposits, MockDeps};
+    use cosmwasm_std::testing::{mock_dependencies, mock_env};
+    use serde_json::json;
+
+    struct Setup {
+        deps: MockDeps,
+        manager: Manager,
+    }
+
+    impl Setup {
+        /// A contract where only minters may send `mint` to `token`.
+        fn new() -> Self {
+            let manager = Manager::builder()
+                .with_boxed_module("rbac", Box::new(Rbac::new()))
+                .unwrap()
+                .with_boxed_module("token", Box::new(Deposits))
+                .unwrap()
+                .with_middleware(Box::new(Rbac::new()))
+                .build();
+            let mut setup = Setup {
+                deps: mock_dependencies(),
+                manager,
+            };
+            admin::set_admin(&mut setup.deps.storage, &addr("admin")).unwrap();
+            let permission = json!({"rbac": {"set_permission": {
+                "module": "token",
+                "action": "mint",
+                "roles": ["minter"],
+            }}});
+            setup.execute("admin", permission).unwrap();
+            setup
+        }
+
+        fn execute(&mut self, sender: &str, msg: Value) -> Result<(), Error> {
+            let msg = msg.to_string();
+            self.manager
+                .execute(&mut self.deps.as_mut(), mock_env(), info(sender, &[]), &msg)
+                .map(|_| ())
+        }
+
+        fn grant(&mut self, role: &str, address: &str) {
+            let msg = json!({"rbac": {"grant": {"role": role, "addresses": [addr(address)]}}});
+            self.execute("admin", msg).unwrap();
+        }
+    }
+
+    #[test]
+    fn permissions_gate_actions_by_role() {
+        let mut setup = Setup::new();
+        let mint = json!({"token": {"mint": {}}});
+        let err = setup.execute("alice", mint.clone()).unwrap_err();
+        assert!(matches!(err, Error::Unauthorized { .. }), "{:?}", err);
+        setup
+            .execute("alice", json!({"token": {"burn": {}}}))
+            .unwrap();
+
+        setup.grant("minter", "alice");
+        setup.execute("alice", mint.clone()).unwrap();
+
+        let renounce = json!({"rbac": {"renounce": {"role": "minter"}}});
+        setup.execute("alice", renounce).unwrap();
+        setup.execute("alice", mint).unwrap_err();
+    }
+
+    #[test]
+    fn wildcard_permissions_cover_other_actions() {
+        let mut setup = Setup::new();
+        let permission = json!({"rbac": {"set_permission": {
+            "module": "token",
+            "action": ANY_ACTION,
+            "roles": ["operator"],
+        }}});
+        setup.execute("admin", permission).unwrap();
+        setup.grant("operator", "bob");
+        setup
+            .execute("bob", json!({"token": {"burn": {}}}))
+            .unwrap();
+        setup
+            .execute("alice", json!({"token": {"burn": {}}}))
+            .unwrap_err();
+        setup
+            .execute("bob", json!({"token": {"mint": {}}}))
+            .unwrap_err();
+    }
+
+    #[test]
+    fn only_the_admin_grants_roles() {
+        let mut setup = Setup::new();
+        let grant = json!({"rbac": {"grant": {"role": "minter", "addresses": [addr("alice")]}}});
+        setup.execute("alice", grant).unwrap_err();
+        assert!(!has_role(&setup.deps.storage, &addr("alice"), "minter").unwrap());
+        let err = require_role(&setup.deps.storage, &addr("alice"), "minter").unwrap_err();
+        assert!(matches!(err, Error::MissingRole { .. }), "{:?}", err);
+    }
+}
@@ -0,0 +1,448 @@
+//! Royalty configuration and payout splitting for NFT sales.
+//!
+//! [Royalties] stores royalty configs, i.e. recipients with their share in
+//! basis points, per collection and optionally per token, with token configs
+//! taking precedence. The [admin][crate::admin] maintains them:
+//!
+//! ```javascript
+//! { "royalties": { "set_collection_royalty": { "collection": "...", "recipients": [
+//!     { "address": "...", "bps": 250 }
+//! ] } } }
+//! ```
+//!
+//! Marketplace modules split a sale with [distribute], or over the query bus
+//! with [QueryMsg::Distribution], and attach the resulting messages to their
+//! response:
+//!
+//! ```ignore
+//! let distribution = royalties::distribute(deps.storage, &collection, Some(&token_id), &price)?;
+//! let resp = Response::new()
+//!     .add_messages(distribution.messages())
+//!     .add_message(BankMsg::Send {
+//!         to_address: seller.to_string(),
+//!         amount: vec![distribution.remainder],
+//!     });
+//! ```
+
+use crate::admin;
+use crate::error::Error;
+use crate::module::Module;
+use crate::response::Response;
+use crate::storage;
+use cosmwasm_std::{
+    Addr, BankMsg, Coin, CustomQuery, Deps, DepsMut, Env, MessageInfo, StdError, StdResult,
+    Storage,
+};
+use serde::{Deserialize, Serialize};
+
+const NAMESPACE: &[u8] = b"royalties";
+
+/// The basis points making up the whole sale amount.
+pub const MAX_BPS: u16 = 10_000;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct InstantiateMsg {}
+
+/// A royalty recipient, as given in messages.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct RecipientMsg {
+    pub address: String,
+    pub bps: u16,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecuteMsg {
+    SetCollectionRoyalty {
+        collection: String,
+        recipients: Vec<RecipientMsg>,
+    },
+    /// Override the collection's royalty for a single token.
+    SetTokenRoyalty {
+        collection: String,
+        token_id: String,
+        recipients: Vec<RecipientMsg>,
+    },
+    RemoveCollectionRoyalty {
+        collection: String,
+    },
+    RemoveTokenRoyalty {
+        collection: String,
+        token_id: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMsg {
+    /// The royalty applying to `token_id`, or to the collection as a whole.
+    Royalty {
+        collection: String,
+        #[serde(default)]
+        token_id: Option<String>,
+    },
+    /// How a sale for `sale` splits between the royalty recipients and the
+    /// seller.
+    Distribution {
+        collection: String,
+        #[serde(default)]
+        token_id: Option<String>,
+        sale: Coin,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum QueryResp {
+    Royalty { royalty: Option<Royalty> },
+    Distribution(Distribution),
+}
+
+/// A royalty recipient.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Recipient {
+    pub address: Addr,
+    pub bps: u16,
+}
+
+/// A royalty config.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Royalty {
+    pub recipients: Vec<Recipient>,
+}
+
+/// A payout to a royalty recipient.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Payout {
+    pub address: Addr,
+    pub amount: Coin,
+}
+
+/// The split of a sale amount.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Distribution {
+    pub payouts: Vec<Payout>,
+    /// What is left for the seller.
+    pub remainder: Coin,
+}
+
+impl Distribution {
+    /// The messages paying out the royalties. Payouts rounding down to zero
+    /// are skipped.
+    pub fn messages(&self) -> Vec<BankMsg> {
+        self.payouts
+            .iter()
+            .filter(|payout| !payout.amount.amount.is_zero())
+            .map(|payout| BankMsg::Send {
+                to_address: payout.address.to_string(),
+                amount: vec![payout.amount.clone()],
+            })
+            .collect()
+    }
+}
+
+/// A module storing royalty configs.
+#[derive(Clone, Debug, Default)]
+pub struct Royalties {}
+
+impl Royalties {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+fn collection_key(collection: &str) -> Vec<u8> {
+    storage::key(&[NAMESPACE, b"collections"], collection.as_bytes())
+}
+
+fn token_key(collection: &str, token_id: &str) -> Vec<u8> {
+    storage::key(&[NAMESPACE, b"tokens", collection.as_bytes()], token_id.as_bytes())
+}
+
+/// Load the royalty applying to `token_id` in `collection`, or to the
+/// collection as a whole if `token_id` is `None` or has no royalty of its
+/// own.
+pub fn royalty(
+    storage: &dyn Storage,
+    collection: &str,
+    token_id: Option<&str>,
+) -> StdResult<Option<Royalty>> {
+    if let Some(token_id) = token_id {
+        if let Some(royalty) = storage::load(storage, &token_key(collection, token_id))? {
+            return Ok(Some(royalty));
+        }
+    }
+    storage::load(storage, &collection_key(collection))
+}
+
+/// Split `sale` between the royalty recipients of `token_id` in
+/// `collection` and the seller. Payouts are rounded down.
+pub fn distribute(
+    storage: &dyn Storage,
+    collection: &str,
+    token_id: Option<&str>,
+    sale: &Coin,
+) -> StdResult<Distribution> {
+    let recipients = royalty(storage, collection, token_id)?
+        .map(|royalty| royalty.recipients)
+        .unwrap_or_default();
+    let mut remainder = sale.amount;
+    let payouts = recipients
+        .into_iter()
+        .map(|recipient| {
+            let amount = sale.amount.multiply_ratio(recipient.bps, MAX_BPS);
+            remainder = remainder.checked_sub(amount)?;
+            Ok(Payout {
+                address: recipient.address,
+                amount: Coin {
+                    denom: sale.denom.clone(),
+                    amount,
+                },
+            })
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(Distribution {
+        payouts,
+        remainder: Coin {
+            denom: sale.denom.clone(),
+            amount: remainder,
+        },
+    })
+}
+
+fn validate<Q: CustomQuery>(
+    deps: &DepsMut<Q>,
+    recipients: Vec<RecipientMsg>,
+) -> StdResult<Royalty> {
+    let total: u32 = recipients.iter().map(|r| u32::from(r.bps)).sum();
+    if total > u32::from(MAX_BPS) {
+        return Err(StdError::generic_err(format!(
+            "royalties of {} bps exceed {} bps",
+            total, MAX_BPS
+        )));
+    }
+    let recipients = recipients
+        .into_iter()
+        .map(|r| {
+            Ok(Recipient {
+                address: deps.api.addr_validate(&r.address)?,
+                bps: r.bps,
+            })
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(Royalty { recipients })
+}
+
+impl<C: Clone, Q: CustomQuery> Module<C, Q> for Royalties {
+    type InstantiateMsg = InstantiateMsg;
+    type ExecuteMsg = ExecuteMsg;
+    type QueryMsg = QueryMsg;
+    type QueryResp = QueryResp;
+    type Error = Error;
+
+    fn instantiate(
+        &mut self,
+        _deps: &mut DepsMut<Q>,
+        _env: &Env,
+        _info: &MessageInfo,
+        _msg: InstantiateMsg,
+    ) -> Result<Response<C>, Error> {
+        Ok(Response::new())
+    }
+
+    fn execute(
+        &mut self,
+        deps: &mut DepsMut<Q>,
+        _env: Env,
+        info: MessageInfo,
+        msg: ExecuteMsg,
+    ) -> Result<Response<C>, Error> {
+        admin::assert_admin(deps.storage, &info.sender)?;
+        match msg {
+            ExecuteMsg::SetCollectionRoyalty {
+                collection,
+                recipients,
+            } => {
+                let royalty = validate(deps, recipients)?;
+                storage::save(deps.storage, &collection_key(&collection), &royalty)?;
+                Ok(Response::new()
+                    .add_attribute("action", "set_collection_royalty")
+                    .add_attribute("collection", collection))
+            }
+            ExecuteMsg::SetTokenRoyalty {
+                collection,
+                token_id,
+                recipients,
+            } => {
+                let royalty = validate(deps, recipients)?;
+                storage::save(deps.storage, &token_key(&collection, &token_id), &royalty)?;
+                Ok(Response::new()
+                    .add_attribute("action", "set_token_royalty")
+                    .add_attribute("collection", collection)
+                    .add_attribute("token_id", token_id))
+            }
+            ExecuteMsg::RemoveCollectionRoyalty { collection } => {
+                storage::remove(deps.storage, &collection_key(&collection));
+                Ok(Response::new()
+                    .add_attribute("action", "remove_collection_royalty")
+                    .add_attribute("collection", collection))
+            }
+            ExecuteMsg::RemoveTokenRoyalty {
+                collection,
+                token_id,
+            } => {
+                storage::remove(deps.storage, &token_key(&collection, &token_id));
+                Ok(Response::new()
+                    .add_attribute("action", "remove_token_royalty")
+                    .add_attribute("collection", collection)
+                    .add_attribute("token_id", token_id))
+            }
+        }
+    }
+
+    fn query(&self, deps: &Deps<Q>, _env: Env, msg: QueryMsg) -> Result<QueryResp, Error> {
+        match msg {
+            QueryMsg::Royalty {
+                collection,
+                token_id,
+            } => Ok(QueryResp::Royalty {
+                royalty: royalty(deps.storage, &collection, token_id.as_deref())?,
+            }),
+            QueryMsg::Distribution {
+                collection,
+                token_id,
+                sale,
+            } => Ok(QueryResp::Distribution(distribute(
+                deps.storage,
+                &collection,
+                token_id.as_deref(),
+                &sale,
+            )?)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manager::Manager;
+    use crate::test_utils::{addr, info, MockDeps};
+    use cosmwasm_std::testing::{mock_dependencies, mock_env};
+    use cosmwasm_std::{coin, from_json};
+    use serde_json::{json, Value};
+
+    struct Setup {
+        deps: MockDeps,
+        manager: Manager,
+    }
+
+    impl Setup {
+        /// A contract where the punks collection pays 2.5% to the artist and
+        /// 1% to the dao.
+        fn new() -> Self {
+            let manager = Manager::builder()
+                .with_boxed_module("royalties", Box::new(Royalties::new()))
+                .unwrap()
+                .build();
+            let mut setup = Setup {
+                deps: mock_dependencies(),
+                manager,
+            };
+            admin::set_admin(&mut setup.deps.storage, &addr("admin")).unwrap();
+            setup
+                .execute(
+                    "admin",
+                    json!({"set_collection_royalty": {
+                        "collection": "punks",
+                        "recipients": [
+                            {"address": addr("artist"), "bps": 250},
+                            {"address": addr("dao"), "bps": 100},
+                        ],
+                    }}),
+                )
+                .unwrap();
+            setup
+        }
+
+        fn execute(&mut self, sender: &str, msg: Value) -> Result<(), Error> {
+            let msg = json!({ "royalties": msg }).to_string();
+            self.manager
+                .execute(&mut self.deps.as_mut(), mock_env(), info(sender, &[]), &msg)
+                .map(|_| ())
+        }
+
+        fn distribution(&mut self, token_id: &str) -> Distribution {
+            let msg = json!({"royalties": {"distribution": {
+                "collection": "punks",
+                "token_id": token_id,
+                "sale": coin(1000, "uxion"),
+            }}});
+            let resp = self
+                .manager
+                .query(&self.deps.as_ref(), mock_env(), &msg.to_string())
+                .unwrap();
+            from_json(resp).unwrap()
+        }
+    }
+
+    fn payout(name: &str, amount: u128) -> Payout {
+        Payout {
+            address: addr(name),
+            amount: coin(amount, "uxion"),
+        }
+    }
+
+    #[test]
+    fn sales_split_between_recipients_and_seller() {
+        let mut setup = Setup::new();
+        let distribution = setup.distribution("1");
+        assert_eq!(
+            distribution,
+            Distribution {
+                payouts: vec![payout("artist", 25), payout("dao", 10)],
+                remainder: coin(965, "uxion"),
+            }
+        );
+        assert_eq!(
+            distribution.messages()[0],
+            BankMsg::Send {
+                to_address: addr("artist").to_string(),
+                amount: vec![coin(25, "uxion")],
+            }
+        );
+    }
+
+    #[test]
+    fn token_royalties_override_the_collection() {
+        let mut setup = Setup::new();
+        let set = json!({"set_token_royalty": {
+            "collection": "punks",
+            "token_id": "1",
+            "recipients": [{"address": addr("artist"), "bps": 1000}],
+        }});
+        setup.execute("admin", set).unwrap();
+
+        assert_eq!(setup.distribution("1").payouts, [payout("artist", 100)]);
+        assert_eq!(setup.distribution("2").payouts.len(), 2);
+
+        let remove = json!({"remove_token_royalty": {"collection": "punks", "token_id": "1"}});
+        setup.execute("admin", remove).unwrap();
+        assert_eq!(setup.distribution("1").payouts.len(), 2);
+    }
+
+    #[test]
+    fn royalties_are_capped_and_admin_only() {
+        let mut setup = Setup::new();
+        let set = |bps: u16| {
+            json!({"set_collection_royalty": {
+                "collection": "punks",
+                "recipients": [{"address": addr("artist"), "bps": bps}],
+            }})
+        };
+        let err = setup.execute("admin", set(MAX_BPS + 1)).unwrap_err();
+        assert!(err.to_string().contains("exceed"), "{}", err);
+        let err = setup.execute("artist", set(MAX_BPS)).unwrap_err();
+        assert!(err.to_string().contains("is not authorized"), "{}", err);
+        assert_eq!(setup.distribution("1").remainder, coin(965, "uxion"));
+    }
+}
@@ -0,0 +1,401 @@
+//! Signature verification.
+//!
+//! [Signatures] verifies secp256k1 and ed25519 signatures over messages
+//! signed as is, or in the formats wallets sign arbitrary data in:
+//!
+//! - [Eip191][MessageFormat::Eip191], as with Ethereum's `personal_sign`,
+//! - [Adr36][MessageFormat::Adr36], as with Keplr's `signArbitrary`.
+//!
+//! Clients check a signature with a query:
+//!
+//! ```javascript
+//! { "signatures": { "verify": {
+//!     "algorithm": "secp256k1",
+//!     "format": { "adr36": { "signer": "xion1..." } },
+//!     "message": "aGVsbG8=",
+//!     "signature": "...",
+//!     "public_key": "..."
+//! } } }
+//! ```
+//!
+//! Sibling modules, e.g. an airdrop checking claims, verify signatures in
+//! process through the [SignatureVerifier] interface, or call [verify]
+//! directly:
+//!
+//! ```ignore
+//! let signatures = shared(Signatures::new());
+//! manager.register("signatures".to_string(), signatures.clone())?;
+//! manager.register_interface::<dyn SignatureVerifier>(SIGNATURES_INTERFACE, signatures)?;
+//! ```
+
+use crate::error::Error;
+use crate::json;
+use crate::module::{Capabilities, Module};
+use crate::response::Response;
+use crate::shared::MaybeSendSync;
+use cosmwasm_std::{Api, Binary, CustomQuery, Deps, DepsMut, Env, MessageInfo, StdError};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sha3::Keccak256;
+
+/// The interface name a [SignatureVerifier] is looked up under.
+pub const SIGNATURES_INTERFACE: &str = "signatures";
+
+/// Verification of signatures for sibling modules.
+pub trait SignatureVerifier: MaybeSendSync {
+    /// Whether the signature of `verification` is valid.
+    fn verify(&self, api: &dyn Api, verification: &Verification) -> Result<bool, Error> {
+        verify(api, verification)
+    }
+    /// The hash of `message` signed in `format`; see [message_hash].
+    fn message_hash(&self, format: &MessageFormat, message: &[u8]) -> Result<Vec<u8>, Error> {
+        message_hash(format, message)
+    }
+}
+
+/// A signature scheme.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Algorithm {
+    Secp256k1,
+    Ed25519,
+}
+
+/// How a message is wrapped before it is signed.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageFormat {
+    /// The message is signed as is.
+    Raw,
+    /// The message is prefixed with `"\x19Ethereum Signed Message:\n"` and
+    /// its length, as specified by EIP-191.
+    Eip191,
+    /// The message is the data of a `sign/MsgSignData` in an otherwise empty
+    /// Amino sign document, as specified by ADR-36.
+    Adr36 { signer: String },
+}
+
+/// A signature to verify.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Verification {
+    pub algorithm: Algorithm,
+    pub format: MessageFormat,
+    pub message: Binary,
+    /// The signature. Recoverable secp256k1 signatures, 65 bytes long, are
+    /// accepted too.
+    pub signature: Binary,
+    pub public_key: Binary,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct InstantiateMsg {}
+
+/// [Signatures] handles no execute messages.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecuteMsg {}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMsg {
+    Verify(Verification),
+    /// The hash of `message` signed in `format`, i.e. what secp256k1
+    /// signatures of it sign.
+    MessageHash {
+        format: MessageFormat,
+        message: Binary,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct VerifyResponse {
+    pub valid: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct MessageHashResponse {
+    pub hash: Binary,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum QueryResp {
+    Verify(VerifyResponse),
+    MessageHash(MessageHashResponse),
+}
+
+/// A query-only module verifying signatures.
+#[derive(Clone, Debug, Default)]
+pub struct Signatures {}
+
+impl Signatures {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// The fields of an ADR-36 sign document, in the sorted order they are
+/// signed in.
+#[derive(Serialize)]
+struct Adr36Doc<'a> {
+    account_number: &'a str,
+    chain_id: &'a str,
+    fee: Adr36Fee<'a>,
+    memo: &'a str,
+    msgs: [Adr36Msg<'a>; 1],
+    sequence: &'a str,
+}
+
+#[derive(Serialize)]
+struct Adr36Fee<'a> {
+    amount: &'a [String],
+    gas: &'a str,
+}
+
+#[derive(Serialize)]
+struct Adr36Msg<'a> {
+    #[serde(rename = "type")]
+    kind: &'a str,
+    value: Adr36Data<'a>,
+}
+
+#[derive(Serialize)]
+struct Adr36Data<'a> {
+    data: Binary,
+    signer: &'a str,
+}
+
+/// The SHA-256 hash of `data`.
+pub fn sha256(data: &[u8]) -> Vec<u8> {
+    Sha256::digest(data).to_vec()
+}
+
+/// The Keccak-256 hash of `data`, as used by Ethereum.
+pub fn keccak256(data: &[u8]) -> Vec<u8> {
+    Keccak256::digest(data).to_vec()
+}
+
+/// The bytes actually signed for `message` in `format`.
+pub fn sign_bytes(format: &MessageFormat, message: &[u8]) -> Result<Vec<u8>, Error> {
+    match format {
+        MessageFormat::Raw => Ok(message.to_vec()),
+        MessageFormat::Eip191 => {
+            let mut bytes = format!("\x19Ethereum Signed Message:\n{}", message.len()).into_bytes();
+            bytes.extend_from_slice(message);
+            Ok(bytes)
+        }
+        MessageFormat::Adr36 { signer } => {
+            let doc = Adr36Doc {
+                account_number: "0",
+                chain_id: "",
+                fee: Adr36Fee {
+                    amount: &[],
+                    gas: "0",
+                },
+                memo: "",
+                msgs: [Adr36Msg {
+                    kind: "sign/MsgSignData",
+                    value: Adr36Data {
+                        data: Binary::from(message),
+                        signer,
+                    },
+                }],
+                sequence: "0",
+            };
+            json::to_vec(&doc)
+        }
+    }
+}
+
+/// The hash of `message` signed in `format`: the Keccak-256 hash of its
+/// [sign_bytes] for EIP-191, and their SHA-256 hash otherwise.
+pub fn message_hash(format: &MessageFormat, message: &[u8]) -> Result<Vec<u8>, Error> {
+    let bytes = sign_bytes(format, message)?;
+    match format {
+        MessageFormat::Eip191 => Ok(keccak256(&bytes)),
+        _ => Ok(sha256(&bytes)),
+    }
+}
+
+/// Whether the signature of `verification` is valid. secp256k1 signatures
+/// sign the [message_hash], ed25519 signatures the [sign_bytes].
+pub fn verify(api: &dyn Api, verification: &Verification) -> Result<bool, Error> {
+    let Verification {
+        algorithm,
+        format,
+        message,
+        signature,
+        public_key,
+    } = verification;
+    let valid = match algorithm {
+        Algorithm::Secp256k1 => {
+            let signature = match signature.len() {
+                65 => &signature[..64],
+                _ => signature.as_slice(),
+            };
+            let hash = message_hash(format, message)?;
+            api.secp256k1_verify(&hash, signature, public_key)
+        }
+        Algorithm::Ed25519 => {
+            let bytes = sign_bytes(format, message)?;
+            api.ed25519_verify(&bytes, signature, public_key)
+        }
+    };
+    Ok(valid.map_err(StdError::from)?)
+}
+
+impl SignatureVerifier for Signatures {}
+
+impl<C: Clone, Q: CustomQuery> Module<C, Q> for Signatures {
+    type InstantiateMsg = InstantiateMsg;
+    type ExecuteMsg = ExecuteMsg;
+    type QueryMsg = QueryMsg;
+    type QueryResp = QueryResp;
+    type Error = Error;
+
+    fn instantiate(
+        &mut self,
+        _deps: &mut DepsMut<Q>,
+        _env: &Env,
+        _info: &MessageInfo,
+        _msg: InstantiateMsg,
+    ) -> Result<Response<C>, Error> {
+        Ok(Response::new())
+    }
+
+    fn execute(
+        &mut self,
+        _deps: &mut DepsMut<Q>,
+        _env: Env,
+        _info: MessageInfo,
+        msg: ExecuteMsg,
+    ) -> Result<Response<C>, Error> {
+        match msg {}
+    }
+
+    fn query(&self, deps: &Deps<Q>, _env: Env, msg: QueryMsg) -> Result<QueryResp, Error> {
+        match msg {
+            QueryMsg::Verify(verification) => Ok(QueryResp::Verify(VerifyResponse {
+                valid: verify(deps.api, &verification)?,
+            })),
+            QueryMsg::MessageHash { format, message } => {
+                Ok(QueryResp::MessageHash(MessageHashResponse {
+                    hash: message_hash(&format, &message)?.into(),
+                }))
+            }
+        }
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            execute: false,
+            payable: false,
+            ..Capabilities::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manager::Manager;
+    use crate::test_utils::{addr, MockDeps};
+    use cosmwasm_std::from_json;
+    use cosmwasm_std::testing::{mock_dependencies, mock_env};
+    use k256::ecdsa::signature::hazmat::PrehashSigner;
+    use k256::ecdsa::{Signature, SigningKey};
+    use serde_json::{json, Value};
+
+    struct Setup {
+        deps: MockDeps,
+        manager: Manager,
+        key: SigningKey,
+    }
+
+    impl Setup {
+        fn new() -> Self {
+            let manager = Manager::builder()
+                .with_boxed_module("signatures", Box::new(Signatures::new()))
+                .unwrap()
+                .build();
+            Setup {
+                deps: mock_dependencies(),
+                manager,
+                key: SigningKey::from_slice(&[7; 32]).unwrap(),
+            }
+        }
+
+        /// A secp256k1 signature of `message` in `format`.
+        fn sign(&self, format: &MessageFormat, message: &[u8]) -> Verification {
+            let hash = message_hash(format, message).unwrap();
+            let signature: Signature = self.key.sign_prehash(&hash).unwrap();
+            Verification {
+                algorithm: Algorithm::Secp256k1,
+                format: format.clone(),
+                message: Binary::from(message),
+                signature: Binary::from(signature.to_bytes().to_vec()),
+                public_key: Binary::from(self.key.verifying_key().to_sec1_bytes().to_vec()),
+            }
+        }
+
+        fn query(&mut self, msg: QueryMsg) -> Value {
+            let msg = json!({ "signatures": msg }).to_string();
+            let resp = self
+                .manager
+                .query(&self.deps.as_ref(), mock_env(), &msg)
+                .unwrap();
+            from_json(resp).unwrap()
+        }
+
+        fn valid(&mut self, verification: Verification) -> bool {
+            self.query(QueryMsg::Verify(verification))["valid"] == json!(true)
+        }
+    }
+
+    #[test]
+    fn secp256k1_signatures_verify_in_every_format() {
+        let mut setup = Setup::new();
+        let formats = [
+            MessageFormat::Raw,
+            MessageFormat::Eip191,
+            MessageFormat::Adr36 {
+                signer: addr("alice").to_string(),
+            },
+        ];
+        for format in formats {
+            let verification = setup.sign(&format, b"hello");
+            assert!(setup.valid(verification.clone()), "{:?}", format);
+
+            let mut recoverable = verification.clone();
+            let mut signature = recoverable.signature.to_vec();
+            signature.push(1);
+            recoverable.signature = signature.into();
+            assert!(setup.valid(recoverable), "{:?}", format);
+
+            let mut tampered = verification;
+            tampered.message = Binary::from(b"hellp".to_vec());
+            assert!(!setup.valid(tampered), "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn signatures_only_verify_in_their_format() {
+        let mut setup = Setup::new();
+        let mut verification = setup.sign(&MessageFormat::Raw, b"hello");
+        verification.format = MessageFormat::Eip191;
+        assert!(!setup.valid(verification));
+    }
+
+    #[test]
+    fn eip191_messages_hash_like_personal_sign() {
+        let mut setup = Setup::new();
+        let resp = setup.query(QueryMsg::MessageHash {
+            format: MessageFormat::Eip191,
+            message: Binary::from(b"hello".to_vec()),
+        });
+        let expected = "50b2c43fd39106bafbba0da34fc430e1f91e3c96ea2acee2bc34119f92b37750";
+        let hash: Binary = serde_json::from_value(resp["hash"].clone()).unwrap();
+        let hex: String = hash.iter().map(|b| format!("{:02x}", b)).collect();
+        assert_eq!(hex, expected);
+    }
+}
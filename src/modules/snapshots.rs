@@ -0,0 +1,321 @@
+//! Point-in-time values.
+//!
+//! [Snapshots] keeps numeric values, such as balances or weights, in named
+//! series, and remembers what they were at any past height. Modules that
+//! track values others need historically, e.g. a token whose balances
+//! weigh governance votes, write them through [set] or [update], or the
+//! [SnapshotStore] interface:
+//!
+//! ```ignore
+//! let snapshots = shared(Snapshots::new());
+//! manager.register("snapshots".to_string(), snapshots.clone())?;
+//! manager.register_interface::<dyn SnapshotStore>(SNAPSHOTS_INTERFACE, snapshots)?;
+//!
+//! // In a handler:
+//! snapshots::set(deps.storage, env.block.height, "balances", sender.as_str(), balance)?;
+//! ```
+//!
+//! Modules reading them, e.g. governance weighing votes by the balances at
+//! the height a proposal was created, call [value_at], and clients query
+//! them:
+//!
+//! ```javascript
+//! { "snapshots": { "value_at": { "series": "balances", "key": "xion1...", "height": 123456 } } }
+//! ```
+//!
+//! As with `SnapshotMap` in `cw-storage-plus`, the value at a height is the
+//! value at the beginning of that block, before any change made in it. Every
+//! value starts at zero.
+
+use crate::error::Error;
+use crate::module::{Capabilities, Module};
+use crate::response::Response;
+use crate::shared::MaybeSendSync;
+use crate::storage;
+use cosmwasm_std::{
+    CustomQuery, Deps, DepsMut, Env, MessageInfo, StdError, StdResult, Storage, Uint128,
+};
+use serde::{Deserialize, Serialize};
+
+const NAMESPACE: &[u8] = b"snapshots";
+
+/// The interface name a [SnapshotStore] is looked up under.
+pub const SNAPSHOTS_INTERFACE: &str = "snapshots";
+
+/// Access to point-in-time values for sibling modules.
+pub trait SnapshotStore: MaybeSendSync {
+    /// The current value of `key` in `series`.
+    fn value(&self, storage: &dyn Storage, series: &str, key: &str) -> StdResult<Uint128> {
+        value(storage, series, key)
+    }
+    /// The value of `key` in `series` at the beginning of block `height`.
+    fn value_at(
+        &self,
+        storage: &dyn Storage,
+        series: &str,
+        key: &str,
+        height: u64,
+    ) -> StdResult<Uint128> {
+        value_at(storage, series, key, height)
+    }
+    /// Set the value of `key` in `series` to `value` as of block `height`,
+    /// the current one.
+    fn set(
+        &self,
+        storage: &mut dyn Storage,
+        height: u64,
+        series: &str,
+        key: &str,
+        value: Uint128,
+    ) -> StdResult<()> {
+        set(storage, height, series, key, value)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct InstantiateMsg {}
+
+/// [Snapshots] handles no execute messages; values are written by sibling
+/// modules.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecuteMsg {}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMsg {
+    /// The current value of `key` in `series`.
+    Value { series: String, key: String },
+    /// The value of `key` in `series` at the beginning of block `height`.
+    ValueAt {
+        series: String,
+        key: String,
+        height: u64,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ValueResponse {
+    pub value: Uint128,
+}
+
+/// A module keeping the history of numeric values.
+#[derive(Clone, Debug, Default)]
+pub struct Snapshots {}
+
+impl Snapshots {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+fn value_key(series: &str, key: &str) -> Vec<u8> {
+    storage::key(&[NAMESPACE, b"values", series.as_bytes()], key.as_bytes())
+}
+
+fn changelog_key(series: &str, key: &str, height: u64) -> Vec<u8> {
+    storage::key(
+        &[NAMESPACE, b"changelog", series.as_bytes(), key.as_bytes()],
+        &height.to_be_bytes(),
+    )
+}
+
+/// The current value of `key` in `series`.
+pub fn value(storage: &dyn Storage, series: &str, key: &str) -> StdResult<Uint128> {
+    Ok(storage::load(storage, &value_key(series, key))?.unwrap_or_default())
+}
+
+/// The value of `key` in `series` at the beginning of block `height`.
+pub fn value_at(storage: &dyn Storage, series: &str, key: &str, height: u64) -> StdResult<Uint128> {
+    // The changelog records the value before the first change in each block
+    // that changed it, so the first entry from `height` on holds the value
+    // at `height`. Without one, the value has not changed since.
+    let start_after = height.checked_sub(1).map(u64::to_be_bytes);
+    let changes = storage::range::<Uint128>(
+        storage,
+        &[NAMESPACE, b"changelog", series.as_bytes(), key.as_bytes()],
+        start_after.as_ref().map(|height| &height[..]),
+        1,
+    )?;
+    match changes.into_iter().next() {
+        Some((_, value)) => Ok(value),
+        None => value(storage, series, key),
+    }
+}
+
+/// Set the value of `key` in `series` to `value` as of block `height`, which
+/// must be the current one.
+pub fn set(
+    storage: &mut dyn Storage,
+    height: u64,
+    series: &str,
+    key: &str,
+    value: Uint128,
+) -> StdResult<()> {
+    let change = changelog_key(series, key, height);
+    if storage.get(&change).is_none() {
+        let previous = self::value(storage, series, key)?;
+        storage::save(storage, &change, &previous)?;
+    }
+    storage::save(storage, &value_key(series, key), &value)
+}
+
+/// Replace the value of `key` in `series` with the result of `action` on it,
+/// as of block `height`, and return the new value.
+pub fn update<E: From<StdError>>(
+    storage: &mut dyn Storage,
+    height: u64,
+    series: &str,
+    key: &str,
+    action: impl FnOnce(Uint128) -> Result<Uint128, E>,
+) -> Result<Uint128, E> {
+    let value = action(self::value(storage, series, key)?)?;
+    set(storage, height, series, key, value)?;
+    Ok(value)
+}
+
+impl SnapshotStore for Snapshots {}
+
+impl<C: Clone, Q: CustomQuery> Module<C, Q> for Snapshots {
+    type InstantiateMsg = InstantiateMsg;
+    type ExecuteMsg = ExecuteMsg;
+    type QueryMsg = QueryMsg;
+    type QueryResp = ValueResponse;
+    type Error = Error;
+
+    fn instantiate(
+        &mut self,
+        _deps: &mut DepsMut<Q>,
+        _env: &Env,
+        _info: &MessageInfo,
+        _msg: InstantiateMsg,
+    ) -> Result<Response<C>, Error> {
+        Ok(Response::new())
+    }
+
+    fn execute(
+        &mut self,
+        _deps: &mut DepsMut<Q>,
+        _env: Env,
+        _info: MessageInfo,
+        msg: ExecuteMsg,
+    ) -> Result<Response<C>, Error> {
+        match msg {}
+    }
+
+    fn query(&self, deps: &Deps<Q>, _env: Env, msg: QueryMsg) -> Result<ValueResponse, Error> {
+        let value = match msg {
+            QueryMsg::Value { series, key } => value(deps.storage, &series, &key)?,
+            QueryMsg::ValueAt {
+                series,
+                key,
+                height,
+            } => value_at(deps.storage, &series, &key, height)?,
+        };
+        Ok(ValueResponse { value })
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            execute: false,
+            ..Capabilities::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manager::Manager;
+    use crate::test_utils::MockDeps;
+    use cosmwasm_std::from_json;
+    use cosmwasm_std::testing::{mock_dependencies, mock_env};
+    use serde_json::{json, Value};
+
+    struct Setup {
+        deps: MockDeps,
+        manager: Manager,
+    }
+
+    impl Setup {
+        fn new() -> Self {
+            let manager = Manager::builder()
+                .with_boxed_module("snapshots", Box::new(Snapshots::new()))
+                .unwrap()
+                .build();
+            Setup {
+                deps: mock_dependencies(),
+                manager,
+            }
+        }
+
+        fn query(&mut self, msg: Value) -> Uint128 {
+            let msg = json!({ "snapshots": msg }).to_string();
+            let resp = self
+                .manager
+                .query(&self.deps.as_ref(), mock_env(), &msg)
+                .unwrap();
+            from_json::<ValueResponse>(resp).unwrap().value
+        }
+
+        fn value_at(&mut self, key: &str, height: u64) -> u128 {
+            let msg = json!({"value_at": {"series": "balances", "key": key, "height": height}});
+            self.query(msg).u128()
+        }
+    }
+
+    #[test]
+    fn values_are_read_as_of_the_beginning_of_a_block() {
+        let mut setup = Setup::new();
+        let storage = &mut setup.deps.storage;
+        set(storage, 10, "balances", "alice", Uint128::new(100)).unwrap();
+        set(storage, 10, "balances", "alice", Uint128::new(150)).unwrap();
+        let add = |value: Uint128| Ok::<_, StdError>(value.checked_add(Uint128::new(50))?);
+        let updated = update(storage, 20, "balances", "alice", add).unwrap();
+        assert_eq!(updated, Uint128::new(200));
+
+        let history: Vec<u128> = [5, 10, 11, 20, 21]
+            .iter()
+            .map(|height| setup.value_at("alice", *height))
+            .collect();
+        assert_eq!(history, [0, 0, 150, 150, 200]);
+        assert_eq!(
+            setup.query(json!({"value": {"series": "balances", "key": "alice"}})),
+            Uint128::new(200)
+        );
+    }
+
+    #[test]
+    fn series_and_keys_are_kept_apart() {
+        let mut setup = Setup::new();
+        let store = Snapshots::new();
+        store
+            .set(
+                &mut setup.deps.storage,
+                10,
+                "balances",
+                "alice",
+                Uint128::new(100),
+            )
+            .unwrap();
+        store
+            .set(
+                &mut setup.deps.storage,
+                10,
+                "weights",
+                "bob",
+                Uint128::new(7),
+            )
+            .unwrap();
+
+        assert_eq!(setup.value_at("bob", 11), 0);
+        assert_eq!(
+            store.value_at(&setup.deps.storage, "weights", "bob", 11),
+            Ok(Uint128::new(7))
+        );
+        assert_eq!(
+            store.value(&setup.deps.storage, "weights", "alice"),
+            Ok(Uint128::zero())
+        );
+    }
+}
@@ -0,0 +1,633 @@
+//! Staking with reward distribution.
+//!
+//! [Staking] lets accounts stake a token and earn a share of the native
+//! rewards paid into the module, proportional to their stake. Rewards are
+//! tracked with a global reward index, so funding and claiming cost the same
+//! regardless of the number of stakers. Unstaked tokens are held for an
+//! unbonding period before they can be withdrawn.
+//!
+//! The staked token is one of:
+//!
+//! - a native token, staked by sending it along with `stake`;
+//! - a cw20 contract, staked by `send`ing tokens with a `{"staking":
+//!   {"stake": {}}}` message; see [receive][crate::receive];
+//! - a token module in the same contract, resolved as a [FungibleToken]
+//!   through the interface registry under [STAKE_TOKEN_INTERFACE].
+//!
+//! ```javascript
+//! { "staking": { "fund": {} } }
+//! { "staking": { "unstake": { "amount": "100" } } }
+//! { "staking": { "withdraw": {} } }
+//! ```
+//!
+//! The module also implements [VotingPower], so stakes can weigh votes in
+//! the [governance][crate::modules::governance] module.
+
+use crate::context::Context;
+use crate::error::Error;
+use crate::module::Module;
+use crate::modules::cw20::{self, Cw20};
+use crate::modules::governance::VotingPower;
+use crate::receive::Received;
+use crate::response::Response;
+use crate::shared::{self, MaybeSendSync};
+use crate::storage;
+use cosmwasm_std::{
+    to_json_binary, Addr, BankMsg, Coin, CosmosMsg, CustomQuery, Decimal, Deps, DepsMut, Env,
+    MessageInfo, StdError, StdResult, Storage, Uint128, WasmMsg,
+};
+use serde::{Deserialize, Serialize};
+
+const NAMESPACE: &[u8] = b"staking";
+
+/// The interface name a [FungibleToken] staked with [StakeToken::Module] is
+/// looked up under.
+pub const STAKE_TOKEN_INTERFACE: &str = "stake_token";
+
+/// A token ledger in the same contract.
+pub trait FungibleToken: MaybeSendSync {
+    /// Move `amount` tokens from `from` to `to`.
+    fn transfer(
+        &self,
+        storage: &mut dyn Storage,
+        from: &Addr,
+        to: &Addr,
+        amount: Uint128,
+    ) -> StdResult<()>;
+}
+
+impl FungibleToken for Cw20 {
+    fn transfer(
+        &self,
+        storage: &mut dyn Storage,
+        from: &Addr,
+        to: &Addr,
+        amount: Uint128,
+    ) -> StdResult<()> {
+        cw20::transfer(storage, from, to, amount)
+    }
+}
+
+/// The token being staked.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StakeToken<A = Addr> {
+    Native { denom: String },
+    Cw20 { contract: A },
+    /// The [FungibleToken] registered as [STAKE_TOKEN_INTERFACE].
+    Module {},
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct InstantiateMsg {
+    pub token: StakeToken<String>,
+    /// The native denom rewards are paid in.
+    pub reward_denom: String,
+    /// How long unstaked tokens are held before they can be withdrawn, in
+    /// seconds.
+    pub unbonding_period: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecuteMsg {
+    /// Stake the tokens sent with the message or, for module tokens,
+    /// `amount` tokens of the sender.
+    Stake {
+        #[serde(default)]
+        amount: Option<Uint128>,
+    },
+    /// Start unbonding `amount` staked tokens.
+    Unstake { amount: Uint128 },
+    /// Withdraw the tokens whose unbonding period is over.
+    Withdraw {},
+    /// Pay out the sender's rewards.
+    ClaimRewards {},
+    /// Distribute the reward tokens sent with the message among the
+    /// current stakers.
+    Fund {},
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMsg {
+    Config {},
+    State {},
+    Staker { address: String },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Config {
+    pub token: StakeToken,
+    pub reward_denom: String,
+    pub unbonding_period: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct State {
+    pub total_staked: Uint128,
+    /// The rewards earned per staked token since instantiation.
+    pub reward_index: Decimal,
+}
+
+/// Tokens being unbonded.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Unbonding {
+    pub amount: Uint128,
+    /// The block time, in seconds, from which the tokens can be withdrawn.
+    pub release_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Staker {
+    pub staked: Uint128,
+    /// The reward index the pending rewards were last brought up to.
+    pub reward_index: Decimal,
+    pub pending_rewards: Uint128,
+    pub unbonding: Vec<Unbonding>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum QueryResp {
+    Config(Config),
+    State(State),
+    Staker(Staker),
+}
+
+/// A module distributing rewards to stakers.
+#[derive(Clone, Debug, Default)]
+pub struct Staking {}
+
+impl Staking {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+fn config_key() -> Vec<u8> {
+    storage::key(&[NAMESPACE], b"config")
+}
+
+fn state_key() -> Vec<u8> {
+    storage::key(&[NAMESPACE], b"state")
+}
+
+fn staker_key(address: &Addr) -> Vec<u8> {
+    storage::key(&[NAMESPACE, b"stakers"], address.as_bytes())
+}
+
+fn config(storage: &dyn Storage) -> StdResult<Config> {
+    storage::load(storage, &config_key())?.ok_or_else(|| StdError::not_found("staking config"))
+}
+
+/// Load the totals of the module.
+pub fn state(storage: &dyn Storage) -> StdResult<State> {
+    Ok(storage::load(storage, &state_key())?.unwrap_or_default())
+}
+
+/// Load the stake of `address`, with its pending rewards brought up to
+/// date.
+pub fn staker(storage: &dyn Storage, address: &Addr) -> StdResult<Staker> {
+    let mut staker: Staker = storage::load(storage, &staker_key(address))?.unwrap_or_default();
+    accrue(&mut staker, &state(storage)?);
+    Ok(staker)
+}
+
+fn save_staker(storage: &mut dyn Storage, address: &Addr, staker: &Staker) -> StdResult<()> {
+    if *staker == Staker::default() {
+        storage::remove(storage, &staker_key(address));
+        Ok(())
+    } else {
+        storage::save(storage, &staker_key(address), staker)
+    }
+}
+
+/// Bring the pending rewards of `staker` up to the reward index of `state`.
+fn accrue(staker: &mut Staker, state: &State) {
+    let earned = staker.staked.mul_floor(state.reward_index - staker.reward_index);
+    staker.pending_rewards += earned;
+    staker.reward_index = state.reward_index;
+}
+
+/// The message paying out `amount` staked tokens to `recipient`, if the
+/// token lives outside the contract. Module tokens are moved right away.
+fn pay_out<C: Clone, Q: CustomQuery>(
+    ctx: &Context<C, Q>,
+    storage: &mut dyn Storage,
+    env: &Env,
+    token: &StakeToken,
+    recipient: &Addr,
+    amount: Uint128,
+) -> Result<Option<CosmosMsg<C>>, Error> {
+    match token {
+        StakeToken::Native { denom } => Ok(Some(
+            BankMsg::Send {
+                to_address: recipient.to_string(),
+                amount: vec![Coin {
+                    denom: denom.clone(),
+                    amount,
+                }],
+            }
+            .into(),
+        )),
+        StakeToken::Cw20 { contract } => {
+            let transfer = cw20::ExecuteMsg::Transfer {
+                recipient: recipient.to_string(),
+                amount,
+            };
+            Ok(Some(
+                WasmMsg::Execute {
+                    contract_addr: contract.to_string(),
+                    msg: to_json_binary(&transfer)?,
+                    funds: vec![],
+                }
+                .into(),
+            ))
+        }
+        StakeToken::Module {} => {
+            transfer_module_token(ctx, storage, &env.contract.address, recipient, amount)?;
+            Ok(None)
+        }
+    }
+}
+
+/// Move module tokens through the [FungibleToken] registered as
+/// [STAKE_TOKEN_INTERFACE].
+fn transfer_module_token<C: Clone, Q: CustomQuery>(
+    ctx: &Context<C, Q>,
+    storage: &mut dyn Storage,
+    from: &Addr,
+    to: &Addr,
+    amount: Uint128,
+) -> Result<(), Error> {
+    let token = ctx.interface::<dyn FungibleToken>(STAKE_TOKEN_INTERFACE)?;
+    let token = shared::try_borrow(&token).ok_or_else(|| Error::ModuleBusy {
+        module: STAKE_TOKEN_INTERFACE.to_string(),
+    })?;
+    Ok(token.transfer(storage, from, to, amount)?)
+}
+
+/// The staker and amount of a `stake` message.
+fn staked_tokens<C: Clone, Q: CustomQuery>(
+    ctx: &Context<C, Q>,
+    deps: &mut DepsMut<Q>,
+    env: &Env,
+    info: &MessageInfo,
+    token: &StakeToken,
+    amount: Option<Uint128>,
+) -> Result<(Addr, Uint128), Error> {
+    match (token, ctx.received()) {
+        (StakeToken::Native { denom }, None) => {
+            let amount = info
+                .funds
+                .iter()
+                .filter(|coin| coin.denom == *denom)
+                .map(|coin| coin.amount)
+                .sum();
+            Ok((info.sender.clone(), amount))
+        }
+        (
+            StakeToken::Cw20 { contract },
+            Some(Received::Cw20 {
+                token,
+                sender,
+                amount,
+            }),
+        ) if token == contract => Ok((deps.api.addr_validate(sender)?, *amount)),
+        (StakeToken::Module {}, None) => {
+            let amount = amount.unwrap_or_default();
+            let contract = &env.contract.address;
+            transfer_module_token(ctx, deps.storage, &info.sender, contract, amount)?;
+            Ok((info.sender.clone(), amount))
+        }
+        _ => Err(StdError::generic_err("sent tokens cannot be staked").into()),
+    }
+}
+
+impl<C: Clone, Q: CustomQuery> Module<C, Q> for Staking {
+    type InstantiateMsg = InstantiateMsg;
+    type ExecuteMsg = ExecuteMsg;
+    type QueryMsg = QueryMsg;
+    type QueryResp = QueryResp;
+    type Error = Error;
+
+    fn instantiate(
+        &mut self,
+        deps: &mut DepsMut<Q>,
+        _env: &Env,
+        _info: &MessageInfo,
+        msg: InstantiateMsg,
+    ) -> Result<Response<C>, Error> {
+        let token = match msg.token {
+            StakeToken::Native { denom } => StakeToken::Native { denom },
+            StakeToken::Cw20 { contract } => StakeToken::Cw20 {
+                contract: deps.api.addr_validate(&contract)?,
+            },
+            StakeToken::Module {} => StakeToken::Module {},
+        };
+        let config = Config {
+            token,
+            reward_denom: msg.reward_denom,
+            unbonding_period: msg.unbonding_period,
+        };
+        storage::save(deps.storage, &config_key(), &config)?;
+        Ok(Response::new())
+    }
+
+    fn execute(
+        &mut self,
+        _deps: &mut DepsMut<Q>,
+        _env: Env,
+        _info: MessageInfo,
+        _msg: ExecuteMsg,
+    ) -> Result<Response<C>, Error> {
+        Err(Error::ParseError {
+            msg: Some("staking requires the dispatch context".to_string()),
+        })
+    }
+
+    fn execute_with_context(
+        &mut self,
+        ctx: &Context<C, Q>,
+        deps: &mut DepsMut<Q>,
+        env: Env,
+        info: MessageInfo,
+        msg: ExecuteMsg,
+    ) -> Result<Response<C>, Error> {
+        let config = config(deps.storage)?;
+        let mut state = state(deps.storage)?;
+        match msg {
+            ExecuteMsg::Stake { amount } => {
+                let (address, amount) =
+                    staked_tokens(ctx, deps, &env, &info, &config.token, amount)?;
+                if amount.is_zero() {
+                    return Err(StdError::generic_err("nothing to stake").into());
+                }
+                let mut staker = staker(deps.storage, &address)?;
+                staker.staked += amount;
+                state.total_staked += amount;
+                save_staker(deps.storage, &address, &staker)?;
+                storage::save(deps.storage, &state_key(), &state)?;
+                Ok(Response::new()
+                    .add_attribute("action", "stake")
+                    .add_attribute("staker", address)
+                    .add_attribute("amount", amount.to_string()))
+            }
+            ExecuteMsg::Unstake { amount } => {
+                let mut staker = staker(deps.storage, &info.sender)?;
+                staker.staked = staker.staked.checked_sub(amount).map_err(StdError::from)?;
+                staker.unbonding.push(Unbonding {
+                    amount,
+                    release_at: env.block.time.seconds() + config.unbonding_period,
+                });
+                state.total_staked -= amount;
+                save_staker(deps.storage, &info.sender, &staker)?;
+                storage::save(deps.storage, &state_key(), &state)?;
+                Ok(Response::new()
+                    .add_attribute("action", "unstake")
+                    .add_attribute("staker", info.sender)
+                    .add_attribute("amount", amount.to_string()))
+            }
+            ExecuteMsg::Withdraw {} => {
+                let now = env.block.time.seconds();
+                let mut staker = staker(deps.storage, &info.sender)?;
+                let (released, unbonding) = std::mem::take(&mut staker.unbonding)
+                    .into_iter()
+                    .partition::<Vec<_>, _>(|unbonding| unbonding.release_at <= now);
+                staker.unbonding = unbonding;
+                let amount: Uint128 = released.iter().map(|unbonding| unbonding.amount).sum();
+                if amount.is_zero() {
+                    return Err(StdError::generic_err("nothing to withdraw").into());
+                }
+                save_staker(deps.storage, &info.sender, &staker)?;
+                let mut resp = Response::new()
+                    .add_attribute("action", "withdraw")
+                    .add_attribute("staker", &info.sender)
+                    .add_attribute("amount", amount.to_string());
+                let msg = pay_out(ctx, deps.storage, &env, &config.token, &info.sender, amount)?;
+                if let Some(msg) = msg {
+                    resp = resp.add_message(msg);
+                }
+                Ok(resp)
+            }
+            ExecuteMsg::ClaimRewards {} => {
+                let mut staker = staker(deps.storage, &info.sender)?;
+                let amount = std::mem::take(&mut staker.pending_rewards);
+                if amount.is_zero() {
+                    return Err(StdError::generic_err("no rewards to claim").into());
+                }
+                save_staker(deps.storage, &info.sender, &staker)?;
+                Ok(Response::new()
+                    .add_message(BankMsg::Send {
+                        to_address: info.sender.to_string(),
+                        amount: vec![Coin {
+                            denom: config.reward_denom,
+                            amount,
+                        }],
+                    })
+                    .add_attribute("action", "claim_rewards")
+                    .add_attribute("staker", info.sender)
+                    .add_attribute("amount", amount.to_string()))
+            }
+            ExecuteMsg::Fund {} => {
+                let amount: Uint128 = info
+                    .funds
+                    .iter()
+                    .filter(|coin| coin.denom == config.reward_denom)
+                    .map(|coin| coin.amount)
+                    .sum();
+                if amount.is_zero() {
+                    return Err(StdError::generic_err(format!(
+                        "funding requires {}",
+                        config.reward_denom
+                    ))
+                    .into());
+                }
+                if state.total_staked.is_zero() {
+                    return Err(StdError::generic_err("no stakers to distribute to").into());
+                }
+                state.reward_index = state
+                    .reward_index
+                    .checked_add(Decimal::from_ratio(amount, state.total_staked))
+                    .map_err(StdError::from)?;
+                storage::save(deps.storage, &state_key(), &state)?;
+                Ok(Response::new()
+                    .add_attribute("action", "fund")
+                    .add_attribute("amount", amount.to_string()))
+            }
+        }
+    }
+
+    fn query(&self, deps: &Deps<Q>, _env: Env, msg: QueryMsg) -> Result<QueryResp, Error> {
+        match msg {
+            QueryMsg::Config {} => Ok(QueryResp::Config(config(deps.storage)?)),
+            QueryMsg::State {} => Ok(QueryResp::State(state(deps.storage)?)),
+            QueryMsg::Staker { address } => {
+                let address = deps.api.addr_validate(&address)?;
+                Ok(QueryResp::Staker(staker(deps.storage, &address)?))
+            }
+        }
+    }
+}
+
+impl VotingPower for Staking {
+    fn voting_power(&self, storage: &dyn Storage, voter: &Addr) -> StdResult<Uint128> {
+        Ok(staker(storage, voter)?.staked)
+    }
+
+    fn total_power(&self, storage: &dyn Storage) -> StdResult<Uint128> {
+        Ok(state(storage)?.total_staked)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manager::Manager;
+    use crate::shared::shared;
+    use crate::test_utils::{addr, info, MockDeps};
+    use cosmwasm_std::testing::{mock_dependencies, mock_env};
+    use cosmwasm_std::{coins, Binary};
+
+    struct Setup {
+        deps: MockDeps,
+        manager: Manager,
+    }
+
+    impl Setup {
+        fn new() -> Self {
+            let mut manager = Manager::new();
+            manager
+                .register("staking".to_string(), shared(Staking::new()))
+                .unwrap();
+            let mut setup = Setup {
+                deps: mock_dependencies(),
+                manager,
+            };
+            let msg = r#"{"staking": {
+                "token": {"native": {"denom": "ustake"}},
+                "reward_denom": "ureward",
+                "unbonding_period": 100
+            }}"#;
+            setup
+                .manager
+                .instantiate(setup.deps.as_mut(), mock_env(), info("creator", &[]), msg)
+                .unwrap();
+            setup
+        }
+
+        fn execute(
+            &mut self,
+            sender: &str,
+            funds: &[Coin],
+            later: u64,
+            msg: &str,
+        ) -> Result<cosmwasm_std::Response<Binary>, Error> {
+            let mut env = mock_env();
+            env.block.time = env.block.time.plus_seconds(later);
+            let msg = format!(r#"{{"staking": {}}}"#, msg);
+            self.manager
+                .execute(&mut self.deps.as_mut(), env, info(sender, funds), &msg)
+        }
+
+        fn staker(&self, address: &str) -> Staker {
+            staker(&self.deps.storage, &addr(address)).unwrap()
+        }
+    }
+
+    fn sent(resp: &cosmwasm_std::Response<Binary>) -> Vec<BankMsg> {
+        resp.messages
+            .iter()
+            .filter_map(|msg| match &msg.msg {
+                CosmosMsg::Bank(msg) => Some(msg.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn rewards_are_shared_by_stake() {
+        let mut setup = Setup::new();
+        let stake = r#"{"stake": {}}"#;
+        setup
+            .execute("alice", &coins(30, "ustake"), 0, stake)
+            .unwrap();
+        setup
+            .execute("bob", &coins(70, "ustake"), 0, stake)
+            .unwrap();
+        setup
+            .execute("carol", &coins(100, "ureward"), 0, r#"{"fund": {}}"#)
+            .unwrap();
+        assert_eq!(state(&setup.deps.storage).unwrap().total_staked.u128(), 100);
+        assert_eq!(setup.staker("alice").pending_rewards.u128(), 30);
+        assert_eq!(setup.staker("bob").pending_rewards.u128(), 70);
+
+        // Stakes joining after funding don't share in earlier rewards.
+        setup
+            .execute("carol", &coins(100, "ustake"), 0, stake)
+            .unwrap();
+        assert_eq!(setup.staker("carol").pending_rewards.u128(), 0);
+
+        let resp = setup
+            .execute("alice", &[], 0, r#"{"claim_rewards": {}}"#)
+            .unwrap();
+        assert_eq!(
+            sent(&resp),
+            vec![BankMsg::Send {
+                to_address: addr("alice").to_string(),
+                amount: coins(30, "ureward"),
+            }]
+        );
+        assert!(setup.staker("alice").pending_rewards.is_zero());
+        setup
+            .execute("alice", &[], 0, r#"{"claim_rewards": {}}"#)
+            .unwrap_err();
+    }
+
+    #[test]
+    fn fund_requires_stakers_and_reward_denom() {
+        let mut setup = Setup::new();
+        let fund = r#"{"fund": {}}"#;
+        setup
+            .execute("carol", &coins(100, "ureward"), 0, fund)
+            .unwrap_err();
+        setup
+            .execute("alice", &coins(10, "ustake"), 0, r#"{"stake": {}}"#)
+            .unwrap();
+        setup
+            .execute("carol", &coins(100, "uother"), 0, fund)
+            .unwrap_err();
+        setup
+            .execute("alice", &coins(10, "uother"), 0, r#"{"stake": {}}"#)
+            .unwrap_err();
+    }
+
+    #[test]
+    fn unstaked_tokens_are_withdrawn_after_unbonding() {
+        let mut setup = Setup::new();
+        setup
+            .execute("alice", &coins(50, "ustake"), 0, r#"{"stake": {}}"#)
+            .unwrap();
+        let unstake = r#"{"unstake": {"amount": "20"}}"#;
+        setup.execute("alice", &[], 0, unstake).unwrap();
+        setup
+            .execute("alice", &[], 0, r#"{"unstake": {"amount": "31"}}"#)
+            .unwrap_err();
+        assert_eq!(setup.staker("alice").staked.u128(), 30);
+        assert_eq!(state(&setup.deps.storage).unwrap().total_staked.u128(), 30);
+
+        let withdraw = r#"{"withdraw": {}}"#;
+        setup.execute("alice", &[], 99, withdraw).unwrap_err();
+        let resp = setup.execute("alice", &[], 100, withdraw).unwrap();
+        assert_eq!(
+            sent(&resp),
+            vec![BankMsg::Send {
+                to_address: addr("alice").to_string(),
+                amount: coins(20, "ustake"),
+            }]
+        );
+        assert!(setup.staker("alice").unbonding.is_empty());
+        setup.execute("alice", &[], 100, withdraw).unwrap_err();
+    }
+}
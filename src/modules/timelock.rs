@@ -0,0 +1,345 @@
+//! Delayed execution of glue messages.
+//!
+//! [Timelock] holds execute messages for other modules until a delay has
+//! passed. Any account can queue a message, which is an ordinary glue
+//! envelope; once the delay is over, anyone can have the Manager dispatch it,
+//! unless the [admin][crate::admin] cancelled it in the meantime:
+//!
+//! ```javascript
+//! { "timelock": { "queue": { "msg": { "treasury": { "withdraw": { ... } } } } } }
+//! { "timelock": { "execute": { "id": 0 } } }
+//! ```
+//!
+//! A queued message is dispatched on behalf of the account that queued it,
+//! without funds, so it can only do what that account could have done
+//! directly; the delay merely gives everyone time to react.
+
+use crate::admin;
+use crate::context::Context;
+use crate::error::Error;
+use crate::module::Module;
+use crate::pagination;
+use crate::response::Response;
+use crate::storage;
+use cosmwasm_std::{
+    Addr, CustomQuery, Deps, DepsMut, Env, MessageInfo, StdError, StdResult, Storage,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+const NAMESPACE: &[u8] = b"timelock";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct InstantiateMsg {
+    /// The minimum delay, in seconds, between queueing a message and
+    /// executing it.
+    pub min_delay: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecuteMsg {
+    /// Queue the glue message `msg`. `delay` defaults to, and must be at
+    /// least, the minimum delay.
+    Queue {
+        msg: Value,
+        #[serde(default)]
+        delay: Option<u64>,
+    },
+    /// Dispatch the queued message `id`, once its delay has passed.
+    Execute { id: u64 },
+    /// Drop the queued message `id`. Only the admin may cancel messages.
+    Cancel { id: u64 },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryMsg {
+    Config {},
+    Operation {
+        id: u64,
+    },
+    /// List the queued messages in order of their id.
+    Operations {
+        #[serde(default)]
+        start_after: Option<u64>,
+        #[serde(default)]
+        limit: Option<u32>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum QueryResp {
+    Config { min_delay: u64 },
+    Operation { operation: Option<Operation> },
+    Operations { operations: Vec<Operation> },
+}
+
+/// A queued message.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Operation {
+    pub id: u64,
+    pub proposer: Addr,
+    pub msg: Value,
+    /// The block time, in seconds, from which the message may be executed.
+    pub executable_at: u64,
+}
+
+/// A module delaying execute messages to its sibling modules.
+#[derive(Clone, Debug, Default)]
+pub struct Timelock {}
+
+impl Timelock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+fn config_key() -> Vec<u8> {
+    storage::key(&[NAMESPACE], b"min_delay")
+}
+
+fn next_id_key() -> Vec<u8> {
+    storage::key(&[NAMESPACE], b"next_id")
+}
+
+fn operation_key(id: u64) -> Vec<u8> {
+    storage::key(&[NAMESPACE, b"operations"], &id.to_be_bytes())
+}
+
+/// Load the queued message `id`, if it is still pending.
+pub fn operation(storage: &dyn Storage, id: u64) -> StdResult<Option<Operation>> {
+    storage::load(storage, &operation_key(id))
+}
+
+fn min_delay(storage: &dyn Storage) -> StdResult<u64> {
+    Ok(storage::load(storage, &config_key())?.unwrap_or_default())
+}
+
+fn must_load(storage: &dyn Storage, id: u64) -> Result<Operation, Error> {
+    operation(storage, id)?
+        .ok_or_else(|| StdError::not_found(format!("timelock operation {}", id)).into())
+}
+
+impl<C: Clone, Q: CustomQuery> Module<C, Q> for Timelock {
+    type InstantiateMsg = InstantiateMsg;
+    type ExecuteMsg = ExecuteMsg;
+    type QueryMsg = QueryMsg;
+    type QueryResp = QueryResp;
+    type Error = Error;
+
+    fn instantiate(
+        &mut self,
+        deps: &mut DepsMut<Q>,
+        _env: &Env,
+        _info: &MessageInfo,
+        msg: InstantiateMsg,
+    ) -> Result<Response<C>, Error> {
+        storage::save(deps.storage, &config_key(), &msg.min_delay)?;
+        Ok(Response::new().add_attribute("min_delay", msg.min_delay.to_string()))
+    }
+
+    fn execute(
+        &mut self,
+        deps: &mut DepsMut<Q>,
+        env: Env,
+        info: MessageInfo,
+        msg: ExecuteMsg,
+    ) -> Result<Response<C>, Error> {
+        match msg {
+            ExecuteMsg::Queue { msg, delay } => {
+                let min_delay = min_delay(deps.storage)?;
+                let delay = delay.unwrap_or(min_delay);
+                if delay < min_delay {
+                    return Err(StdError::generic_err(format!(
+                        "delay of {}s is shorter than the minimum of {}s",
+                        delay, min_delay
+                    ))
+                    .into());
+                }
+                let id: u64 = storage::load(deps.storage, &next_id_key())?.unwrap_or_default();
+                storage::save(deps.storage, &next_id_key(), &(id + 1))?;
+                let operation = Operation {
+                    id,
+                    proposer: info.sender,
+                    msg,
+                    executable_at: env.block.time.seconds().saturating_add(delay),
+                };
+                storage::save(deps.storage, &operation_key(id), &operation)?;
+                Ok(Response::new()
+                    .add_attribute("action", "queue")
+                    .add_attribute("id", id.to_string())
+                    .add_attribute("executable_at", operation.executable_at.to_string()))
+            }
+            ExecuteMsg::Execute { .. } => Err(Error::ParseError {
+                msg: Some("executing queued messages requires the dispatch context".to_string()),
+            }),
+            ExecuteMsg::Cancel { id } => {
+                admin::assert_admin(deps.storage, &info.sender)?;
+                must_load(deps.storage, id)?;
+                storage::remove(deps.storage, &operation_key(id));
+                Ok(Response::new()
+                    .add_attribute("action", "cancel")
+                    .add_attribute("id", id.to_string()))
+            }
+        }
+    }
+
+    fn execute_with_context(
+        &mut self,
+        ctx: &Context<C, Q>,
+        deps: &mut DepsMut<Q>,
+        env: Env,
+        info: MessageInfo,
+        msg: ExecuteMsg,
+    ) -> Result<Response<C>, Error> {
+        match msg {
+            ExecuteMsg::Execute { id } => {
+                let operation = must_load(deps.storage, id)?;
+                if env.block.time.seconds() < operation.executable_at {
+                    return Err(StdError::generic_err(format!(
+                        "timelock operation {} is not executable before {}",
+                        id, operation.executable_at
+                    ))
+                    .into());
+                }
+                storage::remove(deps.storage, &operation_key(id));
+                let proposer = MessageInfo {
+                    sender: operation.proposer,
+                    funds: vec![],
+                };
+                let dispatched = ctx.dispatch(deps, &env, &proposer, &operation.msg)?;
+                let mut resp = Response::new()
+                    .add_attribute("action", "execute")
+                    .add_attribute("id", id.to_string());
+                resp.merge(dispatched)?;
+                Ok(resp)
+            }
+            msg => <Self as Module<C, Q>>::execute(self, deps, env, info, msg),
+        }
+    }
+
+    fn query(&self, deps: &Deps<Q>, _env: Env, msg: QueryMsg) -> Result<QueryResp, Error> {
+        match msg {
+            QueryMsg::Config {} => Ok(QueryResp::Config {
+                min_delay: min_delay(deps.storage)?,
+            }),
+            QueryMsg::Operation { id } => Ok(QueryResp::Operation {
+                operation: operation(deps.storage, id)?,
+            }),
+            QueryMsg::Operations { start_after, limit } => {
+                let limit = pagination::limit(limit);
+                let start_after = start_after.map(u64::to_be_bytes);
+                let operations = storage::range(
+                    deps.storage,
+                    &[NAMESPACE, b"operations"],
+                    start_after.as_ref().map(|id| &id[..]),
+                    limit,
+                )?;
+                Ok(QueryResp::Operations {
+                    operations: operations.into_iter().map(|(_, op)| op).collect(),
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manager::Manager;
+    use crate::test_utils::{addr, info, instantiate, senders_seen, Deposits, MockDeps};
+    use cosmwasm_std::testing::{mock_dependencies, mock_env};
+    use cosmwasm_std::Binary;
+    use serde_json::json;
+
+    struct Setup {
+        deps: MockDeps,
+        manager: Manager,
+    }
+
+    impl Setup {
+        /// A contract holding messages to `treasury` for at least 100s.
+        fn new() -> Self {
+            let manager = Manager::builder()
+                .with_boxed_module("timelock", Box::new(Timelock::new()))
+                .unwrap()
+                .with_boxed_module("treasury", Box::new(Deposits))
+                .unwrap()
+                .build();
+            let mut setup = Setup {
+                deps: mock_dependencies(),
+                manager,
+            };
+            admin::set_admin(&mut setup.deps.storage, &addr("admin")).unwrap();
+            let msg = InstantiateMsg { min_delay: 100 };
+            instantiate::<Timelock>(&mut setup.deps, "admin", msg).unwrap();
+            setup
+        }
+
+        /// Execute `msg` as `sender`, `seconds` after the mock block time.
+        fn execute(
+            &mut self,
+            seconds: u64,
+            sender: &str,
+            msg: Value,
+        ) -> Result<cosmwasm_std::Response<Binary>, Error> {
+            let mut env = mock_env();
+            env.block.time = env.block.time.plus_seconds(seconds);
+            let msg = json!({ "timelock": msg }).to_string();
+            self.manager
+                .execute(&mut self.deps.as_mut(), env, info(sender, &[]), &msg)
+        }
+
+        /// Queue a withdrawal from the treasury as alice.
+        fn queue(&mut self, delay: Option<u64>) -> Result<(), Error> {
+            let msg = json!({"treasury": {"withdraw": {}}});
+            let queue = json!({"queue": {"msg": msg, "delay": delay}});
+            self.execute(0, "alice", queue).map(|_| ())
+        }
+    }
+
+    #[test]
+    fn queued_messages_run_as_their_proposer_after_the_delay() {
+        let mut setup = Setup::new();
+        setup.queue(None).unwrap();
+
+        let execute = json!({"execute": {"id": 0}});
+        let err = setup.execute(99, "bob", execute.clone()).unwrap_err();
+        assert!(err.to_string().contains("not executable before"), "{}", err);
+
+        let resp = setup.execute(100, "bob", execute.clone()).unwrap();
+        assert_eq!(senders_seen(&resp), [addr("alice").as_str()]);
+        assert_eq!(operation(&setup.deps.storage, 0).unwrap(), None);
+        setup.execute(100, "bob", execute).unwrap_err();
+    }
+
+    #[test]
+    fn delays_shorter_than_the_minimum_are_rejected() {
+        let mut setup = Setup::new();
+        let err = setup.queue(Some(99)).unwrap_err();
+        assert!(
+            err.to_string().contains("shorter than the minimum"),
+            "{}",
+            err
+        );
+        setup.queue(Some(200)).unwrap();
+        let executable_at = mock_env().block.time.seconds() + 200;
+        let operation = operation(&setup.deps.storage, 0).unwrap().unwrap();
+        assert_eq!(operation.executable_at, executable_at);
+    }
+
+    #[test]
+    fn only_the_admin_cancels_queued_messages() {
+        let mut setup = Setup::new();
+        setup.queue(None).unwrap();
+        let cancel = json!({"cancel": {"id": 0}});
+        setup.execute(0, "alice", cancel.clone()).unwrap_err();
+        setup.execute(0, "admin", cancel).unwrap();
+        let err = setup
+            .execute(100, "bob", json!({"execute": {"id": 0}}))
+            .unwrap_err();
+        assert!(err.to_string().contains("not found"), "{}", err);
+    }
+}
@@ -0,0 +1,66 @@
+//! Compatibility with `cw-orch`.
+//!
+//! `cw-orch` generates typed functions for a contract's messages, for use in
+//! deployment scripts and tests against real chains. The functions of a glue
+//! contract come from its modules: each module message derives
+//! [ExecuteFns] or [QueryFns] with `impl_into` naming the contract's
+//! aggregated message, which [aggregate_msg][crate::aggregate_msg] declares
+//! with a `From` conversion per module:
+//!
+//! ```ignore
+//! #[cw_serde]
+//! #[derive(cw_orch::ExecuteFns)]
+//! #[impl_into(ExecuteMsg)]
+//! pub enum TokenExecuteMsg {
+//!     Transfer { recipient: String, amount: Uint128 },
+//! }
+//!
+//! burnt_glue::aggregate_msg! {
+//!     pub enum ExecuteMsg {
+//!         Token(TokenExecuteMsg),
+//!         Sale(SaleExecuteMsg),
+//!     }
+//! }
+//!
+//! #[cw_orch::interface(InstantiateMsg, ExecuteMsg, QueryMsg, Empty)]
+//! pub struct Shop;
+//!
+//! // Sends `{"token": {"transfer": {...}}}` to the contract.
+//! shop.transfer(amount, recipient)?;
+//! ```
+//!
+//! The expansions rely on `cw_orch` and `serde` being direct dependencies
+//! of the contract.
+
+pub use cw_orch::{ExecuteFns, QueryFns};
+
+/// Declare an aggregated message for a glue contract, convertible from the
+/// message of each module.
+///
+/// Variants are serialized in snake case, so a variant named `Token` is
+/// addressed with the `token` key, which must match the name the module is
+/// registered under in the [Manager][crate::manager::Manager].
+#[macro_export]
+macro_rules! aggregate_msg {
+    (
+        $(#[$meta:meta])*
+        $vis:vis enum $name:ident {
+            $( $(#[$vmeta:meta])* $variant:ident($msg:ty) ),+ $(,)?
+        }
+    ) => {
+        #[derive(::serde::Serialize, ::serde::Deserialize, Clone, Debug, PartialEq)]
+        #[serde(rename_all = "snake_case")]
+        $(#[$meta])*
+        $vis enum $name {
+            $( $(#[$vmeta])* $variant($msg), )+
+        }
+
+        $(
+            impl ::core::convert::From<$msg> for $name {
+                fn from(msg: $msg) -> Self {
+                    $name::$variant(msg)
+                }
+            }
+        )+
+    };
+}
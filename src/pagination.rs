@@ -0,0 +1,100 @@
+//! Pagination of queries listing entries.
+//!
+//! Every listing query of the reusable modules takes an optional
+//! `start_after`, the key of the last entry of the previous page, and an
+//! optional `limit`, which defaults to [DEFAULT_LIMIT] and is capped at
+//! [MAX_LIMIT], unless the standard a module implements sets its own cap,
+//! as cw721 does:
+//!
+//! ```javascript
+//! { "rbac": { "members": { "role": "minter", "start_after": "xion1...", "limit": 20 } } }
+//! ```
+//!
+//! Modules follow the same convention with [PageRequest] and
+//! [PageResponse], which [Map::page][crate::storage::Map::page] reads from
+//! module-namespaced storage:
+//!
+//! ```ignore
+//! const BIDS: Map<Addr, Uint128> = Map::new("bids");
+//!
+//! QueryMsg::Bids(page) => {
+//!     let bids = BIDS.page(ctx, deps.storage, &page)?;
+//!     Ok(QueryResp::Bids(bids))
+//! }
+//! ```
+//!
+//! The `next_key` of a response is the `start_after` of the next page, and
+//! is unset once the last page was read.
+
+use serde::{Deserialize, Serialize};
+
+/// The number of entries in a page when the request sets no limit.
+pub const DEFAULT_LIMIT: u32 = 10;
+
+/// The largest number of entries in a page.
+pub const MAX_LIMIT: u32 = 30;
+
+/// The number of entries to read for the requested `limit`.
+pub fn limit(limit: Option<u32>) -> usize {
+    limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize
+}
+
+/// A request for a page of entries keyed by `K`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PageRequest<K = String> {
+    /// The key of the last entry of the previous page, if any.
+    #[serde(default)]
+    pub start_after: Option<K>,
+    #[serde(default)]
+    pub limit: Option<u32>,
+}
+
+impl<K> Default for PageRequest<K> {
+    fn default() -> Self {
+        PageRequest {
+            start_after: None,
+            limit: None,
+        }
+    }
+}
+
+impl<K> PageRequest<K> {
+    /// The number of entries to read; see [limit][fn@limit].
+    pub fn limit(&self) -> usize {
+        limit(self.limit)
+    }
+}
+
+/// A page of entries keyed by `K`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PageResponse<T, K = String> {
+    pub items: Vec<T>,
+    /// The `start_after` of the next page, unless this is the last one.
+    pub next_key: Option<K>,
+    /// The number of entries in all pages, if the module keeps count.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total: Option<u64>,
+}
+
+impl<T, K> PageResponse<T, K> {
+    /// The page of `items` read with a limit of `limit`. If the page is
+    /// full, the next one starts after the key of its last item, given by
+    /// `key`.
+    pub fn new(items: Vec<T>, limit: usize, key: impl Fn(&T) -> K) -> Self {
+        let next_key = match items.last() {
+            Some(last) if items.len() >= limit => Some(key(last)),
+            _ => None,
+        };
+        PageResponse {
+            items,
+            next_key,
+            total: None,
+        }
+    }
+
+    /// Report `total` entries in all pages.
+    pub fn with_total(mut self, total: u64) -> Self {
+        self.total = Some(total);
+        self
+    }
+}
@@ -0,0 +1,191 @@
+//! Pausing dispatch to individual modules.
+//!
+//! Besides pausing individual modules, the whole contract can be paused,
+//! rejecting execute messages to every module except a set of emergency
+//! modules with [ContractPaused][crate::error::Error::ContractPaused].
+//!
+//! A paused module rejects execute messages with
+//! [ModulePaused][crate::error::Error::ModulePaused], while its queries keep
+//! working. The flags are stored under a glue-reserved key, so they persist
+//! across calls until the module is unpaused. Use
+//! [Manager::pause_module][crate::manager::Manager::pause_module] and
+//! [Manager::unpause_module][crate::manager::Manager::unpause_module] to set
+//! them.
+//!
+//! Disabling a module goes further, turning it off like a feature flag
+//! until it is enabled again: execute messages fail with
+//! [ModuleDisabled][crate::error::Error::ModuleDisabled], and so do its
+//! queries unless they were kept live. Use
+//! [Manager::disable_module][crate::manager::Manager::disable_module] and
+//! [Manager::enable_module][crate::manager::Manager::enable_module], or the
+//! admin's `disable_module` and `enable_module` messages:
+//!
+//! ```javascript
+//! { "_manager": { "disable_module": { "module": "sale", "keep_queries": true } } }
+//! ```
+
+use crate::storage;
+use cosmwasm_std::{StdResult, Storage};
+use serde::{Deserialize, Serialize};
+
+const NAMESPACE: &[u8] = b"paused";
+const DISABLED_NAMESPACE: &[u8] = b"disabled";
+
+/// The state of a disabled module.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Disabled {
+    /// Whether queries to the module are still answered.
+    pub keep_queries: bool,
+}
+
+/// The state of a contract-wide pause.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct GlobalPause {
+    /// Modules that remain callable while the contract is paused.
+    pub emergency_modules: Vec<String>,
+}
+
+fn global_key() -> Vec<u8> {
+    storage::key(&[b"global_pause"], b"")
+}
+
+/// Load the contract-wide pause, if the contract is paused.
+pub fn global_pause(storage: &dyn Storage) -> StdResult<Option<GlobalPause>> {
+    storage::load(storage, &global_key())
+}
+
+pub(crate) fn pause_all(storage: &mut dyn Storage, pause: &GlobalPause) -> StdResult<()> {
+    storage::save(storage, &global_key(), pause)
+}
+
+pub(crate) fn unpause_all(storage: &mut dyn Storage) {
+    storage::remove(storage, &global_key())
+}
+
+fn key(module: &str) -> Vec<u8> {
+    storage::key(&[NAMESPACE], module.as_bytes())
+}
+
+/// Whether dispatch to `module` is paused.
+pub fn is_paused(storage: &dyn Storage, module: &str) -> StdResult<bool> {
+    Ok(storage::load::<bool>(storage, &key(module))?.unwrap_or(false))
+}
+
+pub(crate) fn pause(storage: &mut dyn Storage, module: &str) -> StdResult<()> {
+    storage::save(storage, &key(module), &true)
+}
+
+pub(crate) fn unpause(storage: &mut dyn Storage, module: &str) {
+    storage::remove(storage, &key(module))
+}
+
+fn disabled_key(module: &str) -> Vec<u8> {
+    storage::key(&[DISABLED_NAMESPACE], module.as_bytes())
+}
+
+/// Load the state of `module`, if it is disabled.
+pub fn disabled(storage: &dyn Storage, module: &str) -> StdResult<Option<Disabled>> {
+    storage::load(storage, &disabled_key(module))
+}
+
+pub(crate) fn disable(storage: &mut dyn Storage, module: &str, state: &Disabled) -> StdResult<()> {
+    storage::save(storage, &disabled_key(module), state)
+}
+
+pub(crate) fn enable(storage: &mut dyn Storage, module: &str) {
+    storage::remove(storage, &disabled_key(module))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::admin;
+    use crate::error::Error;
+    use crate::manager::Manager;
+    use crate::test_utils::{addr, info, Deposits, MockDeps};
+    use cosmwasm_std::testing::{mock_dependencies, mock_env};
+
+    struct Setup {
+        deps: MockDeps,
+        manager: Manager,
+    }
+
+    impl Setup {
+        fn new() -> Self {
+            let manager = Manager::builder()
+                .with_boxed_module("sale", Box::new(Deposits))
+                .unwrap()
+                .with_boxed_module("guardian", Box::new(Deposits))
+                .unwrap()
+                .build();
+            let mut deps = mock_dependencies();
+            admin::set_admin(&mut deps.storage, &addr("admin")).unwrap();
+            Setup { deps, manager }
+        }
+
+        fn execute(&mut self, sender: &str, msg: &str) -> Result<(), Error> {
+            self.manager
+                .execute(&mut self.deps.as_mut(), mock_env(), info(sender, &[]), msg)
+                .map(|_| ())
+        }
+
+        fn query(&mut self, module: &str) -> Result<(), String> {
+            let msg = format!(r#"{{"{}": {{}}}}"#, module);
+            self.manager
+                .query(&self.deps.as_ref(), mock_env(), &msg)
+                .map(|_| ())
+                .map_err(|err| err.to_string())
+        }
+    }
+
+    #[test]
+    fn paused_modules_reject_execute_messages_but_answer_queries() {
+        let mut setup = Setup::new();
+        setup
+            .manager
+            .pause_module(&mut setup.deps.storage, "sale")
+            .unwrap();
+        let err = setup.execute("alice", r#"{"sale": {}}"#).unwrap_err();
+        assert!(matches!(err, Error::ModulePaused { .. }), "{:?}", err);
+        setup.query("sale").unwrap();
+        setup.execute("alice", r#"{"guardian": {}}"#).unwrap();
+
+        setup
+            .manager
+            .unpause_module(&mut setup.deps.storage, "sale")
+            .unwrap();
+        setup.execute("alice", r#"{"sale": {}}"#).unwrap();
+    }
+
+    #[test]
+    fn disabled_modules_drop_queries_unless_kept() {
+        let mut setup = Setup::new();
+        let disable =
+            r#"{"_manager": {"disable_module": {"module": "sale", "keep_queries": false}}}"#;
+        setup.execute("alice", disable).unwrap_err();
+        setup.execute("admin", disable).unwrap();
+        let err = setup.execute("alice", r#"{"sale": {}}"#).unwrap_err();
+        assert!(matches!(err, Error::ModuleDisabled { .. }), "{:?}", err);
+        assert!(setup.query("sale").unwrap_err().contains("disabled"));
+
+        let keep = r#"{"_manager": {"disable_module": {"module": "sale", "keep_queries": true}}}"#;
+        setup.execute("admin", keep).unwrap();
+        setup.query("sale").unwrap();
+    }
+
+    #[test]
+    fn contract_pause_spares_emergency_modules() {
+        let mut setup = Setup::new();
+        let pause = r#"{"_manager": {"pause_all": {"emergency_modules": ["guardian"]}}}"#;
+        setup.execute("admin", pause).unwrap();
+        let err = setup.execute("alice", r#"{"sale": {}}"#).unwrap_err();
+        assert!(matches!(err, Error::ContractPaused {}), "{:?}", err);
+        setup.execute("alice", r#"{"guardian": {}}"#).unwrap();
+
+        setup
+            .execute("admin", r#"{"_manager": {"unpause_all": {}}}"#)
+            .unwrap();
+        assert_eq!(global_pause(&setup.deps.storage).unwrap(), None);
+        setup.execute("alice", r#"{"sale": {}}"#).unwrap();
+    }
+}
@@ -0,0 +1,349 @@
+//! Queries authenticated with permits.
+//!
+//! Queries carry no sender, so modules can't tell who is asking. A permit is
+//! an off-chain signature that lets a user prove it, and thus lets modules
+//! answer with private, per-user results.
+//!
+//! Users first register the secp256k1 public key they sign permits with:
+//!
+//! ```javascript
+//! { "_permit": { "register_pubkey": { "pub_key": "<base64 of the compressed key>" } } }
+//! ```
+//!
+//! A permit names the modules it may be used with, and is signed over the
+//! SHA-256 hash of the JSON document
+//!
+//! ```javascript
+//! {
+//!   "chain_id": "...",
+//!   "contract": "<address of the contract>",
+//!   "permit_name": "...",
+//!   "signer": "<address of the signer>",
+//!   "allowed_modules": ["..."]
+//! }
+//! ```
+//!
+//! with its keys in this order and no whitespace; see [sign_doc_hash]. The
+//! query to authenticate is then wrapped along with the permit under the
+//! reserved [PERMIT_KEY]:
+//!
+//! ```javascript
+//! { "_permit": {
+//!     "permit": {
+//!         "params": { "permit_name": "...", "signer": "...", "allowed_modules": ["bank"] },
+//!         "signature": "..."
+//!     },
+//!     "query": { "bank": { "balance": {} } }
+//! } }
+//! ```
+//!
+//! The Manager verifies the permit against the registered key before
+//! dispatching the query, and the module learns the signer from
+//! [Context::signer][crate::context::Context::signer]. A signer can revoke a
+//! permit by name with `{ "_permit": { "revoke": { "permit_name": "..." } } }`.
+
+use crate::error::Error;
+use crate::json;
+use crate::modules::signatures;
+use crate::storage;
+use cosmwasm_std::{Addr, Binary, CustomQuery, Deps, Env, StdResult, Storage};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// The reserved key under which queries are authenticated with a permit, and
+/// permit keys are managed.
+pub const PERMIT_KEY: &str = "_permit";
+
+const NAMESPACE: &[u8] = b"permit";
+
+/// An off-chain signature authenticating queries on behalf of its signer.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Permit {
+    pub params: PermitParams,
+    /// The 64-byte secp256k1 signature of the [sign_doc_hash] of `params`.
+    pub signature: Binary,
+}
+
+/// What a [Permit] grants.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PermitParams {
+    /// The name under which the signer may revoke the permit.
+    pub permit_name: String,
+    /// The address of the signer.
+    pub signer: String,
+    /// The modules the permit may be used to query.
+    pub allowed_modules: Vec<String>,
+}
+
+/// A query sent under [PERMIT_KEY].
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PermitQuery {
+    pub permit: Permit,
+    /// The query to dispatch, addressed to a single module.
+    pub query: Value,
+}
+
+/// Execute messages sent under [PERMIT_KEY]. Unlike those of the Manager,
+/// any account may send these, on its own behalf.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PermitExecute {
+    /// Register `pub_key` as the key the sender signs permits with,
+    /// replacing any key registered before.
+    RegisterPubkey { pub_key: Binary },
+    /// Reject any further use of the sender's permits named `permit_name`.
+    Revoke { permit_name: String },
+}
+
+/// The document signed for a permit; its fields are serialized in order.
+#[derive(Serialize)]
+struct SignDoc<'a> {
+    chain_id: &'a str,
+    contract: &'a str,
+    permit_name: &'a str,
+    signer: &'a str,
+    allowed_modules: &'a [String],
+}
+
+fn pubkey_key(address: &Addr) -> Vec<u8> {
+    storage::key(&[NAMESPACE, b"pubkey"], address.as_bytes())
+}
+
+fn revoked_key(signer: &Addr, permit_name: &str) -> Vec<u8> {
+    storage::key(&[NAMESPACE, b"revoked", signer.as_bytes()], permit_name.as_bytes())
+}
+
+/// Load the public key `address` signs permits with, if it registered one.
+pub fn pubkey(storage: &dyn Storage, address: &Addr) -> StdResult<Option<Binary>> {
+    storage::load(storage, &pubkey_key(address))
+}
+
+pub(crate) fn set_pubkey(storage: &mut dyn Storage, address: &Addr, key: &Binary) -> StdResult<()> {
+    storage::save(storage, &pubkey_key(address), key)
+}
+
+/// Whether `signer` revoked its permits named `permit_name`.
+pub fn is_revoked(storage: &dyn Storage, signer: &Addr, permit_name: &str) -> StdResult<bool> {
+    Ok(storage::load::<bool>(storage, &revoked_key(signer, permit_name))?.is_some())
+}
+
+pub(crate) fn revoke(storage: &mut dyn Storage, signer: &Addr, permit_name: &str) -> StdResult<()> {
+    storage::save(storage, &revoked_key(signer, permit_name), &true)
+}
+
+/// The SHA-256 hash of the document the signer of a permit with `params`
+/// signs for it to be valid for the contract of `env`.
+pub fn sign_doc_hash(env: &Env, params: &PermitParams) -> Result<Vec<u8>, Error> {
+    let doc = SignDoc {
+        chain_id: &env.block.chain_id,
+        contract: env.contract.address.as_str(),
+        permit_name: &params.permit_name,
+        signer: &params.signer,
+        allowed_modules: &params.allowed_modules,
+    };
+    Ok(signatures::sha256(&json::to_vec(&doc)?))
+}
+
+/// Verify `permit` against the public key registered by its signer and
+/// return the signer.
+pub fn verify<Q: CustomQuery>(deps: &Deps<Q>, env: &Env, permit: &Permit) -> Result<Addr, Error> {
+    let invalid = |reason: &str| Error::InvalidPermit {
+        reason: reason.to_string(),
+    };
+    let signer = deps.api.addr_validate(&permit.params.signer)?;
+    let key = pubkey(deps.storage, &signer)?
+        .ok_or_else(|| invalid("the signer has not registered a public key"))?;
+    if is_revoked(deps.storage, &signer, &permit.params.permit_name)? {
+        return Err(invalid("the permit was revoked"));
+    }
+    let hash = sign_doc_hash(env, &permit.params)?;
+    let valid = deps
+        .api
+        .secp256k1_verify(&hash, &permit.signature, &key)
+        .map_err(|e| invalid(&e.to_string()))?;
+    if !valid {
+        return Err(invalid("the signature does not match"));
+    }
+    Ok(signer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::Context;
+    use crate::manager::Manager;
+    use crate::module::Module;
+    use crate::response::Response;
+    use crate::test_utils::{addr, info, MockDeps};
+    use cosmwasm_std::testing::{mock_dependencies, mock_env};
+    use cosmwasm_std::{from_json, DepsMut, MessageInfo, StdError};
+    use k256::ecdsa::signature::hazmat::PrehashSigner;
+    use k256::ecdsa::{Signature, SigningKey};
+    use serde_json::json;
+
+    /// A module answering queries with the signer of their permit.
+    struct Whoami;
+
+    impl Module for Whoami {
+        type InstantiateMsg = Value;
+        type ExecuteMsg = Value;
+        type QueryMsg = Value;
+        type QueryResp = Option<Addr>;
+        type Error = Error;
+
+        fn instantiate(
+            &mut self,
+            _deps: &mut DepsMut,
+            _env: &Env,
+            _info: &MessageInfo,
+            _msg: Value,
+        ) -> Result<Response, Error> {
+            Ok(Response::new())
+        }
+
+        fn execute(
+            &mut self,
+            _deps: &mut DepsMut,
+            _env: Env,
+            _info: MessageInfo,
+            _msg: Value,
+        ) -> Result<Response, Error> {
+            Ok(Response::new())
+        }
+
+        fn query(&self, _deps: &Deps, _env: Env, _msg: Value) -> Result<Option<Addr>, Error> {
+            Err(StdError::generic_err("needs a context").into())
+        }
+
+        fn query_with_context(
+            &self,
+            ctx: &Context,
+            _deps: &Deps,
+            _env: Env,
+            _msg: Value,
+        ) -> Result<Option<Addr>, Error> {
+            Ok(ctx.signer().cloned())
+        }
+    }
+
+    struct Setup {
+        deps: MockDeps,
+        manager: Manager,
+        key: SigningKey,
+    }
+
+    impl Setup {
+        /// A contract where alice registered the key she signs permits with.
+        fn new() -> Self {
+            let manager = Manager::builder()
+                .with_boxed_module("bank", Box::new(Whoami))
+                .unwrap()
+                .with_boxed_module("vault", Box::new(Whoami))
+                .unwrap()
+                .build();
+            let mut setup = Setup {
+                deps: mock_dependencies(),
+                manager,
+                key: SigningKey::from_slice(&[7; 32]).unwrap(),
+            };
+            let pub_key = Binary::from(setup.key.verifying_key().to_sec1_bytes().to_vec());
+            let msg = json!({"_permit": {"register_pubkey": {"pub_key": pub_key}}});
+            setup.execute("alice", msg).unwrap();
+            setup
+        }
+
+        fn execute(&mut self, sender: &str, msg: Value) -> Result<(), Error> {
+            let msg = msg.to_string();
+            self.manager
+                .execute(&mut self.deps.as_mut(), mock_env(), info(sender, &[]), &msg)
+                .map(|_| ())
+        }
+
+        /// A permit of alice for `allowed_modules`, signed with her key.
+        fn permit(&self, allowed_modules: &[&str]) -> Permit {
+            let params = PermitParams {
+                permit_name: "wallet".to_string(),
+                signer: addr("alice").to_string(),
+                allowed_modules: allowed_modules.iter().map(|m| m.to_string()).collect(),
+            };
+            let hash = sign_doc_hash(&mock_env(), &params).unwrap();
+            let signature: Signature = self.key.sign_prehash(&hash).unwrap();
+            Permit {
+                params,
+                signature: signature.to_bytes().to_vec().into(),
+            }
+        }
+
+        /// Query `module` with `permit`, returning the signer it saw.
+        fn query(&mut self, permit: &Permit, module: &str) -> StdResult<Option<Addr>> {
+            let msg = json!({"_permit": {"permit": permit, "query": {module: {}}}});
+            let resp = self
+                .manager
+                .query(&self.deps.as_ref(), mock_env(), &msg.to_string())?;
+            Ok(from_json(resp).unwrap())
+        }
+    }
+
+    #[test]
+    fn permits_authenticate_their_signer() {
+        let mut setup = Setup::new();
+        let permit = setup.permit(&["bank"]);
+        assert_eq!(setup.query(&permit, "bank").unwrap(), Some(addr("alice")));
+        assert_eq!(
+            verify(&setup.deps.as_ref(), &mock_env(), &permit).unwrap(),
+            addr("alice")
+        );
+    }
+
+    #[test]
+    fn permits_only_reach_their_allowed_modules() {
+        let mut setup = Setup::new();
+        let permit = setup.permit(&["bank"]);
+        let err = setup.query(&permit, "vault").unwrap_err();
+        assert!(
+            err.to_string().contains("does not allow querying"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn tampered_permits_are_rejected() {
+        let mut setup = Setup::new();
+        let mut permit = setup.permit(&["bank"]);
+        permit.params.allowed_modules.push("vault".to_string());
+        let err = setup.query(&permit, "vault").unwrap_err();
+        assert!(
+            err.to_string().contains("signature does not match"),
+            "{}",
+            err
+        );
+
+        let mut permit = setup.permit(&["bank"]);
+        permit.params.signer = addr("bob").to_string();
+        let err = setup.query(&permit, "bank").unwrap_err();
+        assert!(
+            err.to_string().contains("not registered a public key"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn revoked_permits_are_rejected() {
+        let mut setup = Setup::new();
+        let permit = setup.permit(&["bank"]);
+        let revoke = json!({"_permit": {"revoke": {"permit_name": "wallet"}}});
+        setup.execute("bob", revoke.clone()).unwrap();
+        assert!(setup.query(&permit, "bank").is_ok());
+
+        setup.execute("alice", revoke).unwrap();
+        let err = setup.query(&permit, "bank").unwrap_err();
+        assert!(
+            err.to_string().contains("the permit was revoked"),
+            "{}",
+            err
+        );
+        assert!(is_revoked(&setup.deps.storage, &addr("alice"), "wallet").unwrap());
+    }
+}
@@ -0,0 +1,195 @@
+//! Modules speaking a binary format, e.g. protobuf, rather than JSON.
+//!
+//! A [RawModule] receives its payloads as bytes and answers with bytes. Wrap
+//! it in [Raw] to register it with the Manager like any other module:
+//!
+//! ```ignore
+//! manager.register_boxed("proto".to_string(), Box::new(Raw(ProtoModule::new())))?;
+//! ```
+//!
+//! Clients address it with the base64 encoding of the payload in place of a
+//! JSON object, which the Manager decodes and hands over without parsing it
+//! any further:
+//!
+//! ```javascript
+//! { "proto": "CgVoZWxsbxIFd29ybGQ=" }
+//! ```
+//!
+//! The data of the module's execute and instantiate responses and its query
+//! responses are returned untouched, rather than encoded as JSON. In an
+//! aggregated response or a `_multi` query, they are encoded as a base64
+//! string alongside the results of the other modules, unless the
+//! [data envelope][crate::response::DataEnvelope] passes bytes through.
+
+use crate::module::{Capabilities, Module};
+use crate::response::Response;
+use cosmwasm_std::{Binary, CustomQuery, Deps, DepsMut, Empty, Env, MessageInfo, StdResult};
+
+/// A module whose messages and responses are raw bytes.
+pub trait RawModule<C = Binary, Q: CustomQuery = Empty> {
+    /// The type of errors this module can generate.
+    type Error: std::error::Error + Send + Sync + 'static;
+
+    /// The instantiate handler for the module, with the decoded payload.
+    fn instantiate(
+        &mut self,
+        deps: &mut DepsMut<Q>,
+        env: &Env,
+        info: &MessageInfo,
+        msg: Binary,
+    ) -> Result<cosmwasm_std::Response<C>, Self::Error>;
+    /// The transaction handler for the module, with the decoded payload. The
+    /// data of the response is returned as is.
+    fn execute(
+        &mut self,
+        deps: &mut DepsMut<Q>,
+        env: Env,
+        info: MessageInfo,
+        msg: Binary,
+    ) -> Result<cosmwasm_std::Response<C>, Self::Error>;
+    /// The query handler for the module, with the decoded payload. The bytes
+    /// returned are the response to the query.
+    fn query(&self, deps: &Deps<Q>, env: Env, msg: Binary) -> Result<Binary, Self::Error>;
+
+    /// The version of this module; see [Module::version].
+    fn version(&self) -> Option<String> {
+        None
+    }
+    /// The kinds of messages this module handles; see
+    /// [Module::capabilities].
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
+}
+
+/// Registers a [RawModule] with the Manager. Its payloads are decoded from
+/// base64 strings.
+pub struct Raw<M>(pub M);
+
+impl<M, C, Q> Module<C, Q> for Raw<M>
+where
+    Q: CustomQuery,
+    M: RawModule<C, Q>,
+{
+    type InstantiateMsg = Binary;
+    type ExecuteMsg = Binary;
+    type QueryMsg = Binary;
+    type QueryResp = Binary;
+    type Error = M::Error;
+
+    fn instantiate(
+        &mut self,
+        deps: &mut DepsMut<Q>,
+        env: &Env,
+        info: &MessageInfo,
+        msg: Binary,
+    ) -> Result<Response<C>, Self::Error> {
+        self.0.instantiate(deps, env, info, msg).map(Response::from)
+    }
+
+    fn execute(
+        &mut self,
+        deps: &mut DepsMut<Q>,
+        env: Env,
+        info: MessageInfo,
+        msg: Binary,
+    ) -> Result<Response<C>, Self::Error> {
+        self.0.execute(deps, env, info, msg).map(Response::from)
+    }
+
+    fn query(&self, deps: &Deps<Q>, env: Env, msg: Binary) -> Result<Binary, Self::Error> {
+        self.0.query(deps, env, msg)
+    }
+
+    fn encode_query_resp(&self, resp: &Binary) -> StdResult<Binary> {
+        Ok(resp.clone())
+    }
+
+    fn encode_camel_case_query_resp(&self, resp: &Binary) -> StdResult<Binary> {
+        Ok(resp.clone())
+    }
+
+    fn version(&self) -> Option<String> {
+        self.0.version()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.0.capabilities()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::case::KeyCase;
+    use crate::manager::Manager;
+    use crate::test_utils::info;
+    use cosmwasm_std::testing::{mock_dependencies, mock_env};
+    use cosmwasm_std::StdError;
+
+    /// A module answering with its payloads reversed.
+    struct Reverse;
+
+    fn reversed(msg: &Binary) -> Binary {
+        msg.iter().rev().copied().collect::<Vec<u8>>().into()
+    }
+
+    impl RawModule for Reverse {
+        type Error = StdError;
+
+        fn instantiate(
+            &mut self,
+            _deps: &mut DepsMut,
+            _env: &Env,
+            _info: &MessageInfo,
+            _msg: Binary,
+        ) -> Result<cosmwasm_std::Response<Binary>, StdError> {
+            Ok(cosmwasm_std::Response::new())
+        }
+
+        fn execute(
+            &mut self,
+            _deps: &mut DepsMut,
+            _env: Env,
+            _info: MessageInfo,
+            msg: Binary,
+        ) -> Result<cosmwasm_std::Response<Binary>, StdError> {
+            Ok(cosmwasm_std::Response::new().set_data(reversed(&msg)))
+        }
+
+        fn query(&self, _deps: &Deps, _env: Env, msg: Binary) -> Result<Binary, StdError> {
+            Ok(reversed(&msg))
+        }
+    }
+
+    fn manager(key_case: KeyCase) -> Manager {
+        Manager::builder()
+            .with_boxed_module("proto", Box::new(Raw(Reverse)))
+            .unwrap()
+            .key_case(key_case)
+            .build()
+    }
+
+    #[test]
+    fn payloads_and_responses_pass_through_as_bytes() {
+        let mut manager = manager(KeyCase::Preserve);
+        let mut deps = mock_dependencies();
+        let msg = format!(r#"{{"proto": "{}"}}"#, Binary::from(b"\x01\x02\xff"));
+        let resp = manager
+            .execute(&mut deps.as_mut(), mock_env(), info("sender", &[]), &msg)
+            .unwrap();
+        assert_eq!(resp.data, Some(Binary::from(b"\xff\x02\x01")));
+
+        let resp = manager.query(&deps.as_ref(), mock_env(), &msg).unwrap();
+        assert_eq!(resp, Binary::from(b"\xff\x02\x01"));
+    }
+
+    #[test]
+    fn responses_are_not_case_converted() {
+        let mut manager = manager(KeyCase::CamelCaseOut);
+        let deps = mock_dependencies();
+        let msg = format!(r#"{{"proto": "{}"}}"#, Binary::from(br#"{"a_b":1}"#));
+        let resp = manager.query(&deps.as_ref(), mock_env(), &msg).unwrap();
+        assert_eq!(resp, Binary::from(br#"}1:"b_a"{"#));
+    }
+}
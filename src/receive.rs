@@ -0,0 +1,292 @@
+//! Routing of token receive hooks to modules.
+//!
+//! Token contracts notify a recipient contract of a transfer by executing a
+//! standard message on it, with an embedded `msg` chosen by the sender. For
+//! glue contracts, the embedded `msg` follows the glue convention: it
+//! addresses the module that should handle the transfer.
+//!
+//! ```javascript
+//! { "receive": { "sender": "...", "amount": "100", "msg": "eyJzYWxlIjp7ImJ1eSI6e319fQ==" } }
+//! ```
+//!
+//! Here `msg` decodes to `{"sale":{"buy":{}}}`, so the Manager dispatches
+//! `{"buy":{}}` to the `sale` module. The module's handler sees the token
+//! contract as `info.sender`, and learns who sent what through
+//! [Context::received][crate::context::Context::received]. NFTs sent to the
+//! contract with `send_nft` are routed the same way from the `receive_nft`
+//! key. A module registered under the name `receive` or `receive_nft` takes
+//! precedence over this routing.
+
+use cosmwasm_std::{to_json_binary, Addr, Binary, Coin, CosmosMsg, StdResult, Uint128, WasmMsg};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The execute key under which cw20 contracts send [Cw20ReceiveMsg].
+pub const CW20_RECEIVE_KEY: &str = "receive";
+
+/// The message a cw20 contract sends with `send`, mirroring
+/// `cw20::Cw20ReceiveMsg`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Cw20ReceiveMsg {
+    pub sender: String,
+    pub amount: Uint128,
+    pub msg: Binary,
+}
+
+impl Cw20ReceiveMsg {
+    /// The message notifying `contract` of the transfer, as sent by a cw20
+    /// contract.
+    pub fn into_cosmos_msg<C>(self, contract: impl Into<String>) -> StdResult<CosmosMsg<C>> {
+        into_cosmos_msg(contract, CW20_RECEIVE_KEY, self)
+    }
+}
+
+/// The execute key under which cw721 contracts send [Cw721ReceiveMsg].
+pub const CW721_RECEIVE_KEY: &str = "receive_nft";
+
+/// The message a cw721 contract sends with `send_nft`, mirroring
+/// `cw721::Cw721ReceiveMsg`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Cw721ReceiveMsg {
+    pub sender: String,
+    pub token_id: String,
+    pub msg: Binary,
+}
+
+impl Cw721ReceiveMsg {
+    /// The message notifying `contract` of the transfer, as sent by a cw721
+    /// contract.
+    pub fn into_cosmos_msg<C>(self, contract: impl Into<String>) -> StdResult<CosmosMsg<C>> {
+        into_cosmos_msg(contract, CW721_RECEIVE_KEY, self)
+    }
+}
+
+fn into_cosmos_msg<C>(
+    contract: impl Into<String>,
+    key: &str,
+    msg: impl Serialize,
+) -> StdResult<CosmosMsg<C>> {
+    Ok(WasmMsg::Execute {
+        contract_addr: contract.into(),
+        msg: to_json_binary(&BTreeMap::from([(key, msg)]))?,
+        funds: vec![],
+    }
+    .into())
+}
+
+/// The tokens a module received along with the message it is handling.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Received {
+    /// cw20 tokens sent with `send`.
+    Cw20 {
+        /// The cw20 contract.
+        token: Addr,
+        /// The account that sent the tokens.
+        sender: String,
+        amount: Uint128,
+    },
+    /// A cw721 token sent with `send_nft`.
+    Cw721 {
+        /// The cw721 contract.
+        collection: Addr,
+        /// The account that sent the token.
+        sender: String,
+        token_id: String,
+    },
+    /// Native tokens transferred over IBC, with the glue message in the
+    /// ibc-hooks memo; see [ibc_hooks][crate::ibc_hooks].
+    Ics20 { funds: Vec<Coin> },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::Context;
+    use crate::manager::Manager;
+    use crate::module::Module;
+    use crate::response::Response;
+    use crate::test_utils::{addr, info, MockDeps};
+    use cosmwasm_std::testing::{mock_dependencies, mock_env};
+    use cosmwasm_std::{Deps, DepsMut, Env, MessageInfo, StdError};
+    use serde_json::{json, Value};
+
+    /// A module reporting what it received, and from whom.
+    struct Seen;
+
+    impl Module for Seen {
+        type InstantiateMsg = Value;
+        type ExecuteMsg = Value;
+        type QueryMsg = Value;
+        type QueryResp = Value;
+        type Error = StdError;
+
+        fn instantiate(
+            &mut self,
+            _deps: &mut DepsMut,
+            _env: &Env,
+            _info: &MessageInfo,
+            _msg: Value,
+        ) -> Result<Response, StdError> {
+            Ok(Response::new())
+        }
+
+        fn execute(
+            &mut self,
+            _deps: &mut DepsMut,
+            _env: Env,
+            _info: MessageInfo,
+            _msg: Value,
+        ) -> Result<Response, StdError> {
+            Err(StdError::generic_err("needs a context"))
+        }
+
+        fn execute_with_context(
+            &mut self,
+            ctx: &Context,
+            _deps: &mut DepsMut,
+            _env: Env,
+            info: MessageInfo,
+            msg: Value,
+        ) -> Result<Response, StdError> {
+            Ok(Response::new()
+                .add_attribute("sender", info.sender)
+                .add_attribute("msg", msg.to_string())
+                .add_attribute("received", format!("{:?}", ctx.received())))
+        }
+
+        fn query(&self, _deps: &Deps, _env: Env, _msg: Value) -> Result<Value, StdError> {
+            Ok(Value::Null)
+        }
+    }
+
+    struct Setup {
+        deps: MockDeps,
+        manager: Manager,
+    }
+
+    impl Setup {
+        fn new(names: &[&str]) -> Self {
+            let mut builder = Manager::builder();
+            for name in names {
+                builder = builder.with_boxed_module(*name, Box::new(Seen)).unwrap();
+            }
+            Setup {
+                deps: mock_dependencies(),
+                manager: builder.build(),
+            }
+        }
+
+        /// Execute `msg` as `sender`, returning the attributes of the
+        /// response.
+        fn execute(&mut self, sender: &str, msg: Value) -> Result<Vec<(String, String)>, String> {
+            let resp = self
+                .manager
+                .execute(
+                    &mut self.deps.as_mut(),
+                    mock_env(),
+                    info(sender, &[]),
+                    &msg.to_string(),
+                )
+                .map_err(|e| e.to_string())?;
+            Ok(resp
+                .attributes
+                .into_iter()
+                .map(|attr| (attr.key, attr.value))
+                .collect())
+        }
+    }
+
+    fn attribute<'a>(attrs: &'a [(String, String)], key: &str) -> &'a str {
+        attrs
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+            .unwrap_or_else(|| panic!("no {} attribute in {:?}", key, attrs))
+    }
+
+    fn glue_msg(msg: Value) -> Binary {
+        to_json_binary(&msg).unwrap()
+    }
+
+    #[test]
+    fn cw20_hooks_reach_the_addressed_module() {
+        let mut setup = Setup::new(&["sale"]);
+        let hook = Cw20ReceiveMsg {
+            sender: addr("alice").to_string(),
+            amount: Uint128::new(100),
+            msg: glue_msg(json!({"sale": {"buy": {}}})),
+        };
+        let attrs = setup
+            .execute("token", json!({ CW20_RECEIVE_KEY: hook }))
+            .unwrap();
+
+        assert_eq!(attribute(&attrs, "sender"), addr("token").as_str());
+        assert_eq!(attribute(&attrs, "msg"), json!({"buy": {}}).to_string());
+        let received = Received::Cw20 {
+            token: addr("token"),
+            sender: addr("alice").to_string(),
+            amount: Uint128::new(100),
+        };
+        assert_eq!(
+            attribute(&attrs, "received"),
+            format!("{:?}", Some(received))
+        );
+    }
+
+    #[test]
+    fn cw721_hooks_reach_the_addressed_module() {
+        let mut setup = Setup::new(&["sale"]);
+        let hook = Cw721ReceiveMsg {
+            sender: addr("alice").to_string(),
+            token_id: "punk".to_string(),
+            msg: glue_msg(json!({"sale": {"list": {}}})),
+        };
+        let attrs = setup
+            .execute("punks", json!({ CW721_RECEIVE_KEY: hook }))
+            .unwrap();
+
+        assert_eq!(attribute(&attrs, "msg"), json!({"list": {}}).to_string());
+        let received = Received::Cw721 {
+            collection: addr("punks"),
+            sender: addr("alice").to_string(),
+            token_id: "punk".to_string(),
+        };
+        assert_eq!(
+            attribute(&attrs, "received"),
+            format!("{:?}", Some(received))
+        );
+    }
+
+    #[test]
+    fn received_tokens_cannot_reach_the_manager() {
+        let mut setup = Setup::new(&["sale"]);
+        let hook = Cw20ReceiveMsg {
+            sender: addr("alice").to_string(),
+            amount: Uint128::new(100),
+            msg: glue_msg(json!({"_manager": {"set_admin": {"admin": addr("alice")}}})),
+        };
+        let err = setup
+            .execute("token", json!({ CW20_RECEIVE_KEY: hook }))
+            .unwrap_err();
+        assert!(err.contains("cannot be routed"), "{}", err);
+    }
+
+    #[test]
+    fn modules_named_after_a_hook_take_precedence() {
+        let mut setup = Setup::new(&["sale", CW20_RECEIVE_KEY]);
+        let hook = Cw20ReceiveMsg {
+            sender: addr("alice").to_string(),
+            amount: Uint128::new(100),
+            msg: glue_msg(json!({"sale": {"buy": {}}})),
+        };
+        let attrs = setup
+            .execute("token", json!({ CW20_RECEIVE_KEY: &hook }))
+            .unwrap();
+
+        assert_eq!(attribute(&attrs, "received"), "None");
+        assert_eq!(
+            attribute(&attrs, "msg"),
+            serde_json::to_value(&hook).unwrap().to_string()
+        );
+    }
+}
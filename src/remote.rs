@@ -0,0 +1,234 @@
+//! Modules that live in another contract.
+//!
+//! A [Remote] is registered with the Manager like any other module, but
+//! instead of handling payloads itself it forwards them to another contract,
+//! typically another glue contract. Execute payloads are sent as a
+//! `WasmMsg::Execute`, together with the funds of the message; query
+//! payloads are sent as a smart query, and the remote response is returned
+//! as-is. This lets a composed system be split across contracts without
+//! changing the messages clients send:
+//!
+//! ```ignore
+//! // `{"tokens": {...}}` sent to this contract is forwarded to the `tokens`
+//! // module of the glue contract at `token_contract`.
+//! let tokens = Remote::new(token_contract, "tokens");
+//! manager.register("tokens".to_string(), shared(tokens))?;
+//! ```
+//!
+//! Note that the remote contract sees this contract, not the original
+//! sender, as `info.sender`, and that the messages are only executed once
+//! the current execution has succeeded, so their results are not available
+//! to the module's siblings.
+
+use crate::error::Error;
+use crate::module::Module;
+use crate::response::Response;
+use cosmwasm_std::{to_json_binary, CustomQuery, Deps, DepsMut, Env, MessageInfo, StdError, WasmMsg};
+use serde_json::{Map, Value};
+
+/// A module forwarding its payloads to another contract.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Remote {
+    contract: String,
+    module: Option<String>,
+}
+
+impl Remote {
+    /// Forward payloads to the module named `module` of the glue contract at
+    /// `contract`.
+    pub fn new(contract: impl Into<String>, module: impl Into<String>) -> Self {
+        Remote {
+            contract: contract.into(),
+            module: Some(module.into()),
+        }
+    }
+
+    /// Forward payloads as-is to the contract at `contract`, e.g. for
+    /// contracts not built with glue.
+    pub fn raw(contract: impl Into<String>) -> Self {
+        Remote {
+            contract: contract.into(),
+            module: None,
+        }
+    }
+
+    /// The address of the remote contract.
+    pub fn contract(&self) -> &str {
+        &self.contract
+    }
+
+    /// The payload sent to the remote contract.
+    fn wrap(&self, payload: Value) -> Value {
+        match &self.module {
+            Some(module) => Value::Object(Map::from_iter([(module.clone(), payload)])),
+            None => payload,
+        }
+    }
+}
+
+impl<C: Clone, Q: CustomQuery> Module<C, Q> for Remote {
+    type InstantiateMsg = Value;
+    type ExecuteMsg = Value;
+    type QueryMsg = Value;
+    type QueryResp = Value;
+    type Error = Error;
+
+    fn validate_instantiate(
+        &self,
+        _deps: &Deps<Q>,
+        _env: &Env,
+        _info: &MessageInfo,
+        _msg: &Value,
+    ) -> Result<(), Error> {
+        Err(StdError::generic_err(format!(
+            "remote module at {} cannot be instantiated",
+            self.contract
+        ))
+        .into())
+    }
+
+    fn instantiate(
+        &mut self,
+        deps: &mut DepsMut<Q>,
+        env: &Env,
+        info: &MessageInfo,
+        msg: Value,
+    ) -> Result<Response<C>, Error> {
+        <Self as Module<C, Q>>::validate_instantiate(self, &deps.as_ref(), env, info, &msg)?;
+        Ok(Response::new())
+    }
+
+    fn execute(
+        &mut self,
+        _deps: &mut DepsMut<Q>,
+        _env: Env,
+        info: MessageInfo,
+        msg: Value,
+    ) -> Result<Response<C>, Error> {
+        Ok(Response::new()
+            .add_message(WasmMsg::Execute {
+                contract_addr: self.contract.clone(),
+                msg: to_json_binary(&self.wrap(msg))?,
+                funds: info.funds,
+            })
+            .add_attribute("action", "forward")
+            .add_attribute("contract", &self.contract))
+    }
+
+    fn query(&self, deps: &Deps<Q>, _env: Env, msg: Value) -> Result<Value, Error> {
+        Ok(deps.querier.query_wasm_smart(&self.contract, &self.wrap(msg))?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manager::Manager;
+    use crate::test_utils::{addr, info, MockDeps};
+    use cosmwasm_std::testing::{mock_dependencies, mock_env};
+    use cosmwasm_std::{
+        coins, from_json, ContractResult, CosmosMsg, SystemError, SystemResult, WasmQuery,
+    };
+    use serde_json::json;
+
+    struct Setup {
+        deps: MockDeps,
+        manager: Manager,
+    }
+
+    impl Setup {
+        /// A contract whose `tokens` module lives in the glue contract at
+        /// `tokens`, and whose `legacy` module is the plain contract at
+        /// `legacy`. Both contracts echo the queries they receive.
+        fn new() -> Self {
+            let manager = Manager::builder()
+                .with_boxed_module("tokens", Box::new(Remote::new(addr("tokens"), "tokens")))
+                .unwrap()
+                .with_boxed_module("legacy", Box::new(Remote::raw(addr("legacy"))))
+                .unwrap()
+                .build();
+            let mut deps = mock_dependencies();
+            deps.querier.update_wasm(|query| match query {
+                WasmQuery::Smart { msg, .. } => SystemResult::Ok(ContractResult::Ok(msg.clone())),
+                _ => SystemResult::Err(SystemError::UnsupportedRequest {
+                    kind: "wasm".to_string(),
+                }),
+            });
+            Setup { deps, manager }
+        }
+
+        fn query(&mut self, msg: Value) -> Value {
+            let resp = self
+                .manager
+                .query(&self.deps.as_ref(), mock_env(), &msg.to_string())
+                .unwrap();
+            from_json(resp).unwrap()
+        }
+    }
+
+    #[test]
+    fn execute_payloads_are_forwarded_with_their_funds() {
+        let mut setup = Setup::new();
+        for (msg, contract, forwarded) in [
+            (
+                json!({"tokens": {"transfer": {}}}),
+                "tokens",
+                json!({"tokens": {"transfer": {}}}),
+            ),
+            (
+                json!({"legacy": {"transfer": {}}}),
+                "legacy",
+                json!({"transfer": {}}),
+            ),
+        ] {
+            let resp = setup
+                .manager
+                .execute(
+                    &mut setup.deps.as_mut(),
+                    mock_env(),
+                    info("alice", &coins(5, "uxion")),
+                    &msg.to_string(),
+                )
+                .unwrap();
+            let expected = WasmMsg::Execute {
+                contract_addr: addr(contract).to_string(),
+                msg: to_json_binary(&forwarded).unwrap(),
+                funds: coins(5, "uxion"),
+            };
+            assert_eq!(resp.messages.len(), 1);
+            assert_eq!(resp.messages[0].msg, CosmosMsg::Wasm(expected));
+        }
+    }
+
+    #[test]
+    fn queries_are_forwarded_and_answered_as_is() {
+        let mut setup = Setup::new();
+        assert_eq!(
+            setup.query(json!({"tokens": {"balance": {}}})),
+            json!({"tokens": {"balance": {}}})
+        );
+        assert_eq!(
+            setup.query(json!({"legacy": {"balance": {}}})),
+            json!({"balance": {}})
+        );
+    }
+
+    #[test]
+    fn remote_modules_cannot_be_instantiated() {
+        let mut setup = Setup::new();
+        let err = setup
+            .manager
+            .instantiate(
+                setup.deps.as_mut(),
+                mock_env(),
+                info("alice", &[]),
+                &json!({"tokens": {}}).to_string(),
+            )
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("cannot be instantiated"),
+            "{}",
+            err
+        );
+    }
+}
@@ -0,0 +1,458 @@
+//! Replies to submessages sent by modules.
+//!
+//! Modules send submessages with an id obtained from
+//! [Context::reply_id][crate::context::Context::reply_id], which tells the
+//! Manager which module the reply belongs to. The contract's `reply`
+//! entrypoint hands replies to
+//! [Manager::reply][crate::manager::Manager::reply], which dispatches them to
+//! the module's [reply][crate::module::Module::reply] handler with the id the
+//! module chose:
+//!
+//! ```ignore
+//! const MINTED: u32 = 1;
+//! let msg = SubMsg::reply_on_success(mint, ctx.reply_id(MINTED)?);
+//! ```
+//!
+//! Module ids range up to `2^31 - 1`: the top bit is reserved for callbacks.
+//!
+//! Rather than managing reply ids, a module can attach a callback to a
+//! submessage with
+//! [Context::send_with_callback][crate::context::Context::send_with_callback].
+//! The callback is kept in storage, under a namespace reserved for glue,
+//! until the reply arrives; it is then removed and handed to the module's
+//! [on_callback][crate::module::Module::on_callback] handler along with the
+//! result of the submessage:
+//!
+//! ```ignore
+//! let callback = MintCallback::Minted { owner };
+//! let msg = ctx.send_with_callback(deps.storage, &env, mint, &callback)?;
+//! ```
+//!
+//! Child contracts instantiated with
+//! [Context::instantiate_child][crate::context::Context::instantiate_child]
+//! use the same mechanism: their reply is handled by glue itself, which
+//! records the address of the child; see [factory][crate::factory].
+//!
+//! Callbacks whose submessage was never sent, e.g. because the module built
+//! it but left it out of its response, are pruned once their block is over.
+//!
+//! Data set by a reply handler becomes the data of the module. When the
+//! submessage was sent from a response aggregating several modules, e.g. an
+//! instantiate, the data replaces the module's entry in the aggregated data,
+//! leaving those of the other modules untouched.
+
+use crate::error::Error;
+use crate::factory::PendingChild;
+use crate::response::ModuleData;
+use crate::storage;
+use cosmwasm_std::{Env, ReplyOn, StdResult, Storage};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+const NAMESPACE: &[u8] = b"replies";
+
+/// The number of stale callbacks pruned with each registration.
+const PRUNE_LIMIT: usize = 10;
+
+/// The bit set in the module's own part of the reply ids of callbacks.
+pub(crate) const CALLBACK_FLAG: u32 = 1 << 31;
+
+/// The payload attached to a submessage with
+/// [Context::send_with_callback][crate::context::Context::send_with_callback].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Callback(Value);
+
+impl Callback {
+    pub(crate) fn new(callback: Value) -> Self {
+        Callback(callback)
+    }
+
+    /// The payload, decoded as `T`.
+    pub fn parse<T: DeserializeOwned>(&self) -> Result<T, Error> {
+        Ok(T::deserialize(&self.0)?)
+    }
+
+    /// The payload as JSON.
+    pub fn value(&self) -> &Value {
+        &self.0
+    }
+}
+
+/// The reply id a module registered as `module` uses for its own reply id
+/// `id`. The upper 32 bits identify the module, and the top bit of the lower
+/// ones tells callbacks apart from the ids modules choose.
+pub fn reply_id(module: &str, id: u32) -> u64 {
+    (u64::from(module_hash(module)) << 32) | u64::from(id)
+}
+
+/// The module hash and the module's own id encoded in a reply id.
+pub(crate) fn split_reply_id(id: u64) -> (u32, u32) {
+    ((id >> 32) as u32, id as u32)
+}
+
+/// The FNV-1a hash of a module name. The Manager refuses to register names
+/// with colliding hashes, so a hash identifies a single module.
+pub(crate) fn module_hash(module: &str) -> u32 {
+    module.bytes().fold(0x811c_9dc5, |hash, byte| {
+        (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+    })
+}
+
+/// Whether any of `msgs` triggers a reply.
+pub(crate) fn expects_reply<C>(msgs: &[cosmwasm_std::SubMsg<C>]) -> bool {
+    msgs.iter().any(|msg| msg.reply_on != ReplyOn::Never)
+}
+
+fn pending_data_key() -> Vec<u8> {
+    storage::key(&[NAMESPACE], b"pending_data")
+}
+
+/// The data of the modules of the last aggregated response that sent
+/// submessages expecting a reply.
+pub(crate) fn pending_data(
+    storage: &dyn Storage,
+) -> StdResult<Option<BTreeMap<String, ModuleData>>> {
+    storage::load(storage, &pending_data_key())
+}
+
+/// Record the module data of a response sending submessages expecting a
+/// reply, or clear it if the response isn't aggregated.
+pub(crate) fn set_pending_data(
+    storage: &mut dyn Storage,
+    data: Option<&BTreeMap<String, ModuleData>>,
+) -> StdResult<()> {
+    match data {
+        Some(data) => storage::save(storage, &pending_data_key(), data),
+        None => {
+            storage::remove(storage, &pending_data_key());
+            Ok(())
+        }
+    }
+}
+
+/// A callback awaiting the reply to its submessage.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub(crate) struct PendingCallback {
+    /// The name the module that sent the submessage is registered under.
+    pub module: String,
+    pub callback: Value,
+    /// The block height the callback was registered at.
+    pub height: u64,
+    /// The child contract instantiated by the submessage, if it is one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub child: Option<PendingChild>,
+}
+
+fn callback_key(seq: u32) -> Vec<u8> {
+    storage::key(&[NAMESPACE, b"callbacks"], &seq.to_be_bytes())
+}
+
+fn next_callback_key() -> Vec<u8> {
+    storage::key(&[NAMESPACE], b"next_callback")
+}
+
+/// Register `callback` for the module registered as `module`, returning the
+/// reply id to send the submessage with. `child` describes the child
+/// contract the submessage instantiates, if it does.
+pub(crate) fn save_callback(
+    storage: &mut dyn Storage,
+    env: &Env,
+    module: &str,
+    callback: Value,
+    child: Option<PendingChild>,
+) -> StdResult<u64> {
+    prune_callbacks(storage, env.block.height)?;
+    let seq: u32 = storage::load(storage, &next_callback_key())?.unwrap_or_default();
+    storage::save(storage, &next_callback_key(), &((seq + 1) & !CALLBACK_FLAG))?;
+    let pending = PendingCallback {
+        module: module.to_string(),
+        callback,
+        height: env.block.height,
+        child,
+    };
+    storage::save(storage, &callback_key(seq), &pending)?;
+    Ok(reply_id(module, CALLBACK_FLAG | seq))
+}
+
+/// Remove and return the callback registered with the module's own reply
+/// id `id`.
+pub(crate) fn take_callback(
+    storage: &mut dyn Storage,
+    id: u32,
+) -> StdResult<Option<PendingCallback>> {
+    let seq = id & !CALLBACK_FLAG;
+    let pending = storage::load(storage, &callback_key(seq))?;
+    storage::remove(storage, &callback_key(seq));
+    Ok(pending)
+}
+
+/// Remove the oldest callbacks registered before `height`. Replies arrive
+/// in the transaction that sent the submessage, so such callbacks belong to
+/// submessages that were never sent.
+fn prune_callbacks(storage: &mut dyn Storage, height: u64) -> StdResult<()> {
+    let oldest = storage::range::<PendingCallback>(
+        storage,
+        &[NAMESPACE, b"callbacks"],
+        None,
+        PRUNE_LIMIT,
+    )?;
+    for (seq, pending) in oldest {
+        if pending.height < height {
+            storage::remove(storage, &storage::key(&[NAMESPACE, b"callbacks"], &seq));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::Context;
+    use crate::manager::Manager;
+    use crate::module::Module;
+    use crate::response::Response;
+    use crate::test_utils::info;
+    use cosmwasm_std::testing::{mock_dependencies, mock_env};
+    use cosmwasm_std::{
+        BankMsg, Deps, DepsMut, MessageInfo, Reply, StdError, SubMsg, SubMsgResult,
+    };
+    use serde_json::json;
+
+    /// A module sending a submessage with a reply id of its own and one with
+    /// a callback, and reporting which handler each reply reaches.
+    struct Replies;
+
+    impl Module for Replies {
+        type InstantiateMsg = Value;
+        type ExecuteMsg = Value;
+        type QueryMsg = Value;
+        type QueryResp = Value;
+        type Error = Error;
+
+        fn instantiate(
+            &mut self,
+            _deps: &mut DepsMut,
+            _env: &Env,
+            _info: &MessageInfo,
+            _msg: Value,
+        ) -> Result<Response, Error> {
+            Ok(Response::new())
+        }
+
+        fn execute(
+            &mut self,
+            _deps: &mut DepsMut,
+            _env: Env,
+            _info: MessageInfo,
+            _msg: Value,
+        ) -> Result<Response, Error> {
+            Err(StdError::generic_err("needs a context").into())
+        }
+
+        fn execute_with_context(
+            &mut self,
+            ctx: &Context,
+            deps: &mut DepsMut,
+            env: Env,
+            _info: MessageInfo,
+            _msg: Value,
+        ) -> Result<Response, Error> {
+            let send = || BankMsg::Send {
+                to_address: "recipient".to_string(),
+                amount: vec![],
+            };
+            let plain = SubMsg::reply_always(send(), ctx.reply_id(7)?);
+            let callback = ctx.send_with_callback(deps.storage, &env, send(), &"minted")?;
+            Ok(Response::new().add_submessages([plain, callback]))
+        }
+
+        fn query(&self, _deps: &Deps, _env: Env, _msg: Value) -> Result<Value, Error> {
+            Ok(Value::Null)
+        }
+
+        fn reply(
+            &mut self,
+            _ctx: &Context,
+            _deps: &mut DepsMut,
+            _env: &Env,
+            reply: Reply,
+        ) -> Result<Response, Error> {
+            Ok(Response::new().add_attribute("reply", reply.id.to_string()))
+        }
+
+        fn on_callback(
+            &mut self,
+            _ctx: &Context,
+            _deps: &mut DepsMut,
+            _env: &Env,
+            callback: Callback,
+            _result: SubMsgResult,
+        ) -> Result<Response, Error> {
+            Ok(Response::new().add_attribute("callback", callback.parse::<String>()?))
+        }
+    }
+
+    fn reply(id: u64) -> Reply {
+        serde_json::from_value(json!({
+            "id": id,
+            "result": {"ok": {"events": [], "data": null}}
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn replies_and_callbacks_reach_their_handlers() {
+        let mut manager = Manager::builder()
+            .with_boxed_module("replies", Box::new(Replies))
+            .unwrap()
+            .build();
+        let mut deps = mock_dependencies();
+        let resp = manager
+            .execute(
+                &mut deps.as_mut(),
+                mock_env(),
+                info("sender", &[]),
+                r#"{"replies": {}}"#,
+            )
+            .unwrap();
+        let ids: Vec<u64> = resp.messages.iter().map(|msg| msg.id).collect();
+        assert_eq!(ids[0], reply_id("replies", 7));
+
+        let resp = manager
+            .reply(deps.as_mut(), mock_env(), reply(ids[0]))
+            .unwrap();
+        assert_eq!(resp.attributes[0].key, "reply");
+        assert_eq!(resp.attributes[0].value, "7");
+        let resp = manager
+            .reply(deps.as_mut(), mock_env(), reply(ids[1]))
+            .unwrap();
+        assert_eq!(resp.attributes[0].key, "callback");
+        assert_eq!(resp.attributes[0].value, "minted");
+    }
+
+    #[test]
+    fn module_ids_may_not_set_the_callback_bit() {
+        let manager: Manager = Manager::new();
+        let ctx = Context::new(&manager, "replies");
+        assert_eq!(
+            ctx.reply_id(CALLBACK_FLAG - 1).unwrap(),
+            reply_id("replies", CALLBACK_FLAG - 1)
+        );
+        let err = ctx.reply_id(CALLBACK_FLAG | 1).unwrap_err();
+        assert!(matches!(err, Error::ReservedReplyId { id, .. } if id == CALLBACK_FLAG | 1));
+    }
+
+    /// A module instantiating with data, and a submessage whose reply
+    /// replaces it.
+    struct Minter;
+
+    impl Module for Minter {
+        type InstantiateMsg = Value;
+        type ExecuteMsg = Value;
+        type QueryMsg = Value;
+        type QueryResp = Value;
+        type Error = Error;
+
+        fn instantiate(
+            &mut self,
+            _deps: &mut DepsMut,
+            _env: &Env,
+            _info: &MessageInfo,
+            _msg: Value,
+        ) -> Result<Response, Error> {
+            Err(StdError::generic_err("needs a context").into())
+        }
+
+        fn instantiate_with_context(
+            &mut self,
+            ctx: &Context,
+            _deps: &mut DepsMut,
+            _env: &Env,
+            _info: &MessageInfo,
+            _msg: Value,
+        ) -> Result<Response, Error> {
+            let send = BankMsg::Send {
+                to_address: "recipient".to_string(),
+                amount: vec![],
+            };
+            Ok(Response::new()
+                .add_submessage(SubMsg::reply_on_success(send, ctx.reply_id(1)?))
+                .set_data(json!({"minted": false})))
+        }
+
+        fn execute(
+            &mut self,
+            _deps: &mut DepsMut,
+            _env: Env,
+            _info: MessageInfo,
+            _msg: Value,
+        ) -> Result<Response, Error> {
+            Ok(Response::new())
+        }
+
+        fn query(&self, _deps: &Deps, _env: Env, _msg: Value) -> Result<Value, Error> {
+            Ok(Value::Null)
+        }
+
+        fn reply(
+            &mut self,
+            _ctx: &Context,
+            _deps: &mut DepsMut,
+            _env: &Env,
+            _reply: Reply,
+        ) -> Result<Response, Error> {
+            Ok(Response::new().set_data(json!({"minted": true})))
+        }
+    }
+
+    #[test]
+    fn reply_data_replaces_the_module_entry_of_aggregated_data() {
+        let mut manager = Manager::builder()
+            .with_boxed_module("minter", Box::new(Minter))
+            .unwrap()
+            .with_boxed_module("treasury", Box::new(Minter))
+            .unwrap()
+            .build();
+        let mut deps = mock_dependencies();
+        let resp = manager
+            .instantiate(
+                deps.as_mut(),
+                mock_env(),
+                info("sender", &[]),
+                r#"{"minter": {}, "treasury": {}}"#,
+            )
+            .unwrap();
+        let data: Value = cosmwasm_std::from_json(resp.data.unwrap()).unwrap();
+        let unminted = json!({"minted": false});
+        assert_eq!(data, json!({"minter": unminted, "treasury": unminted}));
+
+        let id = reply_id("minter", 1);
+        assert!(resp.messages.iter().any(|msg| msg.id == id));
+        let resp = manager.reply(deps.as_mut(), mock_env(), reply(id)).unwrap();
+        let data: Value = cosmwasm_std::from_json(resp.data.unwrap()).unwrap();
+        assert_eq!(
+            data,
+            json!({"minter": {"minted": true}, "treasury": unminted})
+        );
+    }
+
+    #[test]
+    fn callbacks_never_replied_to_are_pruned_after_their_block() {
+        let mut deps = mock_dependencies();
+        let mut env = mock_env();
+        let stale = save_callback(&mut deps.storage, &env, "replies", json!(1), None).unwrap();
+        let kept = save_callback(&mut deps.storage, &env, "replies", json!(2), None).unwrap();
+        assert_ne!(stale, kept);
+        let (_, own) = split_reply_id(kept);
+        let pending = take_callback(&mut deps.storage, own).unwrap().unwrap();
+        assert_eq!(pending.callback, json!(2));
+        assert_eq!(take_callback(&mut deps.storage, own).unwrap(), None);
+
+        env.block.height += 1;
+        save_callback(&mut deps.storage, &env, "replies", json!(3), None).unwrap();
+        let (_, own) = split_reply_id(stale);
+        assert_eq!(take_callback(&mut deps.storage, own).unwrap(), None);
+    }
+}
@@ -1,21 +1,114 @@
+use crate::error::Error;
+use crate::json;
 use cosmwasm_std::{Attribute, Binary, CosmosMsg, Event, SubMsg};
-use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
 use serde_json::Value::Null;
-use serde_json::{Map, Value};
+use serde_json::Value;
+use std::collections::BTreeMap;
 
+/// The `Response` of the cosmwasm-std version glue is built against, as
+/// returned by the contract entrypoints.
+pub type ContractResponse<C = cosmwasm_std::Empty> = cosmwasm_std::Response<C>;
+
+/// The `SubMsg` of the cosmwasm-std version glue is built against.
+pub type ContractSubMsg<C = cosmwasm_std::Empty> = cosmwasm_std::SubMsg<C>;
+
+/// How the attributes a module adds to the main `wasm` event are tagged with
+/// the module's name, so indexers can tell which module emitted what; see
+/// [Manager::set_attribute_tagging][crate::manager::Manager::set_attribute_tagging].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum AttributeTagging {
+    /// Leave attributes untouched.
+    #[default]
+    None,
+    /// Move the attributes into a dedicated `glue-{module}` event.
+    Event,
+    /// Prefix attribute keys with `{module}.`.
+    Prefix,
+}
+
+/// How the data of several modules is encoded into the data of the
+/// aggregated response.
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub enum DataEnvelope {
+    /// A JSON object keyed by module name.
+    #[default]
+    JsonMap,
+    /// The data of the given module alone, discarding the data of the other
+    /// modules.
+    Module(String),
+    /// For each module with data, in order: the module name prefixed with
+    /// its length as a big-endian `u16`, then the module's data prefixed with
+    /// its length as a big-endian `u32`. Suited to clients that can't parse
+    /// JSON, e.g. proto-based tooling.
+    LengthPrefixed,
+}
+
+/// The data of a single module's response.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ModuleData {
+    Json(Value),
+    Binary(Binary),
+}
+
+impl ModuleData {
+    pub(crate) fn is_empty(&self) -> bool {
+        matches!(self, ModuleData::Json(Null))
+    }
+
+    /// The data as JSON. Binary data is encoded as a base64 string.
+    fn to_value(&self) -> Result<Value, Error> {
+        match self {
+            ModuleData::Json(data) => Ok(data.clone()),
+            ModuleData::Binary(data) => Ok(serde_json::to_value(data)?),
+        }
+    }
+
+    /// The data as bytes. Binary data is passed through untouched.
+    pub(crate) fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        match self {
+            ModuleData::Json(data) => json::to_vec(data),
+            ModuleData::Binary(data) => Ok(data.to_vec()),
+        }
+    }
+}
+
+/// Folds the responses of several modules into a single response.
+///
+/// Data is keyed by module name in a `BTreeMap` rather than a
+/// `serde_json::Map`, whose ordering depends on serde_json's
+/// `preserve_order` feature being enabled anywhere in the dependency graph.
 #[derive(Clone, Debug, PartialEq)]
-pub struct Aggregator {
-    resp: cosmwasm_std::Response<Binary>,
-    data: Map<String, Value>,
+pub struct Aggregator<C = Binary> {
+    resp: cosmwasm_std::Response<C>,
+    data: BTreeMap<String, ModuleData>,
+    envelope: DataEnvelope,
 }
 
-impl Aggregator {
+impl<C: Clone> Aggregator<C> {
     pub fn new() -> Self {
         Self::default()
     }
 
-    pub fn fold_response(&mut self, module: String, resp: Response) {
-        self.data.insert(module, resp.data);
+    /// Create an Aggregator encoding the data of modules with `envelope`.
+    pub fn with_envelope(envelope: DataEnvelope) -> Self {
+        Aggregator {
+            envelope,
+            ..Self::default()
+        }
+    }
+
+    pub fn fold_response(&mut self, module: String, resp: Response<C>) -> Result<(), Error> {
+        if let Some(msg) = resp.error {
+            return Err(Error::SerializationError { msg });
+        }
+        let data = match resp.binary_data {
+            Some(data) => ModuleData::Binary(data),
+            None => ModuleData::Json(resp.data),
+        };
+        self.data.insert(module, data);
         self.resp
             .events
             .extend_from_slice(resp.response.events.as_slice());
@@ -25,42 +118,160 @@ impl Aggregator {
         self.resp
             .messages
             .extend_from_slice(resp.response.messages.as_slice());
+        Ok(())
     }
 
-    pub fn aggregate(&mut self) -> cosmwasm_std::Response<Binary> {
-        if !self.data.is_empty() {
-            let bytes = serde_json::to_vec(&self.data).unwrap().into();
-            self.resp.data = Some(bytes);
+    /// Create an Aggregator holding the `data` of modules, as returned by
+    /// [module_data][Self::module_data], encoded with `envelope`.
+    pub(crate) fn with_data(envelope: DataEnvelope, data: BTreeMap<String, ModuleData>) -> Self {
+        Aggregator {
+            envelope,
+            data,
+            ..Self::default()
         }
-        self.resp.clone()
+    }
+
+    /// The data of each module folded so far.
+    pub(crate) fn module_data(&self) -> &BTreeMap<String, ModuleData> {
+        &self.data
+    }
+
+    /// Replace the data of `module`.
+    pub(crate) fn set_module_data(&mut self, module: String, data: ModuleData) {
+        self.data.insert(module, data);
+    }
+
+    pub fn aggregate(&mut self) -> Result<cosmwasm_std::Response<C>, Error> {
+        self.resp.data = self.encode_data()?;
+        Ok(self.resp.clone())
+    }
+
+    /// The data of the modules, encoded with the Aggregator's envelope.
+    pub(crate) fn encode_data(&self) -> Result<Option<Binary>, Error> {
+        Ok(match &self.envelope {
+            _ if self.data.is_empty() => None,
+            DataEnvelope::JsonMap => {
+                let data = self
+                    .data
+                    .iter()
+                    .map(|(module, data)| Ok((module, data.to_value()?)))
+                    .collect::<Result<BTreeMap<_, _>, Error>>()?;
+                Some(json::to_vec(&data)?.into())
+            }
+            DataEnvelope::Module(module) => match self.data.get(module) {
+                Some(data) if !data.is_empty() => Some(data.to_bytes()?.into()),
+                _ => None,
+            },
+            DataEnvelope::LengthPrefixed => Some(length_prefixed(&self.data)?.into()),
+        })
     }
 }
 
-impl Default for Aggregator {
+impl<C> Default for Aggregator<C> {
     fn default() -> Self {
         Aggregator {
             resp: cosmwasm_std::Response::new(),
-            data: Map::new(),
+            data: BTreeMap::new(),
+            envelope: DataEnvelope::default(),
         }
     }
 }
 
+/// Typed access to the data of an aggregated response encoded with
+/// [DataEnvelope::JsonMap], the default:
+///
+/// ```ignore
+/// let data = AggregatedData::from_binary(&resp.data)?;
+/// let minted: MintResponse = data.get("token")?;
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AggregatedData {
+    data: BTreeMap<String, Value>,
+}
+
+impl AggregatedData {
+    /// Parse the data of an aggregated response. A response without data has
+    /// no module data.
+    pub fn from_binary(data: &Option<Binary>) -> Result<Self, Error> {
+        match data {
+            Some(data) => Ok(AggregatedData {
+                data: json::from_slice(data)?,
+            }),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// The names of the modules with data, in sorted order.
+    pub fn modules(&self) -> Vec<String> {
+        self.data.keys().cloned().collect()
+    }
+
+    /// Whether `module` has data.
+    pub fn contains(&self, module: &str) -> bool {
+        self.data.contains_key(module)
+    }
+
+    /// The data of `module`, decoded as `T`. Fails with
+    /// [NotFoundError][Error::NotFoundError] if the module has no data.
+    pub fn get<T: DeserializeOwned>(&self, module: &str) -> Result<T, Error> {
+        let data = self
+            .data
+            .get(module)
+            .ok_or_else(|| Error::not_found(module, self.modules()))?;
+        Ok(T::deserialize(data)?)
+    }
+
+    /// The data `module` set with [Response::set_binary_data].
+    pub fn get_binary(&self, module: &str) -> Result<Binary, Error> {
+        self.get(module)
+    }
+}
+
+/// Encode the data of modules as described by [DataEnvelope::LengthPrefixed].
+fn length_prefixed(data: &BTreeMap<String, ModuleData>) -> Result<Vec<u8>, Error> {
+    let mut out = Vec::new();
+    for (module, data) in data {
+        if data.is_empty() {
+            continue;
+        }
+        let data = data.to_bytes()?;
+        let too_long = |what: &str| Error::SerializationError {
+            msg: format!("{} of module {:?} is too long to length-prefix", what, module),
+        };
+        let name_len = u16::try_from(module.len()).map_err(|_| too_long("name"))?;
+        let data_len = u32::try_from(data.len()).map_err(|_| too_long("data"))?;
+        out.extend_from_slice(&name_len.to_be_bytes());
+        out.extend_from_slice(module.as_bytes());
+        out.extend_from_slice(&data_len.to_be_bytes());
+        out.extend_from_slice(&data);
+    }
+    Ok(out)
+}
+
 #[derive(Debug, PartialEq)]
-pub struct Response {
-    pub response: cosmwasm_std::Response<Binary>,
+pub struct Response<C = Binary> {
+    pub response: cosmwasm_std::Response<C>,
     pub data: Value,
+    /// Raw data set with [set_binary_data][Self::set_binary_data], which
+    /// takes precedence over `data`.
+    binary_data: Option<Binary>,
+    /// A serialization failure recorded by [set_data][Self::set_data], reported
+    /// when the response is converted or aggregated.
+    error: Option<String>,
 }
 
-impl Default for Response {
+impl<C> Default for Response<C> {
     fn default() -> Self {
         Response {
             response: cosmwasm_std::Response::new(),
             data: Null,
+            binary_data: None,
+            error: None,
         }
     }
 }
 
-impl Response {
+impl<C: Clone> Response<C> {
     /// Create a new response. Modules written with glue should always create glue Responses rather
     /// than cosmwasm_std::Response.
     pub fn new() -> Self {
@@ -77,14 +288,14 @@ impl Response {
 
     /// This creates a "fire and forget" message, by using `SubMsg::new()` to wrap it,
     /// and adds it to the list of messages to process.
-    pub fn add_message(mut self, msg: impl Into<CosmosMsg<Binary>>) -> Self {
+    pub fn add_message(mut self, msg: impl Into<CosmosMsg<C>>) -> Self {
         self.response = self.response.clone().add_message(msg);
         self
     }
 
     /// This takes an explicit SubMsg (creates via eg. `reply_on_error`)
     /// and adds it to the list of messages to process.
-    pub fn add_submessage(mut self, msg: SubMsg<Binary>) -> Self {
+    pub fn add_submessage(mut self, msg: SubMsg<C>) -> Self {
         self.response = self.response.clone().add_submessage(msg);
         self
     }
@@ -109,7 +320,7 @@ impl Response {
     /// Adding a list of attributes using the pair notation for key and value:
     ///
     /// ```
-    /// use cosmwasm_std::Response;
+    /// use burnt_glue::cosmwasm_std::Response;
     ///
     /// let attrs = vec![
     ///     ("action", "reaction"),
@@ -123,7 +334,7 @@ impl Response {
     /// Adding an optional value as an optional attribute by turning it into a list of 0 or 1 elements:
     ///
     /// ```
-    /// use cosmwasm_std::{Attribute, Response};
+    /// use burnt_glue::cosmwasm_std::{Attribute, Response};
     ///
     /// // Some value
     /// let value: Option<String> = Some("sarah".to_string());
@@ -153,13 +364,13 @@ impl Response {
     /// ## Examples
     ///
     /// ```
-    /// use cosmwasm_std::{CosmosMsg, Response};
+    /// use burnt_glue::cosmwasm_std::{CosmosMsg, Response};
     ///
     /// fn make_response_with_msgs(msgs: Vec<CosmosMsg>) -> Response {
     ///     Response::new().add_messages(msgs)
     /// }
     /// ```
-    pub fn add_messages<M: Into<CosmosMsg<Binary>>>(
+    pub fn add_messages<M: Into<CosmosMsg<C>>>(
         mut self,
         msgs: impl IntoIterator<Item = M>,
     ) -> Self {
@@ -173,13 +384,13 @@ impl Response {
     /// ## Examples
     ///
     /// ```
-    /// use cosmwasm_std::{SubMsg, Response};
+    /// use burnt_glue::cosmwasm_std::{SubMsg, Response};
     ///
     /// fn make_response_with_submsgs(msgs: Vec<SubMsg>) -> Response {
     ///     Response::new().add_submessages(msgs)
     /// }
     /// ```
-    pub fn add_submessages(mut self, msgs: impl IntoIterator<Item = SubMsg<Binary>>) -> Self {
+    pub fn add_submessages(mut self, msgs: impl IntoIterator<Item = SubMsg<C>>) -> Self {
         self.response = self.response.clone().add_submessages(msgs);
         self
     }
@@ -194,26 +405,154 @@ impl Response {
         self
     }
 
+    /// Tag the attributes of this response with the name of `module`.
+    pub(crate) fn tag_attributes(&mut self, module: &str, tagging: AttributeTagging) {
+        let attributes = &mut self.response.attributes;
+        match tagging {
+            AttributeTagging::None => {}
+            AttributeTagging::Event if attributes.is_empty() => {}
+            AttributeTagging::Event => {
+                let event = Event::new(format!("glue-{}", module))
+                    .add_attributes(std::mem::take(attributes));
+                self.response.events.push(event);
+            }
+            AttributeTagging::Prefix => {
+                for attribute in attributes {
+                    attribute.key = format!("{}.{}", module, attribute.key);
+                }
+            }
+        }
+    }
+
+    /// Append the messages, attributes, and events of `other` to this
+    /// response, discarding its data.
+    pub fn merge(&mut self, other: Response<C>) -> Result<(), Error> {
+        if let Some(msg) = other.error {
+            return Err(Error::SerializationError { msg });
+        }
+        self.response.messages.extend(other.response.messages);
+        self.response.attributes.extend(other.response.attributes);
+        self.response.events.extend(other.response.events);
+        Ok(())
+    }
+
     /// Set the binary data included in the response.
-    pub fn set_data(mut self, data: impl Serialize) -> Self {
-        self.data = serde_json::to_value(data).unwrap();
-        self
+    ///
+    /// If `data` fails to serialize, the failure is recorded and reported when
+    /// the response is handed back to the [Manager][crate::manager::Manager].
+    /// Use [try_set_data][Self::try_set_data] to handle the error immediately.
+    pub fn set_data(self, data: impl Serialize) -> Self {
+        match serde_json::to_value(data) {
+            Ok(data) => Self {
+                data,
+                binary_data: None,
+                error: None,
+                ..self
+            },
+            Err(e) => Self {
+                error: Some(e.to_string()),
+                ..self
+            },
+        }
+    }
+
+    /// Set the binary data included in the response, failing if `data` cannot
+    /// be serialized.
+    pub fn try_set_data(mut self, data: impl Serialize) -> Result<Self, serde_json::Error> {
+        self.data = serde_json::to_value(data)?;
+        self.binary_data = None;
+        self.error = None;
+        Ok(self)
+    }
+
+    /// Set raw binary data included in the response, e.g. protobuf-encoded
+    /// data for reply-data protocols. The bytes are passed through untouched
+    /// rather than encoded as JSON. When aggregated into a JSON envelope
+    /// alongside the data of other modules, they are encoded as a base64
+    /// string.
+    pub fn set_binary_data(self, data: impl Into<Binary>) -> Self {
+        Self {
+            data: Null,
+            binary_data: Some(data.into()),
+            error: None,
+            ..self
+        }
+    }
+
+    /// Take the data out of this response, failing if setting it failed.
+    pub(crate) fn take_module_data(&mut self) -> Result<ModuleData, Error> {
+        if let Some(msg) = self.error.take() {
+            return Err(Error::SerializationError { msg });
+        }
+        Ok(match self.binary_data.take() {
+            Some(data) => ModuleData::Binary(data),
+            None => ModuleData::Json(std::mem::take(&mut self.data)),
+        })
+    }
+
+    /// Take the data out of this response as JSON, encoding binary data as a
+    /// base64 string.
+    pub(crate) fn take_data_value(&mut self) -> Result<Value, Error> {
+        match self.binary_data.take() {
+            Some(data) => Ok(serde_json::to_value(data)?),
+            None => Ok(std::mem::take(&mut self.data)),
+        }
+    }
+}
+
+/// Wrap a `cosmwasm_std::Response`, e.g. one built by an existing handler,
+/// in a glue Response. Its data, if any, is kept as
+/// [binary data][Response::set_binary_data].
+impl<C> From<cosmwasm_std::Response<C>> for Response<C> {
+    fn from(r: cosmwasm_std::Response<C>) -> Self {
+        let mut response = cosmwasm_std::Response::new();
+        response.messages = r.messages;
+        response.attributes = r.attributes;
+        response.events = r.events;
+        Response {
+            response,
+            data: Null,
+            binary_data: r.data,
+            error: None,
+        }
     }
 }
 
-impl From<Response> for cosmwasm_std::Response<Binary> {
-    fn from(r: Response) -> Self {
+/// Adapts a `cosmwasm_std::Response` into a glue Response carrying JSON data,
+/// easing the port of existing handlers:
+///
+/// ```ignore
+/// let resp = cosmwasm_std::Response::new().add_attribute("action", "mint");
+/// Ok(resp.with_data(&MintResponse { id }))
+/// ```
+pub trait ResponseExt<C> {
+    /// Convert into a glue Response, replacing any data with `data`; see
+    /// [Response::set_data].
+    fn with_data(self, data: impl Serialize) -> Response<C>;
+}
+
+impl<C: Clone> ResponseExt<C> for cosmwasm_std::Response<C> {
+    fn with_data(self, data: impl Serialize) -> Response<C> {
+        Response::from(self).set_data(data)
+    }
+}
+
+impl<C> TryFrom<Response<C>> for cosmwasm_std::Response<C> {
+    type Error = Error;
+
+    fn try_from(r: Response<C>) -> Result<Self, Self::Error> {
+        if let Some(msg) = r.error {
+            return Err(Error::SerializationError { msg });
+        }
         let mut cr = cosmwasm_std::Response::new();
-        cr.data = match r.data {
-            Null => None,
-            data => {
-                let bs = serde_json::to_vec(&data).unwrap();
-                Some(bs.into())
-            }
+        cr.data = match (r.binary_data, r.data) {
+            (Some(data), _) => Some(data),
+            (None, Null) => None,
+            (None, data) => Some(json::to_vec(&data)?.into()),
         };
         cr.messages = r.response.messages;
         cr.attributes = r.response.attributes;
         cr.events = r.response.events;
-        cr
+        Ok(cr)
     }
 }
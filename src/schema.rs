@@ -0,0 +1,98 @@
+//! Compatibility with `cosmwasm-schema`.
+//!
+//! Contracts built with glue expose a single query entrypoint that routes a
+//! payload of the shape `{ "module_name": { /* module query */ } }` to the
+//! module registered under `module_name`. That envelope is exactly the JSON
+//! representation of an externally tagged enum with one variant per module,
+//! so the aggregated query message can be described to `cosmwasm-schema` with
+//! the [query_msg][crate::query_msg] macro:
+//!
+//! ```ignore
+//! burnt_glue::query_msg! {
+//!     pub enum QueryMsg {
+//!         Token(TokenQueryMsg),
+//!         Sale(SaleQueryMsg),
+//!     }
+//! }
+//!
+//! fn main() {
+//!     cosmwasm_schema::write_api! {
+//!         instantiate: InstantiateMsg,
+//!         execute: ExecuteMsg,
+//!         query: QueryMsg,
+//!     }
+//! }
+//! ```
+//!
+//! Each module query type must itself implement
+//! [QueryResponses][cosmwasm_schema::QueryResponses]; the response types of
+//! every module are merged into the aggregated enum. The expansion relies on
+//! `cosmwasm_schema` being a direct dependency of the contract.
+
+pub use cosmwasm_schema::{cw_serde, QueryResponses};
+
+pub mod typescript;
+
+/// Declare the aggregated query message for a glue contract.
+///
+/// Variants are serialized in snake case, so a variant named `Token` is
+/// addressed with the `token` key, which must match the name the module is
+/// registered under in the [Manager][crate::manager::Manager].
+#[macro_export]
+macro_rules! query_msg {
+    (
+        $(#[$meta:meta])*
+        $vis:vis enum $name:ident {
+            $( $(#[$vmeta:meta])* $variant:ident($msg:ty) ),+ $(,)?
+        }
+    ) => {
+        #[$crate::schema::cw_serde]
+        #[derive($crate::schema::QueryResponses)]
+        #[query_responses(nested)]
+        $(#[$meta])*
+        $vis enum $name {
+            $( $(#[$vmeta])* $variant($msg), )+
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cw_serde]
+    #[derive(QueryResponses)]
+    pub enum TokenQueryMsg {
+        #[returns(u64)]
+        Balance { address: String },
+    }
+
+    #[cw_serde]
+    #[derive(QueryResponses)]
+    pub enum SaleQueryMsg {
+        #[returns(String)]
+        Price {},
+    }
+
+    crate::query_msg! {
+        pub enum QueryMsg {
+            Token(TokenQueryMsg),
+            Sale(SaleQueryMsg),
+        }
+    }
+
+    #[test]
+    fn responses_of_every_module_are_merged() {
+        let schemas = QueryMsg::response_schemas().unwrap();
+        assert_eq!(schemas.keys().collect::<Vec<_>>(), ["balance", "price"]);
+    }
+
+    #[test]
+    fn variants_are_addressed_by_module_name() {
+        let msg = QueryMsg::Sale(SaleQueryMsg::Price {});
+        assert_eq!(
+            serde_json::to_string(&msg).unwrap(),
+            r#"{"sale":{"price":{}}}"#
+        );
+    }
+}
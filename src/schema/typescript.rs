@@ -0,0 +1,281 @@
+//! TypeScript clients for glue contracts.
+//!
+//! [TypeScript] turns the schemas of a contract's modules into a TypeScript
+//! file with one namespace per module. Each namespace declares the module's
+//! message types and `execute` and `query` functions that wrap messages in
+//! the module's key, so frontends don't maintain the envelope by hand:
+//!
+//! ```ignore
+//! let client = TypeScript::new()
+//!     .with_module(
+//!         "token",
+//!         Some(schema_for!(TokenExecuteMsg)),
+//!         Some(schema_for!(TokenQueryMsg)),
+//!     )
+//!     .with_module("sale", Some(schema_for!(SaleExecuteMsg)), None)
+//!     .generate()?;
+//! std::fs::write("client.ts", client)?;
+//! ```
+//!
+//! ```typescript
+//! await Token.execute(client, sender, contract, { transfer: { recipient, amount } });
+//! ```
+//!
+//! The functions accept any client with the methods of cosmjs'
+//! `SigningCosmWasmClient` they use.
+
+use cosmwasm_schema::schemars::schema::RootSchema;
+use cosmwasm_std::{StdError, StdResult};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+const PRELUDE: &str = "\
+// Generated by burnt-glue. Do not edit.
+
+export interface Coin {
+  denom: string;
+  amount: string;
+}
+
+export interface QueryClient {
+  queryContractSmart(address: string, msg: Record<string, unknown>): Promise<any>;
+}
+
+export interface ExecuteClient {
+  execute(
+    sender: string,
+    address: string,
+    msg: Record<string, unknown>,
+    fee: any,
+    memo?: string,
+    funds?: readonly Coin[],
+  ): Promise<any>;
+}
+";
+
+struct ModuleSchema {
+    name: String,
+    execute: Option<RootSchema>,
+    query: Option<RootSchema>,
+}
+
+/// A builder for the TypeScript client of a glue contract.
+#[derive(Default)]
+pub struct TypeScript {
+    modules: Vec<ModuleSchema>,
+}
+
+impl TypeScript {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the module registered as `name`, with the schemas of its execute
+    /// and query messages. Modules without a schema for a kind of message
+    /// get no function for it.
+    pub fn with_module(
+        mut self,
+        name: impl Into<String>,
+        execute: Option<RootSchema>,
+        query: Option<RootSchema>,
+    ) -> Self {
+        self.modules.push(ModuleSchema {
+            name: name.into(),
+            execute,
+            query,
+        });
+        self
+    }
+
+    /// Render the client.
+    pub fn generate(&self) -> StdResult<String> {
+        let mut out = PRELUDE.to_string();
+        for module in &self.modules {
+            out.push('\n');
+            render_module(&mut out, module)?;
+        }
+        Ok(out)
+    }
+}
+
+fn render_module(out: &mut String, module: &ModuleSchema) -> StdResult<()> {
+    let key = Value::String(module.name.clone()).to_string();
+    let mut types: Vec<(&str, Value)> = Vec::new();
+    let mut definitions: BTreeMap<String, Value> = BTreeMap::new();
+    for (name, schema) in [("ExecuteMsg", &module.execute), ("QueryMsg", &module.query)] {
+        if let Some(schema) = schema {
+            let mut schema = serde_json::to_value(schema)
+                .map_err(|e| StdError::generic_err(e.to_string()))?;
+            let defs = schema.as_object_mut().and_then(|s| s.remove("definitions"));
+            if let Some(Value::Object(defs)) = defs {
+                definitions.extend(defs);
+            }
+            types.push((name, schema));
+        }
+    }
+
+    let _ = writeln!(out, "export namespace {} {{", namespace(&module.name));
+    for (name, schema) in &types {
+        let _ = writeln!(out, "  export type {} = {};", name, ts_type(schema));
+    }
+    for (name, schema) in &definitions {
+        let _ = writeln!(out, "  export type {} = {};", name, ts_type(schema));
+    }
+    if module.execute.is_some() {
+        let _ = write!(
+            out,
+            "
+  export function execute(
+    client: ExecuteClient,
+    sender: string,
+    contract: string,
+    msg: ExecuteMsg,
+    fee: any = \"auto\",
+    funds?: readonly Coin[],
+  ): Promise<any> {{
+    return client.execute(sender, contract, {{ {}: msg }}, fee, undefined, funds);
+  }}
+",
+            key
+        );
+    }
+    if module.query.is_some() {
+        let _ = write!(
+            out,
+            "
+  export function query<T = any>(
+    client: QueryClient,
+    contract: string,
+    msg: QueryMsg,
+  ): Promise<T> {{
+    return client.queryContractSmart(contract, {{ {}: msg }});
+  }}
+",
+            key
+        );
+    }
+    out.push_str("}\n");
+    Ok(())
+}
+
+/// The TypeScript identifier for the namespace of module `name`, in pascal
+/// case.
+fn namespace(name: &str) -> String {
+    let mut ident: String = name
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+                None => String::new(),
+            }
+        })
+        .collect();
+    if !ident.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        ident.insert(0, '_');
+    }
+    ident
+}
+
+/// The TypeScript type described by a JSON schema.
+fn ts_type(schema: &Value) -> String {
+    let schema = match schema {
+        Value::Bool(true) => return "unknown".to_string(),
+        Value::Bool(false) => return "never".to_string(),
+        Value::Object(schema) => schema,
+        _ => return "unknown".to_string(),
+    };
+    if let Some(Value::String(reference)) = schema.get("$ref") {
+        return reference.rsplit('/').next().unwrap_or(reference).to_string();
+    }
+    if let Some(Value::Array(values)) = schema.get("enum") {
+        return union(values.iter().map(Value::to_string));
+    }
+    if let Some(value) = schema.get("const") {
+        return value.to_string();
+    }
+    for key in ["allOf", "anyOf", "oneOf"] {
+        if let Some(Value::Array(schemas)) = schema.get(key) {
+            let types = schemas.iter().map(ts_type);
+            return if key == "allOf" {
+                types.collect::<Vec<_>>().join(" & ")
+            } else {
+                union(types)
+            };
+        }
+    }
+    match schema.get("type") {
+        Some(Value::String(ty)) => instance_type(ty, schema),
+        Some(Value::Array(tys)) => union(
+            tys.iter()
+                .filter_map(Value::as_str)
+                .map(|ty| instance_type(ty, schema)),
+        ),
+        _ => "unknown".to_string(),
+    }
+}
+
+fn union(types: impl Iterator<Item = String>) -> String {
+    let types: Vec<String> = types.collect();
+    match types.len() {
+        0 => "never".to_string(),
+        1 => types.into_iter().next().unwrap_or_default(),
+        _ => format!("({})", types.join(" | ")),
+    }
+}
+
+fn instance_type(ty: &str, schema: &Map<String, Value>) -> String {
+    match ty {
+        "null" => "null".to_string(),
+        "boolean" => "boolean".to_string(),
+        "integer" | "number" => "number".to_string(),
+        "string" => "string".to_string(),
+        "array" => match schema.get("items") {
+            Some(Value::Array(items)) => {
+                let items: Vec<String> = items.iter().map(ts_type).collect();
+                format!("[{}]", items.join(", "))
+            }
+            Some(items) => format!("{}[]", ts_type(items)),
+            None => "unknown[]".to_string(),
+        },
+        "object" => object_type(schema),
+        _ => "unknown".to_string(),
+    }
+}
+
+fn object_type(schema: &Map<String, Value>) -> String {
+    let required: Vec<&str> = match schema.get("required") {
+        Some(Value::Array(required)) => required.iter().filter_map(Value::as_str).collect(),
+        _ => Vec::new(),
+    };
+    let properties = match schema.get("properties") {
+        Some(Value::Object(properties)) if !properties.is_empty() => properties,
+        _ => {
+            return match schema.get("additionalProperties") {
+                Some(Value::Bool(false)) | None => "Record<string, never>".to_string(),
+                Some(values) => format!("Record<string, {}>", ts_type(values)),
+            };
+        }
+    };
+    let fields: Vec<String> = properties
+        .iter()
+        .map(|(name, value)| {
+            let optional = if required.contains(&name.as_str()) { "" } else { "?" };
+            format!("{}{}: {}", property(name), optional, ts_type(value))
+        })
+        .collect();
+    format!("{{ {} }}", fields.join("; "))
+}
+
+/// The property `name`, quoted unless it is a valid identifier.
+fn property(name: &str) -> String {
+    let is_ident = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_' || c == '$')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$');
+    if is_ident {
+        name.to_string()
+    } else {
+        Value::String(name.to_string()).to_string()
+    }
+}
@@ -0,0 +1,166 @@
+//! The container modules and interfaces are shared in.
+//!
+//! Modules are registered with the Manager as [Shared] values, which other
+//! modules may hold references to as well. By default these are
+//! `Rc<RefCell<T>>`, which makes the Manager neither `Send` nor `Sync`. With
+//! the `sync` feature enabled they are `Arc<RwLock<T>>` instead, and modules,
+//! middleware, and interfaces must be `Send + Sync`, so the Manager can be
+//! moved across threads, e.g. by cw-multi-test app wrappers:
+//!
+//! ```ignore
+//! let token = shared(Cw20::new());
+//! manager.register("token".to_string(), token.clone())?;
+//! manager.register_interface::<dyn FungibleToken>(STAKE_TOKEN_INTERFACE, token)?;
+//! ```
+//!
+//! Code that should build with and without the feature goes through
+//! [shared], [try_borrow], [try_borrow_mut], and [into_inner] rather than
+//! the methods of the underlying container.
+//!
+//! Modules no other module references need not be shared at all: register
+//! them as a `Box` with
+//! [register_boxed][crate::manager::Manager::register_boxed].
+
+#[cfg(not(feature = "sync"))]
+mod container {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// A value shared between the Manager and the modules referencing it.
+    pub type Shared<T> = Rc<RefCell<T>>;
+    /// A shared borrow of a [Shared] value.
+    pub type Ref<'a, T> = std::cell::Ref<'a, T>;
+    /// An exclusive borrow of a [Shared] value.
+    pub type RefMut<'a, T> = std::cell::RefMut<'a, T>;
+
+    /// The bounds of shared values: none, without the `sync` feature.
+    pub trait MaybeSendSync {}
+
+    impl<T: ?Sized> MaybeSendSync for T {}
+
+    pub(crate) type AnyShared = dyn std::any::Any;
+
+    /// Wrap `value` in a new [Shared] container.
+    pub fn shared<T>(value: T) -> Shared<T> {
+        Rc::new(RefCell::new(value))
+    }
+
+    /// Borrow `value`, unless it is exclusively borrowed, e.g. because the
+    /// module is executing.
+    pub fn try_borrow<T: ?Sized>(value: &Shared<T>) -> Option<Ref<'_, T>> {
+        value.try_borrow().ok()
+    }
+
+    /// Borrow `value` exclusively, unless it is already borrowed.
+    pub fn try_borrow_mut<T: ?Sized>(value: &Shared<T>) -> Option<RefMut<'_, T>> {
+        value.try_borrow_mut().ok()
+    }
+
+    /// Take the value out of `value`, unless it is referenced elsewhere.
+    pub fn into_inner<T>(value: Shared<T>) -> Option<T> {
+        Rc::try_unwrap(value).ok().map(RefCell::into_inner)
+    }
+}
+
+#[cfg(feature = "sync")]
+mod container {
+    use std::sync::{Arc, PoisonError, RwLock, TryLockError};
+
+    /// A value shared between the Manager and the modules referencing it.
+    pub type Shared<T> = Arc<RwLock<T>>;
+    /// A shared borrow of a [Shared] value.
+    pub type Ref<'a, T> = std::sync::RwLockReadGuard<'a, T>;
+    /// An exclusive borrow of a [Shared] value.
+    pub type RefMut<'a, T> = std::sync::RwLockWriteGuard<'a, T>;
+
+    /// The bounds of shared values: `Send + Sync`, with the `sync` feature.
+    pub trait MaybeSendSync: Send + Sync {}
+
+    impl<T: ?Sized + Send + Sync> MaybeSendSync for T {}
+
+    pub(crate) type AnyShared = dyn std::any::Any + Send + Sync;
+
+    /// Wrap `value` in a new [Shared] container.
+    pub fn shared<T>(value: T) -> Shared<T> {
+        Arc::new(RwLock::new(value))
+    }
+
+    /// Borrow `value`, unless it is exclusively borrowed, e.g. because the
+    /// module is executing. A lock poisoned by a panic is borrowed anyway,
+    /// as a `RefCell` would be.
+    pub fn try_borrow<T: ?Sized>(value: &Shared<T>) -> Option<Ref<'_, T>> {
+        match value.try_read() {
+            Ok(guard) => Some(guard),
+            Err(TryLockError::Poisoned(err)) => Some(err.into_inner()),
+            Err(TryLockError::WouldBlock) => None,
+        }
+    }
+
+    /// Borrow `value` exclusively, unless it is already borrowed. A lock
+    /// poisoned by a panic is borrowed anyway.
+    pub fn try_borrow_mut<T: ?Sized>(value: &Shared<T>) -> Option<RefMut<'_, T>> {
+        match value.try_write() {
+            Ok(guard) => Some(guard),
+            Err(TryLockError::Poisoned(err)) => Some(err.into_inner()),
+            Err(TryLockError::WouldBlock) => None,
+        }
+    }
+
+    /// Take the value out of `value`, unless it is referenced elsewhere. A
+    /// lock poisoned by a panic still yields its value, as a `RefCell`
+    /// would.
+    pub fn into_inner<T>(value: Shared<T>) -> Option<T> {
+        let lock = Arc::try_unwrap(value).ok()?;
+        Some(lock.into_inner().unwrap_or_else(PoisonError::into_inner))
+    }
+}
+
+pub(crate) use container::AnyShared;
+pub use container::{
+    into_inner, shared, try_borrow, try_borrow_mut, MaybeSendSync, Ref, RefMut, Shared,
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn borrows_are_exclusive() {
+        let value = shared(1);
+        let first = try_borrow(&value).unwrap();
+        assert!(try_borrow(&value).is_some());
+        assert!(try_borrow_mut(&value).is_none());
+        drop(first);
+        let mut exclusive = try_borrow_mut(&value).unwrap();
+        *exclusive += 1;
+        assert!(try_borrow(&value).is_none());
+        drop(exclusive);
+        assert_eq!(into_inner(value), Some(2));
+    }
+
+    #[test]
+    fn referenced_values_are_not_taken() {
+        let value = shared(1);
+        let other = value.clone();
+        assert!(into_inner(value).is_none());
+        assert_eq!(into_inner(other), Some(1));
+    }
+
+    #[test]
+    #[cfg(feature = "sync")]
+    fn poisoned_locks_are_still_borrowed() {
+        let value = shared(1);
+        let poisoner = value.clone();
+        std::thread::spawn(move || {
+            let _guard = poisoner.write().unwrap();
+            panic!("module panicked");
+        })
+        .join()
+        .unwrap_err();
+        assert!(value.is_poisoned());
+
+        assert_eq!(*try_borrow(&value).unwrap(), 1);
+        *try_borrow_mut(&value).unwrap() += 1;
+        assert_eq!(into_inner(value), Some(2));
+    }
+}
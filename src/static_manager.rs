@@ -0,0 +1,410 @@
+//! Compile-time dispatch for contracts whose set of modules is known up front.
+//!
+//! The [Manager][crate::manager::Manager] stores modules as trait objects in a
+//! map and looks them up at runtime. Contracts that know their modules at
+//! compile time can instead use the [static_manager][crate::static_manager!]
+//! macro, which generates a struct holding each module by value and
+//! dispatches messages with a `match` on the module name:
+//!
+//! ```ignore
+//! burnt_glue::static_manager! {
+//!     pub struct Contract {
+//!         token: TokenModule,
+//!         auction: AuctionModule,
+//!     }
+//! }
+//!
+//! let mut contract = Contract {
+//!     token: TokenModule::new(),
+//!     auction: AuctionModule::new(),
+//! };
+//! contract.execute(&mut deps, env, info, &msg)?;
+//! ```
+//!
+//! The generated `execute`, `query`, and `instantiate` methods accept the
+//! basic envelopes of the Manager: a single module key for execute and query,
+//! and a key per module for instantiate, each named like the field holding
+//! the module. Batches, `_manager` messages, aliases, ids, and `_funds`
+//! allocations are not supported. Instantiate payloads are dispatched in
+//! the order the modules are declared. `instantiate_tagged` additionally tags
+//! the attributes of each module with its name, like
+//! [Manager::set_attribute_tagging][crate::manager::Manager::set_attribute_tagging]:
+//!
+//! ```ignore
+//! contract.instantiate_tagged(deps, env, info, &msg, AttributeTagging::Prefix)?;
+//! ```
+
+use crate::error::{Error, ModuleError};
+use crate::json;
+use crate::response::{AttributeTagging, Response};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+#[doc(hidden)]
+pub use cosmwasm_std;
+
+/// Split an execute or query envelope into the addressed module name and its
+/// payload.
+#[doc(hidden)]
+pub fn envelope(msg: &[u8]) -> Result<(String, Value), Error> {
+    match json::parse_message(msg)? {
+        Value::Object(obj) => {
+            let mut vals: Vec<(String, Value)> = obj.into_iter().collect();
+            match vals.len() {
+                1 => Ok(vals.remove(0)),
+                _ => Err(Error::ParseError {
+                    msg: Some("too many module payloads".to_string()),
+                }),
+            }
+        }
+        _ => Err(Error::ParseError { msg: None }),
+    }
+}
+
+/// Split an instantiate envelope into its per-module payloads, rejecting
+/// modules that aren't part of `modules`.
+#[doc(hidden)]
+pub fn instantiate_envelope(
+    msgs: &[u8],
+    modules: &[&str],
+) -> Result<BTreeMap<String, Value>, Error> {
+    match json::parse_message(msgs)? {
+        Value::Object(obj) => {
+            let vals: BTreeMap<String, Value> = obj.into_iter().collect();
+            if let Some(unknown) = vals.keys().find(|k| !modules.contains(&k.as_str())) {
+                let registered = modules.iter().map(|m| m.to_string()).collect();
+                return Err(Error::not_found(unknown, registered));
+            }
+            Ok(vals)
+        }
+        _ => Err(Error::ParseError { msg: None }),
+    }
+}
+
+/// Tag the attributes of `resp`, the response of the module `module`.
+#[doc(hidden)]
+pub fn tag_attributes<C: Clone>(resp: &mut Response<C>, module: &str, tagging: AttributeTagging) {
+    resp.tag_attributes(module, tagging)
+}
+
+/// Deserialize a module payload into the module's message type.
+#[doc(hidden)]
+pub fn payload<T: DeserializeOwned>(payload: &Value) -> Result<T, ModuleError> {
+    T::deserialize(payload).map_err(ModuleError::from_error)
+}
+
+/// Generate a struct that dispatches messages to a fixed set of modules
+/// without trait objects or a runtime map. See the
+/// [static_manager][mod@crate::static_manager] module documentation.
+#[macro_export]
+macro_rules! static_manager {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $( $module:ident : $ty:ty ),+ $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name {
+            $( pub $module: $ty, )+
+        }
+
+        impl $name {
+            /// The names of the modules, in declaration order.
+            pub const MODULES: &'static [&'static str] = &[$( stringify!($module) ),+];
+
+            /// Dispatch a JSON-encoded execute message to the addressed module.
+            pub fn execute(
+                &mut self,
+                deps: &mut $crate::static_manager::cosmwasm_std::DepsMut,
+                env: $crate::static_manager::cosmwasm_std::Env,
+                info: $crate::static_manager::cosmwasm_std::MessageInfo,
+                msg: &[u8],
+            ) -> ::std::result::Result<
+                $crate::static_manager::cosmwasm_std::Response<
+                    $crate::static_manager::cosmwasm_std::Binary,
+                >,
+                $crate::error::Error,
+            > {
+                let (module_name, payload) = $crate::static_manager::envelope(msg)?;
+                match module_name.as_str() {
+                    $(
+                        stringify!($module) => {
+                            let resp = $crate::static_manager::payload(&payload)
+                                .and_then(|msg| {
+                                    <$ty as $crate::module::Module>::execute(
+                                        &mut self.$module,
+                                        deps,
+                                        env,
+                                        info,
+                                        msg,
+                                    )
+                                    .map_err(|e| {
+                                        $crate::error::ModuleError::from_module::<
+                                            $ty,
+                                            $crate::static_manager::cosmwasm_std::Binary,
+                                            $crate::static_manager::cosmwasm_std::Empty,
+                                        >(&self.$module, e)
+                                    })
+                                })
+                                .map_err(|err| $crate::error::Error::ExecutionError {
+                                    module: module_name.clone(),
+                                    err,
+                                })?;
+                            ::std::convert::TryFrom::try_from(resp)
+                        }
+                    )+
+                    _ => Err($crate::error::Error::not_found(
+                        &module_name,
+                        Self::MODULES.iter().map(|m| m.to_string()).collect(),
+                    )),
+                }
+            }
+
+            /// Dispatch a JSON-encoded query message to the addressed module.
+            pub fn query(
+                &self,
+                deps: &$crate::static_manager::cosmwasm_std::Deps,
+                env: $crate::static_manager::cosmwasm_std::Env,
+                msg: &[u8],
+            ) -> $crate::static_manager::cosmwasm_std::StdResult<
+                $crate::static_manager::cosmwasm_std::Binary,
+            > {
+                let (module_name, payload) = $crate::static_manager::envelope(msg)?;
+                match module_name.as_str() {
+                    $(
+                        stringify!($module) => {
+                            let resp = $crate::static_manager::payload(&payload)
+                                .and_then(|msg| {
+                                    <$ty as $crate::module::Module>::query(
+                                        &self.$module,
+                                        deps,
+                                        env,
+                                        msg,
+                                    )
+                                    .map_err(|e| {
+                                        $crate::error::ModuleError::from_module::<
+                                            $ty,
+                                            $crate::static_manager::cosmwasm_std::Binary,
+                                            $crate::static_manager::cosmwasm_std::Empty,
+                                        >(&self.$module, e)
+                                    })
+                                })
+                                .map_err(|err| $crate::error::Error::QueryError {
+                                    module: module_name.clone(),
+                                    err,
+                                })?;
+                            $crate::static_manager::cosmwasm_std::to_json_binary(&resp)
+                        }
+                    )+
+                    _ => Err($crate::error::Error::not_found(
+                        &module_name,
+                        Self::MODULES.iter().map(|m| m.to_string()).collect(),
+                    )
+                    .into()),
+                }
+            }
+
+            /// Dispatch JSON-encoded instantiate messages to the addressed
+            /// modules, in declaration order.
+            pub fn instantiate(
+                &mut self,
+                deps: $crate::static_manager::cosmwasm_std::DepsMut,
+                env: $crate::static_manager::cosmwasm_std::Env,
+                info: $crate::static_manager::cosmwasm_std::MessageInfo,
+                msgs: &[u8],
+            ) -> ::std::result::Result<
+                $crate::static_manager::cosmwasm_std::Response<
+                    $crate::static_manager::cosmwasm_std::Binary,
+                >,
+                $crate::error::Error,
+            > {
+                self.instantiate_tagged(
+                    deps,
+                    env,
+                    info,
+                    msgs,
+                    $crate::response::AttributeTagging::None,
+                )
+            }
+
+            /// Like `instantiate`, tagging the attributes of each module with
+            /// its name as `tagging` specifies, so attributes with the same
+            /// key from different modules stay apart.
+            pub fn instantiate_tagged(
+                &mut self,
+                mut deps: $crate::static_manager::cosmwasm_std::DepsMut,
+                env: $crate::static_manager::cosmwasm_std::Env,
+                info: $crate::static_manager::cosmwasm_std::MessageInfo,
+                msgs: &[u8],
+                tagging: $crate::response::AttributeTagging,
+            ) -> ::std::result::Result<
+                $crate::static_manager::cosmwasm_std::Response<
+                    $crate::static_manager::cosmwasm_std::Binary,
+                >,
+                $crate::error::Error,
+            > {
+                let payloads = $crate::static_manager::instantiate_envelope(msgs, Self::MODULES)?;
+                let mut aggregator = $crate::response::Aggregator::new();
+                $(
+                    if let Some(payload) = payloads.get(stringify!($module)) {
+                        let mut resp = $crate::static_manager::payload(payload)
+                            .and_then(|msg| {
+                                <$ty as $crate::module::Module>::instantiate(
+                                    &mut self.$module,
+                                    &mut deps,
+                                    &env,
+                                    &info,
+                                    msg,
+                                )
+                                .map_err(|e| {
+                                    $crate::error::ModuleError::from_module::<
+                                        $ty,
+                                        $crate::static_manager::cosmwasm_std::Binary,
+                                        $crate::static_manager::cosmwasm_std::Empty,
+                                    >(&self.$module, e)
+                                })
+                            })
+                            .map_err(|err| $crate::error::Error::InstantiateError {
+                                module: stringify!($module).to_string(),
+                                err,
+                            })?;
+                        $crate::static_manager::tag_attributes(
+                            &mut resp,
+                            stringify!($module),
+                            tagging,
+                        );
+                        aggregator.fold_response(stringify!($module).to_string(), resp)?;
+                    }
+                )+
+                aggregator.aggregate()
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::Error;
+    use crate::module::Module;
+    use crate::response::Response;
+    use crate::test_utils::{funds_seen, info, Deposits};
+    use cosmwasm_std::testing::{mock_dependencies, mock_env};
+    use cosmwasm_std::{coins, from_json, Deps, DepsMut, Env, MessageInfo, StdError};
+    use serde_json::{json, Value};
+
+    /// A module counting the execute messages it receives.
+    #[derive(Default)]
+    struct Counter {
+        count: u64,
+    }
+
+    impl Module for Counter {
+        type InstantiateMsg = u64;
+        type ExecuteMsg = Value;
+        type QueryMsg = Value;
+        type QueryResp = u64;
+        type Error = StdError;
+
+        fn instantiate(
+            &mut self,
+            _deps: &mut DepsMut,
+            _env: &Env,
+            _info: &MessageInfo,
+            start: u64,
+        ) -> Result<Response, StdError> {
+            self.count = start;
+            Ok(Response::new().add_attribute("count", start.to_string()))
+        }
+
+        fn execute(
+            &mut self,
+            _deps: &mut DepsMut,
+            _env: Env,
+            _info: MessageInfo,
+            _msg: Value,
+        ) -> Result<Response, StdError> {
+            self.count += 1;
+            Ok(Response::new())
+        }
+
+        fn query(&self, _deps: &Deps, _env: Env, _msg: Value) -> Result<u64, StdError> {
+            Ok(self.count)
+        }
+    }
+
+    crate::static_manager! {
+        struct Contract {
+            counter: Counter,
+            deposits: Deposits,
+        }
+    }
+
+    fn contract() -> Contract {
+        Contract {
+            counter: Counter::default(),
+            deposits: Deposits,
+        }
+    }
+
+    #[test]
+    fn dispatches_to_declared_modules() {
+        let mut deps = mock_dependencies();
+        let mut contract = contract();
+        let msg = br#"{"counter": 5, "deposits": {}}"#;
+        let resp = contract
+            .instantiate(deps.as_mut(), mock_env(), info("sender", &[]), msg)
+            .unwrap();
+        assert_eq!(resp.attributes[0].value, "5");
+
+        let resp = contract
+            .execute(
+                &mut deps.as_mut(),
+                mock_env(),
+                info("sender", &coins(10, "uxion")),
+                br#"{"deposits": {}}"#,
+            )
+            .unwrap();
+        assert_eq!(funds_seen(&resp), ["10uxion"]);
+        contract
+            .execute(
+                &mut deps.as_mut(),
+                mock_env(),
+                info("sender", &[]),
+                br#"{"counter": {}}"#,
+            )
+            .unwrap();
+
+        let count = contract
+            .query(&deps.as_ref(), mock_env(), br#"{"counter": {}}"#)
+            .unwrap();
+        assert_eq!(from_json::<Value>(&count).unwrap(), json!(6));
+    }
+
+    #[test]
+    fn rejects_unknown_and_repeated_modules() {
+        let mut deps = mock_dependencies();
+        let mut contract = contract();
+        let err = contract
+            .execute(
+                &mut deps.as_mut(),
+                mock_env(),
+                info("sender", &[]),
+                br#"{"token": {}}"#,
+            )
+            .unwrap_err();
+        assert!(matches!(err, Error::NotFoundError { .. }), "{:?}", err);
+
+        let msg = br#"{"counter": 1, "counter": 2}"#;
+        let err = contract
+            .instantiate(deps.as_mut(), mock_env(), info("sender", &[]), msg)
+            .unwrap_err();
+        assert!(
+            matches!(&err, Error::DuplicateModuleKey { module } if module == "counter"),
+            "{:?}",
+            err
+        );
+        assert_eq!(contract.counter.count, 0);
+    }
+}
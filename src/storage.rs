@@ -0,0 +1,417 @@
+//! Helpers for the state glue keeps in contract storage.
+//!
+//! All glue-managed state lives under the reserved `glue` root namespace.
+//! Namespaces are length-prefixed when building keys, so keys in different
+//! namespaces can never collide with each other or with module state.
+//!
+//! Modules keep their own state in an [Item] or a [Map], which scope their
+//! keys to the name the module is registered under. Two instances of the
+//! same module registered under different names thus never share state:
+//!
+//! ```ignore
+//! const CONFIG: Item<Config> = Item::new("config");
+//! const BALANCES: Map<Addr, Uint128> = Map::new("balances");
+//!
+//! let config = CONFIG.load(ctx, deps.storage)?;
+//! BALANCES.update(ctx, deps.storage, &info.sender, |balance| {
+//!     Ok(balance.unwrap_or_default() + amount)
+//! })?;
+//! ```
+
+use crate::context::Context;
+use crate::pagination::{PageRequest, PageResponse};
+use cosmwasm_std::{from_json, to_json_vec, Addr, CustomQuery, Order, StdError, StdResult, Storage};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::marker::PhantomData;
+
+/// The root namespace for all glue-managed state.
+const ROOT: &[u8] = b"glue";
+
+/// The namespace of the state of modules, kept in an [Item] or a [Map].
+const MODULES: &[u8] = b"modules";
+
+/// Build a storage key for `key` within `namespaces`, under the glue root.
+pub(crate) fn key(namespaces: &[&[u8]], key: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for ns in std::iter::once(&ROOT).chain(namespaces.iter()) {
+        out.extend_from_slice(&(ns.len() as u16).to_be_bytes());
+        out.extend_from_slice(ns);
+    }
+    out.extend_from_slice(key);
+    out
+}
+
+pub(crate) fn load<T: DeserializeOwned>(storage: &dyn Storage, key: &[u8]) -> StdResult<Option<T>> {
+    storage.get(key).map(|v| from_json(&v)).transpose()
+}
+
+pub(crate) fn save<T: Serialize>(
+    storage: &mut dyn Storage,
+    key: &[u8],
+    value: &T,
+) -> StdResult<()> {
+    storage.set(key, &to_json_vec(value)?);
+    Ok(())
+}
+
+pub(crate) fn remove(storage: &mut dyn Storage, key: &[u8]) {
+    storage.remove(key)
+}
+
+/// Load up to `limit` entries within `namespaces` in ascending key order,
+/// starting after `start_after` if given. Keys are returned without the
+/// namespace prefix.
+pub(crate) fn range<T: DeserializeOwned>(
+    storage: &dyn Storage,
+    namespaces: &[&[u8]],
+    start_after: Option<&[u8]>,
+    limit: usize,
+) -> StdResult<Vec<(Vec<u8>, T)>> {
+    let prefix = key(namespaces, b"");
+    let start = match start_after {
+        Some(after) => {
+            let mut start = key(namespaces, after);
+            start.push(0);
+            start
+        }
+        None => prefix.clone(),
+    };
+    let end = prefix_end(&prefix);
+    storage
+        .range(Some(&start), end.as_deref(), Order::Ascending)
+        .take(limit)
+        .map(|(k, v)| Ok((k[prefix.len()..].to_vec(), from_json(&v)?)))
+        .collect()
+}
+
+/// The first key after every key starting with `prefix`, if there is one.
+fn prefix_end(prefix: &[u8]) -> Option<Vec<u8>> {
+    let mut end = prefix.to_vec();
+    while let Some(last) = end.pop() {
+        if last < u8::MAX {
+            end.push(last + 1);
+            return Some(end);
+        }
+    }
+    None
+}
+
+/// A single value in the state of a module, stored under `key` within the
+/// namespace of the name the module is registered under.
+pub struct Item<T> {
+    key: &'static str,
+    value: PhantomData<T>,
+}
+
+impl<T: Serialize + DeserializeOwned> Item<T> {
+    pub const fn new(key: &'static str) -> Self {
+        Item {
+            key,
+            value: PhantomData,
+        }
+    }
+
+    fn storage_key<C, Q: CustomQuery>(&self, ctx: &Context<C, Q>) -> Vec<u8> {
+        key(&[MODULES, ctx.module().as_bytes()], self.key.as_bytes())
+    }
+
+    /// Save `value` in the state of the module `ctx` dispatches to.
+    pub fn save<C, Q: CustomQuery>(
+        &self,
+        ctx: &Context<C, Q>,
+        storage: &mut dyn Storage,
+        value: &T,
+    ) -> StdResult<()> {
+        save(storage, &self.storage_key(ctx), value)
+    }
+
+    /// Load the value, failing if it was never saved.
+    pub fn load<C, Q: CustomQuery>(
+        &self,
+        ctx: &Context<C, Q>,
+        storage: &dyn Storage,
+    ) -> StdResult<T> {
+        self.may_load(ctx, storage)?
+            .ok_or_else(|| StdError::not_found(std::any::type_name::<T>()))
+    }
+
+    /// Load the value, if it was saved.
+    pub fn may_load<C, Q: CustomQuery>(
+        &self,
+        ctx: &Context<C, Q>,
+        storage: &dyn Storage,
+    ) -> StdResult<Option<T>> {
+        load(storage, &self.storage_key(ctx))
+    }
+
+    /// Whether the value was saved.
+    pub fn exists<C, Q: CustomQuery>(&self, ctx: &Context<C, Q>, storage: &dyn Storage) -> bool {
+        storage.get(&self.storage_key(ctx)).is_some()
+    }
+
+    pub fn remove<C, Q: CustomQuery>(&self, ctx: &Context<C, Q>, storage: &mut dyn Storage) {
+        remove(storage, &self.storage_key(ctx))
+    }
+
+    /// Replace the value with the result of `action` on the current value,
+    /// if any, returning the new value.
+    pub fn update<C, Q: CustomQuery, E: From<StdError>>(
+        &self,
+        ctx: &Context<C, Q>,
+        storage: &mut dyn Storage,
+        action: impl FnOnce(Option<T>) -> Result<T, E>,
+    ) -> Result<T, E> {
+        let value = action(self.may_load(ctx, storage)?)?;
+        self.save(ctx, storage, &value)?;
+        Ok(value)
+    }
+}
+
+/// A key of a [Map]. Encoded keys sort in the order of the keys they encode,
+/// so ranges come out in key order.
+pub trait MapKey: Sized {
+    fn to_key(&self) -> Vec<u8>;
+
+    fn from_key(key: &[u8]) -> StdResult<Self>;
+}
+
+impl MapKey for String {
+    fn to_key(&self) -> Vec<u8> {
+        self.as_bytes().to_vec()
+    }
+
+    fn from_key(key: &[u8]) -> StdResult<Self> {
+        String::from_utf8(key.to_vec()).map_err(StdError::invalid_utf8)
+    }
+}
+
+impl MapKey for Addr {
+    fn to_key(&self) -> Vec<u8> {
+        self.as_bytes().to_vec()
+    }
+
+    fn from_key(key: &[u8]) -> StdResult<Self> {
+        String::from_key(key).map(Addr::unchecked)
+    }
+}
+
+impl MapKey for Vec<u8> {
+    fn to_key(&self) -> Vec<u8> {
+        self.clone()
+    }
+
+    fn from_key(key: &[u8]) -> StdResult<Self> {
+        Ok(key.to_vec())
+    }
+}
+
+macro_rules! int_map_key {
+    ($($t:ty),*) => {
+        $(
+            impl MapKey for $t {
+                fn to_key(&self) -> Vec<u8> {
+                    self.to_be_bytes().to_vec()
+                }
+
+                fn from_key(key: &[u8]) -> StdResult<Self> {
+                    let bytes = key.try_into().map_err(|_| {
+                        StdError::generic_err(format!("invalid {} key", stringify!($t)))
+                    })?;
+                    Ok(<$t>::from_be_bytes(bytes))
+                }
+            }
+        )*
+    };
+}
+
+int_map_key!(u8, u16, u32, u64, u128);
+
+/// A map of values in the state of a module, stored under `namespace` within
+/// the namespace of the name the module is registered under.
+pub struct Map<K, V> {
+    namespace: &'static str,
+    entries: PhantomData<(K, V)>,
+}
+
+impl<K: MapKey, V: Serialize + DeserializeOwned> Map<K, V> {
+    pub const fn new(namespace: &'static str) -> Self {
+        Map {
+            namespace,
+            entries: PhantomData,
+        }
+    }
+
+    fn namespaces<'a, C, Q: CustomQuery>(&'a self, ctx: &'a Context<C, Q>) -> [&'a [u8]; 3] {
+        [MODULES, ctx.module().as_bytes(), self.namespace.as_bytes()]
+    }
+
+    fn storage_key<C, Q: CustomQuery>(&self, ctx: &Context<C, Q>, k: &K) -> Vec<u8> {
+        key(&self.namespaces(ctx), &k.to_key())
+    }
+
+    /// Save `value` under `k` in the state of the module `ctx` dispatches
+    /// to.
+    pub fn save<C, Q: CustomQuery>(
+        &self,
+        ctx: &Context<C, Q>,
+        storage: &mut dyn Storage,
+        k: &K,
+        value: &V,
+    ) -> StdResult<()> {
+        save(storage, &self.storage_key(ctx, k), value)
+    }
+
+    /// Load the value under `k`, failing if there is none.
+    pub fn load<C, Q: CustomQuery>(
+        &self,
+        ctx: &Context<C, Q>,
+        storage: &dyn Storage,
+        k: &K,
+    ) -> StdResult<V> {
+        self.may_load(ctx, storage, k)?
+            .ok_or_else(|| StdError::not_found(std::any::type_name::<V>()))
+    }
+
+    /// Load the value under `k`, if there is one.
+    pub fn may_load<C, Q: CustomQuery>(
+        &self,
+        ctx: &Context<C, Q>,
+        storage: &dyn Storage,
+        k: &K,
+    ) -> StdResult<Option<V>> {
+        load(storage, &self.storage_key(ctx, k))
+    }
+
+    /// Whether there is a value under `k`.
+    pub fn has<C, Q: CustomQuery>(
+        &self,
+        ctx: &Context<C, Q>,
+        storage: &dyn Storage,
+        k: &K,
+    ) -> bool {
+        storage.get(&self.storage_key(ctx, k)).is_some()
+    }
+
+    pub fn remove<C, Q: CustomQuery>(&self, ctx: &Context<C, Q>, storage: &mut dyn Storage, k: &K) {
+        remove(storage, &self.storage_key(ctx, k))
+    }
+
+    /// Replace the value under `k` with the result of `action` on the
+    /// current value, if any, returning the new value.
+    pub fn update<C, Q: CustomQuery, E: From<StdError>>(
+        &self,
+        ctx: &Context<C, Q>,
+        storage: &mut dyn Storage,
+        k: &K,
+        action: impl FnOnce(Option<V>) -> Result<V, E>,
+    ) -> Result<V, E> {
+        let value = action(self.may_load(ctx, storage, k)?)?;
+        self.save(ctx, storage, k, &value)?;
+        Ok(value)
+    }
+
+    /// Load up to `limit` entries in key order, starting after `start_after`
+    /// if given.
+    pub fn range<C, Q: CustomQuery>(
+        &self,
+        ctx: &Context<C, Q>,
+        storage: &dyn Storage,
+        start_after: Option<&K>,
+        limit: usize,
+    ) -> StdResult<Vec<(K, V)>> {
+        let start_after = start_after.map(MapKey::to_key);
+        range::<V>(storage, &self.namespaces(ctx), start_after.as_deref(), limit)?
+            .into_iter()
+            .map(|(k, v)| Ok((K::from_key(&k)?, v)))
+            .collect()
+    }
+
+    /// Load the page of entries `page` requests, in key order; see
+    /// [pagination][crate::pagination].
+    pub fn page<C, Q: CustomQuery>(
+        &self,
+        ctx: &Context<C, Q>,
+        storage: &dyn Storage,
+        page: &PageRequest<K>,
+    ) -> StdResult<PageResponse<(K, V), K>>
+    where
+        K: Clone,
+    {
+        let limit = page.limit();
+        let entries = self.range(ctx, storage, page.start_after.as_ref(), limit)?;
+        Ok(PageResponse::new(entries, limit, |(k, _)| k.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manager::Manager;
+    use cosmwasm_std::testing::MockStorage;
+
+    const COUNT: Item<u64> = Item::new("count");
+    const BALANCES: Map<String, u64> = Map::new("balances");
+
+    #[test]
+    fn items_are_scoped_to_the_module() {
+        let manager: Manager = Manager::new();
+        let (a, b) = (Context::new(&manager, "a"), Context::new(&manager, "b"));
+        let mut storage = MockStorage::new();
+
+        COUNT.save(&a, &mut storage, &1).unwrap();
+        assert_eq!(COUNT.load(&a, &storage).unwrap(), 1);
+        assert_eq!(COUNT.may_load(&b, &storage).unwrap(), None);
+        assert!(!COUNT.exists(&b, &storage));
+
+        COUNT.save(&b, &mut storage, &2).unwrap();
+        COUNT.remove(&a, &mut storage);
+        assert_eq!(COUNT.may_load(&a, &storage).unwrap(), None);
+        assert_eq!(COUNT.load(&b, &storage).unwrap(), 2);
+    }
+
+    #[test]
+    fn maps_are_scoped_to_the_module() {
+        let manager: Manager = Manager::new();
+        let (a, b) = (Context::new(&manager, "a"), Context::new(&manager, "b"));
+        let mut storage = MockStorage::new();
+
+        for (name, balance) in [("alice", 1), ("bob", 2)] {
+            BALANCES
+                .save(&a, &mut storage, &name.to_string(), &balance)
+                .unwrap();
+        }
+        BALANCES
+            .save(&b, &mut storage, &"carol".to_string(), &3)
+            .unwrap();
+
+        assert!(!BALANCES.has(&b, &storage, &"alice".to_string()));
+        assert_eq!(
+            BALANCES.range(&a, &storage, None, 10).unwrap(),
+            vec![("alice".to_string(), 1), ("bob".to_string(), 2)]
+        );
+        assert_eq!(
+            BALANCES
+                .range(&a, &storage, Some(&"alice".to_string()), 10)
+                .unwrap(),
+            vec![("bob".to_string(), 2)]
+        );
+        assert_eq!(
+            BALANCES.range(&b, &storage, None, 10).unwrap(),
+            vec![("carol".to_string(), 3)]
+        );
+    }
+
+    #[test]
+    fn module_names_sharing_a_prefix_do_not_collide() {
+        let manager: Manager = Manager::new();
+        let (a, ab) = (Context::new(&manager, "a"), Context::new(&manager, "ab"));
+        let mut storage = MockStorage::new();
+
+        BALANCES
+            .save(&ab, &mut storage, &"alice".to_string(), &1)
+            .unwrap();
+        assert!(BALANCES.range(&a, &storage, None, 10).unwrap().is_empty());
+        assert_eq!(COUNT.may_load(&a, &storage).unwrap(), None);
+    }
+}
@@ -0,0 +1,195 @@
+//! Forwarding to a successor contract.
+//!
+//! A glue contract being replaced, e.g. during a blue/green migration of a
+//! composed system, can hand over to its successor one module at a time: once
+//! the admin sets a successor, execute messages and queries addressed to keys
+//! that match no registered module are forwarded to it, so clients keep
+//! sending their messages to the old address while modules are removed from
+//! it:
+//!
+//! ```javascript
+//! { "_manager": { "set_successor": { "successor": "xion1..." } } }
+//! ```
+//!
+//! Forwarded payloads keep their key, `{ "tokens": { ... } }` being sent as
+//! is to the successor, like a [Remote][crate::remote::Remote] module does.
+//! Execute payloads are sent as a `WasmMsg::Execute` with the funds of the
+//! message, and queries as a smart query. The successor sees this contract,
+//! not the original sender, as `info.sender`, so it must trust this contract
+//! to act on behalf of its users, or the forwarded messages must not depend
+//! on the sender.
+//!
+//! A [fallback][crate::manager::Manager::set_fallback] module takes
+//! precedence over the successor. Forwarded execute messages are rejected
+//! while the contract is paused.
+
+use crate::error::Error;
+use crate::response::Response;
+use crate::storage;
+use cosmwasm_std::{
+    to_json_binary, Addr, Binary, CustomQuery, Deps, MessageInfo, StdResult, Storage, WasmMsg,
+};
+use serde_json::{Map, Value};
+
+fn key() -> Vec<u8> {
+    storage::key(&[b"successor"], b"")
+}
+
+/// Load the successor, if one is set.
+pub fn successor(storage: &dyn Storage) -> StdResult<Option<Addr>> {
+    storage::load(storage, &key())
+}
+
+/// Forward messages to unknown keys to `successor`.
+pub fn set_successor(storage: &mut dyn Storage, successor: &Addr) -> StdResult<()> {
+    storage::save(storage, &key(), successor)
+}
+
+/// Stop forwarding messages, failing those to unknown keys again.
+pub fn clear_successor(storage: &mut dyn Storage) {
+    storage::remove(storage, &key())
+}
+
+/// The message sent to the successor for `payload` addressed to `name`.
+fn wrap(name: &str, payload: &Value) -> Value {
+    Value::Object(Map::from_iter([(name.to_string(), payload.clone())]))
+}
+
+/// Forward the execute `payload` addressed to `name` to `successor`, along
+/// with the funds of the message.
+pub(crate) fn forward_execute<C: Clone>(
+    successor: &Addr,
+    info: &MessageInfo,
+    name: &str,
+    payload: &Value,
+) -> Result<Response<C>, Error> {
+    Ok(Response::new()
+        .add_message(WasmMsg::Execute {
+            contract_addr: successor.to_string(),
+            msg: to_json_binary(&wrap(name, payload))?,
+            funds: info.funds.clone(),
+        })
+        .add_attribute("action", "forward")
+        .add_attribute("module", name)
+        .add_attribute("successor", successor))
+}
+
+/// Forward the query `payload` addressed to `name` to `successor`, returning
+/// its response.
+pub(crate) fn forward_query<Q: CustomQuery>(
+    deps: &Deps<Q>,
+    successor: &Addr,
+    name: &str,
+    payload: &Value,
+) -> Result<Binary, Error> {
+    let resp: Value = deps.querier.query_wasm_smart(successor, &wrap(name, payload))?;
+    Ok(to_json_binary(&resp)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::admin;
+    use crate::manager::Manager;
+    use crate::test_utils::{addr, info, Deposits, MockDeps};
+    use cosmwasm_std::testing::{mock_dependencies, mock_env};
+    use cosmwasm_std::{
+        coins, from_json, Coin, ContractResult, CosmosMsg, SystemError, SystemResult, WasmQuery,
+    };
+    use serde_json::json;
+
+    struct Setup {
+        deps: MockDeps,
+        manager: Manager,
+    }
+
+    impl Setup {
+        /// A contract keeping its `sale` module, whose successor echoes the
+        /// queries it receives.
+        fn new() -> Self {
+            let manager = Manager::builder()
+                .with_boxed_module("sale", Box::new(Deposits))
+                .unwrap()
+                .build();
+            let mut deps = mock_dependencies();
+            deps.querier.update_wasm(|query| match query {
+                WasmQuery::Smart { contract_addr, msg }
+                    if *contract_addr == addr("next").as_str() =>
+                {
+                    SystemResult::Ok(ContractResult::Ok(msg.clone()))
+                }
+                _ => SystemResult::Err(SystemError::UnsupportedRequest {
+                    kind: "wasm".to_string(),
+                }),
+            });
+            admin::set_admin(&mut deps.storage, &addr("admin")).unwrap();
+            Setup { deps, manager }
+        }
+
+        fn execute(
+            &mut self,
+            sender: &str,
+            funds: &[Coin],
+            msg: &str,
+        ) -> Result<cosmwasm_std::Response<Binary>, Error> {
+            self.manager.execute(
+                &mut self.deps.as_mut(),
+                mock_env(),
+                info(sender, funds),
+                msg,
+            )
+        }
+
+        fn set_successor(&mut self) {
+            let msg = json!({"_manager": {"set_successor": {"successor": addr("next")}}});
+            self.execute("admin", &[], &msg.to_string()).unwrap();
+        }
+    }
+
+    #[test]
+    fn unknown_execute_messages_are_forwarded_with_their_funds() {
+        let mut setup = Setup::new();
+        let msg = r#"{"tokens": {"transfer": {}}}"#;
+        setup.execute("alice", &[], msg).unwrap_err();
+
+        setup.set_successor();
+        let resp = setup.execute("alice", &coins(5, "uxion"), msg).unwrap();
+        let forwarded = match &resp.messages[..] {
+            [sub] => &sub.msg,
+            messages => panic!("unexpected messages {:?}", messages),
+        };
+        let expected = WasmMsg::Execute {
+            contract_addr: addr("next").to_string(),
+            msg: to_json_binary(&json!({"tokens": {"transfer": {}}})).unwrap(),
+            funds: coins(5, "uxion"),
+        };
+        assert_eq!(*forwarded, CosmosMsg::Wasm(expected));
+
+        let resp = setup.execute("alice", &[], r#"{"sale": {}}"#).unwrap();
+        assert!(resp.messages.is_empty());
+    }
+
+    #[test]
+    fn unknown_queries_are_forwarded() {
+        let mut setup = Setup::new();
+        setup.set_successor();
+        let msg = r#"{"tokens": {"balance": {}}}"#;
+        let resp = setup
+            .manager
+            .query(&setup.deps.as_ref(), mock_env(), msg)
+            .unwrap();
+        assert_eq!(
+            from_json::<Value>(&resp).unwrap(),
+            json!({"tokens": {"balance": {}}})
+        );
+
+        setup
+            .execute("admin", &[], r#"{"_manager": {"clear_successor": {}}}"#)
+            .unwrap();
+        assert_eq!(successor(&setup.deps.storage).unwrap(), None);
+        setup
+            .manager
+            .query(&setup.deps.as_ref(), mock_env(), msg)
+            .unwrap_err();
+    }
+}
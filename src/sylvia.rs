@@ -0,0 +1,161 @@
+//! Registering Sylvia contracts as modules.
+//!
+//! Contracts written with the Sylvia framework dispatch their generated
+//! messages themselves. [SylviaContract] describes such a contract, and is
+//! implemented for one with the [sylvia_contract][crate::sylvia_contract]
+//! macro; wrapped in [Sylvia], the contract can then be registered with a
+//! Manager like any other module:
+//!
+//! ```ignore
+//! burnt_glue::sylvia_contract! {
+//!     CounterContract {
+//!         instantiate: InstantiateMsg,
+//!         exec: ContractExecMsg,
+//!         query: ContractQueryMsg,
+//!         error: ContractError,
+//!     }
+//! }
+//!
+//! let counter = Sylvia::new(CounterContract::new());
+//! manager.register("counter".to_string(), shared(counter))?;
+//! ```
+//!
+//! Sylvia contracts keep their state under the storage keys they choose,
+//! which must not collide with those of the other modules.
+
+use crate::module::Module;
+use crate::response::Response;
+use cosmwasm_std::{
+    from_json, Binary, CustomQuery, Deps, DepsMut, Empty, Env, MessageInfo, StdError,
+};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+/// A contract dispatching its own messages, as generated by Sylvia.
+pub trait SylviaContract<C = Empty, Q: CustomQuery = Empty> {
+    type InstantiateMsg: DeserializeOwned;
+    type ExecMsg: DeserializeOwned;
+    type QueryMsg: DeserializeOwned;
+    type Error: std::error::Error + Send + Sync + From<StdError> + 'static;
+
+    fn instantiate(
+        &self,
+        deps: DepsMut<Q>,
+        env: Env,
+        info: MessageInfo,
+        msg: Self::InstantiateMsg,
+    ) -> Result<cosmwasm_std::Response<C>, Self::Error>;
+    fn execute(
+        &self,
+        deps: DepsMut<Q>,
+        env: Env,
+        info: MessageInfo,
+        msg: Self::ExecMsg,
+    ) -> Result<cosmwasm_std::Response<C>, Self::Error>;
+    fn query(&self, deps: Deps<Q>, env: Env, msg: Self::QueryMsg) -> Result<Binary, Self::Error>;
+}
+
+/// Implement [SylviaContract] for a contract generated by Sylvia, given its
+/// generated message types and its error type.
+#[macro_export]
+macro_rules! sylvia_contract {
+    (
+        $contract:ty {
+            instantiate: $instantiate:ty,
+            exec: $exec:ty,
+            query: $query:ty,
+            error: $error:ty $(,)?
+        }
+    ) => {
+        impl $crate::sylvia::SylviaContract for $contract {
+            type InstantiateMsg = $instantiate;
+            type ExecMsg = $exec;
+            type QueryMsg = $query;
+            type Error = $error;
+
+            fn instantiate(
+                &self,
+                deps: ::cosmwasm_std::DepsMut,
+                env: ::cosmwasm_std::Env,
+                info: ::cosmwasm_std::MessageInfo,
+                msg: $instantiate,
+            ) -> ::core::result::Result<::cosmwasm_std::Response, $error> {
+                msg.dispatch(self, (deps, env, info))
+            }
+
+            fn execute(
+                &self,
+                deps: ::cosmwasm_std::DepsMut,
+                env: ::cosmwasm_std::Env,
+                info: ::cosmwasm_std::MessageInfo,
+                msg: $exec,
+            ) -> ::core::result::Result<::cosmwasm_std::Response, $error> {
+                msg.dispatch(self, (deps, env, info))
+            }
+
+            fn query(
+                &self,
+                deps: ::cosmwasm_std::Deps,
+                env: ::cosmwasm_std::Env,
+                msg: $query,
+            ) -> ::core::result::Result<::cosmwasm_std::Binary, $error> {
+                msg.dispatch(self, (deps, env))
+            }
+        }
+    };
+}
+
+/// A module dispatching to a [SylviaContract].
+#[derive(Clone, Debug, Default)]
+pub struct Sylvia<T> {
+    contract: T,
+}
+
+impl<T> Sylvia<T> {
+    pub fn new(contract: T) -> Self {
+        Sylvia { contract }
+    }
+
+    /// The wrapped contract.
+    pub fn contract(&self) -> &T {
+        &self.contract
+    }
+}
+
+impl<T, C, Q> Module<C, Q> for Sylvia<T>
+where
+    T: SylviaContract<C, Q>,
+    Q: CustomQuery,
+{
+    type InstantiateMsg = T::InstantiateMsg;
+    type ExecuteMsg = T::ExecMsg;
+    type QueryMsg = T::QueryMsg;
+    type QueryResp = Value;
+    type Error = T::Error;
+
+    fn instantiate(
+        &mut self,
+        deps: &mut DepsMut<Q>,
+        env: &Env,
+        info: &MessageInfo,
+        msg: T::InstantiateMsg,
+    ) -> Result<Response<C>, T::Error> {
+        let resp = self.contract.instantiate(deps.branch(), env.clone(), info.clone(), msg)?;
+        Ok(resp.into())
+    }
+
+    fn execute(
+        &mut self,
+        deps: &mut DepsMut<Q>,
+        env: Env,
+        info: MessageInfo,
+        msg: T::ExecMsg,
+    ) -> Result<Response<C>, T::Error> {
+        Ok(self.contract.execute(deps.branch(), env, info, msg)?.into())
+    }
+
+    fn query(&self, deps: &Deps<Q>, env: Env, msg: T::QueryMsg) -> Result<Value, T::Error> {
+        let resp = self.contract.query(*deps, env, msg)?;
+        Ok(from_json(&resp)?)
+    }
+}
@@ -0,0 +1,130 @@
+//! Fixtures shared by the unit tests.
+
+use crate::error::Error;
+use crate::manager::Manager;
+use crate::module::Module;
+use crate::response::Response;
+use cosmwasm_std::testing::{mock_dependencies, mock_env, MockApi, MockQuerier, MockStorage};
+use cosmwasm_std::{Addr, Binary, Coin, Deps, DepsMut, Env, MessageInfo, OwnedDeps, StdError};
+use serde_json::Value;
+
+/// The dependencies [mock_dependencies] returns.
+pub type MockDeps = OwnedDeps<MockStorage, MockApi, MockQuerier>;
+
+/// The address of `name`. The mock API of cosmwasm-std 2.x only accepts
+/// bech32 addresses, which `addr_make` builds with either version.
+pub fn addr(name: &str) -> Addr {
+    MockApi::default().addr_make(name)
+}
+
+/// A message from `addr(sender)` with `funds` attached.
+pub fn info(sender: &str, funds: &[Coin]) -> MessageInfo {
+    MessageInfo {
+        sender: addr(sender),
+        funds: funds.to_vec(),
+    }
+}
+
+/// Instantiate a new `M` on `deps` as `sender`.
+pub fn instantiate<M: Module + Default>(
+    deps: &mut MockDeps,
+    sender: &str,
+    msg: M::InstantiateMsg,
+) -> Result<Response, M::Error> {
+    M::default().instantiate(&mut deps.as_mut(), &mock_env(), &info(sender, &[]), msg)
+}
+
+/// Execute `msg` on a new `M` as `sender`. Modules keep their state in
+/// `deps`, so each call sees the state the previous ones left.
+pub fn execute<M: Module + Default>(
+    deps: &mut MockDeps,
+    sender: &str,
+    msg: M::ExecuteMsg,
+) -> Result<Response, M::Error> {
+    M::default().execute(&mut deps.as_mut(), mock_env(), info(sender, &[]), msg)
+}
+
+/// A module reporting the funds and sender of the messages it receives in
+/// `funds` and `sender` attributes.
+pub struct Deposits;
+
+impl Deposits {
+    fn report(info: &MessageInfo) -> Response {
+        let funds: Vec<String> = info.funds.iter().map(Coin::to_string).collect();
+        Response::new()
+            .add_attribute("funds", funds.join(","))
+            .add_attribute("sender", info.sender.clone())
+    }
+}
+
+impl Module for Deposits {
+    type InstantiateMsg = Value;
+    type ExecuteMsg = Value;
+    type QueryMsg = Value;
+    type QueryResp = Value;
+    type Error = StdError;
+
+    fn instantiate(
+        &mut self,
+        _deps: &mut DepsMut,
+        _env: &Env,
+        info: &MessageInfo,
+        _msg: Value,
+    ) -> Result<Response, StdError> {
+        Ok(Deposits::report(info))
+    }
+
+    fn execute(
+        &mut self,
+        _deps: &mut DepsMut,
+        _env: Env,
+        info: MessageInfo,
+        _msg: Value,
+    ) -> Result<Response, StdError> {
+        Ok(Deposits::report(&info))
+    }
+
+    fn query(&self, _deps: &Deps, _env: Env, _msg: Value) -> Result<Value, StdError> {
+        Ok(Value::Null)
+    }
+}
+
+/// Execute `msg` through `manager` on fresh mock dependencies, with `funds`
+/// attached.
+pub fn dispatch(
+    mut manager: Manager,
+    funds: &[Coin],
+    msg: &str,
+) -> Result<cosmwasm_std::Response<Binary>, Error> {
+    let mut deps = mock_dependencies();
+    manager.execute(&mut deps.as_mut(), mock_env(), info("sender", funds), msg)
+}
+
+/// Instantiate the modules of `manager` on fresh mock dependencies, with
+/// `funds` attached.
+pub fn instantiate_all(
+    mut manager: Manager,
+    funds: &[Coin],
+    msg: &str,
+) -> Result<cosmwasm_std::Response<Binary>, Error> {
+    let mut deps = mock_dependencies();
+    manager.instantiate(deps.as_mut(), mock_env(), info("sender", funds), msg)
+}
+
+/// The funds each [Deposits] module saw, in order.
+pub fn funds_seen(resp: &cosmwasm_std::Response<Binary>) -> Vec<&str> {
+    attributes(resp, "funds")
+}
+
+/// The senders each [Deposits] module saw, in order.
+pub fn senders_seen(resp: &cosmwasm_std::Response<Binary>) -> Vec<&str> {
+    attributes(resp, "sender")
+}
+
+fn attributes<'a>(resp: &'a cosmwasm_std::Response<Binary>, key: &str) -> Vec<&'a str> {
+    resp.attributes
+        .iter()
+        .filter(|attr| attr.key == key)
+        .map(|attr| attr.value.as_str())
+        .collect()
+}
@@ -0,0 +1,284 @@
+//! A test double standing in for a module.
+//!
+//! [MockModule] records every message it receives and answers with scripted
+//! responses, so a module can be tested against fake siblings:
+//!
+//! ```ignore
+//! let oracle = shared(MockModule::new().with_query_response(&PriceResponse { price: 42 }));
+//! manager.register("oracle".to_string(), oracle.clone())?;
+//! // ... dispatch messages ...
+//! shared::try_borrow(&oracle).unwrap().assert_queried_with(&OracleQuery::Price {});
+//! ```
+
+use crate::module::Module;
+use crate::response::Response;
+use cosmwasm_std::{Addr, Coin, CustomQuery, Deps, DepsMut, Env, MessageInfo, StdError};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+/// A message received by a [MockModule].
+#[derive(Clone, Debug, PartialEq)]
+pub enum Call {
+    Instantiate {
+        sender: Addr,
+        funds: Vec<Coin>,
+        msg: Value,
+    },
+    Execute {
+        sender: Addr,
+        funds: Vec<Coin>,
+        msg: Value,
+    },
+    Query {
+        msg: Value,
+    },
+}
+
+/// A module recording the messages it receives and answering with scripted
+/// responses. Scripted responses are used once, in order; once they run out,
+/// execute messages succeed with an empty response and queries return the
+/// default query response.
+///
+/// The recorded messages are kept behind a `Mutex`, so the mock can be
+/// registered with the `sync` feature enabled too.
+#[derive(Debug, Default)]
+pub struct MockModule {
+    calls: Mutex<Vec<Call>>,
+    execute_responses: VecDeque<Result<Value, String>>,
+    query_responses: Mutex<VecDeque<Result<Value, String>>>,
+    default_query_response: Value,
+}
+
+impl MockModule {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer queries with `resp` once the scripted responses run out.
+    pub fn with_query_response(mut self, resp: &impl Serialize) -> Self {
+        self.default_query_response = to_value(resp);
+        self
+    }
+
+    /// Answer the next execute message with `data`.
+    pub fn push_execute_data(&mut self, data: &impl Serialize) {
+        self.execute_responses.push_back(Ok(to_value(data)));
+    }
+
+    /// Fail the next execute message with `err`.
+    pub fn push_execute_error(&mut self, err: impl Into<String>) {
+        self.execute_responses.push_back(Err(err.into()));
+    }
+
+    /// Answer the next query with `resp`.
+    pub fn push_query_response(&mut self, resp: &impl Serialize) {
+        self.query_responses
+            .get_mut()
+            .unwrap()
+            .push_back(Ok(to_value(resp)));
+    }
+
+    /// Fail the next query with `err`.
+    pub fn push_query_error(&mut self, err: impl Into<String>) {
+        self.query_responses
+            .get_mut()
+            .unwrap()
+            .push_back(Err(err.into()));
+    }
+
+    /// Every message received so far, in order.
+    pub fn calls(&self) -> Vec<Call> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    /// The execute messages received so far, in order.
+    pub fn executions(&self) -> Vec<Value> {
+        self.calls
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|call| match call {
+                Call::Execute { msg, .. } => Some(msg.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The queries received so far, in order.
+    pub fn queries(&self) -> Vec<Value> {
+        self.calls
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|call| match call {
+                Call::Query { msg } => Some(msg.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn execute_count(&self) -> usize {
+        self.executions().len()
+    }
+
+    pub fn query_count(&self) -> usize {
+        self.queries().len()
+    }
+
+    /// Panic unless the module was instantiated with `msg`.
+    pub fn assert_instantiated_with(&self, msg: &impl Serialize) {
+        let msg = to_value(msg);
+        let instantiated = self
+            .calls
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|call| matches!(call, Call::Instantiate { msg: m, .. } if *m == msg));
+        assert!(instantiated, "module was not instantiated with {}", msg);
+    }
+
+    /// Panic unless the module received the execute message `msg`.
+    pub fn assert_executed_with(&self, msg: &impl Serialize) {
+        let msg = to_value(msg);
+        let executions = self.executions();
+        assert!(
+            executions.contains(&msg),
+            "module was not executed with {}; executions: {:?}",
+            msg,
+            executions
+        );
+    }
+
+    /// Panic unless the module received the query `msg`.
+    pub fn assert_queried_with(&self, msg: &impl Serialize) {
+        let msg = to_value(msg);
+        let queries = self.queries();
+        assert!(
+            queries.contains(&msg),
+            "module was not queried with {}; queries: {:?}",
+            msg,
+            queries
+        );
+    }
+}
+
+fn to_value(value: &impl Serialize) -> Value {
+    serde_json::to_value(value).expect("mock response must serialize to JSON")
+}
+
+impl<C: Clone, Q: CustomQuery> Module<C, Q> for MockModule {
+    type InstantiateMsg = Value;
+    type ExecuteMsg = Value;
+    type QueryMsg = Value;
+    type QueryResp = Value;
+    type Error = StdError;
+
+    fn instantiate(
+        &mut self,
+        _deps: &mut DepsMut<Q>,
+        _env: &Env,
+        info: &MessageInfo,
+        msg: Value,
+    ) -> Result<Response<C>, StdError> {
+        self.calls.get_mut().unwrap().push(Call::Instantiate {
+            sender: info.sender.clone(),
+            funds: info.funds.clone(),
+            msg,
+        });
+        Ok(Response::new())
+    }
+
+    fn execute(
+        &mut self,
+        _deps: &mut DepsMut<Q>,
+        _env: Env,
+        info: MessageInfo,
+        msg: Value,
+    ) -> Result<Response<C>, StdError> {
+        self.calls.get_mut().unwrap().push(Call::Execute {
+            sender: info.sender,
+            funds: info.funds,
+            msg,
+        });
+        match self.execute_responses.pop_front() {
+            Some(Ok(data)) => Ok(Response::new().set_data(data)),
+            Some(Err(err)) => Err(StdError::generic_err(err)),
+            None => Ok(Response::new()),
+        }
+    }
+
+    fn query(&self, _deps: &Deps<Q>, _env: Env, msg: Value) -> Result<Value, StdError> {
+        self.calls.lock().unwrap().push(Call::Query { msg });
+        match self.query_responses.lock().unwrap().pop_front() {
+            Some(resp) => resp.map_err(StdError::generic_err),
+            None => Ok(self.default_query_response.clone()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manager::Manager;
+    use crate::shared::{self, shared};
+    use crate::test_utils::{addr, info};
+    use cosmwasm_std::testing::{mock_dependencies, mock_env};
+    use cosmwasm_std::{coins, from_json};
+    use serde_json::json;
+
+    #[test]
+    fn mocks_record_calls_and_answer_with_scripted_responses() {
+        let mut oracle = MockModule::new().with_query_response(&json!({"price": 42}));
+        oracle.push_execute_data(&json!({"updated": true}));
+        oracle.push_execute_error("stale price");
+        oracle.push_query_response(&json!({"price": 41}));
+        let oracle = shared(oracle);
+        let mut manager: Manager = Manager::new();
+        manager
+            .register("oracle".to_string(), oracle.clone())
+            .unwrap();
+        let mut deps = mock_dependencies();
+
+        let mut execute = |msg: &Value| {
+            manager.execute(
+                &mut deps.as_mut(),
+                mock_env(),
+                info("alice", &coins(1, "uxion")),
+                &json!({ "oracle": msg }).to_string(),
+            )
+        };
+        let resp = execute(&json!({"update": {}})).unwrap();
+        assert_eq!(
+            from_json::<Value>(resp.data.unwrap()).unwrap(),
+            json!({"updated": true})
+        );
+        let err = execute(&json!({"update": {}})).unwrap_err();
+        assert!(err.to_string().contains("stale price"), "{}", err);
+        execute(&json!({"reset": {}})).unwrap();
+
+        let prices: Vec<Value> = (0..2)
+            .map(|_| {
+                let msg = json!({"oracle": {"price": {}}}).to_string();
+                let resp = manager.query(&deps.as_ref(), mock_env(), &msg).unwrap();
+                from_json(resp).unwrap()
+            })
+            .collect();
+        assert_eq!(prices, [json!({"price": 41}), json!({"price": 42})]);
+
+        let oracle = shared::try_borrow(&oracle).unwrap();
+        assert_eq!(oracle.execute_count(), 3);
+        assert_eq!(oracle.query_count(), 2);
+        oracle.assert_executed_with(&json!({"reset": {}}));
+        oracle.assert_queried_with(&json!({"price": {}}));
+        assert_eq!(
+            oracle.calls()[0],
+            Call::Execute {
+                sender: addr("alice"),
+                funds: coins(1, "uxion"),
+                msg: json!({"update": {}}),
+            }
+        );
+    }
+}
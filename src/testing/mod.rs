@@ -0,0 +1,5 @@
+//! Helpers for testing glue contracts. Enabled with the `testing` feature.
+
+pub mod mock;
+pub mod multi_test;
+pub mod snapshot;
@@ -0,0 +1,202 @@
+//! Helpers for testing glue contracts with `cw-multi-test`.
+//!
+//! [GlueContract] wraps a function building the contract's Manager into a
+//! multi-test contract, and [execute_module] and [query_module] address
+//! typed messages to a single module:
+//!
+//! ```ignore
+//! fn manager() -> Manager<Empty, Empty> {
+//!     Manager::builder()
+//!         .with_module("token", shared(Token::new()))
+//!         .unwrap()
+//!         .build()
+//! }
+//!
+//! let mut app = App::default();
+//! let code_id = app.store_code(Box::new(GlueContract::new(manager)));
+//! // ... instantiate the contract ...
+//! execute_module(&mut app, owner, contract.clone(), "token", &TokenMsg::Mint {}, &[])?;
+//! let supply: SupplyResponse = query_module(&app, contract, "token", &TokenQuery::Supply {})?;
+//! ```
+
+use crate::manager::Manager;
+use anyhow::{bail, Result as AnyResult};
+use cosmwasm_std::{
+    Addr, Binary, Coin, Deps, DepsMut, Empty, Env, MessageInfo, Reply, Response, StdError,
+    StdResult,
+};
+use cw_multi_test::{App, AppResponse, Contract, Executor};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+/// A multi-test contract dispatching every call to a freshly built Manager,
+/// just as a glue contract's entrypoints do.
+pub struct GlueContract {
+    build: fn() -> Manager<Empty, Empty>,
+}
+
+impl GlueContract {
+    /// Wrap `build`, which constructs the contract's Manager.
+    pub fn new(build: fn() -> Manager<Empty, Empty>) -> Self {
+        GlueContract { build }
+    }
+}
+
+impl Contract<Empty> for GlueContract {
+    fn execute(
+        &self,
+        mut deps: DepsMut<Empty>,
+        env: Env,
+        info: MessageInfo,
+        msg: Vec<u8>,
+    ) -> AnyResult<Response<Empty>> {
+        Ok((self.build)().execute_raw(&mut deps, env, info, &msg)?)
+    }
+
+    fn instantiate(
+        &self,
+        deps: DepsMut<Empty>,
+        env: Env,
+        info: MessageInfo,
+        msg: Vec<u8>,
+    ) -> AnyResult<Response<Empty>> {
+        Ok((self.build)().instantiate_raw(deps, env, info, &msg)?)
+    }
+
+    fn query(&self, deps: Deps<Empty>, env: Env, msg: Vec<u8>) -> AnyResult<Binary> {
+        Ok((self.build)().query_raw(&deps, env, &msg)?)
+    }
+
+    fn sudo(&self, _deps: DepsMut<Empty>, _env: Env, _msg: Vec<u8>) -> AnyResult<Response<Empty>> {
+        bail!("glue contracts do not implement sudo")
+    }
+
+    fn reply(&self, _deps: DepsMut<Empty>, _env: Env, _msg: Reply) -> AnyResult<Response<Empty>> {
+        bail!("glue contracts do not implement reply")
+    }
+
+    fn migrate(&self, deps: DepsMut<Empty>, env: Env, msg: Vec<u8>) -> AnyResult<Response<Empty>> {
+        Ok((self.build)().migrate_raw(deps, env, &msg)?)
+    }
+}
+
+/// Wrap `msg` in the envelope addressing it to `module`.
+pub fn module_msg(module: &str, msg: &impl Serialize) -> serde_json::Result<Value> {
+    Ok(json!({ module: serde_json::to_value(msg)? }))
+}
+
+/// Execute `msg` on the module registered under `module` in `contract`.
+pub fn execute_module(
+    app: &mut App,
+    sender: Addr,
+    contract: Addr,
+    module: &str,
+    msg: &impl Serialize,
+    funds: &[Coin],
+) -> AnyResult<AppResponse> {
+    let msg = module_msg(module, msg)?;
+    app.execute_contract(sender, contract, &msg, funds)
+}
+
+/// Query the module registered under `module` in `contract`.
+pub fn query_module<T: DeserializeOwned>(
+    app: &App,
+    contract: impl Into<String>,
+    module: &str,
+    msg: &impl Serialize,
+) -> StdResult<T> {
+    let msg = module_msg(module, msg).map_err(|e| StdError::generic_err(e.to_string()))?;
+    app.wrap().query_wasm_smart(contract, &msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::module::Module;
+    use crate::response::Response as GlueResponse;
+    use crate::shared::shared;
+
+    /// A module counting the execute messages it receives.
+    struct Counter;
+
+    impl Module<Empty> for Counter {
+        type InstantiateMsg = u32;
+        type ExecuteMsg = Value;
+        type QueryMsg = Value;
+        type QueryResp = u32;
+        type Error = StdError;
+
+        fn instantiate(
+            &mut self,
+            deps: &mut DepsMut,
+            _env: &Env,
+            _info: &MessageInfo,
+            start: u32,
+        ) -> Result<GlueResponse<Empty>, StdError> {
+            deps.storage.set(b"count", &start.to_be_bytes());
+            Ok(GlueResponse::new())
+        }
+
+        fn execute(
+            &mut self,
+            deps: &mut DepsMut,
+            env: Env,
+            _info: MessageInfo,
+            _msg: Value,
+        ) -> Result<GlueResponse<Empty>, StdError> {
+            let count = self.query(&deps.as_ref(), env, Value::Null)? + 1;
+            deps.storage.set(b"count", &count.to_be_bytes());
+            Ok(GlueResponse::new().add_attribute("count", count.to_string()))
+        }
+
+        fn query(&self, deps: &Deps, _env: Env, _msg: Value) -> Result<u32, StdError> {
+            let count = deps
+                .storage
+                .get(b"count")
+                .ok_or_else(|| StdError::not_found("count"))?;
+            Ok(u32::from_be_bytes(count.try_into().unwrap()))
+        }
+    }
+
+    fn manager() -> Manager<Empty, Empty> {
+        Manager::builder()
+            .with_module("counter", shared(Counter))
+            .unwrap()
+            .build()
+    }
+
+    #[test]
+    fn glue_contracts_run_in_multi_test() {
+        let mut app = App::default();
+        let code_id = app.store_code(Box::new(GlueContract::new(manager)));
+        let owner = Addr::unchecked("owner");
+        let contract = app
+            .instantiate_contract(
+                code_id,
+                owner.clone(),
+                &json!({"counter": 5}),
+                &[],
+                "counter",
+                None,
+            )
+            .unwrap();
+
+        let resp = execute_module(
+            &mut app,
+            owner,
+            contract.clone(),
+            "counter",
+            &json!({}),
+            &[],
+        )
+        .unwrap();
+        assert!(resp
+            .events
+            .iter()
+            .flat_map(|event| &event.attributes)
+            .any(|attr| attr.key == "count" && attr.value == "6"));
+        let count: u32 = query_module(&app, contract, "counter", &json!({})).unwrap();
+        assert_eq!(count, 6);
+    }
+}
@@ -0,0 +1,140 @@
+//! Golden-file testing of aggregated responses.
+//!
+//! [snapshot] renders the externally observable parts of a response, i.e.
+//! its messages, attributes, events, and data, as stable, pretty-printed
+//! JSON, and [assert_snapshot] compares it with a file checked into the
+//! repository:
+//!
+//! ```ignore
+//! let resp = manager.execute(&mut deps.as_mut(), env, info, msg)?;
+//! assert_snapshot(&resp, "tests/snapshots/mint.json");
+//! ```
+//!
+//! Missing snapshot files are written on first use. Set the
+//! `GLUE_UPDATE_SNAPSHOTS` environment variable to rewrite existing ones
+//! after an intended change.
+
+use cosmwasm_std::Response;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::fs;
+use std::path::Path;
+
+/// The environment variable which, when set, makes [assert_snapshot]
+/// rewrite snapshot files instead of comparing against them.
+pub const UPDATE_ENV: &str = "GLUE_UPDATE_SNAPSHOTS";
+
+/// Render `resp` as canonical JSON: object keys are sorted and data is
+/// decoded as JSON when possible, and shown as base64 otherwise.
+pub fn snapshot<C: Serialize>(resp: &Response<C>) -> String {
+    let data = resp.data.as_ref().map(|data| {
+        serde_json::from_slice::<Value>(data)
+            .unwrap_or_else(|_| json!({ "binary": data.to_base64() }))
+    });
+    let value = json!({
+        "messages": resp.messages,
+        "attributes": resp.attributes,
+        "events": resp.events,
+        "data": data,
+    });
+    let mut out = serde_json::to_string_pretty(&canonicalize(value))
+        .expect("responses serialize to JSON");
+    out.push('\n');
+    out
+}
+
+/// Compare the [snapshot] of `resp` with the contents of `path`, panicking
+/// on a mismatch. The file is written if it doesn't exist or if
+/// [UPDATE_ENV] is set.
+pub fn assert_snapshot<C: Serialize>(resp: &Response<C>, path: impl AsRef<Path>) {
+    let path = path.as_ref();
+    let actual = snapshot(resp);
+    if std::env::var_os(UPDATE_ENV).is_some() || !path.exists() {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).expect("failed to create snapshot directory");
+        }
+        fs::write(path, &actual).expect("failed to write snapshot");
+        return;
+    }
+    let expected = fs::read_to_string(path).expect("failed to read snapshot");
+    assert!(
+        expected == actual,
+        "response does not match snapshot {}; rerun with {} set to update it\n\
+         --- expected\n{}\n--- actual\n{}",
+        path.display(),
+        UPDATE_ENV,
+        expected,
+        actual
+    );
+}
+
+/// Sort the keys of every object in `value`, independent of whether
+/// serde_json's `preserve_order` feature is enabled.
+fn canonicalize(value: Value) -> Value {
+    match value {
+        Value::Object(obj) => {
+            let mut entries: Vec<(String, Value)> = obj.into_iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            let obj: Map<String, Value> = entries
+                .into_iter()
+                .map(|(k, v)| (k, canonicalize(v)))
+                .collect();
+            Value::Object(obj)
+        }
+        Value::Array(values) => Value::Array(values.into_iter().map(canonicalize).collect()),
+        value => value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::{to_json_binary, Binary, Empty};
+    use std::path::PathBuf;
+
+    fn response() -> Response<Empty> {
+        Response::new()
+            .add_attribute("action", "mint")
+            .set_data(to_json_binary(&json!({"minted": 5, "id": "a"})).unwrap())
+    }
+
+    /// A snapshot path of its own for the test `name`.
+    fn snapshot_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("glue-snapshots-{}", std::process::id()));
+        dir.join(format!("{}.json", name))
+    }
+
+    #[test]
+    fn snapshots_sort_keys_and_decode_json_data() {
+        let rendered: Value = serde_json::from_str(&snapshot(&response())).unwrap();
+        assert_eq!(rendered["data"], json!({"id": "a", "minted": 5}));
+        assert_eq!(
+            rendered["attributes"],
+            json!([{"key": "action", "value": "mint"}])
+        );
+        let keys: Vec<&String> = rendered["data"].as_object().unwrap().keys().collect();
+        assert_eq!(keys, ["id", "minted"]);
+
+        let binary: Response<Empty> = Response::new().set_data(Binary::from(vec![0xff]));
+        let rendered: Value = serde_json::from_str(&snapshot(&binary)).unwrap();
+        assert_eq!(rendered["data"], json!({"binary": "/w=="}));
+    }
+
+    #[test]
+    fn missing_snapshots_are_written_then_compared() {
+        let path = snapshot_path("written");
+        let _ = fs::remove_file(&path);
+        assert_snapshot(&response(), &path);
+        assert_eq!(fs::read_to_string(&path).unwrap(), snapshot(&response()));
+        assert_snapshot(&response(), &path);
+    }
+
+    #[test]
+    #[should_panic(expected = "does not match snapshot")]
+    fn changed_responses_fail_the_comparison() {
+        let path = snapshot_path("changed");
+        let _ = fs::remove_file(&path);
+        assert_snapshot(&response(), &path);
+        assert_snapshot(&response().add_attribute("extra", "1"), &path);
+    }
+}
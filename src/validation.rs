@@ -0,0 +1,395 @@
+//! Validation of payloads against JSON schemas.
+//!
+//! With [Manager::set_validate_schemas][crate::manager::Manager::set_validate_schemas]
+//! enabled, payloads addressed to a module declaring
+//! [schemas][crate::module::Module::schemas] are checked against them before
+//! being deserialized. Instead of serde's first error, every violation is
+//! reported with the path of the offending value and what was expected:
+//!
+//! ```text
+//! $.transfer.amount: expected string, found integer; $.transfer: missing field "recipient"
+//! ```
+//!
+//! The subset of JSON Schema produced by `schemars` is supported: `type`,
+//! `enum`, `const`, `properties`, `required`, `additionalProperties`, `items`,
+//! `minItems`, `maxItems`, `minimum`, `maximum`, `allOf`, `anyOf`, `oneOf`,
+//! and local `$ref`s into `definitions` or `$defs`. Other keywords, such as
+//! `format`, are ignored.
+
+use serde_json::{Map, Value};
+use std::borrow::Cow;
+use std::fmt;
+
+/// A violation of a schema by a payload.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SchemaError {
+    /// The path of the offending value, e.g. `$.transfer.amount`.
+    pub path: String,
+    pub msg: String,
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.msg)
+    }
+}
+
+/// Validate `value` against `schema`, returning every violation found.
+pub fn validate(schema: &Value, value: &Value) -> Vec<SchemaError> {
+    let mut errors = Vec::new();
+    let validator = Validator {
+        root: schema,
+        name: None,
+    };
+    validator.check(schema, value, "$", &mut errors);
+    errors
+}
+
+/// Like [validate], matching the keys of the objects of `value` with the
+/// properties of `schema` by the names `name` converts them to, e.g.
+/// [to_snake_case][crate::case::to_snake_case].
+pub fn validate_named(schema: &Value, value: &Value, name: fn(&str) -> String) -> Vec<SchemaError> {
+    let mut errors = Vec::new();
+    let validator = Validator {
+        root: schema,
+        name: Some(name),
+    };
+    validator.check(schema, value, "$", &mut errors);
+    errors
+}
+
+struct Validator<'a> {
+    root: &'a Value,
+    name: Option<fn(&str) -> String>,
+}
+
+impl<'a> Validator<'a> {
+    fn check(&self, schema: &'a Value, value: &Value, path: &str, errors: &mut Vec<SchemaError>) {
+        let schema = match schema {
+            Value::Bool(true) => return,
+            Value::Bool(false) => return error(errors, path, "no value is allowed here"),
+            Value::Object(schema) => schema,
+            _ => return,
+        };
+        if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+            match self.resolve(reference) {
+                Some(target) => self.check(target, value, path, errors),
+                None => error(errors, path, format!("unresolved schema reference {}", reference)),
+            }
+        }
+        if let Some(expected) = schema.get("type") {
+            if !type_matches(expected, value) {
+                let msg = format!("expected {}, found {}", type_list(expected), type_name(value));
+                return error(errors, path, msg);
+            }
+        }
+        if let Some(Value::Array(allowed)) = schema.get("enum") {
+            if !allowed.contains(value) {
+                let allowed: Vec<String> = allowed.iter().map(Value::to_string).collect();
+                error(errors, path, format!("expected one of {}", allowed.join(", ")));
+            }
+        }
+        if let Some(expected) = schema.get("const") {
+            if expected != value {
+                error(errors, path, format!("expected {}", expected));
+            }
+        }
+        if let Some(Value::Array(schemas)) = schema.get("allOf") {
+            for schema in schemas {
+                self.check(schema, value, path, errors);
+            }
+        }
+        for keyword in ["anyOf", "oneOf"] {
+            if let Some(Value::Array(variants)) = schema.get(keyword) {
+                self.check_variants(variants, value, path, errors);
+            }
+        }
+        match value {
+            Value::Object(object) => self.check_object(schema, object, path, errors),
+            Value::Array(items) => self.check_array(schema, items, path, errors),
+            Value::Number(number) => {
+                let number = number.as_f64().unwrap_or_default();
+                if let Some(minimum) = schema.get("minimum").and_then(Value::as_f64) {
+                    if number < minimum {
+                        error(errors, path, format!("expected at least {}", minimum));
+                    }
+                }
+                if let Some(maximum) = schema.get("maximum").and_then(Value::as_f64) {
+                    if number > maximum {
+                        error(errors, path, format!("expected at most {}", maximum));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Check `value` against the variants of an `anyOf` or `oneOf`. When no
+    /// variant matches, the errors of the variant expecting the single key of
+    /// `value`, as with externally tagged enums, are reported if there is one.
+    fn check_variants(
+        &self,
+        variants: &'a [Value],
+        value: &Value,
+        path: &str,
+        errors: &mut Vec<SchemaError>,
+    ) {
+        let mut closest = None;
+        for variant in variants {
+            let mut variant_errors = Vec::new();
+            self.check(variant, value, path, &mut variant_errors);
+            if variant_errors.is_empty() {
+                return;
+            }
+            if let (Value::Object(object), Some(required)) = (value, self.required(variant)) {
+                let keys = object.keys().map(|key| self.name(key));
+                if object.len() == 1 && keys.eq(required.iter().map(Cow::from)) {
+                    closest = Some(variant_errors);
+                }
+            }
+        }
+        match closest {
+            Some(variant_errors) => errors.extend(variant_errors),
+            None => {
+                let keys: Vec<String> = variants
+                    .iter()
+                    .filter_map(|variant| match self.required(variant)?.as_slice() {
+                        [key] => Some(format!("{:?}", key)),
+                        _ => None,
+                    })
+                    .collect();
+                let msg = if keys.len() == variants.len() {
+                    format!("expected one of the keys {}", keys.join(", "))
+                } else {
+                    "matches none of the allowed variants".to_string()
+                };
+                error(errors, path, msg);
+            }
+        }
+    }
+
+    fn check_object(
+        &self,
+        schema: &'a Map<String, Value>,
+        object: &Map<String, Value>,
+        path: &str,
+        errors: &mut Vec<SchemaError>,
+    ) {
+        if let Some(Value::Array(required)) = schema.get("required") {
+            for key in required.iter().filter_map(Value::as_str) {
+                if !object.keys().any(|name| self.name(name) == key) {
+                    error(errors, path, format!("missing field {:?}", key));
+                }
+            }
+        }
+        let properties = schema.get("properties").and_then(Value::as_object);
+        let additional = schema.get("additionalProperties");
+        for (key, value) in object {
+            let path = format!("{}.{}", path, key);
+            let name = self.name(key);
+            match (properties.and_then(|p| p.get(name.as_ref())), additional) {
+                (Some(schema), _) => self.check(schema, value, &path, errors),
+                (None, Some(Value::Bool(false))) => error(errors, &path, "unknown field"),
+                (None, Some(schema)) => self.check(schema, value, &path, errors),
+                (None, None) => {}
+            }
+        }
+    }
+
+    fn check_array(
+        &self,
+        schema: &'a Map<String, Value>,
+        items: &[Value],
+        path: &str,
+        errors: &mut Vec<SchemaError>,
+    ) {
+        if let Some(min) = schema.get("minItems").and_then(Value::as_u64) {
+            if (items.len() as u64) < min {
+                error(errors, path, format!("expected at least {} items", min));
+            }
+        }
+        if let Some(max) = schema.get("maxItems").and_then(Value::as_u64) {
+            if items.len() as u64 > max {
+                error(errors, path, format!("expected at most {} items", max));
+            }
+        }
+        match schema.get("items") {
+            Some(Value::Array(schemas)) => {
+                for (index, (schema, item)) in schemas.iter().zip(items).enumerate() {
+                    self.check(schema, item, &format!("{}[{}]", path, index), errors);
+                }
+            }
+            Some(schema) => {
+                for (index, item) in items.iter().enumerate() {
+                    self.check(schema, item, &format!("{}[{}]", path, index), errors);
+                }
+            }
+            None => {}
+        }
+    }
+
+    /// The name of the property `key` stands for.
+    fn name<'k>(&self, key: &'k str) -> Cow<'k, str> {
+        match self.name {
+            Some(name) => Cow::Owned(name(key)),
+            None => Cow::Borrowed(key),
+        }
+    }
+
+    /// The schema a local `$ref` such as `#/definitions/Uint128` points to.
+    fn resolve(&self, reference: &str) -> Option<&'a Value> {
+        let pointer = reference.strip_prefix('#')?;
+        self.root.pointer(pointer)
+    }
+
+    /// The required fields of `schema`, following a `$ref`.
+    fn required(&self, schema: &'a Value) -> Option<Vec<String>> {
+        let schema = match schema.get("$ref").and_then(Value::as_str) {
+            Some(reference) => self.resolve(reference)?,
+            None => schema,
+        };
+        let required = schema.get("required")?.as_array()?;
+        Some(required.iter().filter_map(Value::as_str).map(String::from).collect())
+    }
+}
+
+fn error(errors: &mut Vec<SchemaError>, path: &str, msg: impl Into<String>) {
+    errors.push(SchemaError {
+        path: path.to_string(),
+        msg: msg.into(),
+    });
+}
+
+fn type_matches(expected: &Value, value: &Value) -> bool {
+    match expected {
+        Value::String(expected) => is_type(expected, value),
+        Value::Array(expected) => expected
+            .iter()
+            .filter_map(Value::as_str)
+            .any(|expected| is_type(expected, value)),
+        _ => true,
+    }
+}
+
+fn is_type(expected: &str, value: &Value) -> bool {
+    match expected {
+        "integer" => type_name(value) == "integer",
+        "number" => value.is_number(),
+        expected => type_name(value) == expected,
+    }
+}
+
+fn type_list(expected: &Value) -> String {
+    match expected {
+        Value::Array(types) => {
+            let types: Vec<&str> = types.iter().filter_map(Value::as_str).collect();
+            types.join(" or ")
+        }
+        Value::String(expected) => expected.clone(),
+        expected => expected.to_string(),
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_i64() || n.is_u64() => "integer",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::case::to_snake_case;
+    use serde_json::json;
+
+    /// The schema of an enum with `transfer` and `burn` variants, as
+    /// `schemars` generates it.
+    fn schema() -> Value {
+        json!({
+            "oneOf": [
+                {
+                    "type": "object",
+                    "required": ["transfer"],
+                    "properties": {"transfer": {
+                        "type": "object",
+                        "required": ["recipient", "amount"],
+                        "properties": {
+                            "recipient": {"type": "string"},
+                            "amount": {"$ref": "#/definitions/Uint128"},
+                        },
+                        "additionalProperties": false,
+                    }},
+                    "additionalProperties": false,
+                },
+                {
+                    "type": "object",
+                    "required": ["burn"],
+                    "properties": {"burn": {
+                        "type": "object",
+                        "required": ["amount"],
+                        "properties": {"amount": {"$ref": "#/definitions/Uint128"}},
+                    }},
+                    "additionalProperties": false,
+                },
+            ],
+            "definitions": {"Uint128": {"type": "string"}},
+        })
+    }
+
+    fn errors(value: Value) -> Vec<String> {
+        validate(&schema(), &value)
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn valid_payloads_pass() {
+        assert!(errors(json!({"transfer": {"recipient": "bob", "amount": "5"}})).is_empty());
+        assert!(errors(json!({"burn": {"amount": "5"}})).is_empty());
+    }
+
+    #[test]
+    fn every_violation_of_the_matching_variant_is_reported() {
+        let errors = errors(json!({"transfer": {"amount": 5, "memo": "hi"}}));
+        assert_eq!(
+            errors,
+            [
+                "$.transfer: missing field \"recipient\"",
+                "$.transfer.amount: expected string, found integer",
+                "$.transfer.memo: unknown field",
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_variants_list_the_expected_keys() {
+        let errors = errors(json!({"mint": {"amount": "5"}}));
+        assert_eq!(
+            errors,
+            ["$: expected one of the keys \"transfer\", \"burn\""]
+        );
+    }
+
+    #[test]
+    fn named_keys_match_their_properties() {
+        let value = json!({"transfer": {"recipient": "bob", "amount": "5"}});
+        assert!(validate_named(&schema(), &value, to_snake_case).is_empty());
+
+        let schema = json!({
+            "type": "object",
+            "required": ["token_id"],
+            "properties": {"token_id": {"type": "string"}},
+            "additionalProperties": false,
+        });
+        let value = json!({"tokenId": "1"});
+        assert!(validate_named(&schema, &value, to_snake_case).is_empty());
+        assert_eq!(validate(&schema, &value).len(), 2);
+    }
+}
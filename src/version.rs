@@ -0,0 +1,163 @@
+//! Per-module version tracking, in the spirit of cw2.
+//!
+//! When a module reporting a [version][crate::module::Module::version] is
+//! instantiated by the [Manager][crate::manager::Manager], its name and
+//! version are stored under a glue-reserved key. During
+//! [migrate][crate::manager::Manager::migrate] the stored version is checked
+//! against the module's current version, refusing downgrades, and updated.
+//!
+//! Modules upgraded across several versions register a
+//! [Migration][crate::module::Migration] for each of them with
+//! [migrations][crate::module::Module::migrations]. The Manager runs the
+//! chain of steps leading from the stored version to the current one, and
+//! reports each step it ran with a `glue-migration` event:
+//!
+//! ```javascript
+//! { "type": "glue-migration",
+//!   "attributes": [{ "key": "module", "value": "token" },
+//!                  { "key": "from", "value": "1.0.0" }, { "key": "to", "value": "1.1.0" }] }
+//! ```
+
+use crate::error::Error;
+use crate::storage;
+use cosmwasm_std::{StdResult, Storage};
+use semver::Version;
+use serde::{Deserialize, Serialize};
+
+const NAMESPACE: &[u8] = b"versions";
+
+/// The version of a module, as recorded in storage.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ModuleVersion {
+    pub module: String,
+    pub version: String,
+}
+
+/// Load the stored version of `module`, if one was recorded.
+pub fn get_module_version(storage: &dyn Storage, module: &str) -> StdResult<Option<ModuleVersion>> {
+    storage::load(storage, &storage::key(&[NAMESPACE], module.as_bytes()))
+}
+
+/// Record `version` as the stored version of `module`.
+pub fn set_module_version(storage: &mut dyn Storage, module: &str, version: &str) -> StdResult<()> {
+    let value = ModuleVersion {
+        module: module.to_string(),
+        version: version.to_string(),
+    };
+    storage::save(storage, &storage::key(&[NAMESPACE], module.as_bytes()), &value)
+}
+
+/// Fail if `version` is older than the stored version of `module`.
+pub(crate) fn assert_not_downgrade(
+    storage: &dyn Storage,
+    module: &str,
+    version: &str,
+) -> Result<(), Error> {
+    let stored = match get_module_version(storage, module)? {
+        Some(stored) => stored.version,
+        None => return Ok(()),
+    };
+    if parse(module, version)? < parse(module, &stored)? {
+        return Err(Error::VersionDowngrade {
+            module: module.to_string(),
+            stored,
+            version: version.to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// The indices of the `steps`, given as the versions they migrate between,
+/// leading from the version `from` of `module` to `to`. Each step starts at
+/// the version the previous one ended at; the chain ends once no step starts
+/// there without going past `to`.
+pub(crate) fn migration_path(
+    module: &str,
+    steps: &[(String, String)],
+    from: &str,
+    to: &str,
+) -> Result<Vec<usize>, Error> {
+    let steps = steps
+        .iter()
+        .map(|(from, to)| Ok((parse(module, from)?, parse(module, to)?)))
+        .collect::<Result<Vec<_>, Error>>()?;
+    if let Some((from, to)) = steps.iter().find(|(from, to)| to <= from) {
+        return Err(Error::InvalidVersion {
+            module: module.to_string(),
+            version: to.to_string(),
+            msg: format!("migration step from {} must move to a later version", from),
+        });
+    }
+    let target = parse(module, to)?;
+    let mut current = parse(module, from)?;
+    let mut path = Vec::new();
+    while let Some(index) = steps
+        .iter()
+        .position(|(from, to)| *from == current && *to <= target)
+    {
+        path.push(index);
+        current = steps[index].1.clone();
+    }
+    Ok(path)
+}
+
+fn parse(module: &str, version: &str) -> Result<Version, Error> {
+    Version::parse(version).map_err(|e| Error::InvalidVersion {
+        module: module.to_string(),
+        version: version.to_string(),
+        msg: e.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::testing::MockStorage;
+
+    fn steps(steps: &[(&str, &str)]) -> Vec<(String, String)> {
+        steps
+            .iter()
+            .map(|(from, to)| (from.to_string(), to.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn downgrades_are_refused() {
+        let mut storage = MockStorage::new();
+        assert_not_downgrade(&storage, "token", "0.1.0").unwrap();
+        set_module_version(&mut storage, "token", "1.2.0").unwrap();
+
+        let err = assert_not_downgrade(&storage, "token", "1.1.9").unwrap_err();
+        assert!(matches!(err, Error::VersionDowngrade { .. }), "{:?}", err);
+        assert_not_downgrade(&storage, "token", "1.2.0").unwrap();
+        assert_not_downgrade(&storage, "token", "2.0.0").unwrap();
+        assert_not_downgrade(&storage, "sale", "0.1.0").unwrap();
+        let stored = get_module_version(&storage, "token").unwrap().unwrap();
+        assert_eq!(stored.version, "1.2.0");
+    }
+
+    #[test]
+    fn migrations_chain_up_to_the_target_version() {
+        let steps = steps(&[("1.1.0", "2.0.0"), ("1.0.0", "1.1.0"), ("2.0.0", "3.0.0")]);
+        assert_eq!(
+            migration_path("token", &steps, "1.0.0", "2.0.0").unwrap(),
+            [1, 0]
+        );
+        assert_eq!(
+            migration_path("token", &steps, "1.1.0", "3.0.0").unwrap(),
+            [0, 2]
+        );
+        assert!(migration_path("token", &steps, "1.0.0", "1.0.5")
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn migrations_must_move_forward() {
+        let backwards = steps(&[("1.0.0", "1.1.0"), ("1.1.0", "1.0.0")]);
+        let err = migration_path("token", &backwards, "1.0.0", "1.1.0").unwrap_err();
+        assert!(matches!(err, Error::InvalidVersion { .. }), "{:?}", err);
+        let invalid = steps(&[("1.0", "1.1.0")]);
+        migration_path("token", &invalid, "1.0.0", "1.1.0").unwrap_err();
+    }
+}